@@ -0,0 +1,513 @@
+//! Derive macros for the `FromDer`, `ToDer` and `Tagged` traits of `derp`.
+//!
+//! `#[derive(DerSequence)]` on a struct with named fields reads and writes it as a `SEQUENCE` of
+//! its fields, in order. Each field is read and written with the traits for its type, and can be
+//! changed with a `#[der(...)]` attribute:
+//!
+//! - `optional`: the field is an `Option` of a type that implements `Tagged`, or has a context
+//!   tag, and is `OPTIONAL`.
+//! - `default = expr`: the field is left out when it equals `expr`, and is `expr` when it is
+//!   missing. DER does not allow the default to be encoded, so reading it fails with
+//!   `ErrorKind::NonCanonical` unless the reader is in `Mode::Lenient`.
+//! - `explicit = n`: the field is wrapped in the context-specific tag `[n]`.
+//! - `implicit = n`: the tag of the field is replaced by `[n]`, see `derp::from_der_implicit`.
+//!
+//! `#[derive(DerChoice)]` on an enum reads and writes it as a `CHOICE` of its variants, each of
+//! which has one unnamed field. The variant is picked by the tag of the next element, so each
+//! variant needs a distinct tag, which can be changed with `explicit = n` or `implicit = n` as for
+//! a field. An enum without a single tag does not implement `Tagged`, so it can only be an
+//! `optional` field when it is `explicit`.
+//!
+//! A lifetime on the type is used as the lifetime of the input, so that fields such as `&'a str`
+//! can borrow from it.
+//!
+//! ```
+//! extern crate derp;
+//! #[macro_use]
+//! extern crate derp_derive;
+//!
+//! use derp::{FromDer, ToDer};
+//!
+//! // Extension ::= SEQUENCE {
+//! //     extnID    OBJECT IDENTIFIER,
+//! //     critical  BOOLEAN DEFAULT FALSE,
+//! //     extnValue OCTET STRING }
+//! #[derive(Debug, PartialEq, DerSequence)]
+//! struct Extension<'a> {
+//!     id: derp::Oid,
+//!     #[der(default = false)]
+//!     critical: bool,
+//!     value: &'a [u8],
+//! }
+//!
+//! fn main() {
+//!     let encoded = [0x30, 0x09, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x04, 0x02, 0x30, 0x00];
+//!     let extension = Extension::decode(&encoded).unwrap();
+//!     assert_eq!(extension.id.to_string(), "2.5.29.19");
+//!     assert!(!extension.critical);
+//!     assert_eq!(extension.value, &[0x30, 0x00]);
+//!     assert_eq!(extension.encode().unwrap(), encoded);
+//! }
+//! ```
+//!
+//! A subset of `GeneralName`, with its strings read as UTF-8:
+//!
+//! ```
+//! extern crate derp;
+//! #[macro_use]
+//! extern crate derp_derive;
+//!
+//! use derp::{FromDer, Oid, ToDer};
+//!
+//! // GeneralName ::= CHOICE {
+//! //     rfc822Name    [1] IA5String,
+//! //     dNSName       [2] IA5String,
+//! //     registeredID  [8] OBJECT IDENTIFIER }
+//! #[derive(Debug, PartialEq, DerChoice)]
+//! enum GeneralName {
+//!     #[der(implicit = 1)]
+//!     Email(String),
+//!     #[der(implicit = 2)]
+//!     Dns(String),
+//!     #[der(implicit = 8)]
+//!     Registered(Oid),
+//! }
+//!
+//! fn main() {
+//!     let dns = GeneralName::Dns("x.io".to_owned());
+//!     let encoded = [0x82, 0x04, 0x78, 0x2e, 0x69, 0x6f];
+//!     assert_eq!(dns.encode().unwrap(), encoded);
+//!     assert_eq!(GeneralName::decode(&encoded), Ok(dns));
+//!     assert_eq!(GeneralName::decode(&[0x05, 0x00]), Err(derp::ErrorKind::UnknownTag.into()));
+//! }
+//! ```
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro2::{Span, TokenStream};
+use syn::meta::ParseNestedMeta;
+use syn::spanned::Spanned;
+use syn::{Data, DeriveInput, Error, Expr, Fields, GenericArgument, Ident, Lifetime, LitInt};
+use syn::{PathArguments, Result, Type};
+
+/// Derive `FromDer`, `ToDer` and `Tagged` for a struct encoded as a `SEQUENCE`.
+#[proc_macro_derive(DerSequence, attributes(der))]
+pub fn derive_der_sequence(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    sequence(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+/// Derive `FromDer` and `ToDer` for an enum encoded as a `CHOICE` of its variants.
+#[proc_macro_derive(DerChoice, attributes(der))]
+pub fn derive_der_choice(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as DeriveInput);
+    choice(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+// How a field or a variant is tagged.
+enum Tagging {
+    // With the tag of its type.
+    Universal,
+    Explicit(u32),
+    Implicit(u32),
+}
+
+impl Tagging {
+    // Parse `explicit = n` or `implicit = n` in a `#[der(...)]` attribute.
+    fn parse(meta: &ParseNestedMeta) -> Result<Option<Tagging>> {
+        let tagging: fn(u32) -> Tagging = match meta.path {
+            ref path if path.is_ident("explicit") => Tagging::Explicit,
+            ref path if path.is_ident("implicit") => Tagging::Implicit,
+            _ => return Ok(None),
+        };
+        Ok(Some(tagging(meta.value()?.parse::<LitInt>()?.base10_parse()?)))
+    }
+
+    // The context tag number, if there is one.
+    fn number(&self) -> Option<u32> {
+        match *self {
+            Tagging::Universal => None,
+            Tagging::Explicit(n) | Tagging::Implicit(n) => Some(n),
+        }
+    }
+
+    // An expression for the tag of a `ty` tagged this way.
+    fn tag(&self, ty: &Type) -> TokenStream {
+        match *self {
+            Tagging::Universal => quote!(<#ty as ::derp::Tagged>::TAG),
+            Tagging::Explicit(n) => {
+                quote!(::derp::Tag::new(::derp::Class::ContextSpecific, true, #n))
+            }
+            Tagging::Implicit(n) => quote! {
+                ::derp::Tag::new(
+                    ::derp::Class::ContextSpecific,
+                    <#ty as ::derp::Tagged>::TAG.is_constructed(),
+                    #n,
+                )
+            },
+        }
+    }
+
+    // An expression reading a `ty` from `reader`.
+    fn read(&self, ty: &Type, lifetime: &Lifetime) -> TokenStream {
+        match *self {
+            Tagging::Universal => quote!(<#ty as ::derp::FromDer<#lifetime>>::from_der(reader)),
+            Tagging::Explicit(n) => {
+                quote!(reader.explicit(#n, <#ty as ::derp::FromDer<#lifetime>>::from_der))
+            }
+            Tagging::Implicit(n) => quote!(::derp::from_der_implicit::<#ty>(reader, #n)),
+        }
+    }
+
+    // A statement writing the reference `value` to `der`.
+    fn write(&self) -> TokenStream {
+        match *self {
+            Tagging::Universal => quote!(::derp::ToDer::write_der(value, der)?;),
+            Tagging::Explicit(n) => {
+                quote!(der.explicit(#n, |der| ::derp::ToDer::write_der(value, der))?;)
+            }
+            Tagging::Implicit(n) => quote!(::derp::write_der_implicit(der, #n, value)?;),
+        }
+    }
+
+    // An expression for the length of what `write` writes.
+    fn len(&self) -> TokenStream {
+        match *self {
+            Tagging::Universal => quote!(::derp::ToDer::encoded_len(value)),
+            Tagging::Explicit(n) => quote! {
+                ::derp::element_len(
+                    ::derp::Tag::new(::derp::Class::ContextSpecific, true, #n),
+                    ::derp::ToDer::encoded_len(value),
+                )
+            },
+            Tagging::Implicit(n) => quote!(::derp::implicit_len(#n, value)),
+        }
+    }
+}
+
+// How a field may be left out.
+enum Presence {
+    Required,
+    // The field is an `Option` of the type it holds.
+    Optional(Type),
+    Default(Expr),
+}
+
+struct Field {
+    ident: Ident,
+    ty: Type,
+    tagging: Tagging,
+    presence: Presence,
+}
+
+impl Field {
+    fn parse(field: &syn::Field) -> Result<Field> {
+        let mut tagging = Tagging::Universal;
+        let mut optional = false;
+        let mut default = None;
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("der")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("optional") {
+                    optional = true;
+                } else if meta.path.is_ident("default") {
+                    default = Some(meta.value()?.parse::<Expr>()?);
+                } else if let Some(parsed) = Tagging::parse(&meta)? {
+                    tagging = parsed;
+                } else {
+                    let expected = "expected `optional`, `default`, `explicit` or `implicit`";
+                    return Err(meta.error(expected));
+                }
+                Ok(())
+            })?;
+        }
+
+        let presence = match (optional, default) {
+            (true, Some(_)) => {
+                return Err(Error::new(field.span(), "a field cannot be both optional and default"));
+            }
+            (true, None) => match option_type(&field.ty) {
+                Some(ty) => Presence::Optional(ty.clone()),
+                None => {
+                    let message = "an optional field must be an Option";
+                    return Err(Error::new(field.ty.span(), message));
+                }
+            },
+            (false, Some(expr)) => Presence::Default(expr),
+            (false, None) => Presence::Required,
+        };
+        Ok(Field {
+            ident: field.ident.clone().expect("named field"),
+            ty: field.ty.clone(),
+            tagging,
+            presence,
+        })
+    }
+
+    // The type that is read and written, which is in the `Option` for an optional field.
+    fn value_type(&self) -> &Type {
+        match self.presence {
+            Presence::Optional(ref ty) => ty,
+            _ => &self.ty,
+        }
+    }
+
+    // An expression reading the value from `reader`, or `None` if it is left out.
+    fn read(&self, lifetime: &Lifetime) -> TokenStream {
+        let ty = self.value_type();
+        let read = self.tagging.read(ty, lifetime);
+        let tag = self.tagging.tag(ty);
+        match self.presence {
+            Presence::Required => read,
+            Presence::Optional(_) => quote!(reader.optional(#tag, |reader| #read)),
+            Presence::Default(ref default) => {
+                quote!(::derp::from_der_default(reader, #tag, #default, |reader| #read))
+            }
+        }
+    }
+
+    // Statements writing the field to `der`.
+    fn write(&self) -> TokenStream {
+        let ident = &self.ident;
+        let write = self.tagging.write();
+        match self.presence {
+            Presence::Required => quote!({ let value = &self.#ident; #write }),
+            Presence::Optional(_) => quote!(if let Some(ref value) = self.#ident { #write }),
+            Presence::Default(ref default) => quote! {
+                if self.#ident != #default {
+                    let value = &self.#ident;
+                    #write
+                }
+            },
+        }
+    }
+
+    // An expression for the length of what `write` writes.
+    fn len(&self) -> TokenStream {
+        let ident = &self.ident;
+        let len = self.tagging.len();
+        match self.presence {
+            Presence::Required => quote!({ let value = &self.#ident; #len }),
+            Presence::Optional(_) => quote!(self.#ident.as_ref().map_or(0, |value| #len)),
+            Presence::Default(ref default) => quote! {
+                match self.#ident != #default {
+                    true => {
+                        let value = &self.#ident;
+                        #len
+                    }
+                    false => 0,
+                }
+            },
+        }
+    }
+}
+
+struct Variant {
+    ident: Ident,
+    ty: Type,
+    tagging: Tagging,
+}
+
+impl Variant {
+    fn parse(variant: &syn::Variant) -> Result<Variant> {
+        let mut tagging = Tagging::Universal;
+        for attr in variant.attrs.iter().filter(|attr| attr.path().is_ident("der")) {
+            attr.parse_nested_meta(|meta| match Tagging::parse(&meta)? {
+                Some(parsed) => {
+                    tagging = parsed;
+                    Ok(())
+                }
+                None => Err(meta.error("expected `explicit` or `implicit`")),
+            })?;
+        }
+        let ty = match variant.fields {
+            Fields::Unnamed(ref fields) if fields.unnamed.len() == 1 => {
+                fields.unnamed[0].ty.clone()
+            }
+            _ => {
+                let message = "a CHOICE variant must have exactly one unnamed field";
+                return Err(Error::new(variant.span(), message));
+            }
+        };
+        Ok(Variant { ident: variant.ident.clone(), ty, tagging })
+    }
+}
+
+// The `T` of a type written as `Option<T>`.
+fn option_type(ty: &Type) -> Option<&Type> {
+    let segment = match *ty {
+        Type::Path(ref path) if path.qself.is_none() => path.path.segments.last()?,
+        _ => return None,
+    };
+    match segment.arguments {
+        PathArguments::AngleBracketed(ref args) if segment.ident == "Option" => {
+            match args.args.first() {
+                Some(GenericArgument::Type(ty)) if args.args.len() == 1 => Some(ty),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+// The lifetime of the input in the `FromDer` impl: the one lifetime of the type, or a new one.
+fn input_lifetime(input: &DeriveInput) -> Result<(Lifetime, bool)> {
+    if let Some(param) = input.generics.type_params().next() {
+        return Err(Error::new(param.span(), "type parameters are not supported"));
+    }
+    if let Some(param) = input.generics.const_params().next() {
+        return Err(Error::new(param.span(), "const parameters are not supported"));
+    }
+    let mut lifetimes = input.generics.lifetimes();
+    match (lifetimes.next(), lifetimes.next()) {
+        (None, _) => Ok((Lifetime::new("'der", Span::call_site()), false)),
+        (Some(param), None) => Ok((param.lifetime.clone(), true)),
+        (Some(_), Some(param)) => {
+            Err(Error::new(param.span(), "at most one lifetime is supported"))
+        }
+    }
+}
+
+fn sequence(input: &DeriveInput) -> Result<TokenStream> {
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => return Err(Error::new(input.span(), "DerSequence needs named fields")),
+        },
+        _ => return Err(Error::new(input.span(), "DerSequence can only be derived for a struct")),
+    };
+    let fields = fields.iter().map(Field::parse).collect::<Result<Vec<_>>>()?;
+
+    let name = &input.ident;
+    let (lifetime, borrowed) = input_lifetime(input)?;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let from_der_generics = match borrowed {
+        true => quote!(#impl_generics),
+        false => quote!(<#lifetime>),
+    };
+
+    let reads = fields.iter().map(|field| {
+        let ident = &field.ident;
+        let read = field.read(&lifetime);
+        let context = format!("{}.{}", name, ident);
+        quote!(let #ident = reader.context(#context, |reader| #read)?;)
+    });
+    let idents = fields.iter().map(|field| &field.ident);
+    let writes = fields.iter().map(Field::write);
+    let lens = fields.iter().map(Field::len);
+
+    Ok(quote! {
+        impl #from_der_generics ::derp::FromDer<#lifetime> for #name #ty_generics #where_clause {
+            fn from_der(reader: &mut ::derp::Reader<#lifetime>) -> ::derp::Result<Self> {
+                reader.sequence(|reader| {
+                    #(#reads)*
+                    Ok(#name { #(#idents),* })
+                })
+            }
+        }
+
+        impl #impl_generics ::derp::ToDer for #name #ty_generics #where_clause {
+            fn write_der<W: ::derp::Write>(
+                &self,
+                der: &mut ::derp::Der<W>,
+            ) -> ::derp::Result<()> {
+                der.sequence(|der| {
+                    #(#writes)*
+                    Ok(())
+                })
+            }
+
+            fn encoded_len(&self) -> usize {
+                ::derp::sequence_len(0 #(+ #lens)*)
+            }
+        }
+
+        impl #impl_generics ::derp::Tagged for #name #ty_generics #where_clause {
+            const TAG: ::derp::Tag = ::derp::Tag::SEQUENCE;
+        }
+    })
+}
+
+fn choice(input: &DeriveInput) -> Result<TokenStream> {
+    let variants = match input.data {
+        Data::Enum(ref data) => &data.variants,
+        _ => return Err(Error::new(input.span(), "DerChoice can only be derived for an enum")),
+    };
+    let parsed = variants.iter().map(Variant::parse).collect::<Result<Vec<_>>>()?;
+    // The context tags must be unique, but the universal ones depend on the types.
+    let mut numbers = Vec::new();
+    for (variant, parsed) in variants.iter().zip(&parsed) {
+        if let Some(n) = parsed.tagging.number() {
+            if numbers.contains(&n) {
+                let message = format!("the tag [{}] is used by more than one variant", n);
+                return Err(Error::new(variant.span(), message));
+            }
+            numbers.push(n);
+        }
+    }
+    let variants = parsed;
+
+    let name = &input.ident;
+    let (lifetime, borrowed) = input_lifetime(input)?;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let from_der_generics = match borrowed {
+        true => quote!(#impl_generics),
+        false => quote!(<#lifetime>),
+    };
+
+    let reads = variants.iter().map(|variant| {
+        let (ident, ty) = (&variant.ident, &variant.ty);
+        let tag = variant.tagging.tag(ty);
+        let read = variant.tagging.read(ty, &lifetime);
+        let context = format!("{}::{}", name, ident);
+        quote! {
+            let value = reader.optional(#tag, |reader| reader.context(#context, |reader| #read))?;
+            if let Some(value) = value {
+                return Ok(#name::#ident(value));
+            }
+        }
+    });
+    let writes = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let write = variant.tagging.write();
+        quote!(#name::#ident(ref value) => { #write })
+    });
+    let lens = variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let len = variant.tagging.len();
+        quote!(#name::#ident(ref value) => #len)
+    });
+
+    Ok(quote! {
+        impl #from_der_generics ::derp::FromDer<#lifetime> for #name #ty_generics #where_clause {
+            fn from_der(reader: &mut ::derp::Reader<#lifetime>) -> ::derp::Result<Self> {
+                #(#reads)*
+                match reader.at_end() {
+                    true => Err(::derp::ErrorKind::EmptyInput.into()),
+                    false => Err(::derp::ErrorKind::UnknownTag.into()),
+                }
+            }
+        }
+
+        impl #impl_generics ::derp::ToDer for #name #ty_generics #where_clause {
+            fn write_der<W: ::derp::Write>(
+                &self,
+                der: &mut ::derp::Der<W>,
+            ) -> ::derp::Result<()> {
+                match *self {
+                    #(#writes)*
+                }
+                Ok(())
+            }
+
+            fn encoded_len(&self) -> usize {
+                match *self {
+                    #(#lens,)*
+                }
+            }
+        }
+    })
+}
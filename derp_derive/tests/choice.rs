@@ -0,0 +1,67 @@
+extern crate derp;
+#[macro_use]
+extern crate derp_derive;
+
+mod common;
+
+use derp::{ErrorKind, FromDer, Oid, Tag};
+
+use common::round_trip;
+
+// Value ::= CHOICE {
+//     flag   BOOLEAN,
+//     text   UTF8String,
+//     number [0] EXPLICIT INTEGER,
+//     id     [1] IMPLICIT OBJECT IDENTIFIER,
+//     nested [2] EXPLICIT Value }
+#[derive(Debug, PartialEq, DerChoice)]
+enum Value<'a> {
+    Flag(bool),
+    Text(&'a str),
+    #[der(explicit = 0)]
+    Number(u64),
+    #[der(implicit = 1)]
+    Id(Oid),
+    #[der(explicit = 2)]
+    Nested(Box<Value<'a>>),
+}
+
+// Entry ::= SEQUENCE { value Value, alternative [5] EXPLICIT Value OPTIONAL }
+#[derive(Debug, PartialEq, DerSequence)]
+struct Entry<'a> {
+    value: Value<'a>,
+    #[der(explicit = 5, optional)]
+    alternative: Option<Value<'a>>,
+}
+
+#[test]
+fn choice_round_trip() {
+    round_trip(Value::Flag(true), &[0x01, 0x01, 0xff]);
+    round_trip(Value::Text("a"), &[0x0c, 0x01, 0x61]);
+    round_trip(Value::Number(5), &[0xa0, 0x03, 0x02, 0x01, 0x05]);
+    round_trip(Value::Id("1.2".parse().unwrap()), &[0x81, 0x01, 0x2a]);
+    round_trip(Value::Nested(Box::new(Value::Flag(false))), &[0xa2, 0x03, 0x01, 0x01, 0x00]);
+
+    round_trip(Entry { value: Value::Text(""), alternative: None }, &[0x30, 0x02, 0x0c, 0x00]);
+    let entry = Entry { value: Value::Flag(true), alternative: Some(Value::Number(0)) };
+    round_trip(entry, &[
+        0x30, 0x0a,
+            0x01, 0x01, 0xff,
+            0xa5, 0x05, 0xa0, 0x03, 0x02, 0x01, 0x00,
+    ]);
+}
+
+#[test]
+fn choice_errors() {
+    assert_eq!(Value::decode(&[0x05, 0x00]), Err(ErrorKind::UnknownTag.into()));
+    assert_eq!(derp::parse(&[], Value::from_der), Err(ErrorKind::EmptyInput.into()));
+
+    // An alternative that is found but cannot be read.
+    let err = Value::decode(&[0xa0, 0x03, 0x01, 0x01, 0xff]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::WrongTag { expected: Tag::INTEGER, found: 0x01 });
+    assert_eq!(err.context().collect::<Vec<_>>(), vec!["Value::Number"]);
+
+    let err = Entry::decode(&[0x30, 0x02, 0x04, 0x00]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::UnknownTag);
+    assert_eq!(err.context().collect::<Vec<_>>(), vec!["Entry.value"]);
+}
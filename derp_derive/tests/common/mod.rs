@@ -0,0 +1,11 @@
+use derp::{FromDer, ToDer};
+
+// Check that `value` is written as `encoded`, with the length it reports, and read back from it.
+pub fn round_trip<'a, T>(value: T, encoded: &'a [u8])
+where
+    T: FromDer<'a> + ToDer + PartialEq + ::std::fmt::Debug,
+{
+    assert_eq!(value.encode().unwrap(), encoded, "{:?}", value);
+    assert_eq!(value.encoded_len(), encoded.len(), "{:?}", value);
+    assert_eq!(T::decode(encoded), Ok(value));
+}
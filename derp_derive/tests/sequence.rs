@@ -0,0 +1,99 @@
+extern crate derp;
+#[macro_use]
+extern crate derp_derive;
+
+mod common;
+
+use derp::{ErrorKind, FromDer, Mode, ParserConfig, Tag};
+
+use common::round_trip;
+
+// Validity ::= SEQUENCE { notBefore INTEGER, notAfter INTEGER }
+#[derive(Debug, PartialEq, DerSequence)]
+struct Validity {
+    not_before: u64,
+    not_after: u64,
+}
+
+// Record ::= SEQUENCE {
+//     version  [0] EXPLICIT INTEGER DEFAULT 1,
+//     name     UTF8String,
+//     alias    [1] IMPLICIT UTF8String OPTIONAL,
+//     validity Validity OPTIONAL,
+//     flags    [3] IMPLICIT SEQUENCE OF BOOLEAN,
+//     data     OCTET STRING }
+#[derive(Debug, PartialEq, DerSequence)]
+struct Record<'a> {
+    #[der(explicit = 0, default = 1)]
+    version: u8,
+    name: &'a str,
+    #[der(implicit = 1, optional)]
+    alias: Option<String>,
+    #[der(optional)]
+    validity: Option<Validity>,
+    #[der(implicit = 3)]
+    flags: Vec<bool>,
+    data: &'a [u8],
+}
+
+#[test]
+fn sequence_round_trip() {
+    round_trip(Validity { not_before: 1, not_after: 0x80 }, &[
+        0x30, 0x07, 0x02, 0x01, 0x01, 0x02, 0x02, 0x00, 0x80,
+    ]);
+
+    let record = Record {
+        version: 1,
+        name: "a",
+        alias: None,
+        validity: None,
+        flags: vec![],
+        data: b"",
+    };
+    round_trip(record, &[0x30, 0x07, 0x0c, 0x01, 0x61, 0xa3, 0x00, 0x04, 0x00]);
+
+    let record = Record {
+        version: 2,
+        name: "a",
+        alias: Some("b".to_owned()),
+        validity: Some(Validity { not_before: 0, not_after: 1 }),
+        flags: vec![true],
+        data: b"c",
+    };
+    round_trip(record, &[
+        0x30, 0x1b,
+            0xa0, 0x03, 0x02, 0x01, 0x02,
+            0x0c, 0x01, 0x61,
+            0x81, 0x01, 0x62,
+            0x30, 0x06, 0x02, 0x01, 0x00, 0x02, 0x01, 0x01,
+            0xa3, 0x03, 0x01, 0x01, 0xff,
+            0x04, 0x01, 0x63,
+    ]);
+    assert_eq!(<Record as derp::Tagged>::TAG, Tag::SEQUENCE);
+}
+
+#[test]
+fn sequence_errors() {
+    // The default version is encoded.
+    let encoded = &[
+        0x30, 0x0c, 0xa0, 0x03, 0x02, 0x01, 0x01, 0x0c, 0x01, 0x61, 0xa3, 0x00, 0x04, 0x00,
+    ];
+    let err = Record::decode(encoded).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::NonCanonical);
+    assert_eq!(err.context().collect::<Vec<_>>(), vec!["Record.version"]);
+    let config = ParserConfig::DER.with_mode(Mode::Lenient);
+    let record = derp::parse_with_config(encoded, config, Record::from_der).unwrap();
+    assert_eq!(record.version, 1);
+
+    // A missing field, and one of the wrong type.
+    let err = Validity::decode(&[0x30, 0x03, 0x02, 0x01, 0x01]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::EmptyInput);
+    assert_eq!(err.context().collect::<Vec<_>>(), vec!["Validity.not_after"]);
+    let err = Validity::decode(&[0x30, 0x06, 0x02, 0x01, 0x01, 0x01, 0x01, 0xff]).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::WrongTag { expected: Tag::INTEGER, found: 0x01 });
+    assert_eq!(err.path(), &[Tag::SEQUENCE]);
+
+    // Elements left over.
+    let encoded = &[0x30, 0x08, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x05, 0x00];
+    assert_eq!(Validity::decode(encoded), Err(ErrorKind::UnconsumedContents.into()));
+}
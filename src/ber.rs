@@ -0,0 +1,424 @@
+//! Reading BER and converting it to DER.
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use std::cmp::Ordering;
+use untrusted::{Input, Reader};
+
+use der::{self, Class, Mode, Tag};
+use writer::Der;
+use {Error, ErrorKind, Result};
+
+/// Re-encode `input`, a single element encoded in BER, as DER.
+///
+/// Lengths are rewritten in their minimal definite form, strings split into constructed segments
+/// are joined into a single primitive string, `BOOLEAN` values other than `0x00` are written as
+/// `0xFF`, the unused bits of a `BIT STRING` are cleared, and the elements of a `SET` are sorted.
+/// As with `validate_der`, a `SET` whose elements are already ordered by tag is left as it is, and
+/// any other is sorted by encoding as a `SET OF`. The contents of other primitive elements are
+/// copied as they are.
+///
+/// ```
+/// extern crate derp;
+///
+/// fn main() {
+///     // SEQUENCE { OCTET STRING "ab" } with indefinite lengths and the string in two segments
+///     let ber = [
+///         0x30, 0x80,
+///             0x24, 0x80,
+///                 0x04, 0x01, 0x61,
+///                 0x04, 0x01, 0x62,
+///             0x00, 0x00,
+///         0x00, 0x00,
+///     ];
+///     let der = derp::canonicalize(&ber).unwrap();
+///     assert_eq!(der, [0x30, 0x04, 0x04, 0x02, 0x61, 0x62]);
+///     assert!(derp::validate_der(&der).is_ok());
+/// }
+/// ```
+pub fn canonicalize(input: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = Reader::new(Input::from(input));
+    let encoding = read_as_der(&mut reader)?;
+    if !reader.at_end() {
+        return Err(ErrorKind::TrailingData.into());
+    }
+    Ok(encoding)
+}
+
+/// Read an `OCTET STRING` and return its contents. In `Mode::Lenient` lengths that are not in their
+/// minimal form are accepted, as is a string split into constructed segments, as BER allows, whose
+/// segments are joined, which is the only case that allocates.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::Mode;
+/// use untrusted::Input;
+///
+/// fn main() {
+///     // OCTET STRING "ab" in two segments
+///     let input = Input::from(&[0x24, 0x80, 0x04, 0x01, 0x61, 0x04, 0x01, 0x62, 0x00, 0x00]);
+///     let contents = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::octet_string_with_mode(input, Mode::Lenient)
+///     }).unwrap();
+///     assert_eq!(&contents[..], b"ab");
+/// }
+/// ```
+pub fn octet_string_with_mode<'a>(input: &mut Reader<'a>, mode: Mode) -> Result<Cow<'a, [u8]>> {
+    if mode == Mode::Strict || !der::next_tag_is(input, CONSTRUCTED_OCTET_STRING) {
+        let contents = der::expect_tag_and_get_value_with_mode(input, Tag::OCTET_STRING, mode)?;
+        return Ok(Cow::Borrowed(contents.as_slice_less_safe()));
+    }
+    let encoding = read_as_der(input)?;
+    let contents = Input::from(&encoding)
+        .read_all(Error::from(ErrorKind::TrailingData), der::octet_string)?;
+    Ok(Cow::Owned(contents.as_slice_less_safe().to_vec()))
+}
+
+/// Read a `BIT STRING` and return the number of unused bits at the end along with its contents, as
+/// `bit_string` does. In `Mode::Lenient` lengths that are not in their minimal form are accepted,
+/// as is a string split into constructed segments, whose segments are joined, clearing any unused
+/// bits that are set in the last segment, which is the only case that allocates.
+pub fn bit_string_with_mode<'a>(
+    input: &mut Reader<'a>,
+    mode: Mode,
+) -> Result<(u8, Cow<'a, [u8]>)> {
+    if mode == Mode::Strict || !der::next_tag_is(input, CONSTRUCTED_BIT_STRING) {
+        let (unused_bits, bits) =
+            der::nested_with_mode(input, Tag::BIT_STRING, mode, der::bit_string_contents)?;
+        return Ok((unused_bits, Cow::Borrowed(bits.as_slice_less_safe())));
+    }
+    let encoding = read_as_der(input)?;
+    let (unused_bits, bits) = Input::from(&encoding)
+        .read_all(Error::from(ErrorKind::TrailingData), der::bit_string)?;
+    Ok((unused_bits, Cow::Owned(bits.as_slice_less_safe().to_vec())))
+}
+
+const CONSTRUCTED_BIT_STRING: Tag = Tag::new(Class::Universal, true, 0x03);
+const CONSTRUCTED_OCTET_STRING: Tag = Tag::new(Class::Universal, true, 0x04);
+
+// Read the next element, encoded in BER, and return its DER encoding.
+fn read_as_der(input: &mut Reader) -> Result<Vec<u8>> {
+    let mut open = Vec::new();
+    convert(input, &mut open).map_err(|err| {
+        open.iter().rev().fold(err, |err, constructed| err.within(constructed.tag))
+    })
+}
+
+// A constructed element whose contents are being converted.
+struct Constructed<'a> {
+    tag: Tag,
+    start: Option<Input<'a>>,
+    // The contents of a definite length element, or `None` if they run up to end-of-contents
+    // octets in the enclosing contents.
+    contents: Option<Reader<'a>>,
+    // The tags and DER encodings of the elements converted so far.
+    elements: Vec<(Tag, Vec<u8>)>,
+}
+
+// Convert the outermost element, without recursing so that deeply nested input cannot overflow
+// the stack. `open` holds the constructed elements being converted, outermost first.
+fn convert<'a>(top: &mut Reader<'a>, open: &mut Vec<Constructed<'a>>) -> Result<Vec<u8>> {
+    loop {
+        if let Some(constructed) = pop_finished(open) {
+            match close(open, constructed)? {
+                Some(encoding) => return Ok(encoding),
+                None => continue,
+            }
+        }
+
+        // An element with indefinite length contents is read from the contents enclosing it.
+        let (start, tag, contents) = {
+            let reader = match open.iter_mut().rev().filter_map(|c| c.contents.as_mut()).next() {
+                Some(reader) => reader,
+                None => &mut *top,
+            };
+            let start = der::position(reader);
+            if reader.at_end() {
+                let kind = match open.is_empty() {
+                    true => ErrorKind::EmptyInput,
+                    false => ErrorKind::UnexpectedEnd,
+                };
+                return Err(Error::from(kind).at(start));
+            }
+            let (tag, contents) = read_header(reader).map_err(|err| err.at(start))?;
+            (start, tag, contents)
+        };
+
+        if tag.class() == Class::Universal && tag.number() == 0 {
+            // The end-of-contents octets `00 00` close the innermost indefinite length element.
+            let indefinite = open.last().is_some_and(|c| c.contents.is_none());
+            match contents {
+                Some(contents) if indefinite && !tag.is_constructed() && contents.is_empty() => {
+                    if let Some(constructed) = open.pop() {
+                        if let Some(encoding) = close(open, constructed)? {
+                            return Ok(encoding);
+                        }
+                    }
+                    continue;
+                }
+                _ => return Err(Error::from(ErrorKind::WrongValue).at(start)),
+            }
+        }
+
+        match contents {
+            _ if tag.is_constructed() => open.push(Constructed {
+                tag,
+                start,
+                contents: contents.map(Reader::new),
+                elements: Vec::new(),
+            }),
+            Some(contents) => {
+                let encoding = primitive(tag, contents.as_slice_less_safe())
+                    .map_err(|err| err.at(start))?;
+                if let Some(encoding) = add(open, tag, encoding) {
+                    return Ok(encoding);
+                }
+            }
+            // Only constructed elements can have indefinite length contents.
+            None => return Err(Error::from(ErrorKind::WrongValue).at(start)),
+        }
+    }
+}
+
+// Read the identifier and length octets of an element, and its contents if their length is
+// definite.
+fn read_header<'a>(input: &mut Reader<'a>) -> Result<(Tag, Option<Input<'a>>)> {
+    let tag = der::read_tag(input)?;
+    match der::read_length(input, Mode::Lenient)? {
+        Some(length) => Ok((tag, Some(input.skip_and_get_input(length)?))),
+        None => Ok((tag, None)),
+    }
+}
+
+// Remove the innermost open element if it has definite length contents that have all been
+// converted.
+fn pop_finished<'a>(open: &mut Vec<Constructed<'a>>) -> Option<Constructed<'a>> {
+    match open.last() {
+        Some(&Constructed { contents: Some(ref contents), .. }) if contents.at_end() => (),
+        _ => return None,
+    }
+    open.pop()
+}
+
+// Encode `constructed`, whose contents have all been converted, and add it to the element
+// containing it.
+fn close<'a>(
+    open: &mut Vec<Constructed<'a>>,
+    constructed: Constructed<'a>,
+) -> Result<Option<Vec<u8>>> {
+    let (outer, start) = (constructed.tag, constructed.start);
+    let (tag, encoding) = encode(constructed).map_err(|err| err.at(start).within(outer))?;
+    Ok(add(open, tag, encoding))
+}
+
+// Add the DER encoding of an element to the open element containing it, or return it if it is the
+// outermost element.
+fn add(open: &mut Vec<Constructed>, tag: Tag, encoding: Vec<u8>) -> Option<Vec<u8>> {
+    match open.last_mut() {
+        Some(constructed) => {
+            constructed.elements.push((tag, encoding));
+            None
+        }
+        None => Some(encoding),
+    }
+}
+
+// The tag and DER encoding of a constructed element, from those of its elements.
+fn encode(constructed: Constructed) -> Result<(Tag, Vec<u8>)> {
+    let Constructed { tag, mut elements, .. } = constructed;
+    if tag.class() == Class::Universal && der::is_string_type(tag.number()) {
+        let tag = Tag::new(Class::Universal, false, tag.number());
+        return Ok((tag, primitive(tag, &join_segments(tag, &elements)?)?));
+    }
+
+    let sorted_by_tag = elements
+        .windows(2)
+        .all(|pair| der::tag_order(pair[0].0, pair[1].0) == Ordering::Less);
+    if tag == Tag::SET && !sorted_by_tag {
+        elements.sort_by(|a, b| der::set_of_order(&a.1, &b.1));
+    }
+    let contents: Vec<u8> = elements.into_iter().flat_map(|(_, encoding)| encoding).collect();
+    let mut encoding = Vec::new();
+    Der::new(&mut encoding).element(tag, &contents)?;
+    Ok((tag, encoding))
+}
+
+// Join the DER encodings of the segments of a constructed string into the contents of a primitive
+// string tagged `tag`.
+fn join_segments(tag: Tag, segments: &[(Tag, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut joined = Vec::new();
+    // Each segment of a `BIT STRING` starts with its number of unused bits, and only the last
+    // segment may have any.
+    let mut unused_bits = 0;
+    for &(segment_tag, ref encoding) in segments {
+        if segment_tag != tag {
+            let kind = ErrorKind::WrongTag { expected: tag, found: encoding[0] };
+            return Err(kind.into());
+        }
+        let contents = Input::from(encoding)
+            .read_all(Error::from(ErrorKind::TrailingData), der::read_any)?
+            .1
+            .as_slice_less_safe();
+        if tag != Tag::BIT_STRING {
+            joined.extend_from_slice(contents);
+        } else if unused_bits != 0 {
+            return Err(ErrorKind::WrongValue.into());
+        } else {
+            unused_bits = contents[0];
+            joined.extend_from_slice(&contents[1..]);
+        }
+    }
+    if tag == Tag::BIT_STRING {
+        joined.insert(0, unused_bits);
+    }
+    Ok(joined)
+}
+
+// The DER encoding of a primitive element tagged `tag` with the BER contents `contents`.
+fn primitive(tag: Tag, contents: &[u8]) -> Result<Vec<u8>> {
+    let mut contents = contents.to_vec();
+    match tag {
+        Tag::BOOLEAN => match contents[..] {
+            [0x00] => (),
+            [_] => contents[0] = 0xff,
+            _ => return Err(ErrorKind::BadBooleanValue.into()),
+        },
+        Tag::BIT_STRING => match contents.split_first_mut() {
+            Some((&mut unused_bits, bits)) => match bits.last_mut() {
+                _ if unused_bits > 7 => return Err(ErrorKind::WrongValue.into()),
+                None if unused_bits != 0 => return Err(ErrorKind::WrongValue.into()),
+                Some(last) => *last &= 0xff << unused_bits,
+                None => (),
+            },
+            None => return Err(ErrorKind::UnexpectedEnd.into()),
+        },
+        _ => (),
+    }
+    let mut encoding = Vec::new();
+    Der::new(&mut encoding).element(tag, &contents)?;
+    Ok(encoding)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn canonicalize_ber() {
+        for &(ber, der) in &[
+            // Already DER
+            (&[0x30, 0x05, 0x02, 0x01, 0x01, 0x05, 0x00][..], &[0x30, 0x05, 0x02, 0x01, 0x01, 0x05, 0x00][..]),
+            // Non-minimal lengths
+            (&[0x04, 0x81, 0x01, 0x61], &[0x04, 0x01, 0x61]),
+            (&[0x30, 0x84, 0x00, 0x00, 0x00, 0x02, 0x05, 0x00], &[0x30, 0x02, 0x05, 0x00]),
+            // Indefinite lengths
+            (&[0x30, 0x80, 0x05, 0x00, 0x00, 0x00], &[0x30, 0x02, 0x05, 0x00]),
+            (&[0xa0, 0x80, 0x30, 0x80, 0x00, 0x00, 0x00, 0x00], &[0xa0, 0x02, 0x30, 0x00]),
+            // Constructed strings, including nested segments
+            (&[0x24, 0x06, 0x04, 0x01, 0x61, 0x04, 0x01, 0x62], &[0x04, 0x02, 0x61, 0x62]),
+            (
+                &[0x2c, 0x80, 0x0c, 0x01, 0x61, 0x2c, 0x80, 0x0c, 0x01, 0x62, 0x00, 0x00, 0x00, 0x00],
+                &[0x0c, 0x02, 0x61, 0x62],
+            ),
+            (&[0x24, 0x00], &[0x04, 0x00]),
+            (&[0x23, 0x08, 0x03, 0x02, 0x00, 0x61, 0x03, 0x02, 0x04, 0x6f], &[0x03, 0x03, 0x04, 0x61, 0x60]),
+            (&[0x23, 0x00], &[0x03, 0x01, 0x00]),
+            // BOOLEAN values and unused bits
+            (&[0x01, 0x01, 0x01], &[0x01, 0x01, 0xff]),
+            (&[0x01, 0x01, 0x00], &[0x01, 0x01, 0x00]),
+            (&[0x03, 0x02, 0x02, 0x87], &[0x03, 0x02, 0x02, 0x84]),
+            // A SET OF is sorted by encoding, a SET already in tag order is not
+            (&[0x31, 0x06, 0x02, 0x01, 0x02, 0x02, 0x01, 0x01], &[0x31, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02]),
+            (&[0x31, 0x04, 0xa0, 0x00, 0x81, 0x00], &[0x31, 0x04, 0xa0, 0x00, 0x81, 0x00]),
+            (&[0x31, 0x80, 0x81, 0x00, 0x80, 0x00, 0x00, 0x00], &[0x31, 0x04, 0x80, 0x00, 0x81, 0x00]),
+        ] {
+            assert_eq!(canonicalize(ber).as_ref().map(|v| &v[..]), Ok(der), "{:02x?}", ber);
+            assert_eq!(::validate_der(der), Ok(()), "{:02x?}", der);
+        }
+    }
+
+    #[test]
+    fn canonicalize_errors() {
+        for &(ber, err) in &[
+            (&[][..], ErrorKind::EmptyInput),
+            (&[0x05, 0x00, 0x05, 0x00], ErrorKind::TrailingData),
+            (&[0x30, 0x80, 0x05, 0x00], ErrorKind::UnexpectedEnd),
+            (&[0x30, 0x03, 0x05, 0x00], ErrorKind::UnexpectedEnd),
+            (&[0x00, 0x00], ErrorKind::WrongValue),
+            (&[0x30, 0x02, 0x00, 0x00], ErrorKind::WrongValue),
+            (&[0x30, 0x80, 0x00, 0x01, 0x00], ErrorKind::WrongValue),
+            (&[0x04, 0x80, 0x00, 0x00], ErrorKind::WrongValue),
+            (&[0x01, 0x02, 0xff, 0xff], ErrorKind::BadBooleanValue),
+            (&[0x03, 0x00], ErrorKind::UnexpectedEnd),
+            (&[0x03, 0x01, 0x01], ErrorKind::WrongValue),
+            (&[0x03, 0x02, 0x08, 0x00], ErrorKind::WrongValue),
+            // Only the last segment of a BIT STRING can have unused bits
+            (&[0x23, 0x08, 0x03, 0x02, 0x04, 0x60, 0x03, 0x02, 0x00, 0x61], ErrorKind::WrongValue),
+            (&[0x24, 0x03, 0x0c, 0x01, 0x61], wrong_tag(Tag::OCTET_STRING, 0x0c)),
+        ] {
+            assert_eq!(canonicalize(ber), Err(err.into()), "{:02x?}", ber);
+        }
+    }
+
+    #[test]
+    fn canonicalize_error_location() {
+        let ber = &[0x30, 0x80, 0xa0, 0x06, 0x30, 0x04, 0x01, 0x02, 0xff, 0xff, 0x00, 0x00];
+        let err = canonicalize(ber).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadBooleanValue);
+        assert_eq!(err.offset(ber), Some(6));
+        let path = [Tag::SEQUENCE, Tag::CONTEXT_SPECIFIC_CONSTRUCTED_0, Tag::SEQUENCE];
+        assert_eq!(err.path(), &path);
+
+        let ber = &[0x30, 0x80, 0x24, 0x03, 0x0c, 0x01, 0x61, 0x00, 0x00];
+        let err = canonicalize(ber).unwrap_err();
+        assert_eq!(err.offset(ber), Some(2));
+        assert_eq!(err.path(), &[Tag::SEQUENCE, Tag::new(Class::Universal, true, 4)]);
+    }
+
+    #[test]
+    fn read_constructed_strings() {
+        let read = |encoded: &[u8], mode| {
+            Input::from(encoded).read_all(Error::from(ErrorKind::TrailingData), |input| {
+                octet_string_with_mode(input, mode).map(|contents| contents.into_owned())
+            })
+        };
+        let segments = [0x24, 0x80, 0x04, 0x01, 0x61, 0x24, 0x03, 0x04, 0x01, 0x62, 0x00, 0x00];
+        assert_eq!(read(&segments, Mode::Lenient), Ok(b"ab".to_vec()));
+        assert_eq!(read(&segments, Mode::Strict), Err(ErrorKind::LongLengthNotSupported.into()));
+        let segment = [0x24, 0x03, 0x04, 0x01, 0x61];
+        assert_eq!(read(&segment, Mode::Strict), Err(wrong_tag(Tag::OCTET_STRING, 0x24).into()));
+        assert_eq!(read(&[0x04, 0x01, 0x61], Mode::Lenient), Ok(b"a".to_vec()));
+        assert_eq!(read(&[0x04, 0x81, 0x01, 0x61], Mode::Lenient), Ok(b"a".to_vec()));
+        let wrong_segment = [0x24, 0x03, 0x03, 0x01, 0x00];
+        assert_eq!(read(&wrong_segment, Mode::Lenient), Err(wrong_tag(Tag::OCTET_STRING, 0x03).into()));
+
+        // The primitive form is borrowed from the input.
+        let primitive = [0x04, 0x01, 0x61];
+        Input::from(&primitive).read_all(Error::from(ErrorKind::TrailingData), |input| {
+            assert!(match octet_string_with_mode(input, Mode::Lenient)? {
+                Cow::Borrowed(contents) => contents == b"a",
+                Cow::Owned(_) => false,
+            });
+            Ok(())
+        }).unwrap();
+
+        let read = |encoded: &[u8], mode| {
+            Input::from(encoded).read_all(Error::from(ErrorKind::TrailingData), |input| {
+                let (unused_bits, bits) = bit_string_with_mode(input, mode)?;
+                Ok((unused_bits, bits.into_owned()))
+            })
+        };
+        let segments = [0x23, 0x08, 0x03, 0x02, 0x00, 0x61, 0x03, 0x02, 0x04, 0x6f];
+        assert_eq!(read(&segments, Mode::Lenient), Ok((4, vec![0x61, 0x60])));
+        assert_eq!(read(&segments, Mode::Strict), Err(wrong_tag(Tag::BIT_STRING, 0x23).into()));
+        assert_eq!(read(&[0x03, 0x02, 0x04, 0x60], Mode::Lenient), Ok((4, vec![0x60])));
+        let segments = [0x23, 0x08, 0x03, 0x02, 0x04, 0x60, 0x03, 0x02, 0x00, 0x61];
+        assert_eq!(read(&segments, Mode::Lenient), Err(ErrorKind::WrongValue.into()));
+    }
+
+    fn wrong_tag(expected: Tag, found: u8) -> ErrorKind {
+        ErrorKind::WrongTag { expected, found }
+    }
+}
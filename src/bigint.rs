@@ -0,0 +1,92 @@
+//! Conversions between `INTEGER` and the `num-bigint` types, enabled with the `bigint` feature.
+
+use num_bigint::{BigInt, BigUint};
+use untrusted::Reader;
+
+use {Der, Result, Write};
+use der::{self, Tag};
+
+/// Read a non-negative `INTEGER` as a `BigUint`.
+///
+/// ```
+/// extern crate derp;
+/// extern crate num_bigint;
+/// extern crate untrusted;
+///
+/// use num_bigint::BigUint;
+/// use untrusted::Input;
+///
+/// fn main() {
+///     let input = Input::from(&[0x02, 0x03, 0x00, 0x80, 0x01]);
+///     let value = input.read_all(derp::ErrorKind::TrailingData.into(), derp::read_biguint).unwrap();
+///     assert_eq!(value, BigUint::from(0x8001u32));
+/// }
+/// ```
+pub fn read_biguint(input: &mut Reader) -> Result<BigUint> {
+    let value = der::nonnegative_integer(input, 0)?;
+    Ok(BigUint::from_bytes_be(value.as_slice_less_safe()))
+}
+
+/// Read an `INTEGER`, which may be negative, as a `BigInt`.
+pub fn read_bigint(input: &mut Reader) -> Result<BigInt> {
+    let value = der::expect_tag_and_get_value(input, Tag::INTEGER)?.as_slice_less_safe();
+    der::check_minimal_signed(value)?;
+    Ok(BigInt::from_signed_bytes_be(value))
+}
+
+impl<'a, W: Write> Der<'a, W> {
+    /// Write a `BigUint` as an `INTEGER`.
+    pub fn biguint(&mut self, value: &BigUint) -> Result<()> {
+        self.positive_integer(&value.to_bytes_be())
+    }
+
+    /// Write a `BigInt` as an `INTEGER` using its minimal two's complement encoding.
+    pub fn bigint(&mut self, value: &BigInt) -> Result<()> {
+        self.element(Tag::INTEGER, &value.to_signed_bytes_be())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use untrusted::Input;
+    use ErrorKind;
+
+    #[test]
+    fn round_trip_biguint() {
+        for value in &[BigUint::from(0u8), BigUint::from(0x80u8), BigUint::from(u128::MAX) << 64u32] {
+            let mut buf = Vec::new();
+            Der::new(&mut buf).biguint(value).unwrap();
+            let parsed = Input::from(&buf).read_all(ErrorKind::TrailingData.into(), read_biguint).unwrap();
+            assert_eq!(&parsed, value);
+        }
+
+        let r = Input::from(&[0x02, 0x01, 0x80]).read_all(ErrorKind::TrailingData.into(), read_biguint);
+        assert_eq!(r, Err(ErrorKind::NegativeValue.into()));
+    }
+
+    #[test]
+    fn round_trip_bigint() {
+        for &(value, encoding) in &[
+            (0, &[0x02, 0x01, 0x00][..]),
+            (128, &[0x02, 0x02, 0x00, 0x80]),
+            (-128, &[0x02, 0x01, 0x80]),
+            (-129, &[0x02, 0x02, 0xff, 0x7f]),
+        ] {
+            let value = BigInt::from(value);
+            let mut buf = Vec::new();
+            Der::new(&mut buf).bigint(&value).unwrap();
+            assert_eq!(&buf, encoding);
+            let parsed = Input::from(&buf).read_all(ErrorKind::TrailingData.into(), read_bigint).unwrap();
+            assert_eq!(parsed, value);
+        }
+
+        let big: BigInt = -(BigInt::from(u128::MAX) << 64u32);
+        let mut buf = Vec::new();
+        Der::new(&mut buf).bigint(&big).unwrap();
+        assert_eq!(Input::from(&buf).read_all(ErrorKind::TrailingData.into(), read_bigint), Ok(big));
+
+        let r = Input::from(&[0x02, 0x02, 0xff, 0x80]).read_all(ErrorKind::TrailingData.into(), read_bigint);
+        assert_eq!(r, Err(ErrorKind::NonCanonical.into()));
+    }
+}
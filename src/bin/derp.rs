@@ -5,7 +5,7 @@ extern crate pem;
 extern crate untrusted;
 
 use clap::{App, Arg, ArgMatches};
-use derp::{Result, Error, Tag};
+use derp::{Result, ErrorKind, Tag};
 use std::fs::File;
 use std::io::Read;
 use untrusted::{Input, Reader};
@@ -30,7 +30,7 @@ fn run_main(matches: ArgMatches) -> Result<String> {
     file.read_to_end(&mut buf)?;
     let buf = parse_to_bytes(&buf);
     let input = Input::from(&buf);
-    Ok(input.read_all(Error::Read, make_printable_string)?)
+    input.read_all(ErrorKind::TrailingData.into(), make_printable_string)
 }
 
 fn parser<'a, 'b>() -> App<'a, 'b> {
@@ -65,43 +65,36 @@ fn parse_to_bytes(bytes: &[u8]) -> Vec<u8> {
 }
 
 fn make_printable_string<'a>(input: &mut Reader<'a>) -> Result<String> {
-    if input.at_end() {
-        return Ok("".into())
-    }
-
-    if input.peek(Tag::Sequence as u8) {
-        let out = derp::nested(input, Tag::Sequence, make_printable_string)?
-            .lines()
-            .map(|l| format!("  {}\n", l))
-            .collect::<String>();
-        return Ok(format!("{}\n{}", Tag::Sequence, out))
-    }
-
-    if input.peek(Tag::BitString as u8) {
-        let out = derp::nested(input, Tag::BitString, make_printable_string)
-            .map(|s| {
-                s.lines()
-                    .map(|l| format!("  {}\n", l))
-                    .collect::<String>()
-            })
-            .unwrap_or_else(|_| "".into());
-        return Ok(format!("{}\n{}", Tag::BitString, out))
-    }
-
-    if input.peek(Tag::OctetString as u8) {
-        let out = derp::nested(input, Tag::OctetString, make_printable_string)
-            .map(|s| {
-                s.lines()
-                    .map(|l| format!("  {}\n", l))
-                    .collect::<String>()
-            })
-            .unwrap_or_else(|_| "".into());
-        return Ok(format!("{}\n{}", Tag::OctetString, out))
-    }
-
     let mut out = String::new();
-    while let Ok((tag, _)) = derp::read_tag_and_get_value(input) {
-        out.push_str(&format!("{}\n", Tag::from_byte(tag).map(|t| format!("{}", t)).unwrap_or_else(|_| format!("0x{:x}", tag))))
+    while let Ok((tag, value)) = derp::read_any(input) {
+        out.push_str(&format!("{}", tag));
+        if tag == Tag::OID {
+            if let Ok(oid) = derp::Oid::from_bytes(value.as_slice_less_safe()) {
+                out.push_str(&format!(" {}", oid));
+                if let Some(name) = derp::oids::name(oid.as_bytes()) {
+                    out.push_str(&format!(" ({})", name));
+                }
+            }
+        }
+        out.push('\n');
+        if tag == Tag::SEQUENCE || tag == Tag::BIT_STRING || tag == Tag::OCTET_STRING {
+            let inner = match tag {
+                Tag::BIT_STRING => value.read_all(ErrorKind::TrailingData.into(), |value| {
+                    value.read_byte()?;
+                    make_printable_string(value)
+                }),
+                _ => value.read_all(ErrorKind::TrailingData.into(), make_printable_string),
+            };
+            // The contents of a string are only shown when they happen to be DER themselves.
+            let inner = match inner {
+                Ok(inner) => inner,
+                Err(_) if tag != Tag::SEQUENCE => String::new(),
+                Err(e) => return Err(e),
+            };
+            for line in inner.lines() {
+                out.push_str(&format!("  {}\n", line));
+            }
+        }
     }
     Ok(out)
 }
@@ -116,6 +109,6 @@ mod test {
     fn parse_ed25519_pk8() {
         let input = parse_to_bytes(ED25519_PK8);
         let input = Input::from(&input);
-        input.read_all(Error::Read, make_printable_string).unwrap();
+        input.read_all(ErrorKind::TrailingData.into(), make_printable_string).unwrap();
     }
 }
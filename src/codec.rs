@@ -0,0 +1,586 @@
+//! Traits for types that are read from and written as DER.
+
+#[cfg(feature = "alloc")]
+use alloc::borrow::{Cow, ToOwned};
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+#[cfg(feature = "alloc")]
+use der::BitFlags;
+use der::{self, Class, Mode, Tag};
+#[cfg(feature = "alloc")]
+use oid::Oid;
+use reader::{self, Reader};
+#[cfg(feature = "alloc")]
+use real;
+use writer::{self, Der, SliceWriter};
+use {ErrorKind, Result, Write};
+
+/// A type that can be read from DER.
+///
+/// Implementations read one value with `reader`, which is usually one element, and compose by
+/// calling `from_der` for the types of their fields. The lifetime is that of the input, so that
+/// types such as `&'a str` can borrow from it.
+///
+/// ```
+/// extern crate derp;
+///
+/// use derp::{FromDer, Reader};
+///
+/// // Point ::= SEQUENCE { x INTEGER, y INTEGER }
+/// #[derive(Debug, PartialEq)]
+/// struct Point {
+///     x: i64,
+///     y: i64,
+/// }
+///
+/// impl<'a> FromDer<'a> for Point {
+///     fn from_der(reader: &mut Reader<'a>) -> derp::Result<Self> {
+///         reader.sequence(|r| Ok(Point { x: i64::from_der(r)?, y: i64::from_der(r)? }))
+///     }
+/// }
+///
+/// fn main() {
+///     let encoded = [0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0xff];
+///     assert_eq!(Point::decode(&encoded), Ok(Point { x: 1, y: -1 }));
+/// }
+/// ```
+pub trait FromDer<'a>: Sized {
+    /// Read a value from `reader`.
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self>;
+
+    /// Read a value from all of `input`, which must be DER, as `parse` does.
+    fn decode(input: &'a [u8]) -> Result<Self> {
+        reader::parse(input, Self::from_der)
+    }
+}
+
+/// A type that can be written as DER.
+///
+/// `encoded_len` is the exact number of octets `write_der` writes, which lets the output be
+/// allocated up front and lets the lengths of enclosing elements be known without writing them.
+///
+#[cfg_attr(feature = "alloc", doc = "```")]
+#[cfg_attr(not(feature = "alloc"), doc = "```ignore")]
+/// extern crate derp;
+///
+/// use derp::{Der, ToDer, Write};
+///
+/// // Point ::= SEQUENCE { x INTEGER, y INTEGER }
+/// struct Point {
+///     x: i64,
+///     y: i64,
+/// }
+///
+/// impl ToDer for Point {
+///     fn write_der<W: Write>(&self, der: &mut Der<W>) -> derp::Result<()> {
+///         der.sequence(|der| {
+///             self.x.write_der(der)?;
+///             self.y.write_der(der)
+///         })
+///     }
+///
+///     fn encoded_len(&self) -> usize {
+///         derp::sequence_len(self.x.encoded_len() + self.y.encoded_len())
+///     }
+/// }
+///
+/// fn main() {
+///     let point = Point { x: 1, y: -1 };
+///     assert_eq!(point.encode().unwrap(), vec![0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0xff]);
+///     assert_eq!(point.encoded_len(), 8);
+/// }
+/// ```
+pub trait ToDer {
+    /// Write this value to `der`.
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()>;
+
+    /// The length of what `write_der` writes, in octets.
+    fn encoded_len(&self) -> usize;
+
+    /// Write this value to a new buffer.
+    #[cfg(feature = "alloc")]
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(self.encoded_len());
+        self.write_der(&mut Der::<Vec<u8>>::in_buffer(&mut buf))?;
+        Ok(buf)
+    }
+
+    /// Write this value to the start of `buf` and return the octets written, without allocating.
+    /// Errors with `ErrorKind::BufferTooSmall` if `buf` is shorter than `encoded_len`.
+    fn encode_to_slice<'b>(&self, buf: &'b mut [u8]) -> Result<&'b [u8]> {
+        let mut writer = SliceWriter::new(buf);
+        self.write_der(&mut Der::slice(&mut writer))?;
+        Ok(writer.into_written())
+    }
+}
+
+/// A type that is always encoded as one element with the tag `TAG`, so that it can be told apart
+/// from other elements when it is `OPTIONAL`, and be given an `IMPLICIT` tag instead.
+pub trait Tagged {
+    /// The tag of the element.
+    const TAG: Tag;
+}
+
+/// The length of the encoding of a `SEQUENCE` whose elements have `len` octets of encodings in
+/// total, for implementing `ToDer::encoded_len`.
+pub fn sequence_len(len: usize) -> usize {
+    der::element_len(Tag::SEQUENCE, len)
+}
+
+// The context-specific tag `[tag_number]` that replaces `tag` when tagging it `IMPLICIT`.
+fn implicit_tag(tag: Tag, tag_number: u32) -> Tag {
+    Tag::new(Class::ContextSpecific, tag.is_constructed(), tag_number)
+}
+
+/// Read a `T` whose tag is replaced by the context-specific tag `[tag_number]`, as for a field
+/// that is tagged `IMPLICIT`.
+///
+/// The contents are decoded from a copy of the element with the tag of `T`, which is why `T`
+/// cannot borrow from the input.
+///
+/// ```
+/// extern crate derp;
+///
+/// fn main() {
+///     // [2] IMPLICIT UTF8String "hi"
+///     let encoded = [0x82, 0x02, 0x68, 0x69];
+///     let value = derp::parse(&encoded, |r| derp::from_der_implicit::<String>(r, 2));
+///     assert_eq!(value, Ok("hi".to_owned()));
+///
+///     let mut buf = Vec::new();
+///     derp::write_der_implicit(&mut derp::Der::new(&mut buf), 2, "hi").unwrap();
+///     assert_eq!(buf, encoded);
+///     assert_eq!(derp::implicit_len(2, "hi"), 4);
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+pub fn from_der_implicit<'a, T>(reader: &mut Reader<'a>, tag_number: u32) -> Result<T>
+where
+    T: Tagged + for<'b> FromDer<'b>,
+{
+    let contents = reader.expect(implicit_tag(T::TAG, tag_number))?;
+    let mut buf = Vec::with_capacity(der::element_len(T::TAG, contents.len()));
+    Der::<Vec<u8>>::in_buffer(&mut buf).element(T::TAG, contents)?;
+    reader::parse_with_config(&buf, reader.config(), T::from_der)
+}
+
+/// Write `value` with its tag replaced by the context-specific tag `[tag_number]`, as
+/// `from_der_implicit` reads it.
+#[cfg(feature = "alloc")]
+pub fn write_der_implicit<W, T>(der: &mut Der<W>, tag_number: u32, value: &T) -> Result<()>
+where
+    W: Write,
+    T: ToDer + Tagged + ?Sized,
+{
+    let buf = value.encode()?;
+    let (octets, len) = implicit_tag(T::TAG, tag_number).identifier_octets();
+    der.raw(&octets[..len])?;
+    der.raw(&buf[T::TAG.identifier_octets().1..])
+}
+
+/// The length of what `write_der_implicit` writes.
+pub fn implicit_len<T: ToDer + Tagged + ?Sized>(tag_number: u32, value: &T) -> usize {
+    let tag_len = implicit_tag(T::TAG, tag_number).identifier_octets().1;
+    value.encoded_len() - T::TAG.identifier_octets().1 + tag_len
+}
+
+// Read a field that is `DEFAULT default` with `decoder` if its tag `tag` is next, for `der_schema!`
+// and the derives. DER does not allow the default to be encoded, so unless the reader is in
+// `Mode::Lenient` that fails with `ErrorKind::NonCanonical`.
+#[doc(hidden)]
+pub fn from_der_default<'a, T, F>(
+    reader: &mut Reader<'a>,
+    tag: Tag,
+    default: T,
+    decoder: F,
+) -> Result<T>
+where
+    T: PartialEq,
+    F: FnOnce(&mut Reader<'a>) -> Result<T>,
+{
+    match reader.optional(tag, decoder)? {
+        Some(ref value) if *value == default && reader.config().mode() == Mode::Strict => {
+            Err(ErrorKind::NonCanonical.into())
+        }
+        Some(value) => Ok(value),
+        None => Ok(default),
+    }
+}
+
+impl<T: ToDer + ?Sized> ToDer for &T {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        (**self).write_der(der)
+    }
+
+    fn encoded_len(&self) -> usize {
+        (**self).encoded_len()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'a, T: FromDer<'a>> FromDer<'a> for Box<T> {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        T::from_der(reader).map(Box::new)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T: Tagged + ?Sized> Tagged for Box<T> {
+    const TAG: Tag = T::TAG;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ToDer + ?Sized> ToDer for Box<T> {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        (**self).write_der(der)
+    }
+
+    fn encoded_len(&self) -> usize {
+        (**self).encoded_len()
+    }
+}
+
+/// `BOOLEAN`
+impl<'a> FromDer<'a> for bool {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        reader.boolean()
+    }
+}
+
+impl Tagged for bool {
+    const TAG: Tag = Tag::BOOLEAN;
+}
+
+impl ToDer for bool {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        der.boolean(*self)
+    }
+
+    fn encoded_len(&self) -> usize {
+        3
+    }
+}
+
+// The length of the encoding of `value` as an `INTEGER`.
+fn unsigned_len(value: u128) -> usize {
+    let bytes = value.to_be_bytes();
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len() - 1);
+    let len = bytes.len() - start + (bytes[start] & 0x80 != 0) as usize;
+    der::element_len(Tag::INTEGER, len)
+}
+
+macro_rules! unsigned {
+    ($($ty:ident),*) => {
+        $(
+            /// `INTEGER`
+            impl<'a> FromDer<'a> for $ty {
+                fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+                    reader.$ty()
+                }
+            }
+
+            impl Tagged for $ty {
+                const TAG: Tag = Tag::INTEGER;
+            }
+
+            impl ToDer for $ty {
+                fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+                    der.$ty(*self)
+                }
+
+                fn encoded_len(&self) -> usize {
+                    unsigned_len(u128::from(*self))
+                }
+            }
+        )*
+    };
+}
+
+unsigned!(u8, u16, u32, u64);
+
+/// `INTEGER`
+impl<'a> FromDer<'a> for i64 {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        reader.i64()
+    }
+}
+
+impl Tagged for i64 {
+    const TAG: Tag = Tag::INTEGER;
+}
+
+impl ToDer for i64 {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        der.i64(*self)
+    }
+
+    fn encoded_len(&self) -> usize {
+        let (bytes, start) = writer::minimal_twos_complement(i128::from(*self));
+        der::element_len(Tag::INTEGER, bytes.len() - start)
+    }
+}
+
+/// `REAL`
+#[cfg(feature = "alloc")]
+impl<'a> FromDer<'a> for f64 {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        reader.real()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Tagged for f64 {
+    const TAG: Tag = Tag::REAL;
+}
+
+#[cfg(feature = "alloc")]
+impl ToDer for f64 {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        der.real(*self)
+    }
+
+    fn encoded_len(&self) -> usize {
+        der::element_len(Tag::REAL, real::real_contents(*self).1)
+    }
+}
+
+/// `OCTET STRING`, borrowed from the input unless BER split it into segments.
+#[cfg(feature = "alloc")]
+impl<'a> FromDer<'a> for Cow<'a, [u8]> {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        reader.octet_string()
+    }
+}
+
+/// `OCTET STRING`, which must be primitive.
+impl<'a> FromDer<'a> for &'a [u8] {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        reader.expect(Tag::OCTET_STRING)
+    }
+}
+
+impl Tagged for [u8] {
+    const TAG: Tag = Tag::OCTET_STRING;
+}
+
+impl Tagged for &[u8] {
+    const TAG: Tag = Tag::OCTET_STRING;
+}
+
+#[cfg(feature = "alloc")]
+impl<'c> Tagged for Cow<'c, [u8]> {
+    const TAG: Tag = Tag::OCTET_STRING;
+}
+
+/// `OCTET STRING`
+impl ToDer for [u8] {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        der.octet_string(self)
+    }
+
+    fn encoded_len(&self) -> usize {
+        der::element_len(Tag::OCTET_STRING, self.len())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<'c> ToDer for Cow<'c, [u8]> {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        der.octet_string(self)
+    }
+
+    fn encoded_len(&self) -> usize {
+        der::element_len(Tag::OCTET_STRING, self.len())
+    }
+}
+
+/// `UTF8String`
+impl<'a> FromDer<'a> for &'a str {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        reader.utf8_string()
+    }
+}
+
+/// `UTF8String`
+#[cfg(feature = "alloc")]
+impl<'a> FromDer<'a> for String {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        reader.utf8_string().map(str::to_owned)
+    }
+}
+
+impl Tagged for str {
+    const TAG: Tag = Tag::UTF8_STRING;
+}
+
+impl Tagged for &str {
+    const TAG: Tag = Tag::UTF8_STRING;
+}
+
+#[cfg(feature = "alloc")]
+impl Tagged for String {
+    const TAG: Tag = Tag::UTF8_STRING;
+}
+
+/// `UTF8String`
+impl ToDer for str {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        der.utf8_string(self)
+    }
+
+    fn encoded_len(&self) -> usize {
+        der::element_len(Tag::UTF8_STRING, self.len())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ToDer for String {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        der.utf8_string(self)
+    }
+
+    fn encoded_len(&self) -> usize {
+        der::element_len(Tag::UTF8_STRING, self.len())
+    }
+}
+
+/// `OBJECT IDENTIFIER`
+#[cfg(feature = "alloc")]
+impl<'a> FromDer<'a> for Oid {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        reader.oid()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Tagged for Oid {
+    const TAG: Tag = Tag::OID;
+}
+
+#[cfg(feature = "alloc")]
+impl ToDer for Oid {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        der.oid(self)
+    }
+
+    fn encoded_len(&self) -> usize {
+        der::element_len(Tag::OID, self.as_bytes().len())
+    }
+}
+
+/// `BIT STRING` holding a named bit list.
+#[cfg(feature = "alloc")]
+impl<'a> FromDer<'a> for BitFlags {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        reader.bit_string_flags()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Tagged for BitFlags {
+    const TAG: Tag = Tag::BIT_STRING;
+}
+
+#[cfg(feature = "alloc")]
+impl ToDer for BitFlags {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        der.bit_string_flags(self)
+    }
+
+    fn encoded_len(&self) -> usize {
+        der::element_len(Tag::BIT_STRING, 1 + self.len().div_ceil(8))
+    }
+}
+
+/// `SEQUENCE OF`, so a `Vec<u8>` is a `SEQUENCE OF INTEGER`; use `[u8]` or `Cow<[u8]>` for an
+/// `OCTET STRING`.
+#[cfg(feature = "alloc")]
+impl<'a, T: FromDer<'a>> FromDer<'a> for Vec<T> {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        reader.sequence_of(T::from_der)
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<T> Tagged for Vec<T> {
+    const TAG: Tag = Tag::SEQUENCE;
+}
+
+#[cfg(feature = "alloc")]
+impl<T: ToDer> ToDer for Vec<T> {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        der.sequence(|der| self.iter().try_for_each(|value| value.write_der(der)))
+    }
+
+    fn encoded_len(&self) -> usize {
+        sequence_len(self.iter().map(ToDer::encoded_len).sum())
+    }
+}
+
+#[cfg(all(test, feature = "alloc"))]
+pub(crate) mod test {
+    use super::*;
+    use ErrorKind;
+
+    // Check that `value` is written as `encoded`, with the length it reports, and is read back.
+    pub(crate) fn round_trip<'a, T>(value: T, encoded: &'a [u8])
+    where
+        T: FromDer<'a> + ToDer + PartialEq + ::std::fmt::Debug,
+    {
+        assert_eq!(value.encode().unwrap(), encoded, "{:?}", value);
+        assert_eq!(value.encoded_len(), encoded.len(), "{:?}", value);
+        assert_eq!(T::decode(encoded), Ok(value));
+    }
+
+    #[test]
+    fn codec_round_trip() {
+        round_trip(true, &[0x01, 0x01, 0xff]);
+        round_trip(0u8, &[0x02, 0x01, 0x00]);
+        round_trip(0x80u16, &[0x02, 0x02, 0x00, 0x80]);
+        round_trip(0x0102_0304u32, &[0x02, 0x04, 0x01, 0x02, 0x03, 0x04]);
+        round_trip(u64::MAX, &[0x02, 0x09, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        round_trip(-129i64, &[0x02, 0x02, 0xff, 0x7f]);
+        round_trip(0.75f64, &[0x09, 0x03, 0x80, 0xfe, 0x03]);
+        round_trip(-0.0f64, &[0x09, 0x01, 0x43]);
+        round_trip(1e300f64, &(1e300f64).encode().unwrap());
+        round_trip(String::from("hi"), &[0x0c, 0x02, 0x68, 0x69]);
+        round_trip(Oid::from_arcs(&[1, 3, 101, 112]).unwrap(), &[0x06, 0x03, 0x2b, 0x65, 0x70]);
+        round_trip(vec![1u8, 2], &[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02]);
+        round_trip(Vec::<u8>::new(), &[0x30, 0x00]);
+
+        let mut flags = BitFlags::new();
+        flags.set(0, true);
+        flags.set(5, true);
+        round_trip(flags, &[0x03, 0x02, 0x02, 0x84]);
+        round_trip(BitFlags::new(), &[0x03, 0x01, 0x00]);
+
+        // Borrowed values, and long lengths.
+        let long = vec![0x61; 200];
+        let mut encoded = vec![0x0c, 0x81, 200];
+        encoded.extend(&long);
+        let string = String::from_utf8(long.clone()).unwrap();
+        assert_eq!(string.encoded_len(), encoded.len());
+        assert_eq!(<&str>::decode(&encoded), Ok(&string[..]));
+        assert_eq!(ToDer::encode(&&string[..]).unwrap(), encoded);
+        assert_eq!(<&[u8]>::decode(&[0x04, 0x01, 0x00]), Ok(&[0x00][..]));
+        let octets = Cow::Borrowed(&b"ab"[..]);
+        assert_eq!(octets.encode().unwrap(), vec![0x04, 0x02, 0x61, 0x62]);
+        assert_eq!(Cow::decode(&[0x04, 0x02, 0x61, 0x62]), Ok(octets));
+        assert_eq!(long[..].encoded_len(), 203);
+        assert_eq!(Box::new(true).encode().unwrap(), vec![0x01, 0x01, 0xff]);
+        assert_eq!(Box::<bool>::decode(&[0x01, 0x01, 0xff]), Ok(Box::new(true)));
+    }
+
+    #[test]
+    fn codec_errors() {
+        assert_eq!(u8::decode(&[0x02, 0x01, 0xff]), Err(ErrorKind::NegativeValue.into()));
+        assert_eq!(bool::decode(&[0x01, 0x01, 0xff, 0x00]), Err(ErrorKind::TrailingData.into()));
+        let err = Vec::<bool>::decode(&[0x30, 0x03, 0x02, 0x01, 0x01]).unwrap_err();
+        assert_eq!(err.path(), &[Tag::SEQUENCE]);
+    }
+}
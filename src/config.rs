@@ -0,0 +1,311 @@
+//! Settings for parsing, threaded through nested elements.
+
+use untrusted::{Input, Reader};
+
+use der::{self, Mode, Tag};
+use {Error, ErrorKind, Result};
+
+/// How input is parsed by the functions ending in `_with_config`.
+///
+/// Rather than a variant of each function for every option, a `ParserConfig` gathers them and is
+/// passed down to the decoder of each nested element. Start from `ParserConfig::DER` or
+/// `ParserConfig::BER` and change what is needed.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::{Mode, ParserConfig, Tag};
+/// use untrusted::Input;
+///
+/// fn main() {
+///     // SEQUENCE { BOOLEAN TRUE } with a BER length and BOOLEAN value
+///     let input = Input::from(&[0x30, 0x81, 0x03, 0x01, 0x01, 0x01]);
+///     let config = ParserConfig::DER.with_mode(Mode::Lenient);
+///     assert_eq!(config, ParserConfig::BER);
+///
+///     let value = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::nested_with_config(input, Tag::SEQUENCE, config, |input, config| {
+///             derp::boolean_with_mode(input, config.mode())
+///         })
+///     });
+///     assert_eq!(value, Ok(true));
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParserConfig {
+    mode: Mode,
+    max_depth: usize,
+    max_len: usize,
+    hex_context: bool,
+    check_strings: bool,
+}
+
+impl ParserConfig {
+    /// Only accept DER, with elements nested at most `DEFAULT_MAX_DEPTH` deep and no limit on
+    /// their length.
+    pub const DER: ParserConfig = ParserConfig {
+        mode: Mode::Strict,
+        max_depth: ParserConfig::DEFAULT_MAX_DEPTH,
+        max_len: usize::MAX,
+        hex_context: false,
+        check_strings: true,
+    };
+
+    /// Also accept encodings that are valid BER but not valid DER, where the reader supports it,
+    /// with elements nested at most `DEFAULT_MAX_DEPTH` deep and no limit on their length.
+    pub const BER: ParserConfig = ParserConfig {
+        mode: Mode::Lenient,
+        max_depth: ParserConfig::DEFAULT_MAX_DEPTH,
+        max_len: usize::MAX,
+        hex_context: false,
+        check_strings: true,
+    };
+
+    /// The default limit on nesting, far deeper than real-world structures such as certificates
+    /// need but shallow enough that a recursive decoder will not overflow the stack.
+    pub const DEFAULT_MAX_DEPTH: usize = 64;
+
+    /// How strictly the encoding rules are applied.
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Use `mode` for how strictly the encoding rules are applied.
+    pub fn with_mode(self, mode: Mode) -> Self {
+        ParserConfig { mode, ..self }
+    }
+
+    /// How many more levels of elements can be entered with `nested_with_config`. Each call passes
+    /// on a config with one less to its decoder, and fails with `ErrorKind::NestingTooDeep` when
+    /// there are none left.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Allow at most `max_depth` levels of elements to be entered with `nested_with_config`.
+    pub fn with_max_depth(self, max_depth: usize) -> Self {
+        ParserConfig { max_depth, ..self }
+    }
+
+    /// The longest contents an element can have, in octets.
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Reject elements whose length is declared to be more than `max_len` octets with
+    /// `ErrorKind::LengthExceedsLimit`, as soon as their length octets are read.
+    pub fn with_max_len(self, max_len: usize) -> Self {
+        ParserConfig { max_len, ..self }
+    }
+
+    /// Whether errors capture the octets around where reading failed.
+    pub fn hex_context(&self) -> bool {
+        self.hex_context
+    }
+
+    /// Have the errors of the functions that parse all of a buffer, such as `parse_with_config`
+    /// and `walk_with_config`, show the octets around where reading failed, as
+    /// `Error::with_hex_context` does.
+    pub fn with_hex_context(self, hex_context: bool) -> Self {
+        ParserConfig { hex_context, ..self }
+    }
+
+    /// Whether the characters of strings whose type restricts them, such as `PrintableString`, are
+    /// checked.
+    pub fn check_strings(&self) -> bool {
+        self.check_strings
+    }
+
+    /// Check that `PrintableString`, `IA5String` and `VisibleString` only contain the characters
+    /// their type allows, failing with `ErrorKind::BadStringValue`, or if `check_strings` is false
+    /// only that they are UTF-8, for input from encoders that ignore the restrictions. This is
+    /// applied by `Reader` and `Value::parse_with_config`.
+    pub fn with_check_strings(self, check_strings: bool) -> Self {
+        ParserConfig { check_strings, ..self }
+    }
+}
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        ParserConfig::DER
+    }
+}
+
+impl From<Mode> for ParserConfig {
+    fn from(mode: Mode) -> Self {
+        ParserConfig::DER.with_mode(mode)
+    }
+}
+
+/// Read whatever element comes next, and return its tag and value, as `read_any_with_mode` does.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::{ErrorKind, ParserConfig};
+/// use untrusted::Input;
+///
+/// fn main() {
+///     // An OCTET STRING claiming to be 2GB long
+///     let input = Input::from(&[0x04, 0x84, 0x7f, 0xff, 0xff, 0xff]);
+///     let config = ParserConfig::DER.with_max_len(1 << 20);
+///     let err = input.read_all(ErrorKind::TrailingData.into(), |input| {
+///         derp::read_any_with_config(input, config)
+///     }).unwrap_err();
+///     assert_eq!(err.kind(), ErrorKind::LengthExceedsLimit);
+/// }
+/// ```
+pub fn read_any_with_config<'a>(
+    input: &mut Reader<'a>,
+    config: ParserConfig,
+) -> Result<(Tag, Input<'a>)> {
+    der::read_any_with_limit(input, config.mode, config.max_len)
+}
+
+/// Read a tag and return its value, as `expect_tag_and_get_value_with_mode` does.
+pub fn expect_tag_and_get_value_with_config<'a>(
+    input: &mut Reader<'a>,
+    tag: Tag,
+    config: ParserConfig,
+) -> Result<Input<'a>> {
+    der::expect_tag_with_limit(input, tag, config.mode, config.max_len)
+}
+
+/// Return the value of the given tag and apply a decoding function to it, passing `config` on to
+/// `decoder` for reading the contents.
+///
+/// This fails with `ErrorKind::NestingTooDeep` once elements are nested more than
+/// `config.max_depth()` deep, so that a decoder for a recursive type cannot be made to overflow
+/// the stack by deeply nested input. The functions that do not take a `ParserConfig`, such as
+/// `nested`, only have the default limit, and only with the `std` feature.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::{ErrorKind, ParserConfig, Tag};
+/// use untrusted::{Input, Reader};
+///
+/// // Count the SEQUENCEs nested in each other.
+/// fn depth(input: &mut Reader, config: ParserConfig) -> derp::Result<usize> {
+///     derp::nested_with_config(input, Tag::SEQUENCE, config, |input, config| {
+///         match input.at_end() {
+///             true => Ok(1),
+///             false => Ok(depth(input, config)? + 1),
+///         }
+///     })
+/// }
+///
+/// fn main() {
+///     let input = Input::from(&[0x30, 0x04, 0x30, 0x02, 0x30, 0x00]);
+///     let read = |config| {
+///         input.read_all(ErrorKind::TrailingData.into(), |input| depth(input, config))
+///     };
+///     assert_eq!(read(ParserConfig::DER), Ok(3));
+///
+///     let err = read(ParserConfig::DER.with_max_depth(2)).unwrap_err();
+///     assert_eq!(err.kind(), ErrorKind::NestingTooDeep);
+///     assert_eq!(err.offset(input.as_slice_less_safe()), Some(4));
+/// }
+/// ```
+pub fn nested_with_config<'a, F, R>(
+    input: &mut Reader<'a>,
+    tag: Tag,
+    config: ParserConfig,
+    decoder: F,
+) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>, ParserConfig) -> Result<R>,
+{
+    if config.max_depth == 0 {
+        return Err(Error::from(ErrorKind::NestingTooDeep).at(der::position(input)));
+    }
+    let value = expect_tag_and_get_value_with_config(input, tag, config)?;
+    let inner = config.with_max_depth(config.max_depth - 1);
+    der::decode_contents(tag, value, |input| decoder(input, inner))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ErrorKind;
+
+    #[test]
+    fn config_mode() {
+        assert_eq!(ParserConfig::default(), ParserConfig::DER);
+        assert_eq!(ParserConfig::from(Mode::Lenient), ParserConfig::BER);
+        assert_eq!(ParserConfig::BER.with_mode(Mode::Strict).mode(), Mode::Strict);
+
+        let read = |encoded, config| {
+            Input::from(encoded).read_all(ErrorKind::TrailingData.into(), |input| {
+                nested_with_config(input, Tag::SEQUENCE, config, |input, config| {
+                    assert_eq!(config.mode(), Mode::Lenient);
+                    read_any_with_config(input, config)?;
+                    expect_tag_and_get_value_with_config(input, Tag::NULL, config).map(|_| ())
+                })
+            })
+        };
+        let encoded = &[0x30, 0x81, 0x05, 0x04, 0x81, 0x00, 0x05, 0x00];
+        assert_eq!(read(encoded, ParserConfig::BER), Ok(()));
+        assert_eq!(read(encoded, ParserConfig::DER), Err(ErrorKind::NonCanonical.into()));
+    }
+
+    #[test]
+    fn config_max_depth() {
+        fn depth(input: &mut Reader, config: ParserConfig) -> Result<usize> {
+            nested_with_config(input, Tag::SEQUENCE, config, |input, config| match input.at_end() {
+                true => Ok(1),
+                false => Ok(depth(input, config)? + 1),
+            })
+        }
+
+        let read = |encoded: &[u8], config| {
+            let input = Input::from(encoded);
+            input.read_all(ErrorKind::TrailingData.into(), |input| depth(input, config))
+        };
+
+        // One level deeper than the default limit.
+        let mut encoded = vec![0x30, 0x00];
+        for _ in 0..ParserConfig::DEFAULT_MAX_DEPTH {
+            let mut outer = vec![0x30, 0x82, 0x00, 0x00];
+            let len = encoded.len();
+            outer[2] = (len >> 8) as u8;
+            outer[3] = len as u8;
+            outer.extend(encoded);
+            encoded = outer;
+        }
+        let err = read(&encoded, ParserConfig::BER).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NestingTooDeep);
+        #[cfg(feature = "alloc")]
+        assert_eq!(err.path().len(), ParserConfig::DEFAULT_MAX_DEPTH);
+        let config = ParserConfig::BER.with_max_depth(ParserConfig::DEFAULT_MAX_DEPTH + 1);
+        assert_eq!(read(&encoded, config), Ok(ParserConfig::DEFAULT_MAX_DEPTH + 1));
+        let config = ParserConfig::DER.with_max_depth(0);
+        assert_eq!(read(&[0x30, 0x00], config), Err(ErrorKind::NestingTooDeep.into()));
+    }
+
+    #[test]
+    fn config_max_len() {
+        let read = |encoded: &[u8], max_len| {
+            let config = ParserConfig::DER.with_max_len(max_len);
+            Input::from(encoded).read_all(ErrorKind::TrailingData.into(), |input| {
+                nested_with_config(input, Tag::SEQUENCE, config, |input, config| {
+                    expect_tag_and_get_value_with_config(input, Tag::OCTET_STRING, config)
+                        .map(|value| value.len())
+                })
+            })
+        };
+        let encoded = &[0x30, 0x05, 0x04, 0x03, 0x61, 0x62, 0x63];
+        assert_eq!(read(encoded, 5), Ok(3));
+        let err = read(encoded, 4).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LengthExceedsLimit);
+        assert_eq!(err.offset(encoded), Some(0));
+
+        // Rejected before finding that the contents are missing.
+        let encoded = &[0x30, 0x84, 0x7f, 0xff, 0xff, 0xff];
+        assert_eq!(read(encoded, 1 << 20), Err(ErrorKind::LengthExceedsLimit.into()));
+        assert_eq!(read(encoded, usize::MAX), Err(ErrorKind::UnexpectedEnd.into()));
+    }
+}
@@ -0,0 +1,569 @@
+//! Date and time values as encoded by `UTCTime` and `GeneralizedTime`.
+//!
+//! With the `chrono` feature enabled, `DerDateTime` converts to and from
+//! `chrono::DateTime<chrono::Utc>`. With the `time` feature enabled, it converts to and from
+//! `time::OffsetDateTime`.
+
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, Datelike, NaiveDate, TimeZone, Timelike, Utc};
+#[cfg(any(feature = "chrono", feature = "time"))]
+use std::convert::TryFrom;
+#[cfg(feature = "time")]
+use time::{Date, Month, OffsetDateTime, UtcOffset};
+use untrusted::Reader;
+
+use {ErrorKind, Result};
+use der::{self, Mode, Tag};
+
+/// A date and time in UTC.
+///
+/// Fractional seconds are only produced when parsing a `GeneralizedTime` in `Mode::Lenient`, so
+/// for times read from DER the nanosecond field is always zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DerDateTime {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+    nanosecond: u32,
+}
+
+impl DerDateTime {
+    /// Create a new `DerDateTime`. Errors with `ErrorKind::BadTimeValue` if any of the fields are out of
+    /// range, including days that do not exist in the given month.
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> Result<Self> {
+        if year > 9999
+            || !(1..=12).contains(&month)
+            || day < 1
+            || day > days_in_month(year, month)
+            || hour > 23
+            || minute > 59
+            || second > 59
+        {
+            return Err(ErrorKind::BadTimeValue.into());
+        }
+
+        Ok(DerDateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            nanosecond: 0,
+        })
+    }
+
+    /// Set the fractional part of the second. Errors with `ErrorKind::BadTimeValue` if `nanosecond` is
+    /// not less than one billion.
+    pub fn with_nanosecond(self, nanosecond: u32) -> Result<Self> {
+        if nanosecond >= 1_000_000_000 {
+            return Err(ErrorKind::BadTimeValue.into());
+        }
+        Ok(DerDateTime { nanosecond, ..self })
+    }
+
+    /// The year, e.g. `2017`.
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// The month, in the range `[1, 12]`.
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// The day of the month, in the range `[1, 31]`.
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// The hour, in the range `[0, 23]`.
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    /// The minute, in the range `[0, 59]`.
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    /// The second, in the range `[0, 59]`.
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+
+    /// The fractional part of the second in nanoseconds, in the range `[0, 999999999]`.
+    pub fn nanosecond(&self) -> u32 {
+        self.nanosecond
+    }
+}
+
+/// The policy for mapping the two digit years of a `UTCTime` to full years.
+///
+/// A pivot covers the hundred years starting at its first year. Two digit years are mapped to the
+/// only year in that window which has the same last two digits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UtcTimePivot {
+    first_year: u16,
+}
+
+impl UtcTimePivot {
+    /// The rule from RFC 5280: `50` through `99` are `1950` through `1999`, and `00` through `49`
+    /// are `2000` through `2049`.
+    pub const RFC_5280: UtcTimePivot = UtcTimePivot { first_year: 1950 };
+
+    /// Create a pivot covering `[first_year, first_year + 99]`. Errors with `ErrorKind::BadTimeValue`
+    /// if the window extends past the year `9999`.
+    pub fn new(first_year: u16) -> Result<Self> {
+        if first_year > 9900 {
+            return Err(ErrorKind::BadTimeValue.into());
+        }
+        Ok(UtcTimePivot { first_year })
+    }
+
+    /// The first year covered by this pivot.
+    pub fn first_year(&self) -> u16 {
+        self.first_year
+    }
+
+    /// The last year covered by this pivot.
+    pub fn last_year(&self) -> u16 {
+        self.first_year + 99
+    }
+
+    /// Map a two digit year in the range `[0, 99]` to a full year.
+    pub fn full_year(&self, two_digit_year: u16) -> u16 {
+        let century = self.first_year - self.first_year % 100;
+        match century + two_digit_year % 100 {
+            y if y < self.first_year => y + 100,
+            y => y,
+        }
+    }
+}
+
+impl Default for UtcTimePivot {
+    fn default() -> Self {
+        UtcTimePivot::RFC_5280
+    }
+}
+
+/// Types that can be written as a `UTCTime` or `GeneralizedTime`.
+pub trait ToDerDateTime {
+    /// Convert `self` to a `DerDateTime`, failing with `ErrorKind::BadTimeValue` if it cannot be
+    /// represented.
+    fn to_der_date_time(&self) -> Result<DerDateTime>;
+}
+
+impl ToDerDateTime for DerDateTime {
+    fn to_der_date_time(&self) -> Result<DerDateTime> {
+        Ok(*self)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<DerDateTime> for DateTime<Utc> {
+    fn from(time: DerDateTime) -> Self {
+        // `DerDateTime` is always a valid date and time within chrono's supported range.
+        Utc.from_utc_datetime(
+            &NaiveDate::from_ymd_opt(time.year.into(), time.month.into(), time.day.into())
+                .and_then(|d| {
+                    d.and_hms_nano_opt(
+                        time.hour.into(),
+                        time.minute.into(),
+                        time.second.into(),
+                        time.nanosecond,
+                    )
+                })
+                .expect("DerDateTime is always valid"),
+        )
+    }
+}
+
+/// chrono represents a leap second as second `59` with an oversized nanosecond field, so leap
+/// seconds are truncated to `:59`.
+#[cfg(feature = "chrono")]
+impl<'a> TryFrom<&'a DateTime<Utc>> for DerDateTime {
+    type Error = ::Error;
+
+    fn try_from(time: &'a DateTime<Utc>) -> Result<Self> {
+        DerDateTime::new(
+            u16::try_from(time.year()).map_err(|_| ErrorKind::BadTimeValue)?,
+            time.month() as u8,
+            time.day() as u8,
+            time.hour() as u8,
+            time.minute() as u8,
+            time.second() as u8,
+        )?
+        .with_nanosecond(time.nanosecond() % 1_000_000_000)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl ToDerDateTime for DateTime<Utc> {
+    fn to_der_date_time(&self) -> Result<DerDateTime> {
+        DerDateTime::try_from(self)
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<DerDateTime> for OffsetDateTime {
+    fn from(time: DerDateTime) -> Self {
+        // `DerDateTime` is always a valid date and time within the time crate's supported range.
+        Month::try_from(time.month)
+            .and_then(|month| Date::from_calendar_date(time.year.into(), month, time.day))
+            .and_then(|date| {
+                date.with_hms_nano(time.hour, time.minute, time.second, time.nanosecond)
+            })
+            .expect("DerDateTime is always valid")
+            .assume_utc()
+    }
+}
+
+/// The time is converted to UTC.
+#[cfg(feature = "time")]
+impl<'a> TryFrom<&'a OffsetDateTime> for DerDateTime {
+    type Error = ::Error;
+
+    fn try_from(time: &'a OffsetDateTime) -> Result<Self> {
+        let time = time
+            .checked_to_offset(UtcOffset::UTC)
+            .ok_or(ErrorKind::BadTimeValue)?;
+
+        DerDateTime::new(
+            u16::try_from(time.year()).map_err(|_| ErrorKind::BadTimeValue)?,
+            time.month().into(),
+            time.day(),
+            time.hour(),
+            time.minute(),
+            time.second(),
+        )?
+        .with_nanosecond(time.nanosecond())
+    }
+}
+
+#[cfg(feature = "time")]
+impl ToDerDateTime for OffsetDateTime {
+    fn to_der_date_time(&self) -> Result<DerDateTime> {
+        DerDateTime::try_from(self)
+    }
+}
+
+fn is_leap_year(year: u16) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Parse a run of ASCII digits as a decimal number.
+fn decimal(digits: &[u8]) -> Result<u16> {
+    digits.iter().try_fold(0u16, |acc, d| {
+        if !d.is_ascii_digit() {
+            return Err(ErrorKind::BadTimeValue.into());
+        }
+        Ok(acc * 10 + u16::from(d - b'0'))
+    })
+}
+
+/// Read a `UTCTime` of the form `YYMMDDHHMMSSZ`.
+///
+/// Two digit years are interpreted as described in RFC 5280: `50` through `99` are `1950` through
+/// `1999`, and `00` through `49` are `2000` through `2049`.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// const UTC_TIME: &'static [u8] = b"\x17\x0d170905120000Z";
+///
+/// fn main() {
+///     let input = Input::from(UTC_TIME);
+///     let time = input.read_all(derp::ErrorKind::TrailingData.into(), derp::utc_time).unwrap();
+///     assert_eq!(time, derp::DerDateTime::new(2017, 9, 5, 12, 0, 0).unwrap());
+/// }
+/// ```
+#[inline]
+pub fn utc_time(input: &mut Reader) -> Result<DerDateTime> {
+    utc_time_with_pivot(input, UtcTimePivot::RFC_5280)
+}
+
+/// Read a `UTCTime` of the form `YYMMDDHHMMSSZ`, using `pivot` to interpret the two digit year.
+pub fn utc_time_with_pivot(input: &mut Reader, pivot: UtcTimePivot) -> Result<DerDateTime> {
+    let value = der::expect_tag_and_get_value(input, Tag::UTC_TIME)?;
+    decode_utc_time(value.as_slice_less_safe(), pivot)
+}
+
+// Decode the contents of a `UTCTime`.
+pub(crate) fn decode_utc_time(value: &[u8], pivot: UtcTimePivot) -> Result<DerDateTime> {
+    if value.len() != 13 || value[12] != b'Z' {
+        return Err(ErrorKind::BadTimeValue.into());
+    }
+
+    DerDateTime::new(
+        pivot.full_year(decimal(&value[0..2])?),
+        decimal(&value[2..4])? as u8,
+        decimal(&value[4..6])? as u8,
+        decimal(&value[6..8])? as u8,
+        decimal(&value[8..10])? as u8,
+        decimal(&value[10..12])? as u8,
+    )
+}
+
+/// Read a `GeneralizedTime` of the form `YYYYMMDDHHMMSSZ`.
+///
+/// This enforces the DER restrictions: the time must be in UTC with a `Z` suffix, seconds must be
+/// present, and midnight must be written as `000000` of the following day rather than `240000`.
+/// Fractional seconds are rejected as required by RFC 5280, use `generalized_time_with_mode` with
+/// `Mode::Lenient` to accept them.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// const GENERALIZED_TIME: &'static [u8] = b"\x18\x0f20500101000000Z";
+///
+/// fn main() {
+///     let input = Input::from(GENERALIZED_TIME);
+///     let time = input.read_all(derp::ErrorKind::TrailingData.into(), derp::generalized_time).unwrap();
+///     assert_eq!(time, derp::DerDateTime::new(2050, 1, 1, 0, 0, 0).unwrap());
+/// }
+/// ```
+#[inline]
+pub fn generalized_time(input: &mut Reader) -> Result<DerDateTime> {
+    generalized_time_with_mode(input, Mode::Strict)
+}
+
+/// Read a `GeneralizedTime`. In `Mode::Lenient` a fractional second of the form
+/// `YYYYMMDDHHMMSS.fffZ` (with `.` or `,` as the separator) is also accepted. Digits beyond
+/// nanosecond precision are truncated.
+pub fn generalized_time_with_mode(input: &mut Reader, mode: Mode) -> Result<DerDateTime> {
+    let value = der::expect_tag_and_get_value_with_mode(input, Tag::GENERALIZED_TIME, mode)?;
+    decode_generalized_time(value.as_slice_less_safe(), mode)
+}
+
+// Decode the contents of a `GeneralizedTime`.
+pub(crate) fn decode_generalized_time(value: &[u8], mode: Mode) -> Result<DerDateTime> {
+    if value.len() < 15 || value[value.len() - 1] != b'Z' {
+        return Err(ErrorKind::BadTimeValue.into());
+    }
+
+    let nanosecond = match &value[14..value.len() - 1] {
+        [] => 0,
+        [sep, fraction @ ..] if mode == Mode::Lenient && (*sep == b'.' || *sep == b',') => {
+            if fraction.is_empty() || !fraction.iter().all(u8::is_ascii_digit) {
+                return Err(ErrorKind::BadTimeValue.into());
+            }
+            // Pad or truncate the fraction to exactly nine digits.
+            (0..9).fold(0u32, |acc, i| {
+                acc * 10 + fraction.get(i).map(|d| u32::from(d - b'0')).unwrap_or(0)
+            })
+        }
+        _ => return Err(ErrorKind::BadTimeValue.into()),
+    };
+
+    DerDateTime::new(
+        decimal(&value[0..4])?,
+        decimal(&value[4..6])? as u8,
+        decimal(&value[6..8])? as u8,
+        decimal(&value[8..10])? as u8,
+        decimal(&value[10..12])? as u8,
+        decimal(&value[12..14])? as u8,
+    )?
+    .with_nanosecond(nanosecond)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use untrusted::Input;
+
+    #[test]
+    fn parse_utc_time() {
+        for &(encoded, expected) in &[
+            (&b"\x17\x0d491231235959Z"[..], (2049, 12, 31, 23, 59, 59)),
+            (b"\x17\x0d500101000000Z", (1950, 1, 1, 0, 0, 0)),
+            (b"\x17\x0d000229000000Z", (2000, 2, 29, 0, 0, 0)),
+        ] {
+            let (year, month, day, hour, minute, second) = expected;
+            let time = Input::from(encoded).read_all(ErrorKind::TrailingData.into(), utc_time).unwrap();
+            assert_eq!(
+                time,
+                DerDateTime::new(year, month, day, hour, minute, second).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn parse_bad_utc_time() {
+        for &encoded in &[
+            &b"\x17\x0b1709051200Z"[..], // missing seconds
+            b"\x17\x0d170905120000z",
+            b"\x17\x0c170905120000",
+            b"\x17\x11170905120000+0100",
+            b"\x17\x0d171305120000Z", // month 13
+            b"\x17\x0d170001120000Z", // month 0
+            b"\x17\x0d170931120000Z", // 31st of September
+            b"\x17\x0d010229120000Z", // not a leap year
+            b"\x17\x0d170905240000Z",
+            b"\x17\x0d170905126000Z",
+            b"\x17\x0d170905120060Z",
+            b"\x17\x0d17090512000aZ",
+            b"\x17\x0d1709+5120000Z",
+        ] {
+            let r = Input::from(encoded).read_all(ErrorKind::TrailingData.into(), utc_time);
+            assert_eq!(r, Err(ErrorKind::BadTimeValue.into()));
+        }
+    }
+
+    #[test]
+    fn parse_generalized_time() {
+        for &(encoded, expected) in &[
+            (&b"\x18\x0f20500101000000Z"[..], (2050, 1, 1, 0, 0, 0)),
+            (b"\x18\x0f19491231235959Z", (1949, 12, 31, 23, 59, 59)),
+            (b"\x18\x0f99991231235959Z", (9999, 12, 31, 23, 59, 59)),
+            (b"\x18\x0f24000229000000Z", (2400, 2, 29, 0, 0, 0)),
+        ] {
+            let (year, month, day, hour, minute, second) = expected;
+            let time = Input::from(encoded).read_all(ErrorKind::TrailingData.into(), generalized_time).unwrap();
+            assert_eq!(
+                time,
+                DerDateTime::new(year, month, day, hour, minute, second).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn parse_bad_generalized_time() {
+        for &encoded in &[
+            &b"\x18\x0f20501231240000Z"[..], // midnight as 2400
+            b"\x18\x0e20500101000000",
+            b"\x18\x0d205001010000Z", // missing seconds
+            b"\x18\x1320500101000000+0000",
+            b"\x18\x0f21000229000000Z", // not a leap year
+            b"\x18\x0d170905120000Z", // UTCTime contents
+        ] {
+            let r = Input::from(encoded).read_all(ErrorKind::TrailingData.into(), generalized_time);
+            assert_eq!(r, Err(ErrorKind::BadTimeValue.into()));
+        }
+
+        let r = Input::from(b"\x17\x0d170905120000Z").read_all(ErrorKind::TrailingData.into(), generalized_time);
+        let kind = ErrorKind::WrongTag { expected: Tag::GENERALIZED_TIME, found: 0x17 };
+        assert_eq!(r, Err(kind.into()));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_round_trip() {
+        use std::convert::TryFrom;
+
+        let time = Input::from(b"\x18\x0f20500102030405Z")
+            .read_all(ErrorKind::TrailingData.into(), generalized_time)
+            .unwrap();
+        let chrono_time = DateTime::<Utc>::from(time);
+        assert_eq!(chrono_time, Utc.with_ymd_and_hms(2050, 1, 2, 3, 4, 5).unwrap());
+        assert_eq!(DerDateTime::try_from(&chrono_time), Ok(time));
+
+        let too_late = Utc.with_ymd_and_hms(10000, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(DerDateTime::try_from(&too_late), Err(ErrorKind::BadTimeValue.into()));
+
+        // A year that would wrap around to 2017 as a `u16`.
+        let wrapped = Utc.with_ymd_and_hms(67553, 9, 5, 12, 0, 0).unwrap();
+        assert_eq!(DerDateTime::try_from(&wrapped), Err(ErrorKind::BadTimeValue.into()));
+        let negative = Utc.with_ymd_and_hms(-1, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(DerDateTime::try_from(&negative), Err(ErrorKind::BadTimeValue.into()));
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn time_round_trip() {
+        use std::convert::TryFrom;
+
+        let time = Input::from(b"\x17\x0d170905120000Z")
+            .read_all(ErrorKind::TrailingData.into(), utc_time)
+            .unwrap();
+        let expected = Date::from_calendar_date(2017, Month::September, 5)
+            .and_then(|d| d.with_hms(12, 0, 0))
+            .unwrap();
+        let offset_time = OffsetDateTime::from(time);
+        assert_eq!(offset_time, expected.assume_utc());
+        assert_eq!(DerDateTime::try_from(&offset_time), Ok(time));
+
+        let with_offset = (expected + ::time::Duration::hours(2))
+            .assume_offset(UtcOffset::from_hms(2, 0, 0).unwrap());
+        assert_eq!(DerDateTime::try_from(&with_offset), Ok(time));
+
+        let negative = Date::from_calendar_date(-1, Month::January, 1)
+            .and_then(|d| d.with_hms(0, 0, 0))
+            .unwrap()
+            .assume_utc();
+        assert_eq!(DerDateTime::try_from(&negative), Err(ErrorKind::BadTimeValue.into()));
+    }
+
+    #[test]
+    fn parse_generalized_time_fractions() {
+        let read = |encoded: &[u8], mode| {
+            Input::from(encoded).read_all(ErrorKind::TrailingData.into(), |input| {
+                generalized_time_with_mode(input, mode)
+            })
+        };
+        let time = DerDateTime::new(2017, 9, 5, 12, 0, 0).unwrap();
+
+        for &(encoded, nanosecond) in &[
+            (&b"\x18\x1120170905120000.5Z"[..], 500_000_000),
+            (b"\x18\x1320170905120000,250Z", 250_000_000),
+            (b"\x18\x1a20170905120000.1234567891Z", 123_456_789),
+        ] {
+            assert_eq!(read(encoded, Mode::Lenient), time.with_nanosecond(nanosecond));
+            assert_eq!(read(encoded, Mode::Strict), Err(ErrorKind::BadTimeValue.into()));
+        }
+
+        for &encoded in &[
+            &b"\x18\x1020170905120000.Z"[..],
+            b"\x18\x1120170905120000.aZ",
+            b"\x18\x1a20170905120000.123456789aZ",
+            b"\x18\x1120170905120000:5Z",
+        ] {
+            assert_eq!(read(encoded, Mode::Lenient), Err(ErrorKind::BadTimeValue.into()));
+        }
+
+        assert_eq!(read(b"\x18\x0f20170905120000Z", Mode::Lenient), Ok(time));
+    }
+
+    #[test]
+    fn utc_time_pivot() {
+        let pivot = UtcTimePivot::new(1970).unwrap();
+        assert_eq!((pivot.first_year(), pivot.last_year()), (1970, 2069));
+        assert_eq!(pivot.full_year(70), 1970);
+        assert_eq!(pivot.full_year(99), 1999);
+        assert_eq!(pivot.full_year(0), 2000);
+        assert_eq!(pivot.full_year(69), 2069);
+
+        let pivot = UtcTimePivot::new(2000).unwrap();
+        assert_eq!(pivot.full_year(0), 2000);
+        assert_eq!(pivot.full_year(99), 2099);
+
+        assert_eq!(UtcTimePivot::default(), UtcTimePivot::RFC_5280);
+        assert!(UtcTimePivot::new(9900).is_ok());
+        assert_eq!(UtcTimePivot::new(9901), Err(ErrorKind::BadTimeValue.into()));
+
+        let time = Input::from(b"\x17\x0d550101000000Z")
+            .read_all(ErrorKind::TrailingData.into(), |input| {
+                utc_time_with_pivot(input, UtcTimePivot::new(1960).unwrap())
+            })
+            .unwrap();
+        assert_eq!(time.year(), 2055);
+    }
+}
@@ -0,0 +1,612 @@
+//! Reading values that implement `serde::Deserialize` from DER.
+
+use alloc::borrow::Cow;
+use serde::de::value::U32Deserializer;
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::Deserialize;
+use std::convert::TryFrom;
+use std::fmt::Display;
+use untrusted::Input;
+
+use config::ParserConfig;
+use der::{Class, Mode, Tag};
+use reader::{self, Reader};
+use {Error, ErrorKind, Result};
+
+/// Read a `T` from all of `input`, which must be DER, see `Deserializer` for how it is mapped.
+///
+/// ```
+/// extern crate derp;
+/// extern crate serde;
+///
+/// use serde::de::{Deserialize, Deserializer, Error, SeqAccess, Visitor};
+/// use std::fmt;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Role<'a> {
+///     name: &'a str,
+///     threshold: u32,
+/// }
+///
+/// impl<'de> Deserialize<'de> for Role<'de> {
+///     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+///         struct RoleVisitor;
+///
+///         impl<'de> Visitor<'de> for RoleVisitor {
+///             type Value = Role<'de>;
+///
+///             fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+///                 f.write_str("a role")
+///             }
+///
+///             fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Role<'de>, A::Error> {
+///                 let name = seq.next_element()?.ok_or(A::Error::missing_field("name"))?;
+///                 let threshold = seq.next_element()?.ok_or(A::Error::invalid_length(1, &self))?;
+///                 Ok(Role { name, threshold })
+///             }
+///         }
+///
+///         deserializer.deserialize_struct("Role", &["name", "threshold"], RoleVisitor)
+///     }
+/// }
+///
+/// fn main() {
+///     let encoded = [0x30, 0x09, 0x0c, 0x04, 0x72, 0x6f, 0x6f, 0x74, 0x02, 0x01, 0x01];
+///     let role: Role = derp::from_slice(&encoded).unwrap();
+///     assert_eq!(role, Role { name: "root", threshold: 1 });
+/// }
+/// ```
+pub fn from_slice<'a, T: Deserialize<'a>>(input: &'a [u8]) -> Result<T> {
+    from_slice_with_config(input, ParserConfig::DER)
+}
+
+/// Read a `T` from all of `input` using `config`, see `Deserializer` for what it changes.
+pub fn from_slice_with_config<'a, T>(input: &'a [u8], config: ParserConfig) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    reader::parse_with_config(input, config, |reader| {
+        T::deserialize(&mut Deserializer::new(reader))
+    })
+}
+
+/// A `serde::Deserializer` that reads DER with a `Reader`.
+///
+/// Values are read as `Serializer` writes them, and the tag of each element has to be the one it
+/// writes for the type the value is read as, or the error is `ErrorKind::WrongTag`:
+///
+/// - `bool` is read from a `BOOLEAN`, the integer types from an `INTEGER`, failing with
+///   `ErrorKind::IntegerOverflow` if it does not fit, and `f32` and `f64` from a `REAL`.
+/// - `char` and strings are read from a `UTF8String`, a `char` from one that holds exactly one
+///   character, and bytes from an `OCTET STRING`.
+/// - `()` and unit structs are read from a `NULL`. An `Option` is `None` if the next element is a
+///   `NULL`, and otherwise has the value that is read from it.
+/// - Sequences, tuples, tuple structs and structs are read from a `SEQUENCE` of their elements or
+///   fields, in order, and maps from a `SEQUENCE` of a `SEQUENCE { key, value }` for each entry.
+///   Everything in the `SEQUENCE` has to be read, so that a struct with fewer fields than were
+///   written fails with `ErrorKind::UnconsumedContents`.
+/// - Enums are read from a context-specific constructed element `[index]`, where `index` is that
+///   of the variant, holding the variant's value. Other tags fail with `ErrorKind::UnknownTag`.
+///
+/// Types read with `deserialize_any`, such as untagged enums, are read by the tag of the element
+/// instead: a `BOOLEAN` as `bool`, an `INTEGER` as `i64`, `u64` or `i128`, whichever it fits, a
+/// `REAL` as `f64`, a string as `&str`, an `OCTET STRING` as bytes, a `NULL` as `()` and a
+/// `SEQUENCE` as a sequence. `deserialize_ignored_any` skips over any element.
+///
+/// How strict reading is follows the `ParserConfig` of the reader. With `Mode::Strict`, as in
+/// `ParserConfig::DER`, the encoding must be DER. With `Mode::Lenient` the BER the config allows
+/// is read too, and strings can also be read from a `PrintableString`, `IA5String` or
+/// `VisibleString`. The limits on length and nesting apply in both.
+pub struct Deserializer<'r, 'a: 'r> {
+    reader: &'r mut Reader<'a>,
+}
+
+impl<'r, 'a> Deserializer<'r, 'a> {
+    /// Create a `Deserializer` that reads from `reader`.
+    pub fn new(reader: &'r mut Reader<'a>) -> Self {
+        Deserializer { reader }
+    }
+
+    // Read a string, of any of the types that `Mode::Lenient` allows.
+    fn string(&mut self) -> Result<&'a str> {
+        match self.reader.config().mode() {
+            Mode::Strict => self.reader.utf8_string(),
+            Mode::Lenient => match self.reader.peek_tag()? {
+                Tag::PRINTABLE_STRING => self.reader.printable_string(),
+                Tag::IA5_STRING => self.reader.ia5_string(),
+                Tag::VISIBLE_STRING => self.reader.visible_string(),
+                _ => self.reader.utf8_string(),
+            },
+        }
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(message: T) -> Error {
+        Error::custom(message)
+    }
+}
+
+// Narrow an integer that has been read as a wider type.
+fn narrow<T, U: TryFrom<T>>(value: T) -> Result<U> {
+    U::try_from(value).map_err(|_| ErrorKind::IntegerOverflow.into())
+}
+
+impl<'de, 'r, 'x> de::Deserializer<'de> for &'x mut Deserializer<'r, 'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.reader.peek_tag()? {
+            Tag::BOOLEAN => self.deserialize_bool(visitor),
+            Tag::INTEGER => {
+                let value = self.reader.i128()?;
+                if let Ok(value) = narrow(value) {
+                    visitor.visit_i64(value)
+                } else if let Ok(value) = narrow(value) {
+                    visitor.visit_u64(value)
+                } else {
+                    visitor.visit_i128(value)
+                }
+            }
+            Tag::REAL => self.deserialize_f64(visitor),
+            Tag::UTF8_STRING => self.deserialize_str(visitor),
+            Tag::PRINTABLE_STRING | Tag::IA5_STRING | Tag::VISIBLE_STRING => {
+                self.deserialize_str(visitor)
+            }
+            Tag::OCTET_STRING => self.deserialize_bytes(visitor),
+            Tag::NULL => self.deserialize_unit(visitor),
+            Tag::SEQUENCE => self.deserialize_seq(visitor),
+            _ => Err(ErrorKind::UnknownTag.into()),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_bool(self.reader.boolean()?)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i8(narrow(self.reader.i64()?)?)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i16(narrow(self.reader.i64()?)?)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i32(narrow(self.reader.i64()?)?)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i64(self.reader.i64()?)
+    }
+
+    fn deserialize_i128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_i128(self.reader.i128()?)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u8(self.reader.u8()?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u16(self.reader.u16()?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u32(self.reader.u32()?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.reader.u64()?)
+    }
+
+    fn deserialize_u128<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u128(self.reader.u128()?)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f32(self.reader.real()? as f32)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f64(self.reader.real()?)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let mut chars = self.string()?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(ErrorKind::WrongValue.into()),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_borrowed_str(self.string()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.reader.octet_string()? {
+            Cow::Borrowed(value) => visitor.visit_borrowed_bytes(value),
+            Cow::Owned(value) => visitor.visit_byte_buf(value),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match !self.reader.at_end() && self.reader.peek_tag()? == Tag::NULL {
+            true => {
+                self.reader.null()?;
+                visitor.visit_none()
+            }
+            false => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.reader.null()?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.reader.sequence(|reader| visitor.visit_seq(Elements { reader }))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.reader.sequence(|reader| visitor.visit_map(Entries { reader, value: None }))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let tag = self.reader.peek_tag()?;
+        match (tag.class(), tag.is_constructed()) {
+            (Class::ContextSpecific, true) => self.reader.nested(tag, |reader| {
+                visitor.visit_enum(Variant { reader, index: tag.number() })
+            }),
+            _ => Err(ErrorKind::UnknownTag.into()),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.reader.skip()?;
+        visitor.visit_unit()
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+// The elements of a `SEQUENCE`.
+struct Elements<'r, 'a: 'r> {
+    reader: &'r mut Reader<'a>,
+}
+
+impl<'de, 'r> de::SeqAccess<'de> for Elements<'r, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        match self.reader.at_end() {
+            true => Ok(None),
+            false => seed.deserialize(&mut Deserializer::new(self.reader)).map(Some),
+        }
+    }
+}
+
+// The entries of a map, each a `SEQUENCE { key, value }`.
+struct Entries<'r, 'a: 'r> {
+    reader: &'r mut Reader<'a>,
+    // The value of the entry whose key has been read, and the config inside the entry.
+    value: Option<(&'a [u8], ParserConfig)>,
+}
+
+impl<'de, 'r> de::MapAccess<'de> for Entries<'r, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.reader.at_end() {
+            return Ok(None);
+        }
+        // The value is read by a later call, so it is kept to be read then.
+        let (key, value) = self.reader.sequence(|reader| {
+            let key = seed.deserialize(&mut Deserializer::new(reader))?;
+            Ok((key, (reader.raw()?, reader.config())))
+        })?;
+        self.value = Some(value);
+        Ok(Some(key))
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let (value, config) = self.value.take().expect("next_value is called after next_key");
+        Reader::read_all(Input::from(value), config, |reader| {
+            seed.deserialize(&mut Deserializer::new(reader))
+        })
+        .map_err(|err| err.within(Tag::SEQUENCE))
+    }
+}
+
+// The contents of the `[index]` element of an enum variant.
+struct Variant<'r, 'a: 'r> {
+    reader: &'r mut Reader<'a>,
+    index: u32,
+}
+
+impl<'de, 'r> de::EnumAccess<'de> for Variant<'r, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let index: U32Deserializer<Error> = self.index.into_deserializer();
+        Ok((seed.deserialize(index)?, self))
+    }
+}
+
+impl<'de, 'r> de::VariantAccess<'de> for Variant<'r, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        self.reader.null()
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(&mut Deserializer::new(self.reader))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(&mut Deserializer::new(self.reader), visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(&mut Deserializer::new(self.reader), visitor)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ser::to_vec;
+    use std::collections::BTreeMap;
+    use std::fmt::Debug;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Unit;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Wrapper(u8);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Key<'a> {
+        id: &'a str,
+        #[serde(with = "serde_bytes")]
+        value: Vec<u8>,
+        expires: Option<i32>,
+        scheme: Scheme,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Scheme {
+        Ed25519,
+        Rsa(u16),
+        Pair(bool, bool),
+        Named { bits: u16 },
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(untagged)]
+    enum Any {
+        Flag(bool),
+        Number(i64),
+        Text(String),
+        List(Vec<Any>),
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Short {
+        id: String,
+    }
+
+    mod serde_bytes {
+        use serde::de::{Deserializer, Visitor};
+        use serde::Serializer;
+        use std::fmt;
+
+        pub fn serialize<S: Serializer>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(value)
+        }
+
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            struct Bytes;
+
+            impl<'de> Visitor<'de> for Bytes {
+                type Value = Vec<u8>;
+
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("bytes")
+                }
+
+                fn visit_bytes<E>(self, value: &[u8]) -> Result<Vec<u8>, E> {
+                    Ok(value.to_vec())
+                }
+            }
+
+            deserializer.deserialize_bytes(Bytes)
+        }
+    }
+
+    fn round_trip<'a, T>(value: T, buf: &'a mut Vec<u8>)
+    where
+        T: ::serde::Serialize + Deserialize<'a> + PartialEq + Debug,
+    {
+        *buf = to_vec(&value).unwrap();
+        assert_eq!(from_slice::<T>(buf), Ok(value));
+    }
+
+    #[test]
+    fn deserialize_values() {
+        let mut buf = Vec::new();
+        round_trip(true, &mut buf);
+        round_trip(-129i16, &mut buf);
+        round_trip(u64::MAX, &mut buf);
+        round_trip(u128::MAX, &mut buf);
+        round_trip(i128::MIN, &mut buf);
+        round_trip(1.5f32, &mut buf);
+        round_trip('é', &mut buf);
+        round_trip("a".to_owned(), &mut buf);
+        round_trip((), &mut buf);
+        round_trip(Unit, &mut buf);
+        round_trip(Wrapper(7), &mut buf);
+        round_trip(Some(1u8), &mut buf);
+        round_trip(None::<u8>, &mut buf);
+        round_trip((1u8, vec![true, false]), &mut buf);
+
+        let mut map = BTreeMap::new();
+        map.insert("a".to_owned(), Some(1u8));
+        map.insert("b".to_owned(), None);
+        round_trip(map, &mut buf);
+
+        assert_eq!(from_slice::<&str>(b"\x0c\x02hi"), Ok("hi"));
+        assert_eq!(from_slice::<u8>(&[0x02, 0x01, 0x01]), Ok(1));
+    }
+
+    #[test]
+    fn deserialize_structs() {
+        let key = Key { id: "k", value: vec![0], expires: Some(-1), scheme: Scheme::Ed25519 };
+        let encoded = to_vec(&key).unwrap();
+        assert_eq!(from_slice::<Key>(&encoded), Ok(key));
+
+        let mut buf = Vec::new();
+        round_trip(Scheme::Rsa(2048), &mut buf);
+        round_trip(Scheme::Pair(true, false), &mut buf);
+        round_trip(Scheme::Named { bits: 1 }, &mut buf);
+        round_trip(vec![Scheme::Ed25519, Scheme::Rsa(1)], &mut buf);
+
+        // SEQUENCE { BOOLEAN TRUE, INTEGER 300, UTF8String "a", SEQUENCE {} }
+        let encoded = [
+            0x30, 0x0c, 0x01, 0x01, 0xff, 0x02, 0x02, 0x01, 0x2c, 0x0c, 0x01, 0x61, 0x30, 0x00,
+        ];
+        let any = vec![
+            Any::Flag(true),
+            Any::Number(300),
+            Any::Text("a".to_owned()),
+            Any::List(vec![]),
+        ];
+        assert_eq!(from_slice(&encoded), Ok(Any::List(any)));
+    }
+
+    #[test]
+    fn deserialize_strictness() {
+        assert_eq!(from_slice::<&str>(b"\x13\x02hi"), Err(ErrorKind::WrongTag {
+            expected: Tag::UTF8_STRING,
+            found: 0x13,
+        }.into()));
+        let lenient = ParserConfig::BER;
+        assert_eq!(from_slice_with_config::<&str>(b"\x13\x02hi", lenient), Ok("hi"));
+        assert_eq!(from_slice_with_config::<&str>(b"\x16\x02hi", lenient), Ok("hi"));
+
+        // A long form length and a constructed OCTET STRING are only BER.
+        let encoded = [
+            0x30, 0x81, 0x10,
+                0x0c, 0x00,
+                0x24, 0x06, 0x04, 0x01, 0x61, 0x04, 0x01, 0x62,
+                0x05, 0x00,
+                0xa0, 0x02, 0x05, 0x00,
+        ];
+        let key = from_slice_with_config::<Key>(&encoded, lenient).unwrap();
+        assert_eq!(key.value, b"ab");
+        assert!(from_slice::<Key>(&encoded).is_err());
+
+        let nested = to_vec(&vec![vec![vec![1u8]]]).unwrap();
+        let config = ParserConfig::DER.with_max_depth(2);
+        let err = from_slice_with_config::<Vec<Vec<Vec<u8>>>>(&nested, config).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NestingTooDeep);
+    }
+
+    #[test]
+    fn deserialize_errors() {
+        assert_eq!(from_slice::<i8>(&[0x02, 0x01, 0x80]), Ok(-128));
+        let overflow = Err(ErrorKind::IntegerOverflow.into());
+        assert_eq!(from_slice::<i8>(&[0x02, 0x02, 0x00, 0x80]), overflow);
+        assert_eq!(from_slice::<char>(b"\x0c\x02hi"), Err(ErrorKind::WrongValue.into()));
+        let trailing = Err(ErrorKind::TrailingData.into());
+        assert_eq!(from_slice::<bool>(&[0x01, 0x01, 0xff, 0x05, 0x00]), trailing);
+        assert_eq!(from_slice::<Scheme>(&[0x30, 0x00]), Err(ErrorKind::UnknownTag.into()));
+
+        let err = from_slice::<Scheme>(&[0xa7, 0x02, 0x05, 0x00]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Custom);
+        assert_eq!(err.path(), &[Tag::new(Class::ContextSpecific, true, 7)]);
+
+        // A struct with fewer fields than were written, and with more.
+        let encoded = to_vec(&("a", 1u8)).unwrap();
+        let err = from_slice::<Short>(&encoded).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnconsumedContents);
+        let err = from_slice::<Key>(&encoded).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WrongTag { expected: Tag::OCTET_STRING, found: 0x02 });
+        assert_eq!(err.path(), &[Tag::SEQUENCE]);
+
+        // An error in the value of a map entry.
+        let encoded = [0x30, 0x08, 0x30, 0x06, 0x0c, 0x01, 0x61, 0x01, 0x01, 0xff];
+        let err = from_slice::<BTreeMap<String, u8>>(&encoded).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WrongTag { expected: Tag::INTEGER, found: 0x01 });
+        assert_eq!(err.path(), &[Tag::SEQUENCE, Tag::SEQUENCE]);
+        assert_eq!(err.offset(&encoded), Some(7));
+    }
+}
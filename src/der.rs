@@ -16,373 +16,2601 @@
 //!
 //! This module contains the foundational parts of an ASN.1 DER parser.
 
-#[cfg(feature = "cli")]
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::cell::Cell;
 use std::fmt::{self, Display, Formatter};
-use untrusted::{Reader, Input};
+use std::cmp::Ordering;
+use untrusted::{Input, Reader};
 
-use {Error, Result};
+use {Error, ErrorKind, Result};
+#[cfg(feature = "std")]
+use ParserConfig;
 
 const CONSTRUCTED: u8 = 1 << 5;
-const CONTEXT_SPECIFIC: u8 = 2 << 6;
-
-/// ASN.1 Tags
-#[derive(Debug, Clone, Copy, PartialEq)]
-#[repr(u8)]
-pub enum Tag {
-    Eoc = 0x00,
-    Boolean = 0x01,
-    Integer = 0x02,
-    BitString = 0x03,
-    OctetString = 0x04,
-    Null = 0x05,
-    Oid = 0x06,
-    Sequence = CONSTRUCTED | 0x10, // 0x30
-    UtcTime = 0x17,
-    GeneralizedTime = 0x18,
-    ContextSpecificConstructed0 = CONTEXT_SPECIFIC | CONSTRUCTED | 0,
-    ContextSpecificConstructed1 = CONTEXT_SPECIFIC | CONSTRUCTED | 1,
-    ContextSpecificConstructed2 = CONTEXT_SPECIFIC | CONSTRUCTED | 2,
-    ContextSpecificConstructed3 = CONTEXT_SPECIFIC | CONSTRUCTED | 3,
-}
-
-#[cfg(feature = "cli")]
-impl Display for Tag {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        let s = match *self {
-            Tag::Eoc => "EOC",
-            Tag::Boolean => "BOOLEAN",
-            Tag::Integer => "INTEGER",
-            Tag::BitString => "BIT STRING",
-            Tag::OctetString => "OCTET STRING",
-            Tag::Null => "NULL",
-            Tag::Oid => "OBJECT IDENTIFIER",
-            Tag::Sequence => "SEQUENCE",
-            Tag::UtcTime => "UTC TIME",
-            Tag::GeneralizedTime => "GENERALIZED TIME",
-            Tag::ContextSpecificConstructed0 => "CONTEXT SPECIFIC CONSTRUCTED 0",
-            Tag::ContextSpecificConstructed1 => "CONTEXT SPECIFIC CONSTRUCTED 1",
-            Tag::ContextSpecificConstructed2 => "CONTEXT SPECIFIC CONSTRUCTED 2",
-            Tag::ContextSpecificConstructed3 => "CONTEXT SPECIFIC CONSTRUCTED 3",
-        };
-        write!(f, "{}", s)
+
+/// The class of a tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Class {
+    Universal,
+    Application,
+    ContextSpecific,
+    Private,
+}
+
+impl Class {
+    // The class bits of an identifier octet.
+    fn bits(self) -> u8 {
+        match self {
+            Class::Universal => 0x00,
+            Class::Application => 0x40,
+            Class::ContextSpecific => 0x80,
+            Class::Private => 0xc0,
+        }
+    }
+
+    fn from_bits(byte: u8) -> Class {
+        match byte & 0xc0 {
+            0x00 => Class::Universal,
+            0x40 => Class::Application,
+            0x80 => Class::ContextSpecific,
+            _ => Class::Private,
+        }
     }
 }
 
-#[cfg(feature = "cli")]
+/// How strictly the encoding rules are applied when parsing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Only accept the canonical DER encoding.
+    Strict,
+    /// Also accept encodings that are valid BER but not valid DER.
+    Lenient,
+}
+
+/// An ASN.1 tag, made up of its class, whether the encoding is constructed, and its number.
+///
+/// The universal tags are available as constants.
+///
+/// ```
+/// use derp::{Class, Tag};
+///
+/// let tag = Tag::new(Class::ContextSpecific, true, 0);
+/// assert_eq!(tag, Tag::CONTEXT_SPECIFIC_CONSTRUCTED_0);
+/// assert_eq!(tag.to_string(), "[0]");
+/// assert_eq!(Tag::SEQUENCE.class(), Class::Universal);
+/// assert!(Tag::SEQUENCE.is_constructed());
+/// assert_eq!(Tag::SEQUENCE.number(), 0x10);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tag {
+    class: Class,
+    constructed: bool,
+    number: u32,
+}
+
 impl Tag {
-    pub fn from_byte(byte: u8) -> Result<Self> {
-        match byte {
-            x if x == Tag::Eoc as u8 => Ok(Tag::Eoc),
-            x if x == Tag::Boolean as u8 => Ok(Tag::Boolean),
-            x if x == Tag::Integer as u8 => Ok(Tag::Integer),
-            x if x == Tag::BitString as u8 => Ok(Tag::BitString),
-            x if x == Tag::OctetString as u8 => Ok(Tag::OctetString),
-            x if x == Tag::Null as u8 => Ok(Tag::Null),
-            x if x == Tag::Oid as u8 => Ok(Tag::Oid),
-            x if x == Tag::Sequence as u8 => Ok(Tag::Sequence),
-            x if x == Tag::UtcTime as u8 => Ok(Tag::UtcTime),
-            x if x == Tag::GeneralizedTime as u8 => Ok(Tag::GeneralizedTime),
-            x if x == Tag::ContextSpecificConstructed0 as u8 => Ok(Tag::ContextSpecificConstructed0),
-            x if x == Tag::ContextSpecificConstructed1 as u8 => Ok(Tag::ContextSpecificConstructed1),
-            x if x == Tag::ContextSpecificConstructed2 as u8 => Ok(Tag::ContextSpecificConstructed2),
-            x if x == Tag::ContextSpecificConstructed3 as u8 => Ok(Tag::ContextSpecificConstructed3),
-            _ => Err(Error::UnknownTag)
+    pub const EOC: Tag = Tag::universal(0x00, false);
+    pub const BOOLEAN: Tag = Tag::universal(0x01, false);
+    pub const INTEGER: Tag = Tag::universal(0x02, false);
+    pub const BIT_STRING: Tag = Tag::universal(0x03, false);
+    pub const OCTET_STRING: Tag = Tag::universal(0x04, false);
+    pub const NULL: Tag = Tag::universal(0x05, false);
+    pub const OID: Tag = Tag::universal(0x06, false);
+    pub const REAL: Tag = Tag::universal(0x09, false);
+    pub const ENUMERATED: Tag = Tag::universal(0x0a, false);
+    pub const UTF8_STRING: Tag = Tag::universal(0x0c, false);
+    pub const SEQUENCE: Tag = Tag::universal(0x10, true);
+    pub const SET: Tag = Tag::universal(0x11, true);
+    pub const NUMERIC_STRING: Tag = Tag::universal(0x12, false);
+    pub const PRINTABLE_STRING: Tag = Tag::universal(0x13, false);
+    pub const TELETEX_STRING: Tag = Tag::universal(0x14, false);
+    pub const IA5_STRING: Tag = Tag::universal(0x16, false);
+    pub const UTC_TIME: Tag = Tag::universal(0x17, false);
+    pub const GENERALIZED_TIME: Tag = Tag::universal(0x18, false);
+    pub const VISIBLE_STRING: Tag = Tag::universal(0x1a, false);
+    pub const UNIVERSAL_STRING: Tag = Tag::universal(0x1c, false);
+    pub const BMP_STRING: Tag = Tag::universal(0x1e, false);
+    pub const CONTEXT_SPECIFIC_CONSTRUCTED_0: Tag = Tag::new(Class::ContextSpecific, true, 0);
+    pub const CONTEXT_SPECIFIC_CONSTRUCTED_1: Tag = Tag::new(Class::ContextSpecific, true, 1);
+    pub const CONTEXT_SPECIFIC_CONSTRUCTED_2: Tag = Tag::new(Class::ContextSpecific, true, 2);
+    pub const CONTEXT_SPECIFIC_CONSTRUCTED_3: Tag = Tag::new(Class::ContextSpecific, true, 3);
+
+    /// Create a tag from its class, whether it is constructed, and its number.
+    pub const fn new(class: Class, constructed: bool, number: u32) -> Tag {
+        Tag { class, constructed, number }
+    }
+
+    const fn universal(number: u32, constructed: bool) -> Tag {
+        Tag::new(Class::Universal, constructed, number)
+    }
+
+    /// The class of the tag.
+    pub fn class(self) -> Class {
+        self.class
+    }
+
+    /// Whether the tag uses the constructed encoding.
+    pub fn is_constructed(self) -> bool {
+        self.constructed
+    }
+
+    /// The number of the tag within its class.
+    pub fn number(self) -> u32 {
+        self.number
+    }
+
+    // The identifier octets of the tag, using high tag number form for numbers above 30. Returns
+    // a buffer and the number of octets used.
+    pub(crate) fn identifier_octets(self) -> ([u8; 6], usize) {
+        let mut octets = [0; 6];
+        let constructed = if self.constructed { CONSTRUCTED } else { 0 };
+        octets[0] = self.class.bits() | constructed;
+        if self.number <= 30 {
+            octets[0] |= self.number as u8;
+            return (octets, 1);
+        }
+
+        octets[0] |= 0x1f;
+        let mut digits = 1;
+        while digits < 5 && self.number >> (7 * digits) != 0 {
+            digits += 1;
         }
+        for i in 0..digits {
+            let more = if i + 1 < digits { 0x80 } else { 0 };
+            octets[1 + i] = more | ((self.number >> (7 * (digits - 1 - i))) as u8 & 0x7f);
+        }
+        (octets, 1 + digits)
+    }
+
+    // The name of a universal tag that derp knows about.
+    fn name(self) -> Option<&'static str> {
+        let name = match self {
+            Tag::EOC => "EOC",
+            Tag::BOOLEAN => "BOOLEAN",
+            Tag::INTEGER => "INTEGER",
+            Tag::BIT_STRING => "BIT STRING",
+            Tag::OCTET_STRING => "OCTET STRING",
+            Tag::NULL => "NULL",
+            Tag::OID => "OBJECT IDENTIFIER",
+            Tag::REAL => "REAL",
+            Tag::ENUMERATED => "ENUMERATED",
+            Tag::UTF8_STRING => "UTF8 STRING",
+            Tag::SEQUENCE => "SEQUENCE",
+            Tag::SET => "SET",
+            Tag::NUMERIC_STRING => "NUMERIC STRING",
+            Tag::PRINTABLE_STRING => "PRINTABLE STRING",
+            Tag::TELETEX_STRING => "TELETEX STRING",
+            Tag::IA5_STRING => "IA5 STRING",
+            Tag::UTC_TIME => "UTC TIME",
+            Tag::GENERALIZED_TIME => "GENERALIZED TIME",
+            Tag::VISIBLE_STRING => "VISIBLE STRING",
+            Tag::UNIVERSAL_STRING => "UNIVERSAL STRING",
+            Tag::BMP_STRING => "BMP STRING",
+            _ => return None,
+        };
+        Some(name)
     }
 }
 
+impl Display for Tag {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(name) = self.name() {
+            return write!(f, "{}", name);
+        }
+        match self.class {
+            Class::Universal => write!(f, "[UNIVERSAL {}]", self.number),
+            Class::Application => write!(f, "[APPLICATION {}]", self.number),
+            Class::ContextSpecific => write!(f, "[{}]", self.number),
+            Class::Private => write!(f, "[PRIVATE {}]", self.number),
+        }
+    }
+}
 
 /// Read a tag and return it's value. Errors when the expect and actual tag do not match.
 pub fn expect_tag_and_get_value<'a>(
     input: &mut Reader<'a>,
     tag: Tag,
 ) -> Result<Input<'a>> {
-    let (actual_tag, inner) = read_tag_and_get_value(input)?;
-    if tag as u8 != actual_tag {
-        return Err(Error::WrongTag);
-    }
-    Ok(inner)
+    expect_tag_and_get_value_with_mode(input, tag, Mode::Strict)
 }
 
-/// Read the next tag, and return it and its value.
-pub fn read_tag_and_get_value<'a>(
+/// Read a tag and return its value, as `expect_tag_and_get_value` does. In `Mode::Lenient` lengths
+/// that are not in their minimal form are accepted.
+pub fn expect_tag_and_get_value_with_mode<'a>(
+    input: &mut Reader<'a>,
+    tag: Tag,
+    mode: Mode,
+) -> Result<Input<'a>> {
+    expect_tag_with_limit(input, tag, mode, usize::MAX)
+}
+
+// Read a tag and return its value, rejecting contents longer than `max_len`.
+pub(crate) fn expect_tag_with_limit<'a>(
     input: &mut Reader<'a>,
-) -> Result<(u8, Input<'a>)> {
-    let tag = input.read_byte()?;
-    if (tag & 0x1F) == 0x1F {
-        return Err(Error::HighTagNumberForm);
+    tag: Tag,
+    mode: Mode,
+    max_len: usize,
+) -> Result<Input<'a>> {
+    let start = position(input);
+    let (actual_tag, inner) = read_any_with_limit(input, mode, max_len)?;
+    if tag != actual_tag {
+        let kind = ErrorKind::WrongTag {
+            expected: tag,
+            found: actual_tag.identifier_octets().0[0],
+        };
+        return Err(Error::from(kind).at(start));
     }
+    Ok(inner)
+}
 
-    // If the high order bit of the first byte is set to zero then the length
-    // is encoded in the seven remaining bits of that byte. Otherwise, those
-    // seven bits represent the number of bytes used to encode the length.
-    let length = match input.read_byte()? {
-        n if (n & 0x80) == 0 => n as usize,
-        0x81 => {
-            let second_byte = input.read_byte()?;
-            if second_byte < 128 {
-                return Err(Error::NonCanonical);
-            }
-            second_byte as usize
-        }
-        0x82 => {
-            let second_byte = input.read_byte()? as usize;
-            let third_byte = input.read_byte()? as usize;
-            let combined = (second_byte << 8) | third_byte;
-            if combined < 256 {
-                return Err(Error::NonCanonical);
-            }
-            combined
+// An empty `Input` at the current position of `input`, used to locate errors.
+pub(crate) fn position<'a>(input: &Reader<'a>) -> Option<Input<'a>> {
+    input.get_input_between_marks(input.mark(), input.mark()).ok()
+}
+
+// Apply `decoder` to all of `value`, the contents of an element tagged `tag`, recording where any
+// error happened.
+pub(crate) fn decode_contents<'a, F, R>(tag: Tag, value: Input<'a>, decoder: F) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+{
+    let mut reader = Reader::new(value);
+    let result = decoder(&mut reader).and_then(|decoded| {
+        if !reader.at_end() {
+            return Err(ErrorKind::UnconsumedContents.into());
         }
-        _ => return Err(Error::LongLengthNotSupported),
-    };
+        Ok(decoded)
+    });
+    // A failed `skip_and_get_input` leaves `reader` past its end, which has no position.
+    let end = Input::from(&value.as_slice_less_safe()[value.len()..]);
+    result.map_err(|err| err.at(position(&reader).or(Some(end))).within(tag))
+}
 
-    let inner = input.skip_and_get_input(length)?;
-    Ok((tag, inner))
+// The tag whose first identifier octet is `first`, unless it is in high tag number form.
+fn low_form_tag(first: u8) -> Option<Tag> {
+    if first & 0x1f == 0x1f {
+        return None;
+    }
+    let constructed = first & CONSTRUCTED != 0;
+    Some(Tag::new(Class::from_bits(first), constructed, u32::from(first & 0x1f)))
 }
 
-pub fn read_null<'a>(input: &mut Reader<'a>) -> Result<()> {
-    expect_tag_and_get_value(input, Tag::Null)
-        .map(|_| ())
+// Read the identifier octets of a tag.
+pub(crate) fn read_tag(input: &mut Reader) -> Result<Tag> {
+    let first = input.read_byte()?;
+    if let Some(tag) = low_form_tag(first) {
+        return Ok(tag);
+    }
+    let class = Class::from_bits(first);
+    let constructed = first & CONSTRUCTED != 0;
+
+    // In high tag number form the number follows in base 128, most significant digit first, with
+    // the high bit of each octet set on all but the last.
+    let mut number: u32 = 0;
+    loop {
+        let byte = input.read_byte()?;
+        if number == 0 && byte == 0x80 {
+            return Err(ErrorKind::NonCanonical.into());
+        }
+        if number > (u32::MAX >> 7) {
+            return Err(ErrorKind::HighTagNumberForm.into());
+        }
+        number = (number << 7) | u32::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    if number < 31 {
+        return Err(ErrorKind::NonCanonical.into());
+    }
+    Ok(Tag::new(class, constructed, number))
 }
 
-/// Read a `BIT STRING` with leading byte `0x00` signifying no unused bits.
+/// Read the next tag, and return it and its value.
+///
+/// This is the same as `read_any`.
+#[inline]
+pub fn read_tag_and_get_value<'a>(
+    input: &mut Reader<'a>,
+) -> Result<(Tag, Input<'a>)> {
+    read_any(input)
+}
+
+/// Read whatever element comes next, and return its tag and value.
+///
+/// This is useful for `ANY` fields, such as the parameters of an `AlgorithmIdentifier`.
 ///
 /// ```
 /// extern crate derp;
 /// extern crate untrusted;
 ///
+/// use derp::Tag;
 /// use untrusted::Input;
 ///
-/// const BIT_STRING: &'static [u8] = &[0x03, 0x04, 0x00, 0x01, 0x02, 0x03];
+/// // AlgorithmIdentifier { rsaEncryption, NULL }
+/// const ALGORITHM: &'static [u8] = &[
+///     0x30, 0x0d,
+///         0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01,
+///         0x05, 0x00,
+/// ];
 ///
 /// fn main() {
-///     let input = Input::from(BIT_STRING);
-///     let bits = input.read_all(derp::Error::Read, |input| {
-///         derp::bit_string_with_no_unused_bits(input)
+///     let input = Input::from(ALGORITHM);
+///     let (tag, parameters) = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::nested(input, Tag::SEQUENCE, |input| {
+///             let _oid = derp::expect_tag_and_get_value(input, Tag::OID)?;
+///             derp::read_any(input)
+///         })
 ///     }).unwrap();
-///     assert_eq!(bits, Input::from(&[0x01, 0x02, 0x03]));
+///
+///     assert_eq!(tag, Tag::NULL);
+///     assert!(parameters.is_empty());
 /// }
 /// ```
-pub fn bit_string_with_no_unused_bits<'a>(
-    input: &mut Reader<'a>,
-) -> Result<Input<'a>> {
-    nested(input, Tag::BitString, |value| {
-        let unused_bits_at_end = value.read_byte()?;
-        if unused_bits_at_end != 0 {
-            return Err(Error::NonZeroUnusedBits);
-        }
-        Ok(value.skip_to_end())
-    })
+pub fn read_any<'a>(input: &mut Reader<'a>) -> Result<(Tag, Input<'a>)> {
+    read_any_with_mode(input, Mode::Strict)
 }
 
-/// Return the value of the given tag and apply a decoding function to it.
+/// Read whatever element comes next, as `read_any` does. In `Mode::Lenient` lengths that are not
+/// in their minimal form, as BER allows, are accepted. The indefinite form is rejected in either
+/// mode since its contents cannot be found without parsing them, use `canonicalize` for input that
+/// has it.
 ///
 /// ```
 /// extern crate derp;
 /// extern crate untrusted;
 ///
-/// use derp::Tag;
+/// use derp::{Mode, Tag};
 /// use untrusted::Input;
 ///
-/// const NESTED: &'static [u8] = &[
-///     0x30, 0x14,                                 // seq
-///         0x30, 0x0c,                             // seq
-///             0x02, 0x04, 0x0a, 0x0b, 0x0c, 0x0d, // x
-///             0x02, 0x04, 0x1a, 0x1b, 0x1c, 0x1d, // y
-///         0x02, 0x04, 0x01, 0x02, 0x03, 0x04,     // z
-/// ];
-/// fn main () {
-///     let input = Input::from(NESTED);
-///     let (x, y, z) = input.read_all(derp::Error::Read, |input| {
-///         derp::nested(input, Tag::Sequence, |input| {
-///             let (x, y) = derp::nested(input, Tag::Sequence, |input| {
-///                 let x = derp::positive_integer(input)?;
-///                 let y = derp::positive_integer(input)?;
-///                 Ok((x, y))
-///             })?;
-///             let z = derp::positive_integer(input)?;
-///             Ok((x, y, z))
-///         })
+/// fn main() {
+///     // NULL with its length in the long form
+///     let input = Input::from(&[0x05, 0x81, 0x00]);
+///     let (tag, value) = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::read_any_with_mode(input, Mode::Lenient)
 ///     }).unwrap();
-///
-///     assert_eq!(x, Input::from(&[0x0a, 0x0b, 0x0c, 0x0d]));
-///     assert_eq!(y, Input::from(&[0x1a, 0x1b, 0x1c, 0x1d]));
-///     assert_eq!(z, Input::from(&[0x01, 0x02, 0x03, 0x04]));
+///     assert_eq!(tag, Tag::NULL);
+///     assert!(value.is_empty());
 /// }
 /// ```
-// TODO: investigate taking decoder as a reference to reduce generated code
-// size.
-pub fn nested<'a, F, R>(input: &mut Reader<'a>, tag: Tag, decoder: F) -> Result<R>
-where
-    F: FnOnce(&mut Reader<'a>) -> Result<R>,
-{
-    let inner = expect_tag_and_get_value(input, tag)?;
-    inner.read_all(Error::Read, decoder)
+pub fn read_any_with_mode<'a>(input: &mut Reader<'a>, mode: Mode) -> Result<(Tag, Input<'a>)> {
+    read_any_with_limit(input, mode, usize::MAX)
 }
 
-/// Return a non-negative integer.
-pub fn nonnegative_integer<'a>(
+// Read whatever element comes next, rejecting contents longer than `max_len` with
+// `ErrorKind::LengthExceedsLimit` before skipping over them.
+pub(crate) fn read_any_with_limit<'a>(
     input: &mut Reader<'a>,
-    min_value: u8,
-) -> Result<Input<'a>> {
-    // Verify that |input|, which has had any leading zero stripped off, is the
-    // encoding of a value of at least |min_value|.
-    fn check_minimum(input: Input, min_value: u8) -> Result<()> {
-        input.read_all(Error::Read, |input| {
-            let first_byte = input.read_byte()?;
-            if input.at_end() && first_byte < min_value {
-                return Err(Error::LessThanMinimum);
-            }
-            let _ = input.skip_to_end();
-            Ok(())
-        })
-    }
-
-    let value = expect_tag_and_get_value(input, Tag::Integer)?;
+    mode: Mode,
+    max_len: usize,
+) -> Result<(Tag, Input<'a>)> {
+    let start = position(input);
+    read_element(input, mode, max_len).map_err(|err| err.at(start))
+}
 
-    value.read_all(Error::Read, |input| {
-        // Empty encodings are not allowed.
-        let first_byte = input.read_byte()?;
+// Read the identifier, length and contents octets of an element.
+fn read_element<'a>(
+    input: &mut Reader<'a>,
+    mode: Mode,
+    max_len: usize,
+) -> Result<(Tag, Input<'a>)> {
+    if input.at_end() {
+        return Err(ErrorKind::EmptyInput.into());
+    }
+    let (tag, length) = read_header(input, mode, max_len)?;
+    let inner = input.skip_and_get_input(length)?;
+    Ok((tag, inner))
+}
 
-        if first_byte == 0 {
-            if input.at_end() {
-                // |value| is the legal encoding of zero.
-                if min_value > 0 {
-                    return Err(Error::LessThanMinimum);
-                }
-                return Ok(value);
-            }
+// Read the identifier and length octets of an element, returning its tag and the length of its
+// contents.
+pub(crate) fn read_header(input: &mut Reader, mode: Mode, max_len: usize) -> Result<(Tag, usize)> {
+    let tag = read_tag(input)?;
+    // The indefinite form is not allowed in DER.
+    let length = read_length(input, mode)?
+        .ok_or(Error::from(ErrorKind::LongLengthNotSupported))?;
+    if length > max_len {
+        return Err(ErrorKind::LengthExceedsLimit.into());
+    }
+    Ok((tag, length))
+}
 
-            let r = input.skip_to_end();
-            r.read_all(Error::Read, |input| {
-                let second_byte = input.read_byte()?;
-                if (second_byte & 0x80) == 0 {
-                    // A leading zero is only allowed when the value's high bit
-                    // is set.
-                    return Err(Error::LeadingZero);
-                }
-                let _ = input.skip_to_end();
-                Ok(())
-            })?;
-            check_minimum(r, min_value)?;
-            return Ok(r);
+// Read the length octets of an element, returning `None` for the indefinite form. In
+// `Mode::Lenient` lengths that are not in their minimal form are accepted.
+pub(crate) fn read_length(input: &mut Reader, mode: Mode) -> Result<Option<usize>> {
+    // If the high order bit of the first byte is set to zero then the length
+    // is encoded in the seven remaining bits of that byte. Otherwise, those
+    // seven bits represent the number of bytes used to encode the length.
+    let num_bytes = match input.read_byte()? {
+        n if (n & 0x80) == 0 => return Ok(Some(n as usize)),
+        0x80 => return Ok(None),
+        n => (n & 0x7f) as usize,
+    };
+    if mode == Mode::Strict && num_bytes > ::std::mem::size_of::<usize>() {
+        return Err(ErrorKind::LengthOverflow.into());
+    }
+    let mut length: usize = 0;
+    for i in 0..num_bytes {
+        let byte = input.read_byte()?;
+        if mode == Mode::Strict && i == 0 && byte == 0 {
+            return Err(ErrorKind::NonCanonical.into());
         }
-
-        // Negative values are not allowed.
-        if (first_byte & 0x80) != 0 {
-            return Err(Error::NegativeValue);
+        if length > (usize::MAX >> 8) {
+            return Err(ErrorKind::LengthOverflow.into());
         }
+        length = (length << 8) | byte as usize;
+    }
+    if mode == Mode::Strict && length < 128 {
+        return Err(ErrorKind::NonCanonical.into());
+    }
+    Ok(Some(length))
+}
 
-        let _ = input.skip_to_end();
-        check_minimum(value, min_value)?;
-        Ok(value)
-    })
+/// Return the tag of the next element without consuming any input. Tags in high tag number form
+/// (numbers above 30) are read in full, and fail with `ErrorKind::HighTagNumberForm` only if the
+/// number does not fit in a `u32`.
+///
+/// `input` is only borrowed mutably because `Reader` can peek at no more than the next byte, so it
+/// is read ahead and then put back where it was.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::Tag;
+/// use untrusted::Input;
+///
+/// fn main() {
+///     let input = Input::from(&[0x05, 0x00]);
+///     input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         assert_eq!(derp::peek_tag(input)?, Tag::NULL);
+///         derp::read_null(input)
+///     }).unwrap();
+/// }
+/// ```
+pub fn peek_tag(input: &mut Reader) -> Result<Tag> {
+    tag_at(lookahead(input))
 }
 
-/// Parse as integer with a value in the in the range [0, 255], returning its
-/// numeric value. This is typically used for parsing version numbers.
-#[inline]
-pub fn small_nonnegative_integer(input: &mut Reader) -> Result<u8> {
-    let value = nonnegative_integer(input, 0)?;
-    value.read_all(Error::Read, |input| {
-        let r = input.read_byte()?;
-        Ok(r)
-    })
+// The tag of the element that `bytes` start with, for `peek_tag` and `Reader::peek_tag`.
+pub(crate) fn tag_at(bytes: &[u8]) -> Result<Tag> {
+    read_tag(&mut Reader::new(Input::from(bytes)))
 }
 
-/// Parses a positive DER integer, returning the big-endian-encoded value, sans
-/// any leading zero byte.
-#[inline]
-pub fn positive_integer<'a>(input: &mut Reader<'a>) -> Result<Input<'a>> {
-    nonnegative_integer(input, 1)
+// The rest of `input`, leaving it where it was. `Reader` can only peek at the next byte, so this
+// reads to the end and then replaces `input` with a `Reader` over the same bytes at the same
+// position, so that marks taken from it before stay valid. The mark at the start of any `Reader` is
+// also the mark at the start of `input`.
+pub(crate) fn lookahead<'a>(input: &mut Reader<'a>) -> &'a [u8] {
+    let rest = input.skip_to_end();
+    let start = Reader::new(Input::from(&[])).mark();
+    let all = input.get_input_between_marks(start, input.mark()).unwrap_or(rest);
+    *input = Reader::new(all);
+    let _ = input.skip(all.len() - rest.len());
+    rest.as_slice_less_safe()
 }
 
-/// Parse a boolean value.
-#[inline]
-pub fn boolean<'a>(input: &mut Reader<'a>) -> Result<bool> {
-    let value = expect_tag_and_get_value(input, Tag::Boolean)?;
-    match value.as_slice_less_safe() {
-        x if x == &[0x00] => Ok(false),
-        x if x == &[0x01] => Ok(true),
-        _ => Err(Error::BadBooleanValue),
+// Whether the next element has the tag `tag`, without consuming any input.
+pub(crate) fn next_tag_is(input: &mut Reader, tag: Tag) -> bool {
+    let (octets, len) = tag.identifier_octets();
+    match input.peek(octets[0]) {
+        true if len > 1 => lookahead(input).starts_with(&octets[..len]),
+        matches => matches,
     }
 }
 
-/// For a given length, how many bytes are required to represent it in DER form.
-pub fn length_of_length(len: usize) -> u8 {
-    let mut i = len;
-    let mut num_bytes = 1;
-
-    while i > 255 {
-        num_bytes += 1;
-        i >>= 8;
+/// Run `decoder` on the next element if it has the tag `tag`, or return `None` if it has a
+/// different tag or the input is at its end. `decoder` is given the whole element, including its
+/// tag.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::Tag;
+/// use untrusted::Input;
+///
+/// fn main() {
+///     // SEQUENCE { version INTEGER OPTIONAL, flag BOOLEAN }
+///     let input = Input::from(&[0x30, 0x03, 0x01, 0x01, 0xff]);
+///     let (version, flag) = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::nested(input, Tag::SEQUENCE, |input| {
+///             let version = derp::read_optional(input, Tag::INTEGER, derp::small_nonnegative_integer)?;
+///             let flag = derp::boolean(input)?;
+///             Ok((version, flag))
+///         })
+///     }).unwrap();
+///
+///     assert_eq!(version, None);
+///     assert!(flag);
+/// }
+/// ```
+pub fn read_optional<'a, F, R>(input: &mut Reader<'a>, tag: Tag, decoder: F) -> Result<Option<R>>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+{
+    if !next_tag_is(input, tag) {
+        return Ok(None);
     }
-
-    num_bytes
+    decoder(input).map(Some)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    fn with_good_i<F, R>(value: &[u8], f: F)
-    where
-        F: FnOnce(&mut Reader) -> Result<R>,
-    {
-        let r = Input::from(value).read_all(Error::Read, f);
-        assert!(r.is_ok());
+/// Read a field with a `DEFAULT` value, returning `default` if the element with tag `tag` is
+/// absent.
+///
+/// DER requires a field equal to its default value to be omitted, so an element that decodes to
+/// `default` is rejected with `ErrorKind::NonCanonical`. Use `read_default_with_mode` with
+/// `Mode::Lenient` to accept it.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// fn read_version(input: &mut untrusted::Reader) -> derp::Result<u8> {
+///     derp::read_explicit(input, 0, derp::small_nonnegative_integer)
+/// }
+///
+/// fn main() {
+///     // version [0] EXPLICIT INTEGER DEFAULT 0
+///     let tag = derp::Tag::CONTEXT_SPECIFIC_CONSTRUCTED_0;
+///
+///     let v3 = Input::from(&[0xa0, 0x03, 0x02, 0x01, 0x02]);
+///     let version = v3.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::read_default(input, tag, 0, read_version)
+///     });
+///     assert_eq!(version, Ok(2));
+///
+///     let v1 = Input::from(&[]);
+///     let version = v1.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::read_default(input, tag, 0, read_version)
+///     });
+///     assert_eq!(version, Ok(0));
+/// }
+/// ```
+#[inline]
+pub fn read_default<'a, F, R>(input: &mut Reader<'a>, tag: Tag, default: R, decoder: F) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    R: PartialEq,
+{
+    read_default_with_mode(input, tag, default, Mode::Strict, decoder)
+}
+
+/// Read a field with a `DEFAULT` value. In `Mode::Lenient` an element that is present but encodes
+/// the default value is accepted.
+pub fn read_default_with_mode<'a, F, R>(
+    input: &mut Reader<'a>,
+    tag: Tag,
+    default: R,
+    mode: Mode,
+    decoder: F,
+) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    R: PartialEq,
+{
+    match read_optional(input, tag, decoder)? {
+        Some(ref value) if *value == default && mode == Mode::Strict => Err(ErrorKind::NonCanonical.into()),
+        Some(value) => Ok(value),
+        None => Ok(default),
+    }
+}
+
+/// A tag and the decoder to run when it is found, used by `choice`.
+pub type Alternative<'a, 'b, R> = (Tag, &'b dyn Fn(&mut Reader<'a>) -> Result<R>);
+
+/// Read a `CHOICE` by running the decoder paired with the tag of the next element. Each decoder is
+/// given the whole element, including its tag. `ErrorKind::UnknownTag` is returned if none of the
+/// tags match.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::{DerDateTime, Tag};
+/// use untrusted::Input;
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Time {
+///     Utc(DerDateTime),
+///     General(DerDateTime),
+/// }
+///
+/// fn main() {
+///     let input = Input::from(b"\x17\x0d500101000000Z");
+///     let time = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::choice(input, &[
+///             (Tag::UTC_TIME, &|input| derp::utc_time(input).map(Time::Utc)),
+///             (Tag::GENERALIZED_TIME, &|input| derp::generalized_time(input).map(Time::General)),
+///         ])
+///     }).unwrap();
+///
+///     assert_eq!(time, Time::Utc(DerDateTime::new(1950, 1, 1, 0, 0, 0).unwrap()));
+/// }
+/// ```
+pub fn choice<'a, R>(input: &mut Reader<'a>, alternatives: &[Alternative<'a, '_, R>]) -> Result<R> {
+    if input.at_end() {
+        return Err(ErrorKind::EmptyInput.into());
+    }
+    for &(tag, decoder) in alternatives {
+        if next_tag_is(input, tag) {
+            return decoder(input);
+        }
+    }
+    Err(ErrorKind::UnknownTag.into())
+}
+
+/// Read the next element, including any nested content, and discard it.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// const DATA: &'static [u8] = &[0x30, 0x02, 0x05, 0x00, 0x01, 0x01, 0xff];
+///
+/// fn main() {
+///     let input = Input::from(DATA);
+///     let value = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::skip_element(input)?;
+///         derp::boolean(input)
+///     }).unwrap();
+///     assert!(value);
+/// }
+/// ```
+pub fn skip_element(input: &mut Reader) -> Result<()> {
+    read_any(input).map(|_| ())
+}
+
+pub fn read_null<'a>(input: &mut Reader<'a>) -> Result<()> {
+    expect_tag_and_get_value(input, Tag::NULL)
+        .map(|_| ())
+}
+
+/// Read a `BIT STRING` with leading byte `0x00` signifying no unused bits.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// const BIT_STRING: &'static [u8] = &[0x03, 0x04, 0x00, 0x01, 0x02, 0x03];
+///
+/// fn main() {
+///     let input = Input::from(BIT_STRING);
+///     let bits = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::bit_string_with_no_unused_bits(input)
+///     }).unwrap();
+///     assert_eq!(bits, Input::from(&[0x01, 0x02, 0x03]));
+/// }
+/// ```
+pub fn bit_string_with_no_unused_bits<'a>(
+    input: &mut Reader<'a>,
+) -> Result<Input<'a>> {
+    nested(input, Tag::BIT_STRING, |value| {
+        let unused_bits_at_end = value.read_byte()?;
+        if unused_bits_at_end != 0 {
+            return Err(ErrorKind::NonZeroUnusedBits.into());
+        }
+        Ok(value.skip_to_end())
+    })
+}
+
+/// Read a `BIT STRING` and return the number of unused bits at the end along with its contents.
+///
+/// The number of unused bits must be less than 8, and zero if the contents are empty, otherwise
+/// this errors with `ErrorKind::WrongValue`. DER requires the unused bits to be zero, so any that are
+/// set are rejected with `ErrorKind::NonZeroUnusedBits`.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// // KeyUsage { digitalSignature, keyCertSign }
+/// const KEY_USAGE: &'static [u8] = &[0x03, 0x02, 0x02, 0x84];
+///
+/// fn main() {
+///     let input = Input::from(KEY_USAGE);
+///     let (unused_bits, bits) = input.read_all(derp::ErrorKind::TrailingData.into(), derp::bit_string).unwrap();
+///     assert_eq!(unused_bits, 2);
+///     assert_eq!(bits, Input::from(&[0x84]));
+/// }
+/// ```
+pub fn bit_string<'a>(input: &mut Reader<'a>) -> Result<(u8, Input<'a>)> {
+    nested(input, Tag::BIT_STRING, bit_string_contents)
+}
+
+// Read the contents of a `BIT STRING`, returning the number of unused bits and the bits.
+pub(crate) fn bit_string_contents<'a>(value: &mut Reader<'a>) -> Result<(u8, Input<'a>)> {
+    let unused_bits = value.read_byte()?;
+    let bits = value.skip_to_end();
+    match bits.as_slice_less_safe().last() {
+        _ if unused_bits > 7 => Err(ErrorKind::WrongValue.into()),
+        None if unused_bits != 0 => Err(ErrorKind::WrongValue.into()),
+        Some(last) if last & ((1 << unused_bits) - 1) != 0 => Err(ErrorKind::NonZeroUnusedBits.into()),
+        _ => Ok((unused_bits, bits)),
+    }
+}
+
+/// The bits of a `BIT STRING` used as a named bit list, such as `KeyUsage`, as read by
+/// `bit_string_flags`.
+///
+/// Bit 0 is the first named bit, the high bit of the first byte of the contents. Bits past the end
+/// of the encoding are unset.
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct BitFlags {
+    bits: Vec<bool>,
+}
+
+#[cfg(feature = "alloc")]
+impl BitFlags {
+    /// Create a `BitFlags` with no bits set.
+    pub fn new() -> Self {
+        BitFlags::default()
+    }
+
+    /// Set or clear the bit at `index`.
+    ///
+    /// The bits up to the last that is set are stored one per byte, so setting a bit allocates up
+    /// to its index, which should come from a schema, where named bits are few, rather than from
+    /// input. Setting a bit too far out for them to be allocated panics, as growing a `Vec` does.
+    pub fn set(&mut self, index: usize, value: bool) {
+        if value {
+            if index >= self.bits.len() {
+                let len = index.checked_add(1).expect("bit index overflows usize");
+                self.bits.resize(len, false);
+            }
+            self.bits[index] = true;
+        } else if index < self.bits.len() {
+            self.bits[index] = false;
+            self.trim();
+        }
+    }
+
+    // Remove trailing zero bits, as DER does.
+    fn trim(&mut self) {
+        while self.bits.last() == Some(&false) {
+            self.bits.pop();
+        }
+    }
+
+    /// Whether the bit at `index` is set.
+    pub fn get(&self, index: usize) -> bool {
+        self.bits.get(index).cloned().unwrap_or(false)
+    }
+
+    /// The number of bits in the encoding. As DER removes trailing zero bits, this is one more than
+    /// the index of the last bit that is set.
+    pub fn len(&self) -> usize {
+        self.bits.len()
+    }
+
+    /// Whether no bits are set.
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    /// Iterate over the bits in the encoding, in order.
+    pub fn iter(&self) -> ::std::iter::Cloned<::std::slice::Iter<'_, bool>> {
+        self.bits.iter().cloned()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl ::std::iter::FromIterator<bool> for BitFlags {
+    fn from_iter<I: IntoIterator<Item = bool>>(iter: I) -> Self {
+        let mut flags = BitFlags { bits: iter.into_iter().collect() };
+        flags.trim();
+        flags
+    }
+}
+
+/// Read a `BIT STRING` used as a named bit list and return its flags.
+///
+/// DER requires trailing zero bits to be removed from a named bit list, so encodings where the
+/// last bit is unset are rejected with `ErrorKind::NonCanonical`.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// // KeyUsage { digitalSignature, keyCertSign }
+/// const KEY_USAGE: &'static [u8] = &[0x03, 0x02, 0x02, 0x84];
+///
+/// fn main() {
+///     let input = Input::from(KEY_USAGE);
+///     let flags = input.read_all(derp::ErrorKind::TrailingData.into(), derp::bit_string_flags).unwrap();
+///     assert!(flags.get(0));
+///     assert!(!flags.get(1));
+///     assert!(flags.get(5));
+///     assert!(!flags.get(8));
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+pub fn bit_string_flags(input: &mut Reader) -> Result<BitFlags> {
+    decode_bit_string_flags(expect_tag_and_get_value(input, Tag::BIT_STRING)?)
+}
+
+// Decode the contents of a `BIT STRING` used as a named bit list.
+#[cfg(feature = "alloc")]
+pub(crate) fn decode_bit_string_flags(value: Input) -> Result<BitFlags> {
+    let (unused_bits, value) =
+        value.read_all(ErrorKind::UnconsumedContents.into(), bit_string_contents)?;
+    let value = value.as_slice_less_safe();
+    if let Some(last) = value.last() {
+        if last & (1 << unused_bits) == 0 {
+            return Err(ErrorKind::NonCanonical.into());
+        }
+    }
+
+    let len = value.len() * 8 - unused_bits as usize;
+    let bits = (0..len).map(|i| value[i / 8] & (0x80 >> (i % 8)) != 0).collect();
+    Ok(BitFlags { bits })
+}
+
+/// Read an `OCTET STRING` and return its contents.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// const OCTET_STRING: &'static [u8] = &[0x04, 0x03, 0x01, 0x02, 0x03];
+///
+/// fn main() {
+///     let input = Input::from(OCTET_STRING);
+///     let octets = input.read_all(derp::ErrorKind::TrailingData.into(), derp::octet_string).unwrap();
+///     assert_eq!(octets, Input::from(&[0x01, 0x02, 0x03]));
+/// }
+/// ```
+#[inline]
+pub fn octet_string<'a>(input: &mut Reader<'a>) -> Result<Input<'a>> {
+    octet_string_with_tag(input, Tag::OCTET_STRING)
+}
+
+/// Read an octet string that has been encoded under `tag` instead of `Tag::OCTET_STRING` and return
+/// its contents.
+#[inline]
+pub fn octet_string_with_tag<'a>(input: &mut Reader<'a>, tag: Tag) -> Result<Input<'a>> {
+    expect_tag_and_get_value(input, tag)
+}
+
+/// Read a `UTF8String`, checking that its contents are well-formed UTF-8.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// const UTF8_STRING: &'static [u8] = &[0x0c, 0x04, 0x64, 0x65, 0x72, 0x70];
+///
+/// fn main() {
+///     let input = Input::from(UTF8_STRING);
+///     let s = input.read_all(derp::ErrorKind::TrailingData.into(), derp::utf8_string).unwrap();
+///     assert_eq!(s, "derp");
+/// }
+/// ```
+pub fn utf8_string<'a>(input: &mut Reader<'a>) -> Result<&'a str> {
+    decode_utf8_string(expect_tag_and_get_value(input, Tag::UTF8_STRING)?)
+}
+
+// Decode the contents of a `UTF8String`.
+pub(crate) fn decode_utf8_string<'a>(value: Input<'a>) -> Result<&'a str> {
+    ::std::str::from_utf8(value.as_slice_less_safe()).map_err(|_| Error::from(ErrorKind::BadStringValue))
+}
+
+/// Read a `NumericString`, checking that it only contains digits and spaces.
+pub fn numeric_string<'a>(input: &mut Reader<'a>) -> Result<&'a str> {
+    restricted_string(input, Tag::NUMERIC_STRING, is_numeric_string_char)
+}
+
+/// Whether or not the byte is in the `NumericString` alphabet.
+pub(crate) fn is_numeric_string_char(b: u8) -> bool {
+    b.is_ascii_digit() || b == b' '
+}
+
+/// Read a `PrintableString`, checking that it only contains characters from the `PrintableString`
+/// alphabet (`A-Z`, `a-z`, `0-9`, space, and `'()+,-./:=?`).
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// const PRINTABLE_STRING: &'static [u8] = &[0x13, 0x02, 0x55, 0x53];
+///
+/// fn main() {
+///     let input = Input::from(PRINTABLE_STRING);
+///     let s = input.read_all(derp::ErrorKind::TrailingData.into(), derp::printable_string).unwrap();
+///     assert_eq!(s, "US");
+/// }
+/// ```
+pub fn printable_string<'a>(input: &mut Reader<'a>) -> Result<&'a str> {
+    restricted_string(input, Tag::PRINTABLE_STRING, is_printable_string_char)
+}
+
+/// Whether or not the byte is in the `PrintableString` alphabet.
+pub(crate) fn is_printable_string_char(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b" '()+,-./:=?".contains(&b)
+}
+
+/// Read a `VisibleString`, checking that it only contains printable ASCII characters and spaces.
+pub fn visible_string<'a>(input: &mut Reader<'a>) -> Result<&'a str> {
+    restricted_string(input, Tag::VISIBLE_STRING, is_visible_string_char)
+}
+
+/// Whether or not the byte is in the `VisibleString` alphabet.
+pub(crate) fn is_visible_string_char(b: u8) -> bool {
+    (b' '..=b'~').contains(&b)
+}
+
+/// Read a `TeletexString` (also known as `T61String`) and return its raw contents.
+///
+/// The T.61 character set is rarely implemented in full, so decoding is left to the caller. See
+/// `teletex_string_as_latin1` for the common case.
+#[inline]
+pub fn teletex_string<'a>(input: &mut Reader<'a>) -> Result<Input<'a>> {
+    expect_tag_and_get_value(input, Tag::TELETEX_STRING)
+}
+
+/// Read a `TeletexString` and decode its contents as Latin-1 (ISO 8859-1), which is how most
+/// certificate authorities that emit `TeletexString` actually use it.
+#[cfg(feature = "alloc")]
+pub fn teletex_string_as_latin1(input: &mut Reader) -> Result<String> {
+    let value = teletex_string(input)?;
+    Ok(value.as_slice_less_safe().iter().map(|b| *b as char).collect())
+}
+
+/// Read an `IA5String`, checking that it only contains ASCII characters.
+pub fn ia5_string<'a>(input: &mut Reader<'a>) -> Result<&'a str> {
+    restricted_string(input, Tag::IA5_STRING, is_ia5_string_char)
+}
+
+/// Whether or not the byte is in the `IA5String` alphabet (ASCII).
+pub(crate) fn is_ia5_string_char(b: u8) -> bool {
+    b.is_ascii()
+}
+
+/// Read a `UniversalString`, decoding its UTF-32BE contents.
+#[cfg(feature = "alloc")]
+pub fn universal_string(input: &mut Reader) -> Result<String> {
+    let value = expect_tag_and_get_value(input, Tag::UNIVERSAL_STRING)?.as_slice_less_safe();
+    if value.len() % 4 != 0 {
+        return Err(ErrorKind::BadStringValue.into());
+    }
+
+    value
+        .chunks(4)
+        .map(|c| {
+            let code_point = c.iter().fold(0u32, |acc, b| (acc << 8) | u32::from(*b));
+            ::std::char::from_u32(code_point).ok_or(Error::from(ErrorKind::BadStringValue))
+        })
+        .collect()
+}
+
+/// Read a `BMPString`, decoding its UTF-16BE contents. Since a `BMPString` is limited to the Basic
+/// Multilingual Plane, surrogate code units are rejected.
+#[cfg(feature = "alloc")]
+pub fn bmp_string(input: &mut Reader) -> Result<String> {
+    let value = expect_tag_and_get_value(input, Tag::BMP_STRING)?.as_slice_less_safe();
+    if value.len() % 2 != 0 {
+        return Err(ErrorKind::BadStringValue.into());
+    }
+
+    value
+        .chunks(2)
+        .map(|c| {
+            let code_unit = (u32::from(c[0]) << 8) | u32::from(c[1]);
+            // `from_u32` rejects the surrogate range 0xD800 to 0xDFFF.
+            ::std::char::from_u32(code_unit).ok_or(Error::from(ErrorKind::BadStringValue))
+        })
+        .collect()
+}
+
+// Read a string type whose character set is a subset of ASCII, checking every byte with `allowed`.
+fn restricted_string<'a>(
+    input: &mut Reader<'a>,
+    tag: Tag,
+    allowed: fn(u8) -> bool,
+) -> Result<&'a str> {
+    decode_restricted_string(expect_tag_and_get_value(input, tag)?, allowed)
+}
+
+// Decode the contents of a string type whose character set is a subset of ASCII.
+pub(crate) fn decode_restricted_string<'a>(
+    value: Input<'a>,
+    allowed: fn(u8) -> bool,
+) -> Result<&'a str> {
+    let value = value.as_slice_less_safe();
+    if !value.iter().all(|b| allowed(*b)) {
+        return Err(ErrorKind::BadStringValue.into());
+    }
+    ::std::str::from_utf8(value).map_err(|_| Error::from(ErrorKind::BadStringValue))
+}
+
+/// Return the value of the given tag and apply a decoding function to it.
+///
+/// With the `std` feature elements can be nested with this and the other functions that do not
+/// take a `ParserConfig` at most `ParserConfig::DEFAULT_MAX_DEPTH` deep on each thread, as with
+/// `ParserConfig::DER`, beyond which this fails with `ErrorKind::NestingTooDeep`. That way a
+/// decoder that recurses through `nested`, as for a recursive schema, cannot be made to overflow
+/// the stack by deeply nested input. Without `std` there is nowhere to count the levels, so there
+/// is no limit and such a decoder should use `nested_with_config` or `Reader::nested` instead.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::Tag;
+/// use untrusted::Input;
+///
+/// const NESTED: &'static [u8] = &[
+///     0x30, 0x14,                                 // seq
+///         0x30, 0x0c,                             // seq
+///             0x02, 0x04, 0x0a, 0x0b, 0x0c, 0x0d, // x
+///             0x02, 0x04, 0x1a, 0x1b, 0x1c, 0x1d, // y
+///         0x02, 0x04, 0x01, 0x02, 0x03, 0x04,     // z
+/// ];
+/// fn main () {
+///     let input = Input::from(NESTED);
+///     let (x, y, z) = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::nested(input, Tag::SEQUENCE, |input| {
+///             let (x, y) = derp::nested(input, Tag::SEQUENCE, |input| {
+///                 let x = derp::positive_integer(input)?;
+///                 let y = derp::positive_integer(input)?;
+///                 Ok((x, y))
+///             })?;
+///             let z = derp::positive_integer(input)?;
+///             Ok((x, y, z))
+///         })
+///     }).unwrap();
+///
+///     assert_eq!(x, Input::from(&[0x0a, 0x0b, 0x0c, 0x0d]));
+///     assert_eq!(y, Input::from(&[0x1a, 0x1b, 0x1c, 0x1d]));
+///     assert_eq!(z, Input::from(&[0x01, 0x02, 0x03, 0x04]));
+/// }
+/// ```
+// TODO: investigate taking decoder as a reference to reduce generated code
+// size.
+pub fn nested<'a, F, R>(input: &mut Reader<'a>, tag: Tag, decoder: F) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+{
+    nested_with_mode(input, tag, Mode::Strict, decoder)
+}
+
+/// Return the value of the given tag and apply a decoding function to it, as `nested` does, and
+/// with the same limit on nesting. In `Mode::Lenient` lengths that are not in their minimal form
+/// are accepted for this element, but `decoder` decides how strictly its contents are read.
+pub fn nested_with_mode<'a, F, R>(
+    input: &mut Reader<'a>,
+    tag: Tag,
+    mode: Mode,
+    decoder: F,
+) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+{
+    let _level = NestedLevel::enter(input)?;
+    let inner = expect_tag_and_get_value_with_mode(input, tag, mode)?;
+    decode_contents(tag, inner, decoder)
+}
+
+// How deeply the functions that do not take a `ParserConfig` are nested on this thread.
+#[cfg(feature = "std")]
+thread_local!(static NESTED_DEPTH: Cell<usize> = const { Cell::new(0) });
+
+// A level of nesting entered by `nested` and the like, which is left again when this is dropped,
+// even if the decoder panics.
+struct NestedLevel;
+
+impl NestedLevel {
+    #[cfg(feature = "std")]
+    fn enter(input: &Reader) -> Result<NestedLevel> {
+        NESTED_DEPTH.with(|depth| {
+            if depth.get() >= ParserConfig::DEFAULT_MAX_DEPTH {
+                return Err(Error::from(ErrorKind::NestingTooDeep).at(position(input)));
+            }
+            depth.set(depth.get() + 1);
+            Ok(NestedLevel)
+        })
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn enter(_: &Reader) -> Result<NestedLevel> {
+        Ok(NestedLevel)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for NestedLevel {
+    fn drop(&mut self) {
+        NESTED_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Return the value of a `SET` and apply a decoding function to it. This does not check the order
+/// of the elements, see `set_of` for that.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// // A RelativeDistinguishedName with countryName = "US"
+/// const RDN: &'static [u8] = &[
+///     0x31, 0x0b,
+///         0x30, 0x09,
+///             0x06, 0x03, 0x55, 0x04, 0x06,
+///             0x13, 0x02, 0x55, 0x53,
+/// ];
+///
+/// fn main() {
+///     let input = Input::from(RDN);
+///     let country = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::nested_set(input, |input| {
+///             derp::nested(input, derp::Tag::SEQUENCE, |input| {
+///                 let _oid = derp::expect_tag_and_get_value(input, derp::Tag::OID)?;
+///                 derp::printable_string(input)
+///             })
+///         })
+///     }).unwrap();
+///
+///     assert_eq!(country, "US");
+/// }
+/// ```
+#[inline]
+pub fn nested_set<'a, F, R>(input: &mut Reader<'a>, decoder: F) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+{
+    nested(input, Tag::SET, decoder)
+}
+
+/// Read a `SET OF`, applying `decoder` to each element in turn and collecting the results.
+/// `decoder` must read exactly one element each time it is called.
+///
+/// DER requires the elements of a `SET OF` to be sorted by their encodings, so this errors with
+/// `ErrorKind::NonCanonical` if they are out of order.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// const SET_OF: &'static [u8] = &[0x31, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02];
+///
+/// fn main() {
+///     let input = Input::from(SET_OF);
+///     let values = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::set_of(input, derp::small_nonnegative_integer)
+///     }).unwrap();
+///     assert_eq!(values, vec![1, 2]);
+///
+///     const UNSORTED: &'static [u8] = &[0x31, 0x06, 0x02, 0x01, 0x02, 0x02, 0x01, 0x01];
+///     let input = Input::from(UNSORTED);
+///     let res = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::set_of(input, derp::small_nonnegative_integer)
+///     });
+///     assert_eq!(res, Err(derp::ErrorKind::NonCanonical.into()));
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+pub fn set_of<'a, F, R>(input: &mut Reader<'a>, mut decoder: F) -> Result<Vec<R>>
+where
+    F: FnMut(&mut Reader<'a>) -> Result<R>,
+{
+    nested(input, Tag::SET, |input| {
+        let mut values = Vec::new();
+        let mut previous: Option<&[u8]> = None;
+
+        while !input.at_end() {
+            let start = input.mark();
+            values.push(decoder(input)?);
+            let encoding = input.get_input_between_marks(start, input.mark())?.as_slice_less_safe();
+            if encoding.is_empty() {
+                // The decoder didn't consume anything, so we would loop forever.
+                return Err(ErrorKind::UnconsumedContents.into());
+            }
+
+            if let Some(previous) = previous {
+                if set_of_order(previous, encoding) == Ordering::Greater {
+                    return Err(ErrorKind::NonCanonical.into());
+                }
+            }
+            previous = Some(encoding);
+        }
+
+        Ok(values)
+    })
+}
+
+/// Compare two encodings by the ordering DER requires for the elements of a `SET OF`. The
+/// encodings are compared as octet strings, with the shorter one padded at the end with zeros.
+pub(crate) fn set_of_order(a: &[u8], b: &[u8]) -> Ordering {
+    let len = a.len().max(b.len());
+    let padded = |x: &[u8], i| x.get(i).cloned().unwrap_or(0);
+    (0..len)
+        .map(|i| padded(a, i).cmp(&padded(b, i)))
+        .find(|o| *o != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+// The order DER requires for the components of a `SET`, by class and then number.
+#[cfg(feature = "alloc")]
+pub(crate) fn tag_order(a: Tag, b: Tag) -> Ordering {
+    let key = |tag: Tag| (tag.class.bits(), tag.number);
+    key(a).cmp(&key(b))
+}
+
+// Whether BER allows an element of the universal type `number` to be split into constructed
+// segments, as it does for the string types.
+#[cfg(feature = "alloc")]
+pub(crate) fn is_string_type(number: u32) -> bool {
+    matches!(number, 0x03 | 0x04 | 0x07 | 0x0c | 0x12..=0x1c | 0x1e)
+}
+
+/// Read a `SEQUENCE OF`, applying `decoder` to each element in turn and collecting the results.
+/// `decoder` must read exactly one element each time it is called.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// const SEQUENCE_OF: &'static [u8] = &[0x30, 0x06, 0x0c, 0x01, 0x62, 0x0c, 0x01, 0x61];
+///
+/// fn main() {
+///     let input = Input::from(SEQUENCE_OF);
+///     let values = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::read_sequence_of(input, derp::utf8_string)
+///     }).unwrap();
+///     assert_eq!(values, vec!["b", "a"]);
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+pub fn read_sequence_of<'a, F, R>(input: &mut Reader<'a>, mut decoder: F) -> Result<Vec<R>>
+where
+    F: FnMut(&mut Reader<'a>) -> Result<R>,
+{
+    nested(input, Tag::SEQUENCE, |input| {
+        let mut values = Vec::new();
+        while !input.at_end() {
+            let start = input.mark();
+            values.push(decoder(input)?);
+            if input.get_input_between_marks(start, input.mark())?.is_empty() {
+                // The decoder didn't consume anything, so we would loop forever.
+                return Err(ErrorKind::UnconsumedContents.into());
+            }
+        }
+        Ok(values)
+    })
+}
+
+/// Iterator over the elements of a `SEQUENCE OF` (or any other element with nested contents),
+/// returned by `sequence_of`.
+///
+/// Each item is a whole element, including its tag and length. Iteration stops after the first
+/// error.
+#[derive(Debug)]
+pub struct SequenceOf<'a> {
+    reader: Reader<'a>,
+    failed: bool,
+}
+
+impl<'a> Iterator for SequenceOf<'a> {
+    type Item = Result<Input<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.reader.at_end() {
+            return None;
+        }
+
+        let start = self.reader.mark();
+        let result = read_any(&mut self.reader).and_then(|_| {
+            Ok(self.reader.get_input_between_marks(start, self.reader.mark())?)
+        });
+        self.failed = result.is_err();
+        Some(result)
+    }
+}
+
+/// Read an element with the tag `tag` and return an iterator over the elements it contains.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::Tag;
+/// use untrusted::Input;
+///
+/// const SEQUENCE_OF: &'static [u8] = &[0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02];
+///
+/// fn main() {
+///     let input = Input::from(SEQUENCE_OF);
+///     let values = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::sequence_of(input, Tag::SEQUENCE)?
+///             .map(|element| element?.read_all(derp::ErrorKind::TrailingData.into(), derp::small_nonnegative_integer))
+///             .collect::<derp::Result<Vec<_>>>()
+///     }).unwrap();
+///     assert_eq!(values, vec![1, 2]);
+/// }
+/// ```
+pub fn sequence_of<'a>(input: &mut Reader<'a>, tag: Tag) -> Result<SequenceOf<'a>> {
+    let inner = expect_tag_and_get_value(input, tag)?;
+    Ok(SequenceOf {
+        reader: Reader::new(inner),
+        failed: false,
+    })
+}
+
+/// Read an `EXPLICIT` context-specific tag `[tag_number]` and apply a decoding function to the
+/// element it wraps.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// // version [0] EXPLICIT INTEGER
+/// const VERSION: &'static [u8] = &[0xa0, 0x03, 0x02, 0x01, 0x02];
+///
+/// fn main() {
+///     let input = Input::from(VERSION);
+///     let version = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::read_explicit(input, 0, derp::small_nonnegative_integer)
+///     }).unwrap();
+///     assert_eq!(version, 2);
+/// }
+/// ```
+#[inline]
+pub fn read_explicit<'a, F, R>(input: &mut Reader<'a>, tag_number: u32, decoder: F) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+{
+    read_tagged_explicit(input, Class::ContextSpecific, tag_number, decoder)
+}
+
+/// Read an `IMPLICIT` context-specific tag `[tag_number]`, which may be either primitive or
+/// constructed, and return its contents for the caller to interpret.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// // dNSName [2] IMPLICIT IA5String
+/// const DNS_NAME: &'static [u8] = &[0x82, 0x04, 0x64, 0x65, 0x72, 0x70];
+///
+/// fn main() {
+///     let input = Input::from(DNS_NAME);
+///     let name = input.read_all(derp::ErrorKind::TrailingData.into(), |input| derp::read_implicit(input, 2)).unwrap();
+///     assert_eq!(name.as_slice_less_safe(), b"derp");
+/// }
+/// ```
+#[inline]
+pub fn read_implicit<'a>(input: &mut Reader<'a>, tag_number: u32) -> Result<Input<'a>> {
+    read_tagged(input, Class::ContextSpecific, tag_number)
+}
+
+/// Read a constructed tag of the given class and number and apply a decoding function to the
+/// element it wraps. Like `nested`, this counts towards the limit on nesting.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::Class;
+/// use untrusted::Input;
+///
+/// // [APPLICATION 10] wrapping a SEQUENCE
+/// const AS_REQ: &'static [u8] = &[0x6a, 0x04, 0x30, 0x02, 0x05, 0x00];
+///
+/// fn main() {
+///     let input = Input::from(AS_REQ);
+///     input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::read_tagged_explicit(input, Class::Application, 10, |input| {
+///             derp::nested(input, derp::Tag::SEQUENCE, derp::read_null)
+///         })
+///     }).unwrap();
+/// }
+/// ```
+pub fn read_tagged_explicit<'a, F, R>(
+    input: &mut Reader<'a>,
+    class: Class,
+    tag_number: u32,
+    decoder: F,
+) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+{
+    let tag = Tag::new(class, true, tag_number);
+    let _level = NestedLevel::enter(input)?;
+    let inner = expect_tag_and_get_value(input, tag)?;
+    decode_contents(tag, inner, decoder)
+}
+
+/// Read a tag of the given class and number, which may be either primitive or constructed, and
+/// return its contents.
+pub fn read_tagged<'a>(input: &mut Reader<'a>, class: Class, tag_number: u32) -> Result<Input<'a>> {
+    let start = position(input);
+    let (actual_tag, inner) = read_any(input)?;
+    if actual_tag.class != class || actual_tag.number != tag_number {
+        let kind = ErrorKind::WrongTag {
+            // Either form is accepted, so expect the one that was found.
+            expected: Tag::new(class, actual_tag.constructed, tag_number),
+            found: actual_tag.identifier_octets().0[0],
+        };
+        return Err(Error::from(kind).at(start));
+    }
+    Ok(inner)
+}
+
+/// Return a non-negative integer.
+pub fn nonnegative_integer<'a>(
+    input: &mut Reader<'a>,
+    min_value: u8,
+) -> Result<Input<'a>> {
+    decode_nonnegative_integer(expect_tag_and_get_value(input, Tag::INTEGER)?, min_value)
+}
+
+// Check the contents of a non-negative `INTEGER` and return them without any leading zero.
+pub(crate) fn decode_nonnegative_integer<'a>(value: Input<'a>, min_value: u8) -> Result<Input<'a>> {
+    // Verify that |input|, which has had any leading zero stripped off, is the
+    // encoding of a value of at least |min_value|.
+    fn check_minimum(input: Input, min_value: u8) -> Result<()> {
+        input.read_all(ErrorKind::UnconsumedContents.into(), |input| {
+            let first_byte = input.read_byte()?;
+            if input.at_end() && first_byte < min_value {
+                return Err(ErrorKind::LessThanMinimum.into());
+            }
+            let _ = input.skip_to_end();
+            Ok(())
+        })
+    }
+
+    value.read_all(ErrorKind::UnconsumedContents.into(), |input| {
+        // Empty encodings are not allowed.
+        let first_byte = input.read_byte()?;
+
+        if first_byte == 0 {
+            if input.at_end() {
+                // |value| is the legal encoding of zero.
+                if min_value > 0 {
+                    return Err(ErrorKind::LessThanMinimum.into());
+                }
+                return Ok(value);
+            }
+
+            let r = input.skip_to_end();
+            r.read_all(Error::from(ErrorKind::UnconsumedContents), |input| {
+                let second_byte = input.read_byte()?;
+                if (second_byte & 0x80) == 0 {
+                    // A leading zero is only allowed when the value's high bit
+                    // is set.
+                    return Err(ErrorKind::LeadingZero.into());
+                }
+                let _ = input.skip_to_end();
+                Ok(())
+            })?;
+            check_minimum(r, min_value)?;
+            return Ok(r);
+        }
+
+        // Negative values are not allowed.
+        if (first_byte & 0x80) != 0 {
+            return Err(ErrorKind::NegativeValue.into());
+        }
+
+        let _ = input.skip_to_end();
+        check_minimum(value, min_value)?;
+        Ok(value)
+    })
+}
+
+/// Parse as integer with a value in the in the range [0, 255], returning its
+/// numeric value. This is typically used for parsing version numbers.
+#[inline]
+pub fn small_nonnegative_integer(input: &mut Reader) -> Result<u8> {
+    let value = nonnegative_integer(input, 0)?;
+    value.read_all(ErrorKind::UnconsumedContents.into(), |input| {
+        let r = input.read_byte()?;
+        Ok(r)
+    })
+}
+
+/// Parses a positive DER integer, returning the big-endian-encoded value, sans
+/// any leading zero byte.
+#[inline]
+pub fn positive_integer<'a>(input: &mut Reader<'a>) -> Result<Input<'a>> {
+    nonnegative_integer(input, 1)
+}
+
+/// Read a non-negative `INTEGER` as a `u8`, erroring with `ErrorKind::IntegerOverflow` if it is too
+/// large.
+#[inline]
+pub fn read_u8(input: &mut Reader) -> Result<u8> {
+    unsigned_integer(input, 1).map(|value| value as u8)
+}
+
+/// Read a non-negative `INTEGER` as a `u16`, erroring with `ErrorKind::IntegerOverflow` if it is too
+/// large.
+#[inline]
+pub fn read_u16(input: &mut Reader) -> Result<u16> {
+    unsigned_integer(input, 2).map(|value| value as u16)
+}
+
+/// Read a non-negative `INTEGER` as a `u32`, erroring with `ErrorKind::IntegerOverflow` if it is too
+/// large.
+#[inline]
+pub fn read_u32(input: &mut Reader) -> Result<u32> {
+    unsigned_integer(input, 4).map(|value| value as u32)
+}
+
+/// Read a non-negative `INTEGER` as a `u64`, erroring with `ErrorKind::IntegerOverflow` if it is too
+/// large.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// const INTEGER: &'static [u8] = &[0x02, 0x05, 0x00, 0x80, 0x00, 0x00, 0x00];
+///
+/// fn main() {
+///     let input = Input::from(INTEGER);
+///     let value = input.read_all(derp::ErrorKind::TrailingData.into(), derp::read_u64).unwrap();
+///     assert_eq!(value, 0x8000_0000);
+///
+///     let input = Input::from(INTEGER);
+///     let res = input.read_all(derp::ErrorKind::TrailingData.into(), derp::read_u16);
+///     assert_eq!(res, Err(derp::ErrorKind::IntegerOverflow.into()));
+/// }
+/// ```
+#[inline]
+pub fn read_u64(input: &mut Reader) -> Result<u64> {
+    unsigned_integer(input, 8).map(|value| value as u64)
+}
+
+/// Read a non-negative `INTEGER` as a `u128`, erroring with `ErrorKind::IntegerOverflow` if it is too
+/// large.
+#[inline]
+pub fn read_u128(input: &mut Reader) -> Result<u128> {
+    unsigned_integer(input, 16)
+}
+
+/// Read an `INTEGER`, which may be negative, as an `i64`. Errors with `ErrorKind::IntegerOverflow` if
+/// it is too large.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// fn main() {
+///     let input = Input::from(&[0x02, 0x02, 0xff, 0x7f]);
+///     let value = input.read_all(derp::ErrorKind::TrailingData.into(), derp::read_i64).unwrap();
+///     assert_eq!(value, -129);
+/// }
+/// ```
+#[inline]
+pub fn read_i64(input: &mut Reader) -> Result<i64> {
+    decode_i64(expect_tag_and_get_value(input, Tag::INTEGER)?.as_slice_less_safe())
+}
+
+/// Read an `INTEGER`, which may be negative, as an `i128`. Errors with `ErrorKind::IntegerOverflow`
+/// if it is too large.
+#[inline]
+pub fn read_i128(input: &mut Reader) -> Result<i128> {
+    decode_signed(expect_tag_and_get_value(input, Tag::INTEGER)?.as_slice_less_safe(), 16)
+}
+
+// Read a non-negative integer whose value fits in `max_len` bytes.
+fn unsigned_integer(input: &mut Reader, max_len: usize) -> Result<u128> {
+    decode_unsigned(expect_tag_and_get_value(input, Tag::INTEGER)?, max_len)
+}
+
+// Decode the contents of a non-negative integer whose value fits in `max_len` bytes.
+pub(crate) fn decode_unsigned(value: Input, max_len: usize) -> Result<u128> {
+    let value = decode_nonnegative_integer(value, 0)?.as_slice_less_safe();
+    if value.len() > max_len {
+        return Err(ErrorKind::IntegerOverflow.into());
+    }
+    Ok(value.iter().fold(0, |acc, b| (acc << 8) | u128::from(*b)))
+}
+
+/// Read an `ENUMERATED` and return its big-endian two's complement contents, checking that they
+/// are minimally encoded.
+pub fn enumerated<'a>(input: &mut Reader<'a>) -> Result<Input<'a>> {
+    let value = expect_tag_and_get_value(input, Tag::ENUMERATED)?;
+    check_minimal_signed(value.as_slice_less_safe())?;
+    Ok(value)
+}
+
+/// Read an `ENUMERATED` as an `i64`.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// // CRLReason keyCompromise
+/// const ENUMERATED: &'static [u8] = &[0x0a, 0x01, 0x01];
+///
+/// fn main() {
+///     let input = Input::from(ENUMERATED);
+///     let reason = input.read_all(derp::ErrorKind::TrailingData.into(), derp::enumerated_i64).unwrap();
+///     assert_eq!(reason, 1);
+/// }
+/// ```
+pub fn enumerated_i64(input: &mut Reader) -> Result<i64> {
+    decode_i64(enumerated(input)?.as_slice_less_safe())
+}
+
+// Check that `value` is the minimal two's complement encoding of an integer.
+pub(crate) fn check_minimal_signed(value: &[u8]) -> Result<()> {
+    match value {
+        [] => Err(ErrorKind::UnexpectedEnd.into()),
+        [0x00, next, ..] if next & 0x80 == 0 => Err(ErrorKind::NonCanonical.into()),
+        [0xff, next, ..] if next & 0x80 != 0 => Err(ErrorKind::NonCanonical.into()),
+        _ => Ok(()),
+    }
+}
+
+// Decode the minimal two's complement encoding `value` as an `i64`.
+pub(crate) fn decode_i64(value: &[u8]) -> Result<i64> {
+    decode_signed(value, 8).map(|value| value as i64)
+}
+
+// Decode the minimal two's complement encoding `value` of an integer that fits in `max_len` bytes.
+pub(crate) fn decode_signed(value: &[u8], max_len: usize) -> Result<i128> {
+    check_minimal_signed(value)?;
+    if value.len() > max_len {
+        return Err(ErrorKind::IntegerOverflow.into());
+    }
+
+    let sign_extension = if value[0] & 0x80 != 0 { -1 } else { 0 };
+    Ok(value.iter().fold(sign_extension, |acc, b| (acc << 8) | i128::from(*b)))
+}
+
+/// Parse a `BOOLEAN` value. DER requires `FALSE` to be encoded as `0x00` and `TRUE` as `0xFF`, so
+/// any other encoding is rejected with `ErrorKind::BadBooleanValue`.
+#[inline]
+pub fn boolean(input: &mut Reader) -> Result<bool> {
+    boolean_with_mode(input, Mode::Strict)
+}
+
+/// Parse a `BOOLEAN` value. In `Mode::Lenient` any non-zero value is accepted as `TRUE`, as BER
+/// allows, and the length may be in a form that is not minimal.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::Mode;
+/// use untrusted::Input;
+///
+/// fn main() {
+///     let input = Input::from(&[0x01, 0x01, 0x01]);
+///     let value = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::boolean_with_mode(input, Mode::Lenient)
+///     });
+///     assert_eq!(value, Ok(true));
+/// }
+/// ```
+pub fn boolean_with_mode(input: &mut Reader, mode: Mode) -> Result<bool> {
+    decode_boolean(expect_tag_and_get_value_with_mode(input, Tag::BOOLEAN, mode)?, mode)
+}
+
+// Decode the contents of a `BOOLEAN`.
+pub(crate) fn decode_boolean(value: Input, mode: Mode) -> Result<bool> {
+    match value.as_slice_less_safe() {
+        [0x00] => Ok(false),
+        [0xff] => Ok(true),
+        [_] if mode == Mode::Lenient => Ok(true),
+        _ => Err(ErrorKind::BadBooleanValue.into()),
+    }
+}
+
+/// For a given length, how many bytes are required to represent it in DER form.
+pub fn length_of_length(len: usize) -> u8 {
+    let mut i = len;
+    let mut num_bytes = 1;
+
+    while i > 255 {
+        num_bytes += 1;
+        i >>= 8;
+    }
+
+    num_bytes
+}
+
+/// The length of the encoding of an element with `tag` and `len` octets of contents, for
+/// implementing `ToDer::encoded_len`.
+pub fn element_len(tag: Tag, len: usize) -> usize {
+    let length_octets = match len >= 128 {
+        true => 1 + length_of_length(len) as usize,
+        false => 1,
+    };
+    tag.identifier_octets().1 + length_octets + len
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod tests {
+    use super::*;
+
+    fn with_good_i<F, R>(value: &[u8], f: F)
+    where
+        F: FnOnce(&mut Reader) -> Result<R>,
+    {
+        let r = Input::from(value).read_all(ErrorKind::TrailingData.into(), f);
+        assert!(r.is_ok());
+    }
+
+    fn with_bad_i<F, R>(value: &[u8], f: F)
+    where
+        F: FnOnce(&mut Reader) -> Result<R>,
+    {
+        let r = Input::from(value).read_all(ErrorKind::TrailingData.into(), f);
+        assert!(r.is_err());
+    }
+
+    fn wrong_tag(expected: Tag, found: u8) -> ErrorKind {
+        ErrorKind::WrongTag { expected, found }
+    }
+
+    static ZERO_INTEGER: &[u8] = &[0x02, 0x01, 0x00];
+
+    static GOOD_POSITIVE_INTEGERS: &[(&[u8], u8)] =
+        &[
+            (&[0x02, 0x01, 0x01], 0x01),
+            (&[0x02, 0x01, 0x02], 0x02),
+            (&[0x02, 0x01, 0x7e], 0x7e),
+            (&[0x02, 0x01, 0x7f], 0x7f),
+
+            // Values that need to have an 0x00 prefix to disambiguate them from
+            // them from negative values.
+            (&[0x02, 0x02, 0x00, 0x80], 0x80),
+            (&[0x02, 0x02, 0x00, 0x81], 0x81),
+            (&[0x02, 0x02, 0x00, 0xfe], 0xfe),
+            (&[0x02, 0x02, 0x00, 0xff], 0xff),
+        ];
+
+    static BAD_NONNEGATIVE_INTEGERS: &[&[u8]] = &[
+        &[], // At end of input
+        &[0x02], // Tag only
+        &[0x02, 0x00], // Empty value
+
+        // Length mismatch
+        &[0x02, 0x00, 0x01],
+        &[0x02, 0x01],
+        &[0x02, 0x01, 0x00, 0x01],
+        &[0x02, 0x01, 0x01, 0x00], // Would be valid if last byte is ignored.
+        &[0x02, 0x02, 0x01],
+
+        // Negative values
+        &[0x02, 0x01, 0x80],
+        &[0x02, 0x01, 0xfe],
+        &[0x02, 0x01, 0xff],
+
+        // Values that have an unnecessary leading 0x00
+        &[0x02, 0x02, 0x00, 0x00],
+        &[0x02, 0x02, 0x00, 0x01],
+        &[0x02, 0x02, 0x00, 0x02],
+        &[0x02, 0x02, 0x00, 0x7e],
+        &[0x02, 0x02, 0x00, 0x7f],
+    ];
+
+    #[test]
+    fn test_boolean() {
+        with_good_i(&[0x01, 0x01, 0x00], |input| {
+            assert!(!boolean(input)?);
+            Ok(())
+        });
+        with_good_i(&[0x01, 0x01, 0xff], |input| {
+            assert!(boolean(input)?);
+            Ok(())
+        });
+
+        for &test_in in &[
+            &[0x01, 0x01, 0x01][..], // BER true, but not DER
+            &[0x01, 0x01, 0x80],
+            &[0x01, 0x00],
+            &[0x01, 0x02, 0x00, 0x00],
+            &[0x02, 0x01, 0xff], // wrong tag
+        ] {
+            with_bad_i(test_in, |input| {
+                let _ = boolean(input)?;
+                Ok(())
+            });
+        }
+
+        let r = Input::from(&[0x01, 0x01, 0x01]).read_all(ErrorKind::TrailingData.into(), boolean);
+        assert_eq!(r, Err(ErrorKind::BadBooleanValue.into()));
+
+        let lenient = |encoded| {
+            Input::from(encoded).read_all(ErrorKind::TrailingData.into(), |input| {
+                boolean_with_mode(input, Mode::Lenient)
+            })
+        };
+        assert_eq!(lenient(&[0x01, 0x01, 0x00]), Ok(false));
+        assert_eq!(lenient(&[0x01, 0x01, 0x01]), Ok(true));
+        assert_eq!(lenient(&[0x01, 0x81, 0x01, 0x80]), Ok(true));
+        assert_eq!(lenient(&[0x01, 0x02, 0x00, 0x00]), Err(ErrorKind::BadBooleanValue.into()));
+    }
+
+    #[test]
+    fn test_bit_string() {
+        for &(test_in, unused_bits, bits) in &[
+            (&[0x03, 0x01, 0x00][..], 0, &[][..]),
+            (&[0x03, 0x02, 0x00, 0xff], 0, &[0xff]),
+            (&[0x03, 0x02, 0x07, 0x80], 7, &[0x80]),
+            (&[0x03, 0x03, 0x01, 0xff, 0xfe], 1, &[0xff, 0xfe]),
+        ] {
+            with_good_i(test_in, |input| {
+                assert_eq!(bit_string(input)?, (unused_bits, Input::from(bits)));
+                Ok(())
+            });
+        }
+
+        for &(test_in, err) in &[
+            (&[0x03, 0x00][..], ErrorKind::UnexpectedEnd),
+            (&[0x03, 0x01, 0x01], ErrorKind::WrongValue),
+            (&[0x03, 0x02, 0x08, 0x00], ErrorKind::WrongValue),
+            (&[0x03, 0x02, 0x01, 0x01], ErrorKind::NonZeroUnusedBits),
+            (&[0x03, 0x02, 0x07, 0x40], ErrorKind::NonZeroUnusedBits),
+            (&[0x04, 0x02, 0x00, 0x00], wrong_tag(Tag::BIT_STRING, 0x04)),
+        ] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), bit_string);
+            assert_eq!(r, Err(err.into()));
+        }
+    }
+
+    #[test]
+    fn test_bit_string_flags() {
+        with_good_i(&[0x03, 0x01, 0x00], |input| {
+            let flags = bit_string_flags(input)?;
+            assert!(flags.is_empty());
+            assert!(!flags.get(0));
+            Ok(())
+        });
+        with_good_i(&[0x03, 0x03, 0x07, 0x40, 0x80], |input| {
+            let flags = bit_string_flags(input)?;
+            assert_eq!(flags.len(), 9);
+            let set = flags.iter().enumerate().filter(|&(_, b)| b).map(|(i, _)| i);
+            assert_eq!(set.collect::<Vec<_>>(), vec![1, 8]);
+            Ok(())
+        });
+
+        for &test_in in &[
+            // Trailing zero bits that should have been removed
+            &[0x03, 0x02, 0x00, 0x80][..],
+            &[0x03, 0x02, 0x06, 0x80],
+            &[0x03, 0x02, 0x00, 0x00],
+        ] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), bit_string_flags);
+            assert_eq!(r, Err(ErrorKind::NonCanonical.into()));
+        }
+    }
+
+    #[test]
+    fn test_bit_flags() {
+        let mut flags = BitFlags::new();
+        flags.set(3, true);
+        flags.set(1, true);
+        assert_eq!(flags.len(), 4);
+        flags.set(3, false);
+        assert_eq!(flags.len(), 2);
+        flags.set(10, false);
+        flags.set(usize::MAX, false);
+        assert_eq!(flags, vec![false, true, false, false].into_iter().collect());
+        flags.set(1, false);
+        assert!(flags.is_empty());
+    }
+
+    #[test]
+    fn test_octet_string() {
+        with_good_i(&[0x04, 0x00], |input| {
+            assert_eq!(octet_string(input)?, Input::from(&[]));
+            Ok(())
+        });
+        with_good_i(&[0x04, 0x02, 0x0a, 0x0b], |input| {
+            assert_eq!(octet_string(input)?, Input::from(&[0x0a, 0x0b]));
+            Ok(())
+        });
+        with_good_i(&[0xa0, 0x01, 0x0a], |input| {
+            let octets = octet_string_with_tag(input, Tag::CONTEXT_SPECIFIC_CONSTRUCTED_0)?;
+            assert_eq!(octets, Input::from(&[0x0a]));
+            Ok(())
+        });
+        with_bad_i(&[0x03, 0x01, 0x00], |input| {
+            let _ = octet_string(input)?;
+            Ok(())
+        });
+        with_bad_i(&[0x04, 0x02, 0x0a], |input| {
+            let _ = octet_string(input)?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_utf8_string() {
+        with_good_i(&[0x0c, 0x00], |input| {
+            assert_eq!(utf8_string(input)?, "");
+            Ok(())
+        });
+        with_good_i(&[0x0c, 0x05, 0x64, 0xc3, 0xa9, 0x72, 0x70], |input| {
+            assert_eq!(utf8_string(input)?, "dérp");
+            Ok(())
+        });
+
+        let r = Input::from(&[0x0c, 0x02, 0xc3, 0x28]).read_all(ErrorKind::TrailingData.into(), utf8_string);
+        assert_eq!(r, Err(ErrorKind::BadStringValue.into()));
+        let r = Input::from(&[0x04, 0x01, 0x64]).read_all(ErrorKind::TrailingData.into(), utf8_string);
+        assert_eq!(r, Err(wrong_tag(Tag::UTF8_STRING, 0x04).into()));
+    }
+
+    #[test]
+    fn test_printable_string() {
+        with_good_i(b"\x13\x0fAZaz09 '()+,-./", |input| {
+            assert_eq!(printable_string(input)?, "AZaz09 '()+,-./");
+            Ok(())
+        });
+        with_good_i(b"\x13\x03:=?", |input| {
+            assert_eq!(printable_string(input)?, ":=?");
+            Ok(())
+        });
+
+        for &test_in in &[&b"\x13\x01@"[..], b"\x13\x01*", b"\x13\x01_", b"\x13\x02\xc3\xa9"] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), printable_string);
+            assert_eq!(r, Err(ErrorKind::BadStringValue.into()));
+        }
+    }
+
+    #[test]
+    fn test_ia5_string() {
+        with_good_i(b"\x16\x10user@example.com", |input| {
+            assert_eq!(ia5_string(input)?, "user@example.com");
+            Ok(())
+        });
+        with_good_i(b"\x16\x02\x00\x7f", |input| {
+            assert_eq!(ia5_string(input)?, "\u{0}\u{7f}");
+            Ok(())
+        });
+
+        let r = Input::from(b"\x16\x02\xc3\xa9").read_all(ErrorKind::TrailingData.into(), ia5_string);
+        assert_eq!(r, Err(ErrorKind::BadStringValue.into()));
+    }
+
+    #[test]
+    fn test_bmp_string() {
+        with_good_i(&[0x1e, 0x00], |input| {
+            assert_eq!(bmp_string(input)?, "");
+            Ok(())
+        });
+        with_good_i(&[0x1e, 0x08, 0x00, 0x64, 0x00, 0xe9, 0x00, 0x72, 0x00, 0x70], |input| {
+            assert_eq!(bmp_string(input)?, "dérp");
+            Ok(())
+        });
+        with_good_i(&[0x1e, 0x02, 0xff, 0xfd], |input| {
+            assert_eq!(bmp_string(input)?, "\u{fffd}");
+            Ok(())
+        });
+
+        for &test_in in &[
+            &[0x1e, 0x01, 0x00][..], // odd length
+            &[0x1e, 0x02, 0xd8, 0x3d], // high surrogate
+            &[0x1e, 0x04, 0xd8, 0x3d, 0xde, 0x00], // surrogate pair
+        ] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), bmp_string);
+            assert_eq!(r, Err(ErrorKind::BadStringValue.into()));
+        }
     }
 
-    fn with_bad_i<F, R>(value: &[u8], f: F)
-    where
-        F: FnOnce(&mut Reader) -> Result<R>,
-    {
-        let r = Input::from(value).read_all(Error::Read, f);
-        assert!(r.is_err());
+    #[test]
+    fn test_universal_string() {
+        with_good_i(&[0x1c, 0x08, 0x00, 0x00, 0x00, 0x64, 0x00, 0x01, 0xf6, 0x00], |input| {
+            assert_eq!(universal_string(input)?, "d\u{1f600}");
+            Ok(())
+        });
+
+        for &test_in in &[
+            &[0x1c, 0x03, 0x00, 0x00, 0x64][..], // length not a multiple of 4
+            &[0x1c, 0x04, 0x00, 0x00, 0xd8, 0x00], // surrogate
+            &[0x1c, 0x04, 0x00, 0x11, 0x00, 0x00], // beyond U+10FFFF
+        ] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), universal_string);
+            assert_eq!(r, Err(ErrorKind::BadStringValue.into()));
+        }
     }
 
-    static ZERO_INTEGER: &'static [u8] = &[0x02, 0x01, 0x00];
+    #[test]
+    fn test_teletex_string() {
+        with_good_i(&[0x14, 0x04, 0x64, 0xe9, 0x72, 0x70], |input| {
+            assert_eq!(teletex_string(input)?, Input::from(&[0x64, 0xe9, 0x72, 0x70]));
+            Ok(())
+        });
+        with_good_i(&[0x14, 0x04, 0x64, 0xe9, 0x72, 0x70], |input| {
+            assert_eq!(teletex_string_as_latin1(input)?, "dérp");
+            Ok(())
+        });
+        with_bad_i(&[0x13, 0x01, 0x64], |input| {
+            let _ = teletex_string(input)?;
+            Ok(())
+        });
+    }
 
-    static GOOD_POSITIVE_INTEGERS: &'static [(&'static [u8], u8)] =
-        &[
-            (&[0x02, 0x01, 0x01], 0x01),
-            (&[0x02, 0x01, 0x02], 0x02),
-            (&[0x02, 0x01, 0x7e], 0x7e),
-            (&[0x02, 0x01, 0x7f], 0x7f),
+    #[test]
+    fn test_numeric_string() {
+        with_good_i(b"\x12\x05012 9", |input| {
+            assert_eq!(numeric_string(input)?, "012 9");
+            Ok(())
+        });
 
-            // Values that need to have an 0x00 prefix to disambiguate them from
-            // them from negative values.
-            (&[0x02, 0x02, 0x00, 0x80], 0x80),
-            (&[0x02, 0x02, 0x00, 0x81], 0x81),
-            (&[0x02, 0x02, 0x00, 0xfe], 0xfe),
-            (&[0x02, 0x02, 0x00, 0xff], 0xff),
+        for &test_in in &[&b"\x12\x01a"[..], b"\x12\x01-", b"\x12\x01+"] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), numeric_string);
+            assert_eq!(r, Err(ErrorKind::BadStringValue.into()));
+        }
+    }
+
+    #[test]
+    fn test_visible_string() {
+        with_good_i(b"\x1a\x05 a~!@", |input| {
+            assert_eq!(visible_string(input)?, " a~!@");
+            Ok(())
+        });
+
+        for &test_in in &[&b"\x1a\x01\x7f"[..], b"\x1a\x01\n", b"\x1a\x02\xc3\xa9"] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), visible_string);
+            assert_eq!(r, Err(ErrorKind::BadStringValue.into()));
+        }
+    }
+
+    #[test]
+    fn test_enumerated() {
+        for &(test_in, test_out) in &[
+            (&[0x0a, 0x01, 0x00][..], 0),
+            (&[0x0a, 0x01, 0x7f], 127),
+            (&[0x0a, 0x02, 0x00, 0x80], 128),
+            (&[0x0a, 0x01, 0xff], -1),
+            (&[0x0a, 0x01, 0x80], -128),
+            (&[0x0a, 0x02, 0xff, 0x7f], -129),
+            (&[0x0a, 0x08, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff], i64::MAX),
+            (&[0x0a, 0x08, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], i64::MIN),
+        ] {
+            with_good_i(test_in, |input| {
+                assert_eq!(enumerated_i64(input)?, test_out);
+                Ok(())
+            });
+        }
+
+        for &(test_in, err) in &[
+            (&[0x0a, 0x00][..], ErrorKind::UnexpectedEnd),
+            (&[0x0a, 0x02, 0x00, 0x7f], ErrorKind::NonCanonical),
+            (&[0x0a, 0x02, 0xff, 0x80], ErrorKind::NonCanonical),
+            (&[0x0a, 0x09, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], ErrorKind::IntegerOverflow),
+            (&[0x02, 0x01, 0x00], wrong_tag(Tag::ENUMERATED, 0x02)),
+        ] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), enumerated_i64);
+            assert_eq!(r, Err(err.into()));
+        }
+    }
+
+    #[test]
+    fn test_error_location() {
+        fn nested_null(input: &mut Reader) -> Result<()> {
+            nested(input, Tag::SEQUENCE, read_null)
+        }
+        fn nested_integer(input: &mut Reader) -> Result<()> {
+            nested(input, Tag::SEQUENCE, |input| positive_integer(input).map(|_| ()))
+        }
+
+        type Decoder = fn(&mut Reader) -> Result<()>;
+        let cases: &[(&[u8], Decoder, ErrorKind, usize)] = &[
+            (&[0x30, 0x02, 0x02, 0x00], nested_null, wrong_tag(Tag::NULL, 0x02), 2),
+            // Truncated contents
+            (&[0x30, 0x03, 0x02, 0x05, 0x01], nested_integer, ErrorKind::UnexpectedEnd, 2),
+            // Contents not fully consumed
+            (&[0x30, 0x04, 0x05, 0x00, 0x05, 0x00], nested_null, ErrorKind::UnconsumedContents, 4),
+            // Bad contents of a primitive element
+            (&[0x30, 0x03, 0x02, 0x01, 0x80], nested_integer, ErrorKind::NegativeValue, 5),
         ];
+        for &(encoded, decoder, kind, offset) in cases {
+            let err = Input::from(encoded).read_all(ErrorKind::TrailingData.into(), decoder).unwrap_err();
+            assert_eq!(err.kind(), kind);
+            assert_eq!(err.offset(encoded), Some(offset));
+            assert_eq!(err.path(), &[Tag::SEQUENCE]);
+            let copy = encoded.to_vec();
+            assert_eq!(err.offset(&copy), None);
+        }
 
-    static BAD_NONNEGATIVE_INTEGERS: &'static [&'static [u8]] = &[
-        &[], // At end of input
-        &[0x02], // Tag only
-        &[0x02, 0x00], // Empty value
+        // Errors are located even when the caller reads the contents itself.
+        let encoded = &[0x30, 0x04, 0x30, 0x02, 0x01, 0x00];
+        let err = Input::from(encoded)
+            .read_all(ErrorKind::TrailingData.into(), |input| {
+                let value = expect_tag_and_get_value(input, Tag::SEQUENCE)?;
+                value.read_all(ErrorKind::TrailingData.into(), nested_null)
+            })
+            .unwrap_err();
+        assert_eq!(err.offset(encoded), Some(4));
 
-        // Length mismatch
-        &[0x02, 0x00, 0x01],
-        &[0x02, 0x01],
-        &[0x02, 0x01, 0x00, 0x01],
-        &[0x02, 0x01, 0x01, 0x00], // Would be valid if last byte is ignored.
-        &[0x02, 0x02, 0x01],
+        let err = Input::from(&[0x30, 0x06, 0xa3, 0x04, 0x30, 0x02, 0x04, 0x00])
+            .read_all(ErrorKind::TrailingData.into(), |input| {
+                nested(input, Tag::SEQUENCE, |input| {
+                    read_explicit(input, 3, |input| nested(input, Tag::SEQUENCE, boolean))
+                })
+            })
+            .unwrap_err();
+        let path = [Tag::SEQUENCE, Tag::CONTEXT_SPECIFIC_CONSTRUCTED_3, Tag::SEQUENCE];
+        assert_eq!(err.path(), &path);
+        let message = concat!(
+            "expected BOOLEAN but found identifier octet 0x04 ",
+            "in SEQUENCE > [3] > SEQUENCE",
+        );
+        assert_eq!(err.to_string(), message);
+    }
 
-        // Negative values
-        &[0x02, 0x01, 0x80],
-        &[0x02, 0x01, 0xfe],
-        &[0x02, 0x01, 0xff],
+    #[test]
+    fn test_nested_set() {
+        with_good_i(&[0x31, 0x05, 0x02, 0x01, 0x01, 0x05, 0x00], |input| {
+            nested_set(input, |input| {
+                assert_eq!(small_nonnegative_integer(input)?, 1);
+                read_null(input)
+            })
+        });
+        with_bad_i(&[0x30, 0x02, 0x05, 0x00], |input| nested_set(input, read_null));
+        // Contents not fully consumed by the decoder.
+        with_bad_i(&[0x31, 0x04, 0x05, 0x00, 0x05, 0x00], |input| nested_set(input, read_null));
+    }
 
-        // Values that have an unnecessary leading 0x00
-        &[0x02, 0x02, 0x00, 0x00],
-        &[0x02, 0x02, 0x00, 0x01],
-        &[0x02, 0x02, 0x00, 0x02],
-        &[0x02, 0x02, 0x00, 0x7e],
-        &[0x02, 0x02, 0x00, 0x7f],
-    ];
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_nested_max_depth() {
+        fn depth(input: &mut Reader) -> Result<usize> {
+            nested(input, Tag::SEQUENCE, |input| match input.at_end() {
+                true => Ok(1),
+                false => Ok(depth(input)? + 1),
+            })
+        }
+
+        let read = |encoded: &[u8]| {
+            Input::from(encoded).read_all(ErrorKind::TrailingData.into(), depth)
+        };
+
+        // Wrap `inner` in `levels` elements with identifier octet `identifier`, in DER.
+        fn wrap(identifier: u8, levels: usize, mut inner: Vec<u8>) -> Vec<u8> {
+            for _ in 0..levels {
+                let len = inner.len();
+                let mut outer = match len {
+                    0..=0x7f => vec![identifier, len as u8],
+                    0x80..=0xff => vec![identifier, 0x81, len as u8],
+                    _ => vec![identifier, 0x82, (len >> 8) as u8, len as u8],
+                };
+                outer.extend(inner);
+                inner = outer;
+            }
+            inner
+        }
+
+        // One level deeper than the default limit.
+        let encoded = wrap(0x30, ParserConfig::DEFAULT_MAX_DEPTH, vec![0x30, 0x00]);
+        let err = read(&encoded).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NestingTooDeep);
+        assert_eq!(err.path().len(), ParserConfig::DEFAULT_MAX_DEPTH);
+        // The levels are left again after the error.
+        let encoded = wrap(0x30, ParserConfig::DEFAULT_MAX_DEPTH - 1, vec![0x30, 0x00]);
+        assert_eq!(read(&encoded), Ok(ParserConfig::DEFAULT_MAX_DEPTH));
+
+        // EXPLICIT tags count as well.
+        let encoded = wrap(0xa0, ParserConfig::DEFAULT_MAX_DEPTH + 1, vec![0x05, 0x00]);
+        fn explicit(input: &mut Reader) -> Result<()> {
+            read_explicit(input, 0, |input| match input.peek(0xa0) {
+                true => explicit(input),
+                false => read_null(input),
+            })
+        }
+        let input = Input::from(&encoded[..]);
+        let err = input.read_all(ErrorKind::TrailingData.into(), explicit).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NestingTooDeep);
+    }
+
+    #[test]
+    fn test_set_of() {
+        with_good_i(&[0x31, 0x00], |input| {
+            assert!(set_of(input, read_null)?.is_empty());
+            Ok(())
+        });
+        // Equal elements are allowed.
+        with_good_i(&[0x31, 0x04, 0x05, 0x00, 0x05, 0x00], |input| {
+            assert_eq!(set_of(input, read_null)?.len(), 2);
+            Ok(())
+        });
+        // Shorter encodings sort first when they are a prefix of a longer one.
+        with_good_i(&[0x31, 0x07, 0x04, 0x01, 0x01, 0x04, 0x02, 0x01, 0x00], |input| {
+            assert_eq!(set_of(input, octet_string)?.len(), 2);
+            Ok(())
+        });
+
+        let r = Input::from(&[0x31, 0x05, 0x05, 0x00, 0x01, 0x01, 0xff])
+            .read_all(ErrorKind::TrailingData.into(), |input| set_of(input, read_any));
+        assert_eq!(r.map(|v| v.len()), Err(ErrorKind::NonCanonical.into()));
+
+        let r = Input::from(&[0x31, 0x02, 0x05, 0x00])
+            .read_all(ErrorKind::TrailingData.into(), |input| set_of(input, |_| Ok(())));
+        assert_eq!(r, Err(ErrorKind::UnconsumedContents.into()));
+    }
+
+    #[test]
+    fn test_read_sequence_of() {
+        with_good_i(&[0x30, 0x06, 0x01, 0x01, 0xff, 0x01, 0x01, 0x00], |input| {
+            assert_eq!(read_sequence_of(input, boolean)?, vec![true, false]);
+            Ok(())
+        });
+        with_good_i(&[0x30, 0x00], |input| {
+            assert!(read_sequence_of(input, boolean)?.is_empty());
+            Ok(())
+        });
+
+        for &(test_in, err) in &[
+            (&[0x31, 0x03, 0x01, 0x01, 0xff][..], wrong_tag(Tag::SEQUENCE, 0x31)),
+            (&[0x30, 0x03, 0x05, 0x00, 0x00], wrong_tag(Tag::BOOLEAN, 0x05)),
+        ] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), |input| read_sequence_of(input, boolean));
+            assert_eq!(r, Err(err.into()));
+        }
+
+        // A decoder that reads nothing must not loop forever.
+        let r = Input::from(&[0x30, 0x02, 0x05, 0x00])
+            .read_all(ErrorKind::TrailingData.into(), |input| read_sequence_of(input, |_| Ok(())));
+        assert_eq!(r, Err(ErrorKind::UnconsumedContents.into()));
+    }
+
+    #[test]
+    fn test_sequence_of() {
+        with_good_i(&[0x30, 0x05, 0x05, 0x00, 0x01, 0x01, 0xff], |input| {
+            let elements = sequence_of(input, Tag::SEQUENCE)?.collect::<Result<Vec<_>>>()?;
+            assert_eq!(elements, vec![Input::from(&[0x05, 0x00]), Input::from(&[0x01, 0x01, 0xff])]);
+            Ok(())
+        });
+        with_good_i(&[0x30, 0x00], |input| {
+            assert_eq!(sequence_of(input, Tag::SEQUENCE)?.count(), 0);
+            Ok(())
+        });
+        with_bad_i(&[0x31, 0x00], |input| sequence_of(input, Tag::SEQUENCE).map(|_| ()));
+
+        // A truncated element is returned as an error, after which iteration stops.
+        with_good_i(&[0x30, 0x04, 0x05, 0x00, 0x01, 0x01], |input| {
+            let mut elements = sequence_of(input, Tag::SEQUENCE)?;
+            assert_eq!(elements.next(), Some(Ok(Input::from(&[0x05, 0x00]))));
+            assert_eq!(elements.next(), Some(Err(ErrorKind::UnexpectedEnd.into())));
+            assert_eq!(elements.next(), None);
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_set_of_order() {
+        assert_eq!(set_of_order(&[0x01], &[0x02]), Ordering::Less);
+        assert_eq!(set_of_order(&[0x01, 0x00], &[0x01]), Ordering::Equal);
+        assert_eq!(set_of_order(&[0x01], &[0x01, 0x01]), Ordering::Less);
+        assert_eq!(set_of_order(&[0x02], &[0x01, 0xff]), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_read_explicit() {
+        with_good_i(&[0xa3, 0x02, 0x05, 0x00], |input| read_explicit(input, 3, read_null));
+        with_good_i(&[0xbe, 0x02, 0x05, 0x00], |input| read_explicit(input, 30, read_null));
+        with_good_i(&[0xbf, 0x81, 0x00, 0x02, 0x05, 0x00], |input| read_explicit(input, 128, read_null));
+
+        let explicit = |number| Tag::new(Class::ContextSpecific, true, number);
+        for &(test_in, tag_number, err) in &[
+            (&[0xa3, 0x02, 0x05, 0x00][..], 2, wrong_tag(explicit(2), 0xa3)),
+            // [3] IMPLICIT, i.e. primitive
+            (&[0x83, 0x02, 0x05, 0x00], 3, wrong_tag(explicit(3), 0x83)),
+            // Trailing data after the wrapped element
+            (&[0xa3, 0x04, 0x05, 0x00, 0x05, 0x00], 3, ErrorKind::UnconsumedContents),
+            (&[0xa3, 0x02, 0x05, 0x00], 31, wrong_tag(explicit(31), 0xa3)),
+        ] {
+            let r = Input::from(test_in)
+                .read_all(ErrorKind::TrailingData.into(), |input| read_explicit(input, tag_number, read_null));
+            assert_eq!(r, Err(err.into()));
+        }
+    }
+
+    #[test]
+    fn test_read_implicit() {
+        for &test_in in &[&[0x81, 0x01, 0xff][..], &[0xa1, 0x01, 0xff]] {
+            with_good_i(test_in, |input| {
+                assert_eq!(read_implicit(input, 1)?, Input::from(&[0xff]));
+                Ok(())
+            });
+        }
+
+        let implicit = |number| Tag::new(Class::ContextSpecific, false, number);
+        for &(test_in, tag_number, err) in &[
+            (&[0x81, 0x01, 0xff][..], 2, wrong_tag(implicit(2), 0x81)),
+            // APPLICATION and UNIVERSAL classes with the same tag number
+            (&[0x41, 0x01, 0xff], 1, wrong_tag(implicit(1), 0x41)),
+            (&[0x01, 0x01, 0xff], 1, wrong_tag(implicit(1), 0x01)),
+            (&[0x81, 0x01, 0xff], 31, wrong_tag(implicit(31), 0x81)),
+        ] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), |input| read_implicit(input, tag_number));
+            assert_eq!(r, Err(err.into()));
+        }
+    }
+
+    #[test]
+    fn test_read_any() {
+        for &(test_in, tag) in &[
+            (&[0x30, 0x00][..], Tag::SEQUENCE),
+            (&[0x5e, 0x00], Tag::new(Class::Application, false, 30)),
+            (&[0x9f, 0x1f, 0x00], Tag::new(Class::ContextSpecific, false, 31)),
+            (&[0xff, 0x81, 0x00, 0x00], Tag::new(Class::Private, true, 128)),
+            (&[0x1f, 0x8f, 0xff, 0xff, 0xff, 0x7f, 0x00], Tag::new(Class::Universal, false, u32::MAX)),
+        ] {
+            with_good_i(test_in, |input| {
+                assert_eq!(read_any(input)?, (tag, Input::from(&[])));
+                Ok(())
+            });
+        }
+
+        for &(test_in, err) in &[
+            // Leading zero digit in the tag number
+            (&[0x1f, 0x80, 0x1f, 0x00][..], ErrorKind::NonCanonical),
+            // Tag number that fits in low tag number form
+            (&[0x1f, 0x1e, 0x00], ErrorKind::NonCanonical),
+            // Tag number that does not fit in a u32
+            (&[0x1f, 0x90, 0x80, 0x80, 0x80, 0x00, 0x00], ErrorKind::HighTagNumberForm),
+            // Tag number that never ends
+            (&[0x1f, 0x81], ErrorKind::UnexpectedEnd),
+        ] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), read_any);
+            assert_eq!(r, Err(err.into()));
+        }
+    }
+
+    #[test]
+    fn test_peek_tag() {
+        with_good_i(&[0xa1, 0x00], |input| {
+            assert_eq!(peek_tag(input)?, Tag::new(Class::ContextSpecific, true, 1));
+            assert_eq!(peek_tag(input)?, Tag::new(Class::ContextSpecific, true, 1));
+            read_implicit(input, 1)?;
+            assert_eq!(peek_tag(input), Err(ErrorKind::UnexpectedEnd.into()));
+            Ok(())
+        });
+        with_good_i(&[0xff, 0x1f, 0x00], |input| {
+            assert_eq!(peek_tag(input)?, Tag::new(Class::Private, true, 31));
+            assert_eq!(peek_tag(input)?, Tag::new(Class::Private, true, 31));
+            skip_element(input)
+        });
+        with_good_i(&[0x1f, 0x90, 0x80, 0x80, 0x80, 0x00], |input| {
+            assert_eq!(peek_tag(input), Err(ErrorKind::HighTagNumberForm.into()));
+            input.skip_to_end();
+            Ok(())
+        });
+        with_good_i(&[0x1f, 0x81], |input| {
+            assert_eq!(peek_tag(input), Err(ErrorKind::UnexpectedEnd.into()));
+            input.skip_to_end();
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn test_read_optional() {
+        with_good_i(&[0x05, 0x00, 0x01, 0x01, 0x00], |input| {
+            assert_eq!(read_optional(input, Tag::NULL, read_null)?, Some(()));
+            assert_eq!(read_optional(input, Tag::NULL, read_null)?, None);
+            assert_eq!(read_optional(input, Tag::BOOLEAN, boolean)?, Some(false));
+            assert_eq!(read_optional(input, Tag::BOOLEAN, boolean)?, None);
+            Ok(())
+        });
+        // Errors from the decoder are not swallowed.
+        with_bad_i(&[0x01, 0x01, 0x01], |input| read_optional(input, Tag::BOOLEAN, boolean));
+
+        // Tags in high tag number form are compared in full.
+        let tag = Tag::new(Class::ContextSpecific, true, 31);
+        with_good_i(&[0xbf, 0x1f, 0x00], |input| {
+            assert_eq!(read_optional(input, tag, skip_element)?, Some(()));
+            Ok(())
+        });
+        with_good_i(&[0xbf, 0x20, 0x00, 0x05, 0x00], |input| {
+            let start = input.mark();
+            assert_eq!(read_optional(input, tag, skip_element)?, None);
+            let tag = Tag::new(Class::ContextSpecific, true, 32);
+            assert_eq!(read_optional(input, tag, skip_element)?, Some(()));
+            let element = input.get_input_between_marks(start, input.mark()).unwrap();
+            assert_eq!(element.as_slice_less_safe(), &[0xbf, 0x20, 0x00]);
+            read_null(input)
+        });
+        with_good_i(&[0x05, 0x00], |input| {
+            assert_eq!(read_optional(input, tag, skip_element)?, None);
+            read_null(input)
+        });
+    }
+
+    #[test]
+    fn test_read_default() {
+        let read = |test_in: &[u8], mode| {
+            Input::from(test_in).read_all(Error::from(ErrorKind::TrailingData), |input| {
+                let value = read_default_with_mode(input, Tag::BOOLEAN, false, mode, boolean)?;
+                read_null(input)?;
+                Ok(value)
+            })
+        };
+
+        assert_eq!(read(&[0x05, 0x00], Mode::Strict), Ok(false));
+        assert_eq!(read(&[0x01, 0x01, 0xff, 0x05, 0x00], Mode::Strict), Ok(true));
+        assert_eq!(read(&[0x01, 0x01, 0x00, 0x05, 0x00], Mode::Strict), Err(ErrorKind::NonCanonical.into()));
+        assert_eq!(read(&[0x01, 0x01, 0x00, 0x05, 0x00], Mode::Lenient), Ok(false));
+        assert_eq!(read(&[0x01, 0x01, 0x01, 0x05, 0x00], Mode::Lenient), Err(ErrorKind::BadBooleanValue.into()));
+    }
+
+    #[test]
+    fn test_choice() {
+        #[derive(Debug, PartialEq)]
+        enum Value<'a> {
+            Flag(bool),
+            Name(&'a str),
+        }
+
+        let read = |test_in| {
+            Input::from(test_in).read_all(ErrorKind::TrailingData.into(), |input| {
+                choice(input, &[
+                    (Tag::BOOLEAN, &|input| boolean(input).map(Value::Flag)),
+                    (Tag::UTF8_STRING, &|input| utf8_string(input).map(Value::Name)),
+                    (Tag::new(Class::Private, false, 31), &|input| {
+                        read_tagged(input, Class::Private, 31)?;
+                        Ok(Value::Flag(false))
+                    }),
+                    (Tag::new(Class::Private, false, 32), &|input| {
+                        read_tagged(input, Class::Private, 32)?;
+                        Ok(Value::Flag(true))
+                    }),
+                ])
+            })
+        };
+
+        assert_eq!(read(&[0x01, 0x01, 0xff]), Ok(Value::Flag(true)));
+        assert_eq!(read(b"\x0c\x01a"), Ok(Value::Name("a")));
+        assert_eq!(read(&[0xdf, 0x1f, 0x00]), Ok(Value::Flag(false)));
+        assert_eq!(read(&[0xdf, 0x20, 0x00]), Ok(Value::Flag(true)));
+        assert_eq!(read(&[0xdf, 0x21, 0x00]), Err(ErrorKind::UnknownTag.into()));
+        assert_eq!(read(&[0x05, 0x00]), Err(ErrorKind::UnknownTag.into()));
+        assert_eq!(read(&[]), Err(ErrorKind::EmptyInput.into()));
+    }
+
+    #[test]
+    fn test_skip_element() {
+        with_good_i(&[0x30, 0x04, 0x30, 0x02, 0x05, 0x00, 0x05, 0x00], |input| {
+            skip_element(input)?;
+            read_null(input)
+        });
+        with_good_i(&[0xbf, 0x81, 0x00, 0x00], skip_element);
+        with_bad_i(&[], skip_element);
+        with_bad_i(&[0x30, 0x03, 0x05, 0x00], skip_element);
+    }
+
+    #[test]
+    fn test_long_length() {
+        for &len in &[128, 255, 256, 0xffff, 0x10000] {
+            let mut buf = vec![0x04, 0x80 | length_of_length(len)];
+            for i in (0..length_of_length(len)).rev() {
+                buf.push((len >> (i * 8)) as u8);
+            }
+            buf.resize(buf.len() + len, 0xaa);
+
+            with_good_i(&buf, |input| {
+                assert_eq!(octet_string(input)?.len(), len);
+                Ok(())
+            });
+        }
+
+        for &(test_in, err) in &[
+            // Indefinite length
+            (&[0x04, 0x80, 0x00, 0x00][..], ErrorKind::LongLengthNotSupported),
+            // Fits in fewer length bytes
+            (&[0x04, 0x81, 0x7f], ErrorKind::NonCanonical),
+            (&[0x04, 0x82, 0x00, 0xff], ErrorKind::NonCanonical),
+            (&[0x04, 0x83, 0x00, 0x01, 0x00], ErrorKind::NonCanonical),
+            // More length bytes than fit in a usize
+            (
+                &[0x04, 0x89, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                ErrorKind::LengthOverflow,
+            ),
+            // Large length with too little data
+            (&[0x04, 0x84, 0xff, 0xff, 0xff, 0xff, 0x00], ErrorKind::UnexpectedEnd),
+        ] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), octet_string);
+            assert_eq!(r, Err(err.into()));
+        }
+    }
+
+    #[test]
+    fn test_long_length_lenient() {
+        let read = |encoded| {
+            Input::from(encoded).read_all(ErrorKind::TrailingData.into(), |input| {
+                read_any_with_mode(input, Mode::Lenient).map(|(tag, value)| (tag, value.len()))
+            })
+        };
+        for &(test_in, len) in &[
+            (&[0x04, 0x00][..], 0),
+            (&[0x04, 0x81, 0x00], 0),
+            (&[0x04, 0x82, 0x00, 0x01, 0xaa], 1),
+            (&[0x04, 0x89, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], 0),
+        ] {
+            assert_eq!(read(test_in), Ok((Tag::OCTET_STRING, len)));
+        }
+
+        for &(test_in, err) in &[
+            (&[0x04, 0x80, 0x00, 0x00][..], ErrorKind::LongLengthNotSupported),
+            (
+                &[0x04, 0x89, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                ErrorKind::LengthOverflow,
+            ),
+            (&[0x04, 0x82, 0x00], ErrorKind::UnexpectedEnd),
+        ] {
+            assert_eq!(read(test_in), Err(err.into()));
+        }
+
+        let r = Input::from(&[0x30, 0x84, 0x00, 0x00, 0x00, 0x02, 0x05, 0x00])
+            .read_all(ErrorKind::TrailingData.into(), |input| {
+                nested_with_mode(input, Tag::SEQUENCE, Mode::Lenient, read_null)
+            });
+        assert_eq!(r, Ok(()));
+    }
+
+    #[test]
+    fn test_read_tagged() {
+        for &(test_in, class) in &[
+            (&[0x05, 0x01, 0xff][..], Class::Universal),
+            (&[0x45, 0x01, 0xff], Class::Application),
+            (&[0x65, 0x01, 0xff], Class::Application),
+            (&[0x85, 0x01, 0xff], Class::ContextSpecific),
+            (&[0xc5, 0x01, 0xff], Class::Private),
+            (&[0xe5, 0x01, 0xff], Class::Private),
+        ] {
+            with_good_i(test_in, |input| {
+                assert_eq!(read_tagged(input, class, 5)?, Input::from(&[0xff]));
+                Ok(())
+            });
+        }
+
+        let r = Input::from(&[0xc5, 0x01, 0xff])
+            .read_all(ErrorKind::TrailingData.into(), |input| read_tagged(input, Class::Application, 5));
+        assert_eq!(r, Err(wrong_tag(Tag::new(Class::Application, false, 5), 0xc5).into()));
+
+        with_good_i(&[0xe1, 0x02, 0x05, 0x00], |input| {
+            read_tagged_explicit(input, Class::Private, 1, read_null)
+        });
+        // EXPLICIT requires the constructed bit.
+        with_bad_i(&[0xc1, 0x02, 0x05, 0x00], |input| {
+            read_tagged_explicit(input, Class::Private, 1, read_null)
+        });
+    }
 
     #[test]
     fn test_small_nonnegative_integer() {
@@ -390,7 +2618,7 @@ mod tests {
             assert_eq!(small_nonnegative_integer(input)?, 0x00);
             Ok(())
         });
-        for &(ref test_in, test_out) in GOOD_POSITIVE_INTEGERS.iter() {
+        for &(test_in, test_out) in GOOD_POSITIVE_INTEGERS.iter() {
             with_good_i(test_in, |input| {
                 assert_eq!(small_nonnegative_integer(input)?, test_out);
                 Ok(())
@@ -404,13 +2632,102 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_read_unsigned() {
+        with_good_i(ZERO_INTEGER, |input| {
+            assert_eq!(read_u8(input)?, 0);
+            Ok(())
+        });
+        with_good_i(&[0x02, 0x02, 0x00, 0xff], |input| {
+            assert_eq!(read_u8(input)?, 0xff);
+            Ok(())
+        });
+        with_good_i(&[0x02, 0x02, 0x01, 0x00], |input| {
+            assert_eq!(read_u16(input)?, 0x100);
+            Ok(())
+        });
+        with_good_i(&[0x02, 0x05, 0x00, 0xff, 0xff, 0xff, 0xff], |input| {
+            assert_eq!(read_u32(input)?, u32::MAX);
+            Ok(())
+        });
+        with_good_i(&[0x02, 0x08, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff], |input| {
+            assert_eq!(read_u64(input)?, i64::MAX as u64);
+            Ok(())
+        });
+        let mut max = vec![0x02, 0x11, 0x00];
+        max.extend_from_slice(&[0xff; 16]);
+        with_good_i(&max, |input| {
+            assert_eq!(read_u128(input)?, u128::MAX);
+            Ok(())
+        });
+
+        for &(test_in, err) in &[
+            (&[0x02, 0x02, 0x01, 0x00][..], ErrorKind::IntegerOverflow),
+            (&[0x02, 0x01, 0x80], ErrorKind::NegativeValue),
+            (&[0x02, 0x02, 0x00, 0x01], ErrorKind::LeadingZero),
+            (&[0x0a, 0x01, 0x01], wrong_tag(Tag::INTEGER, 0x0a)),
+        ] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), read_u8);
+            assert_eq!(r, Err(err.into()));
+        }
+        let r = Input::from(&[0x02, 0x03, 0x01, 0x00, 0x00]).read_all(ErrorKind::TrailingData.into(), read_u16);
+        assert_eq!(r, Err(ErrorKind::IntegerOverflow.into()));
+        let r = Input::from(&[0x02, 0x05, 0x01, 0x00, 0x00, 0x00, 0x00]).read_all(ErrorKind::TrailingData.into(), read_u32);
+        assert_eq!(r, Err(ErrorKind::IntegerOverflow.into()));
+    }
+
+    #[test]
+    fn test_read_signed() {
+        for &(test_in, value) in &[
+            (&[0x02, 0x01, 0x00][..], 0),
+            (&[0x02, 0x01, 0x7f], 127),
+            (&[0x02, 0x02, 0x00, 0x80], 128),
+            (&[0x02, 0x01, 0xff], -1),
+            (&[0x02, 0x01, 0x80], -128),
+            (&[0x02, 0x02, 0xff, 0x7f], -129),
+            (&[0x02, 0x08, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], i64::MIN),
+            (&[0x02, 0x08, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff], i64::MAX),
+        ] {
+            with_good_i(test_in, |input| {
+                assert_eq!(read_i64(input)?, value);
+                Ok(())
+            });
+            with_good_i(test_in, |input| {
+                assert_eq!(read_i128(input)?, i128::from(value));
+                Ok(())
+            });
+        }
+
+        let mut min = vec![0x02, 0x10, 0x80];
+        min.extend_from_slice(&[0x00; 15]);
+        with_good_i(&min, |input| {
+            assert_eq!(read_i128(input)?, i128::MIN);
+            Ok(())
+        });
+        assert_eq!(Input::from(&min).read_all(ErrorKind::TrailingData.into(), read_i64), Err(ErrorKind::IntegerOverflow.into()));
+
+        for &(test_in, err) in &[
+            (&[0x02, 0x00][..], ErrorKind::UnexpectedEnd),
+            (&[0x02, 0x02, 0x00, 0x7f], ErrorKind::NonCanonical),
+            (&[0x02, 0x02, 0xff, 0x80], ErrorKind::NonCanonical),
+            (
+                &[0x02, 0x09, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+                ErrorKind::IntegerOverflow,
+            ),
+            (&[0x0a, 0x01, 0x01], wrong_tag(Tag::INTEGER, 0x0a)),
+        ] {
+            let r = Input::from(test_in).read_all(ErrorKind::TrailingData.into(), read_i64);
+            assert_eq!(r, Err(err.into()));
+        }
+    }
+
     #[test]
     fn test_positive_integer() {
         with_bad_i(ZERO_INTEGER, |input| {
             let _ = positive_integer(input)?;
             Ok(())
         });
-        for &(ref test_in, test_out) in GOOD_POSITIVE_INTEGERS.iter() {
+        for &(test_in, test_out) in GOOD_POSITIVE_INTEGERS.iter() {
             with_good_i(test_in, |input| {
                 let test_out = [test_out];
                 assert_eq!(
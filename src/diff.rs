@@ -0,0 +1,267 @@
+//! Finding where two DER encodings differ in structure or value.
+
+use alloc::vec::Vec;
+use std::fmt::{self, Display};
+use untrusted::Input;
+
+use config::ParserConfig;
+use der::{self, Tag};
+use {Error, ErrorKind, Result, ResultExt};
+
+/// How two elements at the same place in the structures compared by `diff` differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DifferenceKind {
+    /// The element has tag `a` in the first input and `b` in the second. Their contents are not
+    /// compared.
+    Tag { a: Tag, b: Tag },
+    /// Primitive elements with the same tag have different contents.
+    Contents(Tag),
+    /// An element is in the first input but not the second.
+    OnlyInA(Tag),
+    /// An element is in the second input but not the first.
+    OnlyInB(Tag),
+}
+
+/// A place where the inputs to `diff` differ.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    kind: DifferenceKind,
+    path: Vec<Tag>,
+    offsets: (usize, usize),
+}
+
+impl Difference {
+    /// How the elements differ.
+    pub fn kind(&self) -> DifferenceKind {
+        self.kind
+    }
+
+    /// The tags of the elements that enclose the difference, outermost first, which are the same
+    /// in both inputs.
+    pub fn path(&self) -> &[Tag] {
+        &self.path
+    }
+
+    /// The offsets of the element in the first and the second input. For an element that is only
+    /// in one of them, the offset in the other is where it would have been.
+    pub fn offsets(&self) -> (usize, usize) {
+        self.offsets
+    }
+}
+
+impl Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            DifferenceKind::Tag { a, b } => write!(f, "{} differs from {}", a, b)?,
+            DifferenceKind::Contents(tag) => write!(f, "{} has different contents", tag)?,
+            DifferenceKind::OnlyInA(tag) => write!(f, "{} is only in the first input", tag)?,
+            DifferenceKind::OnlyInB(tag) => write!(f, "{} is only in the second input", tag)?,
+        }
+        write!(f, " at offsets {} and {}", self.offsets.0, self.offsets.1)?;
+        for (i, tag) in self.path.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " in " } else { " > " }, tag)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare the DER elements of `a` with those of `b`, and return every place where they differ,
+/// in the order they come in the inputs, so that the first is the earliest difference.
+///
+/// The inputs are walked in lockstep, comparing the elements at each level in order. The contents
+/// of constructed elements are compared when their tags are the same, and the contents of
+/// primitive elements as octets. An input that cannot be read fails with the error from reading
+/// it, with the context `"first input"` or `"second input"`.
+///
+/// ```
+/// extern crate derp;
+///
+/// use derp::{DifferenceKind, Tag};
+///
+/// fn main() {
+///     // SEQUENCE { INTEGER 1, BOOLEAN TRUE } and SEQUENCE { INTEGER 2, BOOLEAN TRUE, NULL }
+///     let a = [0x30, 0x06, 0x02, 0x01, 0x01, 0x01, 0x01, 0xff];
+///     let b = [0x30, 0x08, 0x02, 0x01, 0x02, 0x01, 0x01, 0xff, 0x05, 0x00];
+///
+///     let differences = derp::diff(&a, &b).unwrap();
+///     assert_eq!(differences.len(), 2);
+///     assert_eq!(differences[0].kind(), DifferenceKind::Contents(Tag::INTEGER));
+///     assert_eq!(differences[0].path(), &[Tag::SEQUENCE]);
+///     assert_eq!(differences[1].offsets(), (8, 8));
+///     assert_eq!(
+///         differences[1].to_string(),
+///         "NULL is only in the second input at offsets 8 and 8 in SEQUENCE"
+///     );
+///     assert!(derp::diff(&a, &a).unwrap().is_empty());
+/// }
+/// ```
+pub fn diff(a: &[u8], b: &[u8]) -> Result<Vec<Difference>> {
+    diff_with_config(a, b, ParserConfig::DER)
+}
+
+/// Compare `a` with `b` using `config` for reading them, as `diff` does.
+pub fn diff_with_config(a: &[u8], b: &[u8], config: ParserConfig) -> Result<Vec<Difference>> {
+    let mut differ = Differ {
+        config,
+        inputs: (a, b),
+        path: Vec::new(),
+        differences: Vec::new(),
+    };
+    differ.compare(Input::from(a), Input::from(b), config.max_depth())?;
+    Ok(differ.differences)
+}
+
+// An element read by `Differ::elements`.
+struct Element<'a> {
+    tag: Tag,
+    contents: Input<'a>,
+    offset: usize,
+}
+
+struct Differ<'x> {
+    config: ParserConfig,
+    inputs: (&'x [u8], &'x [u8]),
+    // The tags of the elements being compared, outermost first.
+    path: Vec<Tag>,
+    differences: Vec<Difference>,
+}
+
+impl<'x> Differ<'x> {
+    // Compare the elements in `a` with those in `b`, entering at most `depth` more levels.
+    fn compare(&mut self, a: Input<'x>, b: Input<'x>, depth: usize) -> Result<()> {
+        let elements_a = self.elements(a, self.inputs.0).context("first input")?;
+        let elements_b = self.elements(b, self.inputs.1).context("second input")?;
+        let ends = (
+            self.offset(a, self.inputs.0) + a.len(),
+            self.offset(b, self.inputs.1) + b.len(),
+        );
+        let len = elements_a.len().max(elements_b.len());
+        for i in 0..len {
+            let (x, y) = match (elements_a.get(i), elements_b.get(i)) {
+                (Some(x), Some(y)) => (x, y),
+                (Some(x), None) => {
+                    self.push(DifferenceKind::OnlyInA(x.tag), (x.offset, ends.1));
+                    continue;
+                }
+                (None, Some(y)) => {
+                    self.push(DifferenceKind::OnlyInB(y.tag), (ends.0, y.offset));
+                    continue;
+                }
+                (None, None) => unreachable!(),
+            };
+            let offsets = (x.offset, y.offset);
+            if x.tag != y.tag {
+                self.push(DifferenceKind::Tag { a: x.tag, b: y.tag }, offsets);
+            } else if !x.tag.is_constructed() {
+                if x.contents.as_slice_less_safe() != y.contents.as_slice_less_safe() {
+                    self.push(DifferenceKind::Contents(x.tag), offsets);
+                }
+            } else if depth == 0 {
+                let element = Input::from(&self.inputs.0[x.offset..]);
+                let err = Error::from(ErrorKind::NestingTooDeep).at(Some(element));
+                return Err(self.locate(err, self.inputs.0)).context("first input");
+            } else {
+                self.path.push(x.tag);
+                self.compare(x.contents, y.contents, depth - 1)?;
+                self.path.pop();
+            }
+        }
+        Ok(())
+    }
+
+    // Read the elements in `contents`, which is part of `input`.
+    fn elements(&self, contents: Input<'x>, input: &[u8]) -> Result<Vec<Element<'x>>> {
+        let (mode, max_len) = (self.config.mode(), self.config.max_len());
+        let read = contents.read_all(Error::from(ErrorKind::TrailingData), |reader| {
+            let mut elements = Vec::new();
+            while !reader.at_end() {
+                let start = der::position(reader);
+                let (tag, contents) = der::read_any_with_limit(reader, mode, max_len)?;
+                let offset = start.map_or(0, |start| self.offset(start, input));
+                elements.push(Element { tag, contents, offset });
+            }
+            Ok(elements)
+        });
+        read.map_err(|err| self.locate(err, input))
+    }
+
+    // Add the path to `err`, and the octets around it if the config asks for them.
+    fn locate(&self, err: Error, input: &[u8]) -> Error {
+        let err = self.path.iter().rev().fold(err, |err, tag| err.within(*tag));
+        match self.config.hex_context() {
+            true => err.with_hex_context(input),
+            false => err,
+        }
+    }
+
+    fn offset(&self, part: Input, input: &[u8]) -> usize {
+        part.as_slice_less_safe().as_ptr() as usize - input.as_ptr() as usize
+    }
+
+    fn push(&mut self, kind: DifferenceKind, offsets: (usize, usize)) {
+        self.differences.push(Difference { kind, path: self.path.clone(), offsets });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use der::Class;
+
+    #[test]
+    fn diff_structures() {
+        // SEQUENCE { [0] { INTEGER 1 }, SET { NULL }, OCTET STRING "a" }
+        let a = &[
+            0x30, 0x0c, 0xa0, 0x03, 0x02, 0x01, 0x01, 0x31, 0x02, 0x05, 0x00, 0x04, 0x01, 0x61,
+        ];
+        // SEQUENCE { [0] { INTEGER 1, INTEGER 2 }, SEQUENCE { NULL }, OCTET STRING "b" }
+        let b = &[
+            0x30, 0x0f, 0xa0, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x30, 0x02, 0x05, 0x00,
+            0x04, 0x01, 0x62,
+        ];
+        let differences = diff(a, b).unwrap();
+        let kinds = differences.iter().map(Difference::kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                DifferenceKind::OnlyInB(Tag::INTEGER),
+                DifferenceKind::Tag { a: Tag::SET, b: Tag::SEQUENCE },
+                DifferenceKind::Contents(Tag::OCTET_STRING),
+            ]
+        );
+        let explicit = Tag::new(Class::ContextSpecific, true, 0);
+        assert_eq!(differences[0].path(), &[Tag::SEQUENCE, explicit]);
+        assert_eq!(differences[0].offsets(), (7, 7));
+        assert_eq!(differences[1].offsets(), (7, 10));
+        assert_eq!(differences[2].offsets(), (11, 14));
+        let message = "OCTET STRING has different contents at offsets 11 and 14 in SEQUENCE";
+        assert_eq!(differences[2].to_string(), message);
+
+        // The top level can have any number of elements.
+        let differences = diff(&[0x05, 0x00, 0x05, 0x00], &[]).unwrap();
+        assert_eq!(differences.len(), 2);
+        assert_eq!(differences[1].kind(), DifferenceKind::OnlyInA(Tag::NULL));
+        assert_eq!(differences[1].offsets(), (2, 0));
+        assert_eq!(diff(&[], &[]), Ok(vec![]));
+    }
+
+    #[test]
+    fn diff_errors() {
+        let err = diff(&[0x30, 0x03, 0x05, 0x00], &[0x30, 0x00]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEnd);
+        assert_eq!(err.context().collect::<Vec<_>>(), vec!["first input"]);
+
+        let a = &[0x30, 0x02, 0x05, 0x00];
+        let b = &[0x30, 0x02, 0x04, 0x81];
+        let err = diff_with_config(a, b, ParserConfig::DER.with_hex_context(true)).unwrap_err();
+        assert_eq!(err.offset(b), Some(2));
+        assert_eq!(err.path(), &[Tag::SEQUENCE]);
+        let message = "second input: unexpected end of input in SEQUENCE \
+                       at offset 2: 30 02 [04] 81";
+        assert_eq!(err.to_string(), message);
+
+        let err = diff_with_config(a, a, ParserConfig::DER.with_max_depth(0)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NestingTooDeep);
+        assert_eq!(diff_with_config(a, b, ParserConfig::DER.with_max_depth(0)), Err(err));
+    }
+}
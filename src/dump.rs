@@ -0,0 +1,211 @@
+//! Rendering DER as annotated text for debugging.
+
+use alloc::borrow::ToOwned;
+use alloc::string::{String, ToString};
+use std::fmt::Write;
+use std::str;
+
+use config::ParserConfig;
+use der::Tag;
+use events::{self, DerVisitor, Element};
+use oid::Oid;
+use oids;
+use Result;
+
+/// Render the single DER element that is all of `input` as an indented text dump, one line per
+/// element, in the style of `openssl asn1parse`.
+///
+/// Each line has the offset of the element, its depth, the length of its identifier and length
+/// octets (`hl`) and of its contents (`l`), whether it is constructed or primitive, and its tag.
+/// Primitive elements are followed by a preview of their contents: `OBJECT IDENTIFIER`s with their
+/// name if it is known, strings and times as text, and anything else as hex, or as text if it is
+/// printable ASCII. Long contents are cut short with `...`.
+///
+/// Input that cannot be read is dumped up to where reading failed, followed by a line describing
+/// the error, so that the dump can be used to find out what is wrong with it.
+///
+/// ```
+/// extern crate derp;
+///
+/// fn main() {
+///     // SEQUENCE { OBJECT IDENTIFIER Ed25519, UTF8String "hi", BOOLEAN }
+///     let encoded = [0x30, 0x0a, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x0c, 0x02, 0x68, 0x69, 0x01];
+///     let dump = "    0:d=0  hl=2 l=  10 cons: SEQUENCE\n\
+///                 \x20   2:d=1  hl=2 l=   3 prim:  OBJECT IDENTIFIER :id-Ed25519 (1.3.101.112)\n\
+///                 \x20   7:d=1  hl=2 l=   2 prim:  UTF8 STRING       :hi\n\
+///                 Error at offset 11: unexpected end of input in SEQUENCE\n";
+///     assert_eq!(derp::dump(&encoded), dump);
+/// }
+/// ```
+pub fn dump(input: &[u8]) -> String {
+    dump_with_config(input, ParserConfig::DER)
+}
+
+/// Render `input` using `config`, as `dump` does.
+pub fn dump_with_config(input: &[u8], config: ParserConfig) -> String {
+    let mut dumper = Dumper { out: String::new() };
+    if let Err(err) = events::walk_with_config(input, config, &mut dumper) {
+        let _ = match err.offset(input) {
+            Some(offset) => writeln!(dumper.out, "Error at offset {}: {}", offset, err),
+            None => writeln!(dumper.out, "Error: {}", err),
+        };
+    }
+    dumper.out
+}
+
+// The most contents shown for a primitive element, in octets.
+const MAX_PREVIEW: usize = 32;
+
+struct Dumper {
+    out: String,
+}
+
+impl Dumper {
+    fn line(&mut self, element: &Element, kind: &str) {
+        let _ = write!(
+            self.out,
+            "{:>5}:d={:<2} hl={} l={:>4} {}: {:indent$}{}",
+            element.offset(),
+            element.depth(),
+            element.header_len(),
+            element.contents().len(),
+            kind,
+            "",
+            element.tag(),
+            indent = element.depth(),
+        );
+    }
+}
+
+impl<'a> DerVisitor<'a> for Dumper {
+    fn enter(&mut self, element: &Element<'a>) -> Result<()> {
+        self.line(element, "cons");
+        self.out.push('\n');
+        Ok(())
+    }
+
+    fn primitive(&mut self, element: &Element<'a>) -> Result<()> {
+        self.line(element, "prim");
+        if let Some(preview) = preview(element.tag(), element.contents()) {
+            // Line up the previews, as far as the tag names allow.
+            let width = 18usize.saturating_sub(element.tag().to_string().len());
+            let _ = write!(self.out, "{:width$}:{}", "", preview, width = width);
+        }
+        self.out.push('\n');
+        Ok(())
+    }
+}
+
+fn preview(tag: Tag, contents: &[u8]) -> Option<String> {
+    match tag {
+        Tag::NULL if contents.is_empty() => None,
+        Tag::BOOLEAN if contents.len() == 1 => {
+            Some(if contents[0] == 0 { "FALSE" } else { "TRUE" }.to_owned())
+        }
+        Tag::OID => Some(match Oid::from_bytes(contents) {
+            Ok(oid) => match oids::name(contents) {
+                Some(name) => format!("{} ({})", name, oid),
+                None => oid.to_string(),
+            },
+            Err(_) => format!("BAD OBJECT IDENTIFIER {}", hex(contents)),
+        }),
+        Tag::UTF8_STRING => match str::from_utf8(contents) {
+            Ok(string) => Some(text(string)),
+            Err(_) => Some(hex(contents)),
+        },
+        Tag::NUMERIC_STRING
+        | Tag::PRINTABLE_STRING
+        | Tag::TELETEX_STRING
+        | Tag::IA5_STRING
+        | Tag::UTC_TIME
+        | Tag::GENERALIZED_TIME
+        | Tag::VISIBLE_STRING => {
+            Some(text(&contents.iter().map(|&b| b as char).collect::<String>()))
+        }
+        Tag::INTEGER | Tag::ENUMERATED | Tag::BIT_STRING => Some(hex(contents)),
+        _ if printable(contents) => Some(text(&String::from_utf8_lossy(contents))),
+        _ => Some(hex(contents)),
+    }
+}
+
+// Whether `contents` is text that can be shown as it is.
+fn printable(contents: &[u8]) -> bool {
+    !contents.is_empty() && contents.iter().all(|&b| b == b' ' || b.is_ascii_graphic())
+}
+
+// At most `MAX_PREVIEW * 2` characters of `string`, with control characters escaped.
+fn text(string: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in string.chars().enumerate() {
+        if i == MAX_PREVIEW * 2 {
+            out.push_str("...");
+            break;
+        }
+        match c.is_control() {
+            true => out.extend(c.escape_default()),
+            false => out.push(c),
+        }
+    }
+    out
+}
+
+// The first `MAX_PREVIEW` octets of `bytes`, in upper case hex as `openssl` shows them.
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for b in bytes.iter().take(MAX_PREVIEW) {
+        let _ = write!(out, "{:02X}", b);
+    }
+    if bytes.len() > MAX_PREVIEW {
+        out.push_str("...");
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dump_elements() {
+        // SEQUENCE { INTEGER 0x0102, [0] { NULL, BOOLEAN TRUE }, OCTET STRING "ab",
+        // [1] 0x00ff, PrintableString "a\n" }
+        let encoded = &[
+            0x30, 0x17, 0x02, 0x02, 0x01, 0x02, 0xa0, 0x05, 0x05, 0x00, 0x01, 0x01, 0xff, 0x04,
+            0x02, 0x61, 0x62, 0x81, 0x02, 0x00, 0xff, 0x13, 0x02, 0x61, 0x0a,
+        ];
+        let lines = [
+            "    0:d=0  hl=2 l=  23 cons: SEQUENCE",
+            "    2:d=1  hl=2 l=   2 prim:  INTEGER           :0102",
+            "    6:d=1  hl=2 l=   5 cons:  [0]",
+            "    8:d=2  hl=2 l=   0 prim:   NULL",
+            "   10:d=2  hl=2 l=   1 prim:   BOOLEAN           :TRUE",
+            "   13:d=1  hl=2 l=   2 prim:  OCTET STRING      :ab",
+            "   17:d=1  hl=2 l=   2 prim:  [1]               :00FF",
+            "   21:d=1  hl=2 l=   2 prim:  PRINTABLE STRING  :a\\n",
+        ];
+        assert_eq!(dump(encoded), lines.join("\n") + "\n");
+
+        // Long contents are cut short.
+        let mut encoded = vec![0x04, 0x21];
+        encoded.extend(vec![0xab; 0x21]);
+        let line = "    0:d=0  hl=2 l=  33 prim: OCTET STRING      :";
+        assert_eq!(dump(&encoded), format!("{}{}...\n", line, "AB".repeat(32)));
+    }
+
+    #[test]
+    fn dump_errors() {
+        assert_eq!(dump(&[]), "Error at offset 0: input is empty where an element was expected\n");
+        let encoded = &[0x30, 0x03, 0x01, 0x01, 0x01];
+        let expected = "    0:d=0  hl=2 l=   3 cons: SEQUENCE\n\
+                        \x20   2:d=1  hl=2 l=   1 prim:  BOOLEAN           :TRUE\n";
+        assert_eq!(dump(encoded), expected);
+        assert_eq!(
+            dump_with_config(&[0x30, 0x81, 0x00], ParserConfig::BER),
+            "    0:d=0  hl=3 l=   0 cons: SEQUENCE\n"
+        );
+        assert_eq!(
+            dump(&[0x30, 0x81, 0x00]),
+            "Error at offset 0: encoding is valid BER but not canonical DER\n"
+        );
+    }
+}
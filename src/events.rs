@@ -0,0 +1,421 @@
+//! Walking the elements of DER without knowing its schema, as a flat stream of events or with a
+//! visitor.
+
+use alloc::vec::Vec;
+use untrusted::{Input, Reader};
+
+use config::{self, ParserConfig};
+use der::{self, Tag};
+use {Error, ErrorKind, Result};
+
+/// Something found by `Events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// The start of a constructed element, whose contents follow as further events.
+    StartConstructed(Tag),
+    /// A primitive element and its contents.
+    Primitive(Tag, &'a [u8]),
+    /// The end of the contents of the innermost constructed element that has been started.
+    EndConstructed,
+}
+
+/// An element found by `Events` or `walk`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Element<'a> {
+    tag: Tag,
+    depth: usize,
+    offset: usize,
+    encoding: &'a [u8],
+    contents: &'a [u8],
+}
+
+impl<'a> Element<'a> {
+    /// The tag of the element.
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    /// How many constructed elements enclose this one.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// How far into the input the element starts, in octets.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The identifier, length and contents octets of the element.
+    pub fn encoding(&self) -> &'a [u8] {
+        self.encoding
+    }
+
+    /// The contents octets of the element.
+    pub fn contents(&self) -> &'a [u8] {
+        self.contents
+    }
+
+    /// The length of the identifier and length octets of the element.
+    pub fn header_len(&self) -> usize {
+        self.encoding.len() - self.contents.len()
+    }
+}
+
+/// Return the events of the single DER element that is all of `input`, as an iterator.
+///
+/// Each constructed element gives a `StartConstructed` event, then the events of its contents,
+/// then an `EndConstructed` event, and each primitive element gives a `Primitive` event. The
+/// contents of primitive elements are not checked. Iteration stops after the first error, which
+/// is located in `input` and has the tags of the enclosing elements as its path.
+///
+/// ```
+/// extern crate derp;
+///
+/// use derp::{Event, Tag};
+///
+/// fn main() {
+///     // SEQUENCE { INTEGER 1, SET { } }
+///     let encoded = [0x30, 0x05, 0x02, 0x01, 0x01, 0x31, 0x00];
+///     let events = derp::events(&encoded).collect::<derp::Result<Vec<_>>>().unwrap();
+///     assert_eq!(events, vec![
+///         Event::StartConstructed(Tag::SEQUENCE),
+///         Event::Primitive(Tag::INTEGER, &[0x01]),
+///         Event::StartConstructed(Tag::SET),
+///         Event::EndConstructed,
+///         Event::EndConstructed,
+///     ]);
+/// }
+/// ```
+pub fn events<'a>(input: &'a [u8]) -> Events<'a> {
+    events_with_config(input, ParserConfig::DER)
+}
+
+/// Return the events of `input` using `config`, as `events` does. Constructed elements can be
+/// nested at most `config.max_depth()` deep.
+pub fn events_with_config<'a>(input: &'a [u8], config: ParserConfig) -> Events<'a> {
+    Events {
+        input,
+        config,
+        contents: vec![Reader::new(Input::from(input))],
+        open: Vec::new(),
+        started: false,
+    }
+}
+
+/// An iterator over the events of DER input, from `events`.
+#[derive(Debug)]
+pub struct Events<'a> {
+    input: &'a [u8],
+    config: ParserConfig,
+    // The input, then the contents of each open element, innermost last. Empty once finished.
+    contents: Vec<Reader<'a>>,
+    // The constructed elements that have been started and not ended.
+    open: Vec<Element<'a>>,
+    // Whether the outermost element has been read.
+    started: bool,
+}
+
+impl<'a> Events<'a> {
+    /// How many constructed elements have been started and not yet ended.
+    pub fn depth(&self) -> usize {
+        self.open.len()
+    }
+
+    /// How far into the input the next event is, in octets.
+    pub fn offset(&self) -> usize {
+        let position = self.contents.last().and_then(der::position);
+        match position {
+            Some(position) => {
+                position.as_slice_less_safe().as_ptr() as usize - self.input.as_ptr() as usize
+            }
+            None => self.input.len(),
+        }
+    }
+
+    // Return the next event along with the element it is for, which for `EndConstructed` is the
+    // element that ended.
+    fn advance(&mut self) -> Result<Option<(Event<'a>, Element<'a>)>> {
+        let contents = match self.contents.last_mut() {
+            Some(contents) => contents,
+            None => return Ok(None),
+        };
+        if self.open.is_empty() && self.started {
+            if !contents.at_end() {
+                return Err(Error::from(ErrorKind::TrailingData).at(der::position(contents)));
+            }
+            self.contents.clear();
+            return Ok(None);
+        }
+        if contents.at_end() {
+            if let Some(element) = self.open.pop() {
+                self.contents.pop();
+                return Ok(Some((Event::EndConstructed, element)));
+            }
+        }
+
+        self.started = true;
+        let start = contents.mark();
+        let (tag, value) = config::read_any_with_config(contents, self.config)?;
+        let encoding = contents.get_input_between_marks(start, contents.mark())?;
+        let element = Element {
+            tag,
+            depth: self.open.len(),
+            offset: encoding.as_slice_less_safe().as_ptr() as usize - self.input.as_ptr() as usize,
+            encoding: encoding.as_slice_less_safe(),
+            contents: value.as_slice_less_safe(),
+        };
+        if !tag.is_constructed() {
+            return Ok(Some((Event::Primitive(tag, element.contents), element)));
+        }
+        if self.open.len() >= self.config.max_depth() {
+            return Err(Error::from(ErrorKind::NestingTooDeep).at(Some(value)));
+        }
+        self.contents.push(Reader::new(value));
+        self.open.push(element);
+        Ok(Some((Event::StartConstructed(tag), element)))
+    }
+
+    // Add the tags of the `depth` outermost open elements to `err`, and end the iteration.
+    fn fail(&mut self, err: Error, depth: usize) -> Error {
+        self.contents.clear();
+        let err = self.open[..depth].iter().rev().fold(err, |err, element| err.within(element.tag));
+        match self.config.hex_context() {
+            true => err.with_hex_context(self.input),
+            false => err,
+        }
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = Result<Event<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.advance() {
+            Ok(event) => event.map(|(event, _)| Ok(event)),
+            Err(err) => {
+                let depth = self.open.len();
+                Some(Err(self.fail(err, depth)))
+            }
+        }
+    }
+}
+
+/// Callbacks for the elements found by `walk`. Each does nothing by default.
+///
+/// An error returned by a callback stops the walk and is returned from it, located at the start of
+/// the element and with the tags of the elements enclosing it as its path.
+pub trait DerVisitor<'a> {
+    /// Called at the start of a constructed element, before its contents are visited.
+    fn enter(&mut self, element: &Element<'a>) -> Result<()> {
+        let _ = element;
+        Ok(())
+    }
+
+    /// Called at the end of a constructed element, after its contents have been visited.
+    fn leave(&mut self, element: &Element<'a>) -> Result<()> {
+        let _ = element;
+        Ok(())
+    }
+
+    /// Called for each primitive element.
+    fn primitive(&mut self, element: &Element<'a>) -> Result<()> {
+        let _ = element;
+        Ok(())
+    }
+}
+
+/// Visit every element of the single DER element that is all of `input`, in the order of their
+/// encoding, as `events` does.
+///
+/// The walk does not recurse, so deeply nested input cannot overflow the stack.
+///
+/// ```
+/// extern crate derp;
+///
+/// use derp::{DerVisitor, Element};
+///
+/// // Find how deep the elements go and the offsets of those that are empty.
+/// #[derive(Default)]
+/// struct Stats {
+///     max_depth: usize,
+///     empty: Vec<usize>,
+/// }
+///
+/// impl<'a> DerVisitor<'a> for Stats {
+///     fn enter(&mut self, element: &Element<'a>) -> derp::Result<()> {
+///         self.max_depth = self.max_depth.max(element.depth() + 1);
+///         self.primitive(element)
+///     }
+///
+///     fn primitive(&mut self, element: &Element<'a>) -> derp::Result<()> {
+///         if element.contents().is_empty() {
+///             self.empty.push(element.offset());
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// fn main() {
+///     // SEQUENCE { NULL, SEQUENCE { INTEGER 1, SET { } } }
+///     let encoded = [0x30, 0x09, 0x05, 0x00, 0x30, 0x05, 0x02, 0x01, 0x01, 0x31, 0x00];
+///     let mut stats = Stats::default();
+///     derp::walk(&encoded, &mut stats).unwrap();
+///     assert_eq!(stats.max_depth, 3);
+///     assert_eq!(stats.empty, vec![2, 9]);
+/// }
+/// ```
+pub fn walk<'a, V>(input: &'a [u8], visitor: &mut V) -> Result<()>
+where
+    V: DerVisitor<'a> + ?Sized,
+{
+    walk_with_config(input, ParserConfig::DER, visitor)
+}
+
+/// Visit every element of `input` using `config`, as `walk` does.
+pub fn walk_with_config<'a, V>(input: &'a [u8], config: ParserConfig, visitor: &mut V) -> Result<()>
+where
+    V: DerVisitor<'a> + ?Sized,
+{
+    let mut events = events_with_config(input, config);
+    loop {
+        let (event, element) = match events.advance() {
+            Ok(Some(next)) => next,
+            Ok(None) => return Ok(()),
+            Err(err) => {
+                let depth = events.open.len();
+                return Err(events.fail(err, depth));
+            }
+        };
+        let visited = match event {
+            Event::StartConstructed(_) => visitor.enter(&element),
+            Event::Primitive(..) => visitor.primitive(&element),
+            Event::EndConstructed => visitor.leave(&element),
+        };
+        if let Err(err) = visited {
+            let err = err.at(Some(Input::from(element.encoding)));
+            return Err(events.fail(err, element.depth));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn collect<'a>(encoded: &'a [u8]) -> Result<Vec<Event<'a>>> {
+        events(encoded).collect()
+    }
+
+    #[test]
+    fn events_good() {
+        assert_eq!(collect(&[0x05, 0x00]), Ok(vec![Event::Primitive(Tag::NULL, &[])]));
+
+        // [0] { SEQUENCE { BOOLEAN TRUE }, [1] 0x61 }
+        let encoded = &[0xa0, 0x08, 0x30, 0x03, 0x01, 0x01, 0xff, 0x81, 0x01, 0x61];
+        let mut events = events(encoded);
+        let mut found = Vec::new();
+        while let Some(event) = events.next() {
+            found.push((events.depth(), event.unwrap()));
+        }
+        assert_eq!(events.offset(), encoded.len());
+        assert_eq!(found, vec![
+            (1, Event::StartConstructed(Tag::CONTEXT_SPECIFIC_CONSTRUCTED_0)),
+            (2, Event::StartConstructed(Tag::SEQUENCE)),
+            (2, Event::Primitive(Tag::BOOLEAN, &[0xff])),
+            (1, Event::EndConstructed),
+            (1, Event::Primitive(Tag::new(::Class::ContextSpecific, false, 1), b"a")),
+            (0, Event::EndConstructed),
+        ]);
+
+        // Non-minimal lengths are only accepted in BER.
+        let encoded = &[0x30, 0x81, 0x00];
+        let config = ParserConfig::BER;
+        let events = events_with_config(encoded, config).collect::<Result<Vec<_>>>();
+        assert_eq!(events, Ok(vec![Event::StartConstructed(Tag::SEQUENCE), Event::EndConstructed]));
+        assert_eq!(collect(encoded), Err(ErrorKind::NonCanonical.into()));
+    }
+
+    #[test]
+    fn events_bad() {
+        for &(encoded, err) in &[
+            (&[][..], ErrorKind::EmptyInput),
+            (&[0x05, 0x00, 0x05, 0x00], ErrorKind::TrailingData),
+            (&[0x30, 0x00, 0x05], ErrorKind::TrailingData),
+            (&[0x30, 0x03, 0x05, 0x00], ErrorKind::UnexpectedEnd),
+            (&[0x30, 0x03, 0x05, 0x02, 0x00], ErrorKind::UnexpectedEnd),
+            (&[0x30, 0x80, 0x00, 0x00], ErrorKind::LongLengthNotSupported),
+        ] {
+            assert_eq!(collect(encoded), Err(err.into()), "{:02x?}", encoded);
+        }
+
+        let encoded = &[0x30, 0x04, 0x31, 0x02, 0x30, 0x00];
+        let mut events = events_with_config(encoded, ParserConfig::DER.with_max_depth(2));
+        assert_eq!(events.next(), Some(Ok(Event::StartConstructed(Tag::SEQUENCE))));
+        assert_eq!(events.next(), Some(Ok(Event::StartConstructed(Tag::SET))));
+        let err = events.next().unwrap().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NestingTooDeep);
+        assert_eq!(err.path(), &[Tag::SEQUENCE, Tag::SET]);
+        assert_eq!(err.offset(encoded), Some(6));
+        assert_eq!(events.next(), None);
+
+        let config = ParserConfig::DER.with_hex_context(true);
+        let mut events = events_with_config(&[0x30, 0x03, 0x05, 0x02, 0x00], config);
+        let err = events.nth(1).unwrap().unwrap_err();
+        let message = "unexpected end of input in SEQUENCE at offset 2: 30 03 [05] 02 00";
+        assert_eq!(err.to_string(), message);
+    }
+
+    #[test]
+    fn walk_visitor() {
+        // Records each call with the depth, offset and length of the element.
+        struct Record(Vec<(&'static str, usize, usize, usize)>);
+
+        impl<'a> DerVisitor<'a> for Record {
+            fn enter(&mut self, element: &Element<'a>) -> Result<()> {
+                let len = element.header_len() + element.contents().len();
+                self.0.push(("enter", element.depth(), element.offset(), len));
+                Ok(())
+            }
+
+            fn leave(&mut self, element: &Element<'a>) -> Result<()> {
+                self.0.push(("leave", element.depth(), element.offset(), element.encoding().len()));
+                Ok(())
+            }
+
+            fn primitive(&mut self, element: &Element<'a>) -> Result<()> {
+                if element.tag() == Tag::BOOLEAN {
+                    return Err(ErrorKind::WrongValue.into());
+                }
+                let len = element.encoding().len();
+                self.0.push(("primitive", element.depth(), element.offset(), len));
+                Ok(())
+            }
+        }
+
+        // SEQUENCE { NULL, [0] { INTEGER 1 } }
+        let encoded = &[0x30, 0x07, 0x05, 0x00, 0xa0, 0x03, 0x02, 0x01, 0x01];
+        let mut record = Record(Vec::new());
+        assert_eq!(walk(encoded, &mut record), Ok(()));
+        assert_eq!(record.0, vec![
+            ("enter", 0, 0, 9),
+            ("primitive", 1, 2, 2),
+            ("enter", 1, 4, 5),
+            ("primitive", 2, 6, 3),
+            ("leave", 1, 4, 5),
+            ("leave", 0, 0, 9),
+        ]);
+
+        // Errors from the visitor are located at the element.
+        let encoded = &[0x30, 0x05, 0xa0, 0x03, 0x01, 0x01, 0xff];
+        let err = walk(encoded, &mut Record(Vec::new())).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WrongValue);
+        assert_eq!(err.offset(encoded), Some(4));
+        assert_eq!(err.path(), &[Tag::SEQUENCE, Tag::CONTEXT_SPECIFIC_CONSTRUCTED_0]);
+
+        // As are errors in the input.
+        let encoded = &[0x30, 0x04, 0x30, 0x02, 0x05, 0x01];
+        let err = walk(encoded, &mut Record(Vec::new())).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEnd);
+        assert_eq!(err.path(), &[Tag::SEQUENCE, Tag::SEQUENCE]);
+    }
+}
@@ -1,6 +1,7 @@
 //! DER Parser (and Writer)
 //!
-//! ```
+#![cfg_attr(feature = "alloc", doc = "```")]
+#![cfg_attr(not(feature = "alloc"), doc = "```ignore")]
 //! extern crate derp;
 //! extern crate untrusted;
 //!
@@ -18,10 +19,10 @@
 //!
 //! fn main() {
 //!     let input = Input::from(MY_DATA);
-//!     let (x, y, bits) = input.read_all(derp::Error::Read, |input| {
-//!         derp::nested(input, Tag::Sequence, |input| {
+//!     let (x, y, bits) = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+//!         derp::nested(input, Tag::SEQUENCE, |input| {
 //!             derp::read_null(input)?;
-//!             let (x, y) = derp::nested(input, Tag::Sequence, |input| {
+//!             let (x, y) = derp::nested(input, Tag::SEQUENCE, |input| {
 //!                 let x = derp::positive_integer(input)?;
 //!                 let y = derp::positive_integer(input)?;
 //!                 Ok((x.as_slice_less_safe(), y.as_slice_less_safe()))
@@ -51,85 +52,578 @@
 //!     assert_eq!(buf.as_slice(), MY_DATA);
 //! }
 //! ```
+//!
+//! The crate is `no_std` when its default `std` feature is disabled. DER is then written through
+//! the crate's own `Write` trait, and reading from a `std::io::Read` with `StreamReader` is not
+//! available.
+//!
+//! Without the `alloc` feature as well the crate never allocates, so it can be used where there is
+//! no heap at all. What remains is reading with the functions that borrow from their input, such
+//! as `nested` and `Reader::sequence`, and writing into a `SliceWriter` with `Der::slice`, which
+//! writes nested elements in place. `ToDer::encoded_len` gives the size to reserve beforehand,
+//! and `oid!` gives the contents of an `OBJECT IDENTIFIER` to write or compare with. Errors then
+//! only record their kind and offset, not their path or context.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+#[cfg(feature = "alloc")]
+#[macro_use]
+extern crate alloc;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(feature = "chrono")]
+extern crate chrono;
+#[cfg(not(any(feature = "std", test)))]
+extern crate core as std;
+#[cfg(feature = "derive")]
+extern crate derp_derive;
+#[cfg(feature = "bigint")]
+extern crate num_bigint;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(all(test, feature = "serde"))]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "time")]
+extern crate time;
 extern crate untrusted;
 
+#[cfg(feature = "alloc")]
+use alloc::borrow::Cow;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::sync::Arc;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "alloc")]
+use std::cmp;
+use std::error;
 use std::fmt::{self, Display};
+#[cfg(feature = "alloc")]
+use std::fmt::Write as FmtWrite;
+#[cfg(feature = "std")]
+use std::io;
 
+#[cfg(feature = "alloc")]
+mod ber;
+#[cfg(feature = "bigint")]
+mod bigint;
+mod codec;
+mod config;
+mod datetime;
+#[cfg(feature = "serde")]
+mod de;
 mod der;
+#[cfg(feature = "alloc")]
+mod diff;
+#[cfg(feature = "alloc")]
+mod dump;
+#[cfg(feature = "alloc")]
+mod events;
+#[macro_use]
+mod macros;
+#[cfg(feature = "alloc")]
+mod oid;
+#[cfg(feature = "alloc")]
+pub mod oids;
+#[cfg(feature = "alloc")]
+mod push;
+mod reader;
+#[cfg(feature = "alloc")]
+mod real;
+#[macro_use]
+mod schema;
+#[cfg(feature = "serde")]
+mod ser;
+#[cfg(feature = "std")]
+mod stream;
+#[cfg(feature = "alloc")]
+mod validate;
+#[cfg(feature = "alloc")]
+mod value;
 mod writer;
 
+#[cfg(feature = "alloc")]
+pub use ber::*;
+#[cfg(feature = "bigint")]
+pub use bigint::*;
+pub use codec::*;
+pub use config::*;
+pub use datetime::*;
+#[cfg(feature = "serde")]
+pub use de::*;
 pub use der::*;
+#[cfg(feature = "derive")]
+pub use derp_derive::{DerChoice, DerSequence};
+#[cfg(feature = "alloc")]
+pub use diff::*;
+#[cfg(feature = "alloc")]
+pub use dump::*;
+#[cfg(feature = "alloc")]
+pub use events::*;
+pub use macros::*;
+#[cfg(feature = "alloc")]
+pub use oid::*;
+#[cfg(feature = "alloc")]
+pub use push::*;
+pub use reader::*;
+#[cfg(feature = "alloc")]
+pub use real::*;
+#[cfg(feature = "serde")]
+pub use ser::*;
+#[cfg(feature = "std")]
+pub use stream::*;
+#[cfg(feature = "alloc")]
+pub use validate::*;
+#[cfg(feature = "alloc")]
+pub use value::*;
 pub use writer::*;
 
-#[derive(Debug, PartialEq, Clone, Copy)]
-pub enum Error {
+/// The kind of an `Error`.
+///
+/// `NonCanonical` means the input is valid BER but not DER, while most other kinds mean the input
+/// is not valid at all. More kinds may be added in the future.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[non_exhaustive]
+pub enum ErrorKind {
     BadBooleanValue,
+    BadStringValue,
+    BadTimeValue,
+    /// The buffer of a `SliceWriter` was too small for what was written to it, or without the
+    /// `alloc` feature a nested element was written with `Der::new`, which has no buffer for it.
+    BufferTooSmall,
+    /// An error with a message, such as from a `serde` implementation. The message is the last of
+    /// `Error::context`.
+    Custom,
+    /// An element was expected but the input was empty.
+    EmptyInput,
     LeadingZero,
+    /// A length was larger than the `max_len` of a `ParserConfig`.
+    LengthExceedsLimit,
+    /// A length was encoded with more octets than fit in a `usize`.
+    LengthOverflow,
     LessThanMinimum,
+    /// The indefinite length form, which DER does not allow.
     LongLengthNotSupported,
     HighTagNumberForm,
+    IntegerOverflow,
     Io,
     NegativeValue,
+    /// Elements were nested deeper than the `max_depth` of a `ParserConfig`.
+    NestingTooDeep,
     NonCanonical,
     NonZeroUnusedBits,
-    Read,
+    /// There was data left over after the end of the input that was read, for use with
+    /// `Input::read_all`.
+    TrailingData,
+    /// A decoder did not read all of the contents of an element.
+    UnconsumedContents,
     UnexpectedEnd,
     UnknownTag,
-    WrongTag,
+    /// An element had a different tag than the one expected. `found` is the first identifier octet
+    /// of the element.
+    WrongTag { expected: Tag, found: u8 },
     WrongValue,
 }
 
-impl Display for Error {
+impl ErrorKind {
+    fn message(&self) -> &'static str {
+        match *self {
+            ErrorKind::BadBooleanValue => "BOOLEAN value is neither 0x00 nor 0xff",
+            ErrorKind::BadStringValue => "string contains characters not allowed by its type",
+            ErrorKind::BadTimeValue => "time value is malformed or out of range",
+            ErrorKind::BufferTooSmall => "output buffer is too small",
+            ErrorKind::Custom => "custom error",
+            ErrorKind::EmptyInput => "input is empty where an element was expected",
+            ErrorKind::LeadingZero => "INTEGER has an unnecessary leading zero",
+            ErrorKind::LengthExceedsLimit => "length is larger than the configured limit",
+            ErrorKind::LengthOverflow => "length is too long to be supported",
+            ErrorKind::LessThanMinimum => "value is less than the allowed minimum",
+            ErrorKind::LongLengthNotSupported => "indefinite length is not allowed in DER",
+            ErrorKind::HighTagNumberForm => "tag number is too large to be supported",
+            ErrorKind::IntegerOverflow => "integer does not fit in the requested type",
+            ErrorKind::Io => "I/O error while reading or writing",
+            ErrorKind::NegativeValue => "INTEGER is negative where a non-negative value is required",
+            ErrorKind::NestingTooDeep => "elements are nested deeper than the configured limit",
+            ErrorKind::NonCanonical => "encoding is valid BER but not canonical DER",
+            ErrorKind::NonZeroUnusedBits => "BIT STRING has non-zero unused bits",
+            ErrorKind::TrailingData => "input has data after the end of the value",
+            ErrorKind::UnconsumedContents => "contents of an element were not fully read",
+            ErrorKind::UnexpectedEnd => "unexpected end of input",
+            ErrorKind::UnknownTag => "unknown tag",
+            ErrorKind::WrongTag { .. } => "element has an unexpected tag",
+            ErrorKind::WrongValue => "element has an invalid value",
+        }
+    }
+}
+
+impl Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let s = match *self {
-            Error::BadBooleanValue => "bad boolean value",
-            Error::LeadingZero => "leading zero",
-            Error::LessThanMinimum => "less than minimum",
-            Error::LongLengthNotSupported => "long length not supported",
-            Error::HighTagNumberForm => "high tag number form",
-            Error::Io => "I/O",
-            Error::NegativeValue => "negative value",
-            Error::NonCanonical => "non-canonical",
-            Error::NonZeroUnusedBits => "non-zero unused bits",
-            Error::Read => "read",
-            Error::UnexpectedEnd => "unexpected end",
-            Error::UnknownTag => "unknown tag",
-            Error::WrongTag => "wrong tag",
-            Error::WrongValue => "wrong value",
+        match *self {
+            ErrorKind::WrongTag { expected, found } => {
+                write!(f, "expected {} but found identifier octet {:#04x}", expected, found)
+            }
+            _ => self.message().fmt(f),
+        }
+    }
+}
+
+/// An error from reading or writing DER.
+///
+/// Errors from reading record where in the input they happened, see `Error::offset`, and the
+/// elements they happened in, see `Error::path`. Two errors are equal when they have the same kind.
+#[derive(Clone)]
+pub struct Error {
+    kind: ErrorKind,
+    // The address of the octet at which reading failed.
+    position: Option<usize>,
+    #[cfg(feature = "alloc")]
+    path: Vec<Tag>,
+    // The error from the reader or writer for `ErrorKind::Io`, shared so that `Error` can stay `Clone`.
+    #[cfg(feature = "std")]
+    io: Option<Arc<io::Error>>,
+    // Descriptions added with `ResultExt::context`, outermost first.
+    #[cfg(feature = "alloc")]
+    context: Vec<Cow<'static, str>>,
+    // The offset and the octets around it, as captured by `with_hex_context`.
+    #[cfg(feature = "alloc")]
+    hex_context: Option<String>,
+}
+
+// How many octets on each side of the offset `Error::with_hex_context` captures.
+#[cfg(feature = "alloc")]
+const HEX_CONTEXT: usize = 8;
+
+impl Error {
+    /// The kind of this error.
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The offset into `input` at which reading failed, if it is known.
+    ///
+    /// `input` must be the buffer that was read, not a copy of it. The offset is that of the
+    /// element for tag and length errors, and of the position reading had reached for errors in
+    /// an element's contents, which for primitive elements is their end.
+    ///
+    /// ```
+    /// extern crate derp;
+    /// extern crate untrusted;
+    ///
+    /// use derp::{ErrorKind, Tag};
+    /// use untrusted::Input;
+    ///
+    /// // SEQUENCE { NULL, BOOLEAN 7 }
+    /// const DATA: &'static [u8] = &[0x30, 0x05, 0x05, 0x00, 0x01, 0x01, 0x07];
+    ///
+    /// fn main() {
+    ///     let err = Input::from(DATA).read_all(ErrorKind::TrailingData.into(), |input| {
+    ///         derp::nested(input, Tag::SEQUENCE, |input| {
+    ///             derp::read_null(input)?;
+    ///             derp::nested(input, Tag::INTEGER, |_| Ok(()))
+    ///         })
+    ///     }).unwrap_err();
+    ///
+    ///     assert_eq!(err.kind(), ErrorKind::WrongTag { expected: Tag::INTEGER, found: 0x01 });
+    ///     assert_eq!(err.offset(DATA), Some(4));
+    /// }
+    /// ```
+    pub fn offset(&self, input: &[u8]) -> Option<usize> {
+        self.position
+            .and_then(|position| position.checked_sub(input.as_ptr() as usize))
+            .filter(|offset| *offset <= input.len())
+    }
+
+    /// Capture the octets of `input` around where reading failed, so that they are shown when the
+    /// error is displayed. This does nothing if the offset into `input` is not known.
+    ///
+    /// Up to eight octets are captured on each side, and the one at the offset is shown in
+    /// brackets. Errors from the functions that parse all of a buffer, such as `parse_with_config`,
+    /// capture it themselves when `ParserConfig::with_hex_context` is set. Without the `alloc`
+    /// feature nothing is captured.
+    ///
+    /// ```
+    /// extern crate derp;
+    ///
+    /// use derp::Tag;
+    ///
+    /// fn main() {
+    ///     // SEQUENCE { INTEGER 1, UTF8String "hi" }
+    ///     let encoded = [0x30, 0x07, 0x02, 0x01, 0x01, 0x0c, 0x02, 0x68, 0x69];
+    ///     let err = derp::parse(&encoded, |r| {
+    ///         r.sequence(|r| {
+    ///             r.u8()?;
+    ///             r.expect(Tag::OCTET_STRING)
+    ///         })
+    ///     }).unwrap_err();
+    ///
+    ///     let message = "expected OCTET STRING but found identifier octet 0x0c in SEQUENCE \
+    ///                    at offset 5: 30 07 02 01 01 [0c] 02 68 69";
+    ///     assert_eq!(err.with_hex_context(&encoded).to_string(), message);
+    /// }
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn with_hex_context(self, input: &[u8]) -> Error {
+        let offset = match self.offset(input) {
+            Some(offset) => offset,
+            None => return self,
         };
-        s.fmt(f)
+        let start = offset.saturating_sub(HEX_CONTEXT);
+        let end = cmp::min(offset + 1 + HEX_CONTEXT, input.len());
+        let mut hex = format!("offset {}:", offset);
+        if start > 0 {
+            hex.push_str(" ...");
+        }
+        for (i, octet) in input[start..end].iter().enumerate() {
+            let _ = match start + i == offset {
+                true => write!(hex, " [{:02x}]", octet),
+                false => write!(hex, " {:02x}", octet),
+            };
+        }
+        match offset == input.len() {
+            true => hex.push_str(" [end]"),
+            false if end < input.len() => hex.push_str(" ..."),
+            false => (),
+        }
+        Error { hex_context: Some(hex), ..self }
+    }
+
+    /// Capture the octets of `input` around where reading failed, which does nothing without the
+    /// `alloc` feature.
+    #[cfg(not(feature = "alloc"))]
+    pub fn with_hex_context(self, _: &[u8]) -> Error {
+        self
+    }
+
+    /// The tags of the elements that enclose where reading failed, outermost first.
+    ///
+    /// An element is part of the path when its contents were read by one of the functions that
+    /// take a decoder, such as `nested` and `read_explicit`. Without the `alloc` feature the path
+    /// is not recorded and is always empty.
+    ///
+    /// ```
+    /// extern crate derp;
+    /// extern crate untrusted;
+    ///
+    /// use derp::{ErrorKind, Tag};
+    /// use untrusted::Input;
+    ///
+    /// // SEQUENCE { [1] { NULL } }
+    /// const DATA: &'static [u8] = &[0x30, 0x04, 0xa1, 0x02, 0x05, 0x00];
+    ///
+    /// fn main() {
+    ///     let err = Input::from(DATA).read_all(ErrorKind::TrailingData.into(), |input| {
+    ///         derp::nested(input, Tag::SEQUENCE, |input| {
+    ///             derp::read_explicit(input, 1, derp::boolean)
+    ///         })
+    ///     }).unwrap_err();
+    ///
+    ///     assert_eq!(err.path(), &[Tag::SEQUENCE, Tag::CONTEXT_SPECIFIC_CONSTRUCTED_1]);
+    ///     let message = "expected BOOLEAN but found identifier octet 0x05 in SEQUENCE > [1]";
+    ///     assert_eq!(err.to_string(), message);
+    /// }
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn path(&self) -> &[Tag] {
+        &self.path
+    }
+
+    /// The tags of the elements that enclose where reading failed, which are not recorded without
+    /// the `alloc` feature.
+    #[cfg(not(feature = "alloc"))]
+    pub fn path(&self) -> &[Tag] {
+        &[]
+    }
+
+    /// The descriptions added with `ResultExt::context`, outermost first.
+    #[cfg(feature = "alloc")]
+    pub fn context(&self) -> impl Iterator<Item = &str> {
+        self.context.iter().map(|context| &**context)
+    }
+
+    // An `ErrorKind::Custom` error with `message`.
+    #[cfg(feature = "serde")]
+    pub(crate) fn custom<T: Display>(message: T) -> Error {
+        let mut err = Error::from(ErrorKind::Custom);
+        err.context.push(format!("{}", message).into());
+        err
+    }
+
+    // Record that this error happened within an element tagged `tag`.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn within(mut self, tag: Tag) -> Error {
+        self.path.insert(0, tag);
+        self
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    pub(crate) fn within(self, _: Tag) -> Error {
+        self
+    }
+
+    // Record that this error happened at the start of `position`, unless it already has a position.
+    pub(crate) fn at(self, position: Option<untrusted::Input>) -> Error {
+        Error {
+            position: self.position.or_else(|| {
+                position.map(|position| position.as_slice_less_safe().as_ptr() as usize)
+            }),
+            ..self
+        }
     }
 }
-impl ::std::error::Error for Error {
-    fn description(&self) -> &str {
-        match *self {
-            Error::BadBooleanValue => "bad boolean value",
-            Error::LeadingZero => "leading zero",
-            Error::LessThanMinimum => "less than minimum",
-            Error::LongLengthNotSupported => "long length not supported",
-            Error::HighTagNumberForm => "high tag number form",
-            Error::Io => "I/O",
-            Error::NegativeValue => "negative value",
-            Error::NonCanonical => "non-canonical",
-            Error::NonZeroUnusedBits => "non-zero unused bits",
-            Error::Read => "read",
-            Error::UnexpectedEnd => "unexpected end",
-            Error::UnknownTag => "unknown tag",
-            Error::WrongTag => "wrong tag",
-            Error::WrongValue => "wrong value",
+
+// The position is an address, which is meaningless to print.
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut debug = f.debug_struct("Error");
+        debug.field("kind", &self.kind);
+        #[cfg(feature = "alloc")]
+        debug.field("path", &self.path);
+        #[cfg(feature = "std")]
+        debug.field("io", &self.io);
+        #[cfg(feature = "alloc")]
+        debug.field("context", &self.context).field("hex_context", &self.hex_context);
+        debug.finish()
+    }
+}
+
+impl PartialEq for Error {
+    fn eq(&self, other: &Error) -> bool {
+        self.kind == other.kind
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Error {
+        Error {
+            kind,
+            position: None,
+            #[cfg(feature = "alloc")]
+            path: Vec::new(),
+            #[cfg(feature = "std")]
+            io: None,
+            #[cfg(feature = "alloc")]
+            context: Vec::new(),
+            #[cfg(feature = "alloc")]
+            hex_context: None,
+        }
+    }
+}
+
+impl Display for Error {
+    #[cfg(feature = "alloc")]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, context) in self.context.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { "" } else { ": " }, context)?;
+        }
+        // The message of a custom error is its last context.
+        match (self.kind, self.context.is_empty()) {
+            (ErrorKind::Custom, false) => (),
+            (_, true) => self.kind.fmt(f)?,
+            (_, false) => write!(f, ": {}", self.kind)?,
+        }
+        for (i, tag) in self.path.iter().enumerate() {
+            write!(f, "{}{}", if i == 0 { " in " } else { " > " }, tag)?;
         }
+        if let Some(ref hex) = self.hex_context {
+            write!(f, " at {}", hex)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.kind.fmt(f)
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        self.kind.message()
+    }
+
+    #[cfg(feature = "std")]
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.io.as_ref().map(|err| &**err as &(dyn error::Error + 'static))
     }
 }
 
 impl From<untrusted::EndOfInput> for Error {
     fn from(_: untrusted::EndOfInput) -> Error {
-        Error::UnexpectedEnd
+        ErrorKind::UnexpectedEnd.into()
     }
 }
 
-impl From<::std::io::Error> for Error {
-    fn from(_: ::std::io::Error) -> Error {
-        Error::Io
+/// The `io::Error` is kept as the `source` of the `Error`, unless it wraps an `Error`, such as one
+/// converted to an `io::Error` with `From`, which is returned as it was.
+#[cfg(feature = "std")]
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        match err.get_ref().and_then(|inner| inner.downcast_ref::<Error>()) {
+            Some(inner) => inner.clone(),
+            None => Error {
+                io: Some(Arc::new(err)),
+                ..ErrorKind::Io.into()
+            },
+        }
+    }
+}
+
+/// I/O errors are converted back to the original `io::Error`, and all others become
+/// `io::ErrorKind::InvalidData` errors wrapping the `Error`.
+#[cfg(feature = "std")]
+impl From<Error> for io::Error {
+    fn from(mut err: Error) -> io::Error {
+        match err.io.take().map(Arc::try_unwrap) {
+            Some(Ok(io)) => io,
+            Some(Err(shared)) => {
+                let kind = shared.kind();
+                err.io = Some(shared);
+                io::Error::new(kind, err)
+            }
+            None => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
+/// Adds context to the errors of a `Result`.
+#[cfg(feature = "alloc")]
+pub trait ResultExt<T> {
+    /// Describe what was being done when the error happened, such as `"parsing tbsCertificate"`.
+    /// The descriptions are shown outermost first when the error is displayed.
+    ///
+    /// ```
+    /// extern crate derp;
+    /// extern crate untrusted;
+    ///
+    /// use derp::{ErrorKind, ResultExt, Tag};
+    /// use untrusted::Input;
+    ///
+    /// // SEQUENCE { INTEGER -128 }
+    /// const DATA: &'static [u8] = &[0x30, 0x03, 0x02, 0x01, 0x80];
+    ///
+    /// fn main() {
+    ///     let err = Input::from(DATA).read_all(ErrorKind::TrailingData.into(), |input| {
+    ///         derp::nested(input, Tag::SEQUENCE, |input| {
+    ///             derp::positive_integer(input).context("parsing serialNumber")
+    ///         }).context("parsing tbsCertificate")
+    ///     }).unwrap_err();
+    ///
+    ///     let message = "parsing tbsCertificate: parsing serialNumber: \
+    ///                    INTEGER is negative where a non-negative value is required in SEQUENCE";
+    ///     assert_eq!(err.to_string(), message);
+    /// }
+    /// ```
+    fn context<C: Into<Cow<'static, str>>>(self, context: C) -> Result<T>;
+}
+
+#[cfg(feature = "alloc")]
+impl<T> ResultExt<T> for Result<T> {
+    fn context<C: Into<Cow<'static, str>>>(self, context: C) -> Result<T> {
+        self.map_err(|mut err| {
+            err.context.insert(0, context.into());
+            err
+        })
     }
 }
 
@@ -0,0 +1,144 @@
+//! The `oid!` macro, and the `const` encoder it expands to, which need no allocation.
+
+/// Encode an `OBJECT IDENTIFIER` written in dotted decimal notation at compile time, producing
+/// the contents of its DER encoding as a `&'static [u8]`.
+///
+/// An invalid OID is a compile error.
+///
+/// With the `alloc` feature the result can be made into an `Oid` with `Oid::new`, but it does not
+/// need any allocation itself.
+///
+/// ```
+/// #[macro_use]
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::Tag;
+/// use untrusted::Input;
+///
+/// const ECDSA_WITH_SHA256: &'static [u8] = oid!("1.2.840.10045.4.3.2");
+///
+/// fn main() {
+///     assert_eq!(ECDSA_WITH_SHA256, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02]);
+///
+///     let input = Input::from(&[0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02]);
+///     let oid = input.read_all(derp::ErrorKind::TrailingData.into(), |input| {
+///         derp::expect_tag_and_get_value(input, Tag::OID)
+///     }).unwrap();
+///     assert_eq!(oid.as_slice_less_safe(), ECDSA_WITH_SHA256);
+/// }
+/// ```
+///
+/// ```compile_fail
+/// #[macro_use]
+/// extern crate derp;
+///
+/// fn main() {
+///     let _ = oid!("3.1");
+/// }
+/// ```
+#[macro_export]
+macro_rules! oid {
+    ($oid:expr) => {{
+        const ENCODED: ([u8; $crate::MAX_OID_MACRO_LEN], usize) = $crate::encode_oid_str($oid);
+        const ALL: &'static [u8] = &ENCODED.0;
+        const BYTES: &'static [u8] = ALL.split_at(ENCODED.1).0;
+        BYTES
+    }};
+}
+
+// The most octets the `oid!` macro can produce.
+#[doc(hidden)]
+pub const MAX_OID_MACRO_LEN: usize = 64;
+
+// Encode a dotted decimal OID for the `oid!` macro, panicking (and so failing to compile) if it is
+// not valid.
+#[doc(hidden)]
+pub const fn encode_oid_str(oid: &str) -> ([u8; MAX_OID_MACRO_LEN], usize) {
+    let oid = oid.as_bytes();
+    let mut out = [0; MAX_OID_MACRO_LEN];
+    let mut len = 0;
+    let mut first_arc = 0;
+    let mut arc_index = 0;
+    let mut i = 0;
+    while i <= oid.len() {
+        let start = i;
+        let mut arc: u128 = 0;
+        while i < oid.len() && oid[i] != b'.' {
+            if !oid[i].is_ascii_digit() {
+                panic!("OID arcs must be decimal numbers");
+            }
+            if i > start && oid[start] == b'0' {
+                panic!("OID arcs must not have leading zeros");
+            }
+            arc = match arc.checked_mul(10) {
+                Some(arc) => arc,
+                None => panic!("OID arc is too large"),
+            };
+            arc = match arc.checked_add((oid[i] - b'0') as u128) {
+                Some(arc) => arc,
+                None => panic!("OID arc is too large"),
+            };
+            i += 1;
+        }
+        if i == start {
+            panic!("OID arcs must not be empty");
+        }
+        i += 1;
+
+        let subidentifier = match arc_index {
+            0 => {
+                if arc > 2 {
+                    panic!("the first OID arc must be 0, 1, or 2");
+                }
+                first_arc = arc;
+                arc_index += 1;
+                continue;
+            }
+            1 => {
+                if first_arc < 2 && arc >= 40 {
+                    panic!("the second OID arc must be less than 40");
+                }
+                match (first_arc * 40).checked_add(arc) {
+                    Some(subidentifier) => subidentifier,
+                    None => panic!("OID arc is too large"),
+                }
+            }
+            _ => arc,
+        };
+        arc_index += 1;
+
+        let (digits, num_digits) = base128(subidentifier);
+        if len + num_digits > MAX_OID_MACRO_LEN {
+            panic!("OID is too long");
+        }
+        let mut d = 0;
+        while d < num_digits {
+            out[len] = digits[d];
+            len += 1;
+            d += 1;
+        }
+    }
+
+    if arc_index < 2 {
+        panic!("OIDs must have at least two arcs");
+    }
+    (out, len)
+}
+
+// The base 128 encoding of an OID subidentifier. Returns a buffer and the number of octets used.
+pub(crate) const fn base128(value: u128) -> ([u8; 19], usize) {
+    let mut num_digits = 1;
+    while num_digits < 19 && value >> (7 * num_digits) != 0 {
+        num_digits += 1;
+    }
+
+    let mut digits = [0; 19];
+    let mut i = 0;
+    while i < num_digits {
+        let more = if i + 1 < num_digits { 0x80 } else { 0 };
+        digits[i] = more | ((value >> (7 * (num_digits - 1 - i))) as u8 & 0x7f);
+        i += 1;
+    }
+    (digits, num_digits)
+}
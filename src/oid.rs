@@ -0,0 +1,397 @@
+//! `OBJECT IDENTIFIER` values.
+
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use untrusted::{Input, Reader};
+
+use {oids, Error, ErrorKind, Result};
+use der::{self, Tag};
+use macros::base128;
+
+/// An `OBJECT IDENTIFIER`, held as the contents of its DER encoding (without the tag and length).
+///
+/// Constants can be declared with `Oid::new`, and compared against parsed values or passed to
+/// `Der::oid`.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::{Der, Oid};
+/// use untrusted::Input;
+///
+/// // id-Ed25519
+/// const ED25519: Oid = Oid::new(&[0x2b, 0x65, 0x70]);
+///
+/// fn main() {
+///     let mut buf = Vec::new();
+///     {
+///         let mut der = Der::new(&mut buf);
+///         der.oid(&ED25519).unwrap();
+///     }
+///     assert_eq!(&buf, &[0x06, 0x03, 0x2b, 0x65, 0x70]);
+///
+///     let input = Input::from(&buf);
+///     input.read_all(derp::ErrorKind::TrailingData.into(), |input| derp::expect_oid(input, &ED25519)).unwrap();
+///     assert_eq!(ED25519.to_string(), "1.3.101.112");
+/// }
+/// ```
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Oid {
+    encoded: Cow<'static, [u8]>,
+}
+
+impl Oid {
+    /// Create an `Oid` from the contents of its DER encoding. This does not check that `encoded` is
+    /// valid, use `Oid::from_bytes` for that.
+    pub const fn new(encoded: &'static [u8]) -> Oid {
+        Oid { encoded: Cow::Borrowed(encoded) }
+    }
+
+    /// Create an `Oid` by copying the contents of a DER encoding, checking that they are valid.
+    pub fn from_bytes(encoded: &[u8]) -> Result<Oid> {
+        decode_arcs(encoded)?;
+        Ok(Oid { encoded: Cow::Owned(encoded.to_vec()) })
+    }
+
+    /// Create an `Oid` from its arcs. Errors with `ErrorKind::WrongValue` if there are fewer than two
+    /// arcs, the first is greater than 2, or the first is 0 or 1 and the second is 40 or more.
+    pub fn from_arcs(arcs: &[u128]) -> Result<Oid> {
+        Ok(Oid { encoded: Cow::Owned(encode_arcs(arcs)?) })
+    }
+
+    /// The contents of the DER encoding.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.encoded
+    }
+
+    /// Decode the arcs of the `Oid`.
+    pub fn arcs(&self) -> Result<OidArcs> {
+        decode_arcs(&self.encoded)
+    }
+}
+
+/// Parses an OID in dotted decimal notation, such as `1.3.6.1.5.5.7.3.1`. Errors with
+/// `ErrorKind::WrongValue` if it is malformed and `ErrorKind::IntegerOverflow` if an arc is too large.
+impl FromStr for Oid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Oid> {
+        let arcs = s
+            .split('.')
+            .map(|arc| {
+                if arc.is_empty() || !arc.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(ErrorKind::WrongValue.into());
+                }
+                if arc.len() > 1 && arc.starts_with('0') {
+                    return Err(ErrorKind::WrongValue.into());
+                }
+                arc.parse::<u128>().map_err(|_| Error::from(ErrorKind::IntegerOverflow))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Oid::from_arcs(&arcs)
+    }
+}
+
+impl AsRef<[u8]> for Oid {
+    fn as_ref(&self) -> &[u8] {
+        &self.encoded
+    }
+}
+
+impl<'a> PartialEq<Input<'a>> for Oid {
+    fn eq(&self, other: &Input<'a>) -> bool {
+        *self.encoded == *other.as_slice_less_safe()
+    }
+}
+
+impl<'a> PartialEq<Oid> for Input<'a> {
+    fn eq(&self, other: &Oid) -> bool {
+        other == self
+    }
+}
+
+/// Includes the name of well-known OIDs, such as `Oid(1.3.101.112, id-Ed25519)`.
+impl fmt::Debug for Oid {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match oids::name(&self.encoded) {
+            Some(name) => write!(f, "Oid({}, {})", self, name),
+            None => write!(f, "Oid({})", self),
+        }
+    }
+}
+
+/// Formats the `Oid` in dotted decimal notation, or as hex if it is not a valid encoding.
+impl Display for Oid {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.arcs() {
+            Ok(arcs) => arcs.fmt(f),
+            Err(_) => {
+                for byte in self.encoded.iter() {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// Encode the arcs of an OID, checking the constraints on the first two.
+fn encode_arcs(arcs: &[u128]) -> Result<Vec<u8>> {
+    let subidentifier = match *arcs {
+        [first, second, ..] if first < 2 && second < 40 => first * 40 + second,
+        [2, second, ..] => second.checked_add(80).ok_or(Error::from(ErrorKind::IntegerOverflow))?,
+        _ => return Err(ErrorKind::WrongValue.into()),
+    };
+
+    let mut encoded = Vec::new();
+    for &subidentifier in [subidentifier].iter().chain(&arcs[2..]) {
+        let (digits, num_digits) = base128(subidentifier);
+        encoded.extend_from_slice(&digits[..num_digits]);
+    }
+    Ok(encoded)
+}
+
+/// Read an `OBJECT IDENTIFIER`, checking that it is valid.
+pub fn oid(input: &mut Reader) -> Result<Oid> {
+    let value = der::expect_tag_and_get_value(input, Tag::OID)?;
+    Oid::from_bytes(value.as_slice_less_safe())
+}
+
+/// Read an `OBJECT IDENTIFIER`, erroring with `ErrorKind::WrongValue` if it is not `expected`.
+pub fn expect_oid(input: &mut Reader, expected: &Oid) -> Result<()> {
+    let value = der::expect_tag_and_get_value(input, Tag::OID)?;
+    if *expected != value {
+        return Err(ErrorKind::WrongValue.into());
+    }
+    Ok(())
+}
+
+/// The arcs of an `OBJECT IDENTIFIER`, as read by `oid_arcs`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OidArcs {
+    arcs: Vec<u128>,
+}
+
+impl OidArcs {
+    /// The arcs, starting with the root.
+    pub fn as_slice(&self) -> &[u128] {
+        &self.arcs
+    }
+}
+
+/// Formats the arcs in dotted decimal notation, such as `1.2.840.113549.1.1.11`.
+impl Display for OidArcs {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (i, arc) in self.arcs.iter().enumerate() {
+            if i > 0 {
+                f.write_str(".")?;
+            }
+            write!(f, "{}", arc)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<OidArcs> for Vec<u128> {
+    fn from(arcs: OidArcs) -> Vec<u128> {
+        arcs.arcs
+    }
+}
+
+/// Read an `OBJECT IDENTIFIER` and decode it into its arcs.
+///
+/// Errors with `ErrorKind::NonCanonical` if a subidentifier is not minimally encoded and
+/// `ErrorKind::IntegerOverflow` if an arc does not fit in a `u128`.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// // sha256WithRSAEncryption
+/// const OID: &'static [u8] = &[0x06, 0x09, 0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b];
+///
+/// fn main() {
+///     let input = Input::from(OID);
+///     let arcs = input.read_all(derp::ErrorKind::TrailingData.into(), derp::oid_arcs).unwrap();
+///     assert_eq!(arcs.as_slice(), &[1, 2, 840, 113549, 1, 1, 11]);
+///     assert_eq!(arcs.to_string(), "1.2.840.113549.1.1.11");
+/// }
+/// ```
+pub fn oid_arcs(input: &mut Reader) -> Result<OidArcs> {
+    let value = der::expect_tag_and_get_value(input, Tag::OID)?;
+    decode_arcs(value.as_slice_less_safe())
+}
+
+// Decode the contents of an `OBJECT IDENTIFIER`.
+fn decode_arcs(value: &[u8]) -> Result<OidArcs> {
+    if value.is_empty() {
+        return Err(ErrorKind::WrongValue.into());
+    }
+
+    let mut arcs = Vec::new();
+    let mut subidentifier: u128 = 0;
+    let mut in_subidentifier = false;
+    for &byte in value {
+        if !in_subidentifier && byte == 0x80 {
+            return Err(ErrorKind::NonCanonical.into());
+        }
+        if subidentifier > (u128::MAX >> 7) {
+            return Err(ErrorKind::IntegerOverflow.into());
+        }
+        subidentifier = (subidentifier << 7) | u128::from(byte & 0x7f);
+        in_subidentifier = byte & 0x80 != 0;
+        if in_subidentifier {
+            continue;
+        }
+
+        // The first subidentifier holds the first two arcs, with the first being 0, 1, or 2.
+        if arcs.is_empty() {
+            let first = (subidentifier / 40).min(2);
+            arcs.push(first);
+            arcs.push(subidentifier - first * 40);
+        } else {
+            arcs.push(subidentifier);
+        }
+        subidentifier = 0;
+    }
+
+    if in_subidentifier {
+        return Err(ErrorKind::UnexpectedEnd.into());
+    }
+    Ok(OidArcs { arcs })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use untrusted::Input;
+
+    fn arcs(value: &[u8]) -> Result<Vec<u128>> {
+        decode_arcs(value).map(Vec::from)
+    }
+
+    #[test]
+    fn read_oid_arcs() {
+        for &(value, expected) in &[
+            (&[0x00][..], &[0, 0][..]),
+            (&[0x27], &[0, 39]),
+            (&[0x28], &[1, 0]),
+            (&[0x2b, 0x65, 0x70], &[1, 3, 101, 112]),
+            (&[0x50], &[2, 0]),
+            // 2.999.3
+            (&[0x88, 0x37, 0x03], &[2, 999, 3]),
+            (
+                &[0x2a, 0x81, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f],
+                &[1, 2, u128::from(u64::MAX)],
+            ),
+        ] {
+            assert_eq!(arcs(value).as_ref().map(Vec::as_slice), Ok(expected));
+        }
+
+        for &(value, err) in &[
+            (&[][..], ErrorKind::WrongValue),
+            (&[0x2a, 0x80, 0x01], ErrorKind::NonCanonical),
+            (&[0x80, 0x01], ErrorKind::NonCanonical),
+            (&[0x2a, 0x86], ErrorKind::UnexpectedEnd),
+            (
+                &[0x2a, 0x84, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80,
+                    0x80, 0x80, 0x80, 0x80, 0x80, 0x00],
+                ErrorKind::IntegerOverflow,
+            ),
+        ] {
+            assert_eq!(arcs(value), Err(err.into()));
+        }
+
+        let r = Input::from(&[0x04, 0x01, 0x2a]).read_all(ErrorKind::TrailingData.into(), oid_arcs);
+        assert_eq!(r, Err(ErrorKind::WrongTag { expected: Tag::OID, found: 0x04 }.into()));
+    }
+
+    #[test]
+    fn display_oid_arcs() {
+        assert_eq!(decode_arcs(&[0x00]).unwrap().to_string(), "0.0");
+        assert_eq!(decode_arcs(&[0x88, 0x37, 0x03]).unwrap().to_string(), "2.999.3");
+        assert_eq!(decode_arcs(&[0x2b, 0x65, 0x70]).unwrap().to_string(), "1.3.101.112");
+    }
+
+    #[test]
+    fn read_oid() {
+        const ED25519: Oid = Oid::new(&[0x2b, 0x65, 0x70]);
+
+        let parsed = Input::from(&[0x06, 0x03, 0x2b, 0x65, 0x70]).read_all(ErrorKind::TrailingData.into(), oid).unwrap();
+        assert_eq!(parsed, ED25519);
+        assert_eq!(ED25519, Input::from(&[0x2b, 0x65, 0x70]));
+        assert!(ED25519 != Input::from(&[0x2b, 0x65, 0x71]));
+
+        let r = Input::from(&[0x06, 0x02, 0x2b, 0x86]).read_all(ErrorKind::TrailingData.into(), oid);
+        assert_eq!(r, Err(ErrorKind::UnexpectedEnd.into()));
+        let r = Input::from(&[0x06, 0x01, 0x2a])
+            .read_all(ErrorKind::TrailingData.into(), |input| expect_oid(input, &ED25519));
+        assert_eq!(r, Err(ErrorKind::WrongValue.into()));
+
+        assert_eq!(Oid::from_bytes(&[0x2a, 0x80, 0x01]), Err(ErrorKind::NonCanonical.into()));
+        assert_eq!(Oid::new(&[0x2a, 0x80]).to_string(), "2a80");
+        assert_eq!(format!("{:?}", ED25519), "Oid(1.3.101.112, id-Ed25519)");
+        assert_eq!(format!("{:?}", Oid::new(&[0x2a, 0x03])), "Oid(1.2.3)");
+    }
+
+    #[test]
+    fn oid_macro() {
+        assert_eq!(oid!("0.0"), &[0x00]);
+        assert_eq!(oid!("1.3.101.112"), &[0x2b, 0x65, 0x70]);
+        assert_eq!(oid!("2.999.3"), &[0x88, 0x37, 0x03]);
+        assert_eq!(oid!("1.2.840.113549.1.1.11"), &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x0b]);
+        assert_eq!(
+            decode_arcs(oid!("1.2.18446744073709551615")).map(Vec::from),
+            Ok(vec![1, 2, u128::from(u64::MAX)])
+        );
+    }
+
+    #[test]
+    fn parse_oid() {
+        for &oid in &["0.0", "1.39", "1.3.101.112", "2.999.3", "1.2.840.113549.1.1.11"] {
+            let parsed = oid.parse::<Oid>().unwrap();
+            assert_eq!(parsed.to_string(), oid);
+        }
+        assert_eq!("2.25.1".parse::<Oid>().unwrap(), Oid::new(oid!("2.25.1")));
+
+        // A UUID under the 2.25 arc, from RFC 4122
+        let uuid = "2.25.329800735698586629295641978511506172918";
+        const UUID: Oid = Oid::new(oid!("2.25.329800735698586629295641978511506172918"));
+        assert_eq!(uuid.parse::<Oid>().unwrap(), UUID);
+        assert_eq!(uuid.parse::<Oid>().unwrap().to_string(), uuid);
+
+        for &(oid, err) in &[
+            ("", ErrorKind::WrongValue),
+            ("1", ErrorKind::WrongValue),
+            ("3.1", ErrorKind::WrongValue),
+            ("1.40", ErrorKind::WrongValue),
+            ("1.2.", ErrorKind::WrongValue),
+            ("1..2", ErrorKind::WrongValue),
+            ("1.2.03", ErrorKind::WrongValue),
+            ("1.2.-3", ErrorKind::WrongValue),
+            ("1.2.+3", ErrorKind::WrongValue),
+            ("1.2.340282366920938463463374607431768211456", ErrorKind::IntegerOverflow),
+            ("2.340282366920938463463374607431768211455", ErrorKind::IntegerOverflow),
+        ] {
+            assert_eq!(oid.parse::<Oid>(), Err(err.into()));
+        }
+    }
+
+    #[test]
+    fn oid_from_arcs() {
+        for &arcs in &[&[0, 39][..], &[1, 3, 101, 112], &[2, 999, 3], &[2, u128::MAX - 80, u128::MAX]] {
+            let oid = Oid::from_arcs(arcs).unwrap();
+            assert_eq!(oid.arcs().map(Vec::from), Ok(arcs.to_vec()));
+        }
+
+        for &arcs in &[&[][..], &[1], &[3, 1], &[0, 40], &[1, 40, 1]] {
+            assert_eq!(Oid::from_arcs(arcs), Err(ErrorKind::WrongValue.into()));
+        }
+        assert_eq!(Oid::from_arcs(&[2, u128::MAX]), Err(ErrorKind::IntegerOverflow.into()));
+    }
+}
@@ -0,0 +1,129 @@
+//! Well-known `OBJECT IDENTIFIER`s.
+//!
+//! ```
+//! extern crate derp;
+//! extern crate untrusted;
+//!
+//! use derp::oids;
+//! use untrusted::Input;
+//!
+//! const ED25519: &'static [u8] = &[0x06, 0x03, 0x2b, 0x65, 0x70];
+//!
+//! fn main() {
+//!     let input = Input::from(ED25519);
+//!     input.read_all(derp::ErrorKind::TrailingData.into(), |input| derp::expect_oid(input, &oids::ED25519)).unwrap();
+//!     assert_eq!(oids::name(oids::ED25519.as_bytes()), Some("id-Ed25519"));
+//! }
+//! ```
+
+use Oid;
+
+// Declare each OID as a constant and add it to the table used by `name`.
+macro_rules! oids {
+    ($($name:ident = $oid:expr, $asn1_name:expr;)*) => {
+        $(
+            #[doc = concat!("`", $asn1_name, "`")]
+            pub const $name: Oid = Oid::new(oid!($oid));
+        )*
+
+        const NAMES: &[(Oid, &str)] = &[$(($name, $asn1_name)),*];
+    };
+}
+
+oids! {
+    // Public key and signature algorithms
+    RSA_ENCRYPTION = "1.2.840.113549.1.1.1", "rsaEncryption";
+    RSASSA_PSS = "1.2.840.113549.1.1.10", "id-RSASSA-PSS";
+    SHA256_WITH_RSA_ENCRYPTION = "1.2.840.113549.1.1.11", "sha256WithRSAEncryption";
+    SHA384_WITH_RSA_ENCRYPTION = "1.2.840.113549.1.1.12", "sha384WithRSAEncryption";
+    SHA512_WITH_RSA_ENCRYPTION = "1.2.840.113549.1.1.13", "sha512WithRSAEncryption";
+    EC_PUBLIC_KEY = "1.2.840.10045.2.1", "id-ecPublicKey";
+    ECDSA_WITH_SHA256 = "1.2.840.10045.4.3.2", "ecdsa-with-SHA256";
+    ECDSA_WITH_SHA384 = "1.2.840.10045.4.3.3", "ecdsa-with-SHA384";
+    ECDSA_WITH_SHA512 = "1.2.840.10045.4.3.4", "ecdsa-with-SHA512";
+    X25519 = "1.3.101.110", "id-X25519";
+    ED25519 = "1.3.101.112", "id-Ed25519";
+    ED448 = "1.3.101.113", "id-Ed448";
+
+    // Elliptic curves
+    SECP256R1 = "1.2.840.10045.3.1.7", "secp256r1";
+    SECP384R1 = "1.3.132.0.34", "secp384r1";
+    SECP521R1 = "1.3.132.0.35", "secp521r1";
+
+    // Hash algorithms
+    SHA1 = "1.3.14.3.2.26", "id-sha1";
+    SHA256 = "2.16.840.1.101.3.4.2.1", "id-sha256";
+    SHA384 = "2.16.840.1.101.3.4.2.2", "id-sha384";
+    SHA512 = "2.16.840.1.101.3.4.2.3", "id-sha512";
+    SHA224 = "2.16.840.1.101.3.4.2.4", "id-sha224";
+
+    // Distinguished name attributes
+    COMMON_NAME = "2.5.4.3", "commonName";
+    SURNAME = "2.5.4.4", "surname";
+    SERIAL_NUMBER = "2.5.4.5", "serialNumber";
+    COUNTRY_NAME = "2.5.4.6", "countryName";
+    LOCALITY_NAME = "2.5.4.7", "localityName";
+    STATE_OR_PROVINCE_NAME = "2.5.4.8", "stateOrProvinceName";
+    STREET_ADDRESS = "2.5.4.9", "streetAddress";
+    ORGANIZATION_NAME = "2.5.4.10", "organizationName";
+    ORGANIZATIONAL_UNIT_NAME = "2.5.4.11", "organizationalUnitName";
+    TITLE = "2.5.4.12", "title";
+    GIVEN_NAME = "2.5.4.42", "givenName";
+    EMAIL_ADDRESS = "1.2.840.113549.1.9.1", "emailAddress";
+    DOMAIN_COMPONENT = "0.9.2342.19200300.100.1.25", "domainComponent";
+
+    // Certificate and CRL extensions
+    SUBJECT_KEY_IDENTIFIER = "2.5.29.14", "subjectKeyIdentifier";
+    KEY_USAGE = "2.5.29.15", "keyUsage";
+    SUBJECT_ALT_NAME = "2.5.29.17", "subjectAltName";
+    ISSUER_ALT_NAME = "2.5.29.18", "issuerAltName";
+    BASIC_CONSTRAINTS = "2.5.29.19", "basicConstraints";
+    CRL_NUMBER = "2.5.29.20", "cRLNumber";
+    CRL_REASON = "2.5.29.21", "reasonCode";
+    NAME_CONSTRAINTS = "2.5.29.30", "nameConstraints";
+    CRL_DISTRIBUTION_POINTS = "2.5.29.31", "cRLDistributionPoints";
+    CERTIFICATE_POLICIES = "2.5.29.32", "certificatePolicies";
+    AUTHORITY_KEY_IDENTIFIER = "2.5.29.35", "authorityKeyIdentifier";
+    EXT_KEY_USAGE = "2.5.29.37", "extKeyUsage";
+    AUTHORITY_INFO_ACCESS = "1.3.6.1.5.5.7.1.1", "authorityInfoAccess";
+
+    // Extended key usage purposes
+    SERVER_AUTH = "1.3.6.1.5.5.7.3.1", "id-kp-serverAuth";
+    CLIENT_AUTH = "1.3.6.1.5.5.7.3.2", "id-kp-clientAuth";
+    CODE_SIGNING = "1.3.6.1.5.5.7.3.3", "id-kp-codeSigning";
+    EMAIL_PROTECTION = "1.3.6.1.5.5.7.3.4", "id-kp-emailProtection";
+    TIME_STAMPING = "1.3.6.1.5.5.7.3.8", "id-kp-timeStamping";
+    OCSP_SIGNING = "1.3.6.1.5.5.7.3.9", "id-kp-OCSPSigning";
+}
+
+/// Look up the ASN.1 name of a well-known OID given the contents of its encoding, such as
+/// `"sha256WithRSAEncryption"`.
+pub fn name(oid: &[u8]) -> Option<&'static str> {
+    NAMES
+        .iter()
+        .find(|known| known.0.as_bytes() == oid)
+        .map(|&(_, name)| name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn oids_are_valid() {
+        assert_eq!(RSA_ENCRYPTION.to_string(), "1.2.840.113549.1.1.1");
+        assert_eq!(DOMAIN_COMPONENT.to_string(), "0.9.2342.19200300.100.1.25");
+        assert_eq!(COMMON_NAME.as_bytes(), &[0x55, 0x04, 0x03]);
+        assert_eq!(SHA256.as_bytes(), &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01]);
+        for (oid, _) in NAMES {
+            assert!(oid.arcs().is_ok());
+        }
+    }
+
+    #[test]
+    fn lookup_name() {
+        assert_eq!(name(&[0x55, 0x1d, 0x13]), Some("basicConstraints"));
+        assert_eq!(name(SECP384R1.as_bytes()), Some("secp384r1"));
+        assert_eq!(name(&[0x2a, 0x03]), None);
+    }
+}
@@ -0,0 +1,211 @@
+//! Collecting DER elements from input that arrives in pieces.
+
+use alloc::vec::Vec;
+use untrusted::Input;
+
+use config::ParserConfig;
+use der;
+use {Error, ErrorKind, Result};
+
+/// What a `PushParser` needs before the next element is complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// At least this many more octets are needed. The count is exact once the identifier and
+    /// length octets of the element have arrived.
+    NeedMore(usize),
+    /// The next element is complete and can be taken with `next_element`.
+    Ready,
+}
+
+/// A parser that is given input as it arrives and reports when it holds a complete element, for
+/// protocols that frame their messages in DER.
+///
+/// Only the identifier and length octets are checked, so that the length of the element is known.
+/// The elements returned by `next_element` can then be decoded with `parse`. The `max_len` of the
+/// config limits how much will be buffered for one element, while `max_depth` is not used.
+///
+/// ```
+/// extern crate derp;
+///
+/// use derp::{PushParser, Status};
+///
+/// fn main() {
+///     // Two messages, SEQUENCE { INTEGER 1 } and SEQUENCE { INTEGER 2 }, split into chunks
+///     let mut parser = PushParser::new();
+///     assert_eq!(parser.feed(&[0x30]), Ok(Status::NeedMore(1)));
+///     assert_eq!(parser.feed(&[0x03, 0x02]), Ok(Status::NeedMore(2)));
+///     assert_eq!(parser.feed(&[0x01, 0x01, 0x30, 0x03, 0x02]), Ok(Status::Ready));
+///
+///     let message = parser.next_element().unwrap().unwrap();
+///     assert_eq!(derp::parse(&message, |r| r.sequence(|r| r.u8())), Ok(1));
+///     assert_eq!(parser.status(), Ok(Status::NeedMore(2)));
+///     assert_eq!(parser.next_element(), Ok(None));
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PushParser {
+    config: ParserConfig,
+    // The input that has arrived, of which the octets before `start` have been taken.
+    buffer: Vec<u8>,
+    start: usize,
+}
+
+impl PushParser {
+    /// A parser for DER.
+    pub fn new() -> Self {
+        PushParser::with_config(ParserConfig::DER)
+    }
+
+    /// A parser using `config`. `Mode::Lenient` accepts lengths that are not in their minimal
+    /// form, but not the indefinite form, since it needs the contents parsed to find their end.
+    pub fn with_config(config: ParserConfig) -> Self {
+        PushParser {
+            config,
+            buffer: Vec::new(),
+            start: 0,
+        }
+    }
+
+    /// The configuration this parser applies.
+    pub fn config(&self) -> ParserConfig {
+        self.config
+    }
+
+    /// How many octets have arrived and not yet been taken.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len() - self.start
+    }
+
+    /// Add `input` to what has arrived, and return the status of the next element.
+    ///
+    /// An error means the next element can never be read, for example because its length is more
+    /// than the `max_len` of the config. Nothing more should be fed to the parser afterwards.
+    pub fn feed(&mut self, input: &[u8]) -> Result<Status> {
+        self.buffer.extend_from_slice(input);
+        self.status()
+    }
+
+    /// What is needed before the next element is complete.
+    pub fn status(&self) -> Result<Status> {
+        self.next_len().map(|needed| match needed {
+            Ok(_) => Status::Ready,
+            Err(more) => Status::NeedMore(more),
+        })
+    }
+
+    /// Remove the next element and return all of its encoding, or `None` if it is not complete.
+    pub fn next_element(&mut self) -> Result<Option<Vec<u8>>> {
+        match self.next_len()? {
+            Ok(len) => {
+                let element = self.buffer[self.start..][..len].to_vec();
+                self.start += len;
+                // What was taken is only removed once it is at least half of the buffer, so that
+                // taking many small elements does not move what follows them each time.
+                if self.start * 2 >= self.buffer.len() {
+                    self.buffer.drain(..self.start);
+                    self.start = 0;
+                }
+                Ok(Some(element))
+            }
+            Err(_) => Ok(None),
+        }
+    }
+
+    // The length of the encoding of the next element if it has all arrived, or how many more
+    // octets are needed to complete it.
+    fn next_len(&self) -> Result<::std::result::Result<usize, usize>> {
+        let buffer = &self.buffer[self.start..];
+        if buffer.is_empty() {
+            return Ok(Err(1));
+        }
+        let (mode, max_len) = (self.config.mode(), self.config.max_len());
+        let input = Input::from(buffer);
+        let header = input.read_all(Error::from(ErrorKind::TrailingData), |input| {
+            let length = der::read_header(input, mode, max_len)?.1;
+            let header_len = buffer.len() - input.skip_to_end().len();
+            Ok(header_len.checked_add(length))
+        });
+        match header {
+            Ok(Some(len)) if len <= buffer.len() => Ok(Ok(len)),
+            Ok(Some(len)) => Ok(Err(len - buffer.len())),
+            Ok(None) => Err(ErrorKind::LengthOverflow.into()),
+            // The identifier and length octets have not all arrived.
+            Err(ref err) if err.kind() == ErrorKind::UnexpectedEnd => Ok(Err(1)),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use der::{Mode, Tag};
+
+    #[test]
+    fn push_elements() {
+        // SEQUENCE { INTEGER 1 }, NULL and the start of a long OCTET STRING, an octet at a time
+        let encoded = &[0x30, 0x03, 0x02, 0x01, 0x01, 0x05, 0x00, 0x04, 0x81, 0x81];
+        let mut parser = PushParser::new();
+        let mut needed = Vec::new();
+        let mut elements = Vec::new();
+        for octet in encoded {
+            match parser.feed(&[*octet]).unwrap() {
+                Status::NeedMore(more) => needed.push(more),
+                Status::Ready => {
+                    needed.push(0);
+                    elements.push(parser.next_element().unwrap().unwrap());
+                }
+            }
+        }
+        assert_eq!(needed, vec![1, 3, 2, 1, 0, 1, 0, 1, 1, 0x81]);
+        assert_eq!(elements, vec![encoded[..5].to_vec(), vec![0x05, 0x00]]);
+        assert_eq!(parser.next_element(), Ok(None));
+        assert_eq!(parser.buffered(), 3);
+
+        assert_eq!(parser.feed(&[0; 0x80]), Ok(Status::NeedMore(1)));
+        assert_eq!(parser.feed(&[0, 0x05]), Ok(Status::Ready));
+        let element = parser.next_element().unwrap().unwrap();
+        let len = ::parse(&element, |r| r.expect(Tag::OCTET_STRING).map(|value| value.len()));
+        assert_eq!(len, Ok(0x81));
+        assert_eq!(parser.status(), Ok(Status::NeedMore(1)));
+        assert_eq!(parser.buffered(), 1);
+
+        // Complete elements stay until they are taken.
+        let mut parser = PushParser::new();
+        assert_eq!(parser.feed(encoded), Ok(Status::Ready));
+        assert_eq!(parser.feed(&[]), Ok(Status::Ready));
+        assert_eq!(parser.next_element(), Ok(Some(encoded[..5].to_vec())));
+        assert_eq!(parser.buffered(), 5);
+
+        // Many elements that arrived together, taken one at a time with more arriving between.
+        let mut parser = PushParser::new();
+        parser.feed(&[0x05, 0x00].repeat(1000)).unwrap();
+        for i in 0..1500 {
+            if i == 500 {
+                parser.feed(&[0x01, 0x01, 0xff].repeat(500)).unwrap();
+            }
+            let expected = match i < 1000 {
+                true => vec![0x05, 0x00],
+                false => vec![0x01, 0x01, 0xff],
+            };
+            assert_eq!(parser.next_element(), Ok(Some(expected)));
+        }
+        assert_eq!(parser.next_element(), Ok(None));
+        assert_eq!(parser.buffered(), 0);
+    }
+
+    #[test]
+    fn push_errors() {
+        let feed = |encoded: &[u8], config| PushParser::with_config(config).feed(encoded);
+        let config = ParserConfig::DER;
+        assert_eq!(feed(&[0x30, 0x81, 0x01], config), Err(ErrorKind::NonCanonical.into()));
+        let lenient = config.with_mode(Mode::Lenient);
+        assert_eq!(feed(&[0x30, 0x81, 0x01], lenient), Ok(Status::NeedMore(1)));
+        let err = feed(&[0x30, 0x80], ParserConfig::BER).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LongLengthNotSupported);
+        let err = feed(&[0x04, 0x82, 0x01, 0x00], config.with_max_len(0xff)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LengthExceedsLimit);
+        let encoded = &[0x04, 0x88, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(feed(encoded, config), Err(ErrorKind::LengthOverflow.into()));
+    }
+}
@@ -0,0 +1,821 @@
+//! A reader that carries its configuration, with the free functions as methods.
+
+#[cfg(feature = "alloc")]
+use alloc::borrow::Cow;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+#[cfg(feature = "bytes")]
+use bytes::Bytes;
+use std::mem;
+use untrusted::{self, Input};
+
+#[cfg(feature = "alloc")]
+use ber;
+use config::{self, ParserConfig};
+use datetime::{self, DerDateTime, UtcTimePivot};
+use der::{self, Class, Tag};
+#[cfg(feature = "alloc")]
+use der::{BitFlags, Mode};
+#[cfg(feature = "alloc")]
+use oid::Oid;
+#[cfg(feature = "alloc")]
+use real;
+use {Error, ErrorKind, Result};
+#[cfg(feature = "alloc")]
+use ResultExt;
+
+/// Apply `decoder` to all of `input`, which must be DER, failing with `ErrorKind::TrailingData` if
+/// it does not read everything.
+///
+/// This and `Reader` need nothing from the `untrusted` crate, so callers do not have to depend on
+/// it themselves.
+///
+/// ```
+/// extern crate derp;
+///
+/// fn main() {
+///     // SEQUENCE { INTEGER 1, UTF8String "hi" }
+///     let encoded = [0x30, 0x07, 0x02, 0x01, 0x01, 0x0c, 0x02, 0x68, 0x69];
+///     let (n, s) = derp::parse(&encoded, |r| {
+///         r.sequence(|r| Ok((r.u8()?, r.utf8_string()?)))
+///     }).unwrap();
+///     assert_eq!((n, s), (1, "hi"));
+/// }
+/// ```
+pub fn parse<'a, F, R>(input: &'a [u8], decoder: F) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+{
+    parse_with_config(input, ParserConfig::DER, decoder)
+}
+
+/// Apply `decoder` to all of `input` using `config`, as `parse` does.
+pub fn parse_with_config<'a, F, R>(input: &'a [u8], config: ParserConfig, decoder: F) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+{
+    Reader::read_all(Input::from(input), config, decoder)
+}
+
+/// Apply `decoder` to all of `input`, which must be DER, as `parse` does, with
+/// `Reader::to_bytes` turning what it reads into `Bytes` that share `input` instead of copying it.
+///
+/// This needs the `bytes` feature.
+///
+/// ```
+/// extern crate bytes;
+/// extern crate derp;
+///
+/// use bytes::Bytes;
+///
+/// fn main() {
+///     // SEQUENCE { INTEGER 0x0102, OCTET STRING "hi" }
+///     let encoded = Bytes::from(vec![0x30, 0x08, 0x02, 0x02, 0x01, 0x02, 0x04, 0x02, 0x68, 0x69]);
+///     let (serial, data) = derp::parse_bytes(&encoded, |r| {
+///         r.sequence(|r| {
+///             let serial = r.positive_integer()?;
+///             let data = r.octet_string()?;
+///             Ok((r.to_bytes(serial), r.to_bytes(data)))
+///         })
+///     }).unwrap();
+///     drop(encoded);
+///
+///     assert_eq!(serial, &[0x01, 0x02][..]);
+///     assert_eq!(data, &b"hi"[..]);
+/// }
+/// ```
+#[cfg(feature = "bytes")]
+pub fn parse_bytes<'a, F, R>(input: &'a Bytes, decoder: F) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+{
+    parse_bytes_with_config(input, ParserConfig::DER, decoder)
+}
+
+/// Apply `decoder` to all of `input` using `config`, as `parse_bytes` does.
+#[cfg(feature = "bytes")]
+pub fn parse_bytes_with_config<'a, F, R>(
+    input: &'a Bytes,
+    config: ParserConfig,
+    decoder: F,
+) -> Result<R>
+where
+    F: FnOnce(&mut Reader<'a>) -> Result<R>,
+{
+    let origin = Origin { start: &input[..], bytes: Some(input) };
+    Reader::read_from(origin, config, decoder)
+}
+
+/// A reader over DER input, with the free functions of this crate as methods.
+///
+/// The `ParserConfig` given to `Reader::read_all` is applied to every element read, including its
+/// limits on nesting and length, and errors are located and given context as they are by the free
+/// functions. The underlying `untrusted::Reader` is available from `untrusted` for anything there
+/// is no method for.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use derp::{ParserConfig, Reader};
+/// use untrusted::Input;
+///
+/// // SEQUENCE { version [0] EXPLICIT INTEGER 2, serialNumber INTEGER 0x0102, critical BOOLEAN }
+/// const DATA: &'static [u8] = &[
+///     0x30, 0x0c,
+///         0xa0, 0x03, 0x02, 0x01, 0x02,
+///         0x02, 0x02, 0x01, 0x02,
+///         0x01, 0x01, 0xff,
+/// ];
+///
+/// fn main() {
+///     let input = Input::from(DATA);
+///     let (version, serial, critical) = Reader::read_all(input, ParserConfig::DER, |r| {
+///         r.sequence(|r| {
+///             let version = r.explicit(0, |r| r.u8())?;
+///             let serial = r.context("serialNumber", |r| r.positive_integer())?;
+///             let critical = r.optional(derp::Tag::BOOLEAN, |r| r.boolean())?.unwrap_or(false);
+///             Ok((version, serial, critical))
+///         })
+///     }).unwrap();
+///
+///     assert_eq!(version, 2);
+///     assert_eq!(serial, &[0x01, 0x02]);
+///     assert!(critical);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Reader<'a> {
+    input: untrusted::Reader<'a>,
+    config: ParserConfig,
+    origin: Origin<'a>,
+}
+
+// Where the input given to `read_all` came from.
+#[derive(Debug, Clone, Copy)]
+struct Origin<'a> {
+    // All of the input, for `offset`.
+    start: &'a [u8],
+    // The buffer it is part of, for `to_bytes`.
+    #[cfg(feature = "bytes")]
+    bytes: Option<&'a Bytes>,
+}
+
+impl<'a> Reader<'a> {
+    /// Apply `decoder` to all of `input` using `config`, failing with `ErrorKind::TrailingData` if
+    /// it does not read everything.
+    pub fn read_all<F, R>(input: Input<'a>, config: ParserConfig, decoder: F) -> Result<R>
+    where
+        F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    {
+        let origin = Origin {
+            start: input.as_slice_less_safe(),
+            #[cfg(feature = "bytes")]
+            bytes: None,
+        };
+        Reader::read_from(origin, config, decoder)
+    }
+
+    fn read_from<F, R>(origin: Origin<'a>, config: ParserConfig, decoder: F) -> Result<R>
+    where
+        F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    {
+        let result = Input::from(origin.start)
+            .read_all(Error::from(ErrorKind::TrailingData), |input| {
+                Reader::wrap(input, config, origin, decoder)
+            });
+        match config.hex_context() {
+            true => result.map_err(|err| err.with_hex_context(origin.start)),
+            false => result,
+        }
+    }
+
+    // Run `decoder` with a `Reader` that takes over `input` until it returns.
+    fn wrap<F, R>(
+        input: &mut untrusted::Reader<'a>,
+        config: ParserConfig,
+        origin: Origin<'a>,
+        decoder: F,
+    ) -> Result<R>
+    where
+        F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    {
+        let empty = untrusted::Reader::new(Input::from(&[]));
+        let mut reader = Reader { input: mem::replace(input, empty), config, origin };
+        let result = decoder(&mut reader);
+        *input = reader.input;
+        result
+    }
+
+    // Read the next element, which must have the tag `tag`, as the config allows and apply
+    // `decode` to its contents. Errors in its contents are located at the start of the element.
+    fn leaf<F, R>(&mut self, tag: Tag, decode: F) -> Result<R>
+    where
+        F: FnOnce(Input<'a>) -> Result<R>,
+    {
+        let start = self.input.mark();
+        let value =
+            config::expect_tag_and_get_value_with_config(&mut self.input, tag, self.config)?;
+        let element = self.input.get_input_between_marks(start, self.input.mark())?;
+        decode(value).map_err(|err| err.at(Some(element)))
+    }
+
+    /// The configuration this reader applies. Inside `nested` its `max_depth` counts the levels
+    /// that remain.
+    pub fn config(&self) -> ParserConfig {
+        self.config
+    }
+
+    /// The underlying reader, for using the free functions directly.
+    pub fn untrusted(&mut self) -> &mut untrusted::Reader<'a> {
+        &mut self.input
+    }
+
+    /// Whether all of the input has been read.
+    pub fn at_end(&self) -> bool {
+        self.input.at_end()
+    }
+
+    /// How far into the input given to `read_all` this reader is, in octets.
+    pub fn offset(&self) -> usize {
+        let start = self.origin.start.as_ptr() as usize;
+        match der::position(&self.input) {
+            Some(position) => position.as_slice_less_safe().as_ptr() as usize - start,
+            None => 0,
+        }
+    }
+
+    /// Add `context` to any error from `decoder`, as `ResultExt::context` does.
+    #[cfg(feature = "alloc")]
+    pub fn context<C, F, R>(&mut self, context: C, decoder: F) -> Result<R>
+    where
+        C: Into<Cow<'static, str>>,
+        F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    {
+        decoder(self).context(context)
+    }
+
+    /// Apply `decoder`. Without the `alloc` feature errors have nowhere to keep `context`, so it
+    /// is dropped.
+    #[cfg(not(feature = "alloc"))]
+    pub fn context<F, R>(&mut self, _context: &'static str, decoder: F) -> Result<R>
+    where
+        F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    {
+        decoder(self)
+    }
+
+    /// Return `value` as `Bytes`. If this reader was made by `parse_bytes` and `value` was read
+    /// from its input, the result shares the buffer instead of copying it. Anything else, such as
+    /// the segments of a BER string joined by `octet_string`, is copied or moved.
+    ///
+    /// This needs the `bytes` feature.
+    #[cfg(feature = "bytes")]
+    pub fn to_bytes<V>(&self, value: V) -> Bytes
+    where
+        V: Into<Cow<'a, [u8]>>,
+    {
+        match (value.into(), self.origin.bytes) {
+            (Cow::Borrowed(value), Some(bytes)) if within(value, bytes) => bytes.slice_ref(value),
+            (Cow::Borrowed(value), _) => Bytes::copy_from_slice(value),
+            (Cow::Owned(value), _) => Bytes::from(value),
+        }
+    }
+
+    /// Read an element with the tag `tag` and apply `decoder` to all of its contents, as
+    /// `nested_with_config` does.
+    pub fn nested<F, R>(&mut self, tag: Tag, decoder: F) -> Result<R>
+    where
+        F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    {
+        let origin = self.origin;
+        config::nested_with_config(&mut self.input, tag, self.config, |input, config| {
+            Reader::wrap(input, config, origin, decoder)
+        })
+    }
+
+    /// Read a `SEQUENCE` and apply `decoder` to its contents.
+    pub fn sequence<F, R>(&mut self, decoder: F) -> Result<R>
+    where
+        F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    {
+        self.nested(Tag::SEQUENCE, decoder)
+    }
+
+    /// Read a `SET` and apply `decoder` to its contents. This does not check the order of the
+    /// elements.
+    pub fn set<F, R>(&mut self, decoder: F) -> Result<R>
+    where
+        F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    {
+        self.nested(Tag::SET, decoder)
+    }
+
+    /// Read a `SEQUENCE OF`, applying `decoder` to each element in turn. `decoder` must read
+    /// exactly one element each time it is called.
+    #[cfg(feature = "alloc")]
+    pub fn sequence_of<F, R>(&mut self, mut decoder: F) -> Result<Vec<R>>
+    where
+        F: FnMut(&mut Reader<'a>) -> Result<R>,
+    {
+        self.sequence(|r| {
+            let mut values = Vec::new();
+            while !r.at_end() {
+                let start = r.offset();
+                values.push(decoder(r)?);
+                if r.offset() == start {
+                    // The decoder didn't consume anything, so we would loop forever.
+                    return Err(ErrorKind::UnconsumedContents.into());
+                }
+            }
+            Ok(values)
+        })
+    }
+
+    /// Read a context-specific constructed element `[tag_number]`, as used for `EXPLICIT` tagging,
+    /// and apply `decoder` to its contents.
+    pub fn explicit<F, R>(&mut self, tag_number: u32, decoder: F) -> Result<R>
+    where
+        F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    {
+        self.nested(Tag::new(Class::ContextSpecific, true, tag_number), decoder)
+    }
+
+    /// Read a context-specific primitive element `[tag_number]`, as used for `IMPLICIT` tagging,
+    /// and return its contents.
+    pub fn implicit(&mut self, tag_number: u32) -> Result<&'a [u8]> {
+        self.expect(Tag::new(Class::ContextSpecific, false, tag_number))
+    }
+
+    /// Run `decoder` if the next element has the tag `tag`, or return `None`, as `read_optional`
+    /// does, though `tag` can be in high tag number form. `decoder` is given the whole element,
+    /// including its tag.
+    pub fn optional<F, R>(&mut self, tag: Tag, decoder: F) -> Result<Option<R>>
+    where
+        F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    {
+        let (octets, len) = tag.identifier_octets();
+        if self.at_end() || !self.lookahead().starts_with(&octets[..len]) {
+            return Ok(None);
+        }
+        decoder(self).map(Some)
+    }
+
+    /// Read whatever element comes next, and return its tag and contents.
+    pub fn any(&mut self) -> Result<(Tag, &'a [u8])> {
+        let (tag, value) = config::read_any_with_config(&mut self.input, self.config)?;
+        Ok((tag, value.as_slice_less_safe()))
+    }
+
+    /// Read an element with the tag `tag` and return its contents.
+    pub fn expect(&mut self, tag: Tag) -> Result<&'a [u8]> {
+        let config = self.config;
+        let value = config::expect_tag_and_get_value_with_config(&mut self.input, tag, config)?;
+        Ok(value.as_slice_less_safe())
+    }
+
+    /// Return the tag of the next element without reading it, as `peek_tag` does.
+    pub fn peek_tag(&self) -> Result<Tag> {
+        if self.at_end() {
+            return Err(ErrorKind::UnexpectedEnd.into());
+        }
+        der::tag_at(self.lookahead())
+    }
+
+    // The input from the current position to the end of all of it, for looking further ahead than
+    // the one byte `untrusted::Reader::peek` allows. It runs past the end of the element being
+    // read, so anything read from it must be read again from `input`.
+    fn lookahead(&self) -> &'a [u8] {
+        let start = self.origin.start.as_ptr() as usize;
+        der::position(&self.input)
+            .and_then(|position| {
+                (position.as_slice_less_safe().as_ptr() as usize).checked_sub(start)
+            })
+            .and_then(|offset| self.origin.start.get(offset..))
+            .unwrap_or(&[])
+    }
+
+    /// Skip over the next element.
+    pub fn skip(&mut self) -> Result<()> {
+        self.any().map(|_| ())
+    }
+
+    /// Read the next element and return all of its encoding, for decoding later or writing
+    /// unchanged with `Der::raw`.
+    pub fn raw(&mut self) -> Result<&'a [u8]> {
+        let start = self.input.mark();
+        config::read_any_with_config(&mut self.input, self.config)?;
+        let element = self.input.get_input_between_marks(start, self.input.mark())?;
+        Ok(element.as_slice_less_safe())
+    }
+
+    /// Read a `NULL`.
+    pub fn null(&mut self) -> Result<()> {
+        match self.expect(Tag::NULL)? {
+            [] => Ok(()),
+            _ => Err(ErrorKind::WrongValue.into()),
+        }
+    }
+
+    /// Read a `BOOLEAN`, as `boolean_with_mode` does.
+    pub fn boolean(&mut self) -> Result<bool> {
+        let mode = self.config.mode();
+        self.leaf(Tag::BOOLEAN, |value| der::decode_boolean(value, mode))
+    }
+
+    /// Read a positive `INTEGER` and return its big-endian contents without any leading zero.
+    pub fn positive_integer(&mut self) -> Result<&'a [u8]> {
+        let value = self.leaf(Tag::INTEGER, |value| der::decode_nonnegative_integer(value, 1))?;
+        Ok(value.as_slice_less_safe())
+    }
+
+    /// Read a non-negative `INTEGER` and return its big-endian contents without any leading zero.
+    pub fn nonnegative_integer(&mut self) -> Result<&'a [u8]> {
+        let value = self.leaf(Tag::INTEGER, |value| der::decode_nonnegative_integer(value, 0))?;
+        Ok(value.as_slice_less_safe())
+    }
+
+    /// Read an `INTEGER` as a `u8`.
+    pub fn u8(&mut self) -> Result<u8> {
+        self.leaf(Tag::INTEGER, |value| der::decode_unsigned(value, 1)).map(|value| value as u8)
+    }
+
+    /// Read an `INTEGER` as a `u16`.
+    pub fn u16(&mut self) -> Result<u16> {
+        self.leaf(Tag::INTEGER, |value| der::decode_unsigned(value, 2)).map(|value| value as u16)
+    }
+
+    /// Read an `INTEGER` as a `u32`.
+    pub fn u32(&mut self) -> Result<u32> {
+        self.leaf(Tag::INTEGER, |value| der::decode_unsigned(value, 4)).map(|value| value as u32)
+    }
+
+    /// Read an `INTEGER` as a `u64`.
+    pub fn u64(&mut self) -> Result<u64> {
+        self.leaf(Tag::INTEGER, |value| der::decode_unsigned(value, 8)).map(|value| value as u64)
+    }
+
+    /// Read an `INTEGER` as a `u128`.
+    pub fn u128(&mut self) -> Result<u128> {
+        self.leaf(Tag::INTEGER, |value| der::decode_unsigned(value, 16))
+    }
+
+    /// Read an `INTEGER` as an `i64`.
+    pub fn i64(&mut self) -> Result<i64> {
+        self.leaf(Tag::INTEGER, |value| der::decode_i64(value.as_slice_less_safe()))
+    }
+
+    /// Read an `INTEGER` as an `i128`.
+    pub fn i128(&mut self) -> Result<i128> {
+        self.leaf(Tag::INTEGER, |value| der::decode_signed(value.as_slice_less_safe(), 16))
+    }
+
+    /// Read an `ENUMERATED` as an `i64`.
+    pub fn enumerated(&mut self) -> Result<i64> {
+        self.leaf(Tag::ENUMERATED, |value| der::decode_i64(value.as_slice_less_safe()))
+    }
+
+    /// Read a `REAL`, as `real_with_mode` does.
+    #[cfg(feature = "alloc")]
+    pub fn real(&mut self) -> Result<f64> {
+        let mode = self.config.mode();
+        self.leaf(Tag::REAL, |value| real::decode_real(value.as_slice_less_safe(), mode))
+    }
+
+    /// Read a `BIT STRING` and return the number of unused bits along with its contents, as
+    /// `bit_string_with_mode` does.
+    #[cfg(feature = "alloc")]
+    pub fn bit_string(&mut self) -> Result<(u8, Cow<'a, [u8]>)> {
+        let mode = self.config.mode();
+        self.string(|input| ber::bit_string_with_mode(input, mode))
+    }
+
+    /// Read an `OCTET STRING` and return its contents, as `octet_string_with_mode` does.
+    #[cfg(feature = "alloc")]
+    pub fn octet_string(&mut self) -> Result<Cow<'a, [u8]>> {
+        let mode = self.config.mode();
+        self.string(|input| ber::octet_string_with_mode(input, mode))
+    }
+
+    // Read a string that BER allows to be split into segments with indefinite lengths, which
+    // `leaf` would reject, so their lengths are only checked against the config in DER.
+    #[cfg(feature = "alloc")]
+    fn string<F, R>(&mut self, read: F) -> Result<R>
+    where
+        F: FnOnce(&mut untrusted::Reader<'a>) -> Result<R>,
+    {
+        match self.config.mode() {
+            Mode::Strict => {
+                let start = self.input.mark();
+                config::read_any_with_config(&mut self.input, self.config)?;
+                let element = self.input.get_input_between_marks(start, self.input.mark())?;
+                element
+                    .read_all(Error::from(ErrorKind::UnconsumedContents), read)
+                    .map_err(|err| err.at(Some(element)))
+            }
+            Mode::Lenient => read(&mut self.input),
+        }
+    }
+
+    /// Read a `BIT STRING` holding a named bit list, as `bit_string_flags` does.
+    #[cfg(feature = "alloc")]
+    pub fn bit_string_flags(&mut self) -> Result<BitFlags> {
+        self.leaf(Tag::BIT_STRING, der::decode_bit_string_flags)
+    }
+
+    /// Read an `OBJECT IDENTIFIER`.
+    #[cfg(feature = "alloc")]
+    pub fn oid(&mut self) -> Result<Oid> {
+        self.leaf(Tag::OID, |value| Oid::from_bytes(value.as_slice_less_safe()))
+    }
+
+    /// Read a `UTF8String`.
+    pub fn utf8_string(&mut self) -> Result<&'a str> {
+        self.leaf(Tag::UTF8_STRING, der::decode_utf8_string)
+    }
+
+    /// Read a `PrintableString`.
+    pub fn printable_string(&mut self) -> Result<&'a str> {
+        self.restricted_string(Tag::PRINTABLE_STRING, der::is_printable_string_char)
+    }
+
+    /// Read an `IA5String`.
+    pub fn ia5_string(&mut self) -> Result<&'a str> {
+        self.restricted_string(Tag::IA5_STRING, der::is_ia5_string_char)
+    }
+
+    /// Read a `VisibleString`.
+    pub fn visible_string(&mut self) -> Result<&'a str> {
+        self.restricted_string(Tag::VISIBLE_STRING, der::is_visible_string_char)
+    }
+
+    // Read a string of the type `tag`, whose characters must be `allowed` if the config checks
+    // them.
+    fn restricted_string(&mut self, tag: Tag, allowed: fn(u8) -> bool) -> Result<&'a str> {
+        match self.config.check_strings() {
+            true => self.leaf(tag, |value| der::decode_restricted_string(value, allowed)),
+            false => self.leaf(tag, der::decode_utf8_string),
+        }
+    }
+
+    /// Read a `UTCTime`, with the two digit year interpreted as RFC 5280 requires.
+    pub fn utc_time(&mut self) -> Result<DerDateTime> {
+        self.leaf(Tag::UTC_TIME, |value| {
+            datetime::decode_utc_time(value.as_slice_less_safe(), UtcTimePivot::RFC_5280)
+        })
+    }
+
+    /// Read a `GeneralizedTime`, as `generalized_time_with_mode` does.
+    pub fn generalized_time(&mut self) -> Result<DerDateTime> {
+        let mode = self.config.mode();
+        self.leaf(Tag::GENERALIZED_TIME, |value| {
+            datetime::decode_generalized_time(value.as_slice_less_safe(), mode)
+        })
+    }
+}
+
+// Whether `value` is part of `bytes`, which is what `Bytes::slice_ref` requires.
+#[cfg(feature = "bytes")]
+fn within(value: &[u8], bytes: &Bytes) -> bool {
+    let start = bytes.as_ptr() as usize;
+    let value_start = value.as_ptr() as usize;
+    value_start >= start && value_start + value.len() <= start + bytes.len()
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use super::*;
+
+    fn read<'a, F, R>(encoded: &'a [u8], decoder: F) -> Result<R>
+    where
+        F: FnOnce(&mut Reader<'a>) -> Result<R>,
+    {
+        Reader::read_all(Input::from(encoded), ParserConfig::DER, decoder)
+    }
+
+    #[test]
+    fn parse_slice() {
+        let encoded = vec![0x30, 0x03, 0x01, 0x01, 0xff];
+        assert_eq!(parse(&encoded, |r| r.sequence(|r| r.boolean())), Ok(true));
+        assert_eq!(parse(&encoded[..2], |r| r.skip()), Err(ErrorKind::UnexpectedEnd.into()));
+        assert_eq!(parse(&[][..], |r| r.skip()), Err(ErrorKind::EmptyInput.into()));
+
+        let encoded = [0x01, 0x01, 0x01];
+        assert_eq!(parse(&encoded, |r| r.boolean()), Err(ErrorKind::BadBooleanValue.into()));
+        assert_eq!(parse_with_config(&encoded, ParserConfig::BER, |r| r.boolean()), Ok(true));
+    }
+
+    #[test]
+    fn reader_values() {
+        assert_eq!(read(&[0x05, 0x00], |r| r.null()), Ok(()));
+        assert_eq!(read(&[0x01, 0x01, 0xff], |r| r.boolean()), Ok(true));
+        assert_eq!(read(&[0x02, 0x02, 0x00, 0x80], |r| r.u16()), Ok(0x80));
+        assert_eq!(read(&[0x02, 0x01, 0xff], |r| r.i64()), Ok(-1));
+        assert_eq!(read(&[0x02, 0x01, 0x80], |r| r.i128()), Ok(-128));
+        assert_eq!(read(&[0x0a, 0x01, 0x03], |r| r.enumerated()), Ok(3));
+        assert_eq!(read(&[0x02, 0x01, 0x00], |r| r.nonnegative_integer()), Ok(&[0x00][..]));
+        assert_eq!(read(&[0x09, 0x00], |r| r.real()), Ok(0.0));
+        assert_eq!(read(b"\x0c\x02hi", |r| r.utf8_string()), Ok("hi"));
+        assert_eq!(read(b"\x13\x02hi", |r| r.printable_string()), Ok("hi"));
+        assert_eq!(read(b"\x16\x02hi", |r| r.ia5_string()), Ok("hi"));
+        assert_eq!(read(b"\x1a\x02hi", |r| r.visible_string()), Ok("hi"));
+        assert_eq!(read(&[0x04, 0x02, 0x61, 0x62], |r| r.octet_string()), Ok(Cow::from(&b"ab"[..])));
+        assert_eq!(read(&[0x03, 0x02, 0x04, 0x60], |r| r.bit_string()), Ok((4, Cow::from(&[0x60][..]))));
+        assert_eq!(read(&[0x06, 0x03, 0x55, 0x04, 0x03], |r| r.oid()), Ok(Oid::new(oid!("2.5.4.3"))));
+        let time = DerDateTime::new(2017, 9, 5, 12, 0, 0).unwrap();
+        assert_eq!(read(b"\x17\x0d170905120000Z", |r| r.utc_time()), Ok(time));
+        assert_eq!(read(b"\x18\x0f20170905120000Z", |r| r.generalized_time()), Ok(time));
+
+        assert_eq!(read(&[0x01, 0x01, 0x01], |r| r.boolean()), Err(ErrorKind::BadBooleanValue.into()));
+        assert_eq!(read(&[0x05, 0x01, 0x00], |r| r.null()), Err(ErrorKind::WrongValue.into()));
+        assert_eq!(read(&[0x05, 0x00, 0x05, 0x00], |r| r.null()), Err(ErrorKind::TrailingData.into()));
+    }
+
+    #[test]
+    fn reader_structure() {
+        // SEQUENCE { [0] { NULL }, [1] "ab", SEQUENCE OF INTEGER { 1, 2 } }
+        let encoded = &[
+            0x30, 0x10, 0xa0, 0x02, 0x05, 0x00, 0x81, 0x02, 0x61, 0x62, 0x30, 0x06, 0x02, 0x01, 0x01,
+            0x02, 0x01, 0x02, 0x05, 0x00,
+        ];
+        let result = read(encoded, |r| {
+            let values = r.sequence(|r| {
+                assert_eq!(r.offset(), 2);
+                r.explicit(0, |r| r.null())?;
+                assert_eq!(r.optional(Tag::BOOLEAN, |r| r.boolean())?, None);
+                assert_eq!(r.peek_tag()?, Tag::new(Class::ContextSpecific, false, 1));
+                assert_eq!(r.implicit(1)?, b"ab");
+                r.sequence_of(|r| r.u8())
+            })?;
+            assert_eq!(r.any()?, (Tag::NULL, &[][..]));
+            assert!(r.at_end());
+            Ok(values)
+        });
+        assert_eq!(result, Ok(vec![1, 2]));
+
+        let result = read(&[0x30, 0x02, 0x05, 0x00], |r| r.sequence_of(|_| Ok(())));
+        assert_eq!(result, Err(ErrorKind::UnconsumedContents.into()));
+        let result = read(&[0x30, 0x02, 0x05, 0x00], |r| r.sequence(|_| Ok(())));
+        assert_eq!(result, Err(ErrorKind::UnconsumedContents.into()));
+
+        // The underlying reader continues from the same place.
+        let result = read(&[0x30, 0x03, 0x01, 0x01, 0xff], |r| {
+            r.sequence(|r| der::boolean(r.untrusted()))
+        });
+        assert_eq!(result, Ok(true));
+
+        // Tags in high tag number form are peeked and compared in full.
+        let encoded = &[0x30, 0x05, 0xbf, 0x1f, 0x02, 0x05, 0x00, 0x05, 0x00];
+        let result = read(encoded, |r| {
+            let tag = |number| Tag::new(Class::ContextSpecific, true, number);
+            r.sequence(|r| {
+                assert_eq!(r.peek_tag()?, tag(31));
+                assert_eq!(r.optional(tag(32), |r| r.explicit(32, |r| r.null()))?, None);
+                r.optional(tag(31), |r| r.explicit(31, |r| r.null()))?;
+                assert_eq!(r.peek_tag(), Err(ErrorKind::UnexpectedEnd.into()));
+                Ok(())
+            })?;
+            assert_eq!(r.peek_tag()?, Tag::NULL);
+            r.null()
+        });
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn reader_config() {
+        let encoded = &[0x30, 0x81, 0x03, 0x01, 0x01, 0x01];
+        let read = |config| {
+            Reader::read_all(Input::from(encoded), config, |r| {
+                assert_eq!(r.config(), config);
+                r.sequence(|r| {
+                    assert_eq!(r.config().max_depth(), config.max_depth() - 1);
+                    r.boolean()
+                })
+            })
+        };
+        assert_eq!(read(ParserConfig::BER), Ok(true));
+        assert_eq!(read(ParserConfig::DER), Err(ErrorKind::NonCanonical.into()));
+        let config = ParserConfig::BER.with_max_len(2);
+        assert_eq!(read(config), Err(ErrorKind::LengthExceedsLimit.into()));
+
+        let segments = &[0x24, 0x80, 0x04, 0x01, 0x61, 0x04, 0x01, 0x62, 0x00, 0x00];
+        let result = Reader::read_all(Input::from(segments), ParserConfig::BER, |r| r.octet_string());
+        assert_eq!(result, Ok(Cow::from(b"ab".to_vec())));
+
+        // Elements with long form lengths that only BER allows.
+        let encoded = &[
+            0x02, 0x81, 0x01, 0x2a, 0x0c, 0x81, 0x02, 0x68, 0x69, 0x06, 0x82, 0x00, 0x03, 0x2a,
+            0x86, 0x48,
+        ];
+        let read = |config| {
+            Reader::read_all(Input::from(encoded), config, |r| {
+                Ok((r.u8()?, r.utf8_string()?, r.oid()?))
+            })
+        };
+        let oid = Oid::from_bytes(&[0x2a, 0x86, 0x48]).unwrap();
+        assert_eq!(read(ParserConfig::BER), Ok((42, "hi", oid)));
+        assert_eq!(read(ParserConfig::DER), Err(ErrorKind::NonCanonical.into()));
+
+        // Strings with characters their type does not allow, which need only be UTF-8 if allowed.
+        let encoded = b"\x13\x03a_b\x16\x02\xc3\xa9\x1a\x01\x7f";
+        let read = |config| {
+            Reader::read_all(Input::from(encoded), config, |r| {
+                Ok((r.printable_string()?, r.ia5_string()?, r.visible_string()?))
+            })
+        };
+        assert_eq!(read(ParserConfig::DER), Err(ErrorKind::BadStringValue.into()));
+        let unchecked = ParserConfig::DER.with_check_strings(false);
+        assert_eq!(read(unchecked), Ok(("a_b", "\u{e9}", "\x7f")));
+        let result = parse_with_config(b"\x13\x01\xff", unchecked, |r| r.printable_string());
+        assert_eq!(result, Err(ErrorKind::BadStringValue.into()));
+    }
+
+    #[test]
+    fn reader_errors() {
+        let encoded = &[0x30, 0x06, 0x02, 0x01, 0x01, 0x01, 0x01, 0x07];
+        let err = read(encoded, |r| {
+            r.sequence(|r| {
+                r.u8()?;
+                r.context("critical", |r| r.boolean())
+            })
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadBooleanValue);
+        assert_eq!(err.offset(encoded), Some(5));
+        assert_eq!(err.path(), &[Tag::SEQUENCE]);
+        assert_eq!(err.context().collect::<Vec<_>>(), vec!["critical"]);
+
+        // With the octets around the offset, cut short on both sides.
+        let mut encoded = vec![0x30, 0x1b];
+        for _ in 0..12 {
+            encoded.extend(&[0x05, 0x00]);
+        }
+        encoded.extend(&[0x01, 0x01, 0x07]);
+        let config = ParserConfig::DER.with_hex_context(true);
+        let read = |encoded: &[u8]| {
+            parse_with_config(encoded, config, |r| {
+                r.sequence(|r| {
+                    for _ in 0..12 {
+                        r.null()?;
+                    }
+                    r.boolean()
+                })
+            })
+            .unwrap_err()
+            .to_string()
+        };
+        let message = "BOOLEAN value is neither 0x00 nor 0xff in SEQUENCE \
+                       at offset 26: ... 05 00 05 00 05 00 05 00 [01] 01 07";
+        assert_eq!(read(&encoded), message);
+        encoded[1] = 0x1a;
+        encoded.pop();
+        let message = "unexpected end of input in SEQUENCE \
+                       at offset 26: ... 05 00 05 00 05 00 05 00 [01] 01";
+        assert_eq!(read(&encoded), message);
+        assert_eq!(read(&[]), "input is empty where an element was expected at offset 0: [end]");
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn reader_bytes() {
+        let shared = |value: &Bytes, encoded: &Bytes| within(value, encoded);
+
+        let encoded = Bytes::from(vec![0x30, 0x06, 0x02, 0x01, 0x05, 0x04, 0x01, 0x61]);
+        let (n, s) = parse_bytes(&encoded, |r| {
+            r.sequence(|r| {
+                let n = r.positive_integer()?;
+                let s = r.octet_string()?;
+                Ok((r.to_bytes(n), r.to_bytes(s)))
+            })
+        })
+        .unwrap();
+        assert_eq!((&n[..], &s[..]), (&[0x05][..], &b"a"[..]));
+        assert!(shared(&n, &encoded) && shared(&s, &encoded));
+
+        // Values from elsewhere, and from readers over slices, are copied.
+        let other = [0x05];
+        let copied = parse_bytes(&encoded, |r| {
+            r.skip()?;
+            Ok(r.to_bytes(&other[..]))
+        })
+        .unwrap();
+        assert_eq!(&copied[..], &other);
+        assert!(!shared(&copied, &encoded));
+        let copied = parse(&encoded, |r| {
+            let n = r.sequence(|r| {
+                let n = r.positive_integer()?;
+                r.skip().map(|_| n)
+            })?;
+            Ok(r.to_bytes(n))
+        });
+        assert!(!shared(&copied.unwrap(), &encoded));
+
+        // Joined BER segments are moved.
+        let encoded = Bytes::from(vec![0x24, 0x80, 0x04, 0x01, 0x61, 0x04, 0x01, 0x62, 0x00, 0x00]);
+        let joined = parse_bytes_with_config(&encoded, ParserConfig::BER, |r| {
+            let value = r.octet_string()?;
+            Ok(r.to_bytes(value))
+        });
+        assert_eq!(joined, Ok(Bytes::from(&b"ab"[..])));
+    }
+}
@@ -0,0 +1,360 @@
+//! `REAL` values.
+
+use alloc::vec::Vec;
+use untrusted::Reader;
+
+use {Der, Error, ErrorKind, Result, Write};
+use der::{self, Mode, Tag};
+
+/// Read a `REAL` as an `f64`.
+///
+/// This only accepts the canonical DER form of binary encodings: base 2, no scaling factor, an odd
+/// mantissa, and a minimally encoded exponent. Zero must have no contents, and the special values
+/// `PLUS-INFINITY`, `MINUS-INFINITY`, `NOT-A-NUMBER` and minus zero are supported. Use
+/// `real_with_mode` with `Mode::Lenient` to accept other bases, scaling factors, and the decimal
+/// encodings.
+///
+/// Mantissas that do not fit in a `u64` are rejected with `ErrorKind::IntegerOverflow`.
+///
+/// ```
+/// extern crate derp;
+/// extern crate untrusted;
+///
+/// use untrusted::Input;
+///
+/// // 0.75 = 3 * 2^-2
+/// const REAL: &'static [u8] = &[0x09, 0x03, 0x80, 0xfe, 0x03];
+///
+/// fn main() {
+///     let input = Input::from(REAL);
+///     let value = input.read_all(derp::ErrorKind::TrailingData.into(), derp::real).unwrap();
+///     assert_eq!(value, 0.75);
+/// }
+/// ```
+#[inline]
+pub fn real(input: &mut Reader) -> Result<f64> {
+    real_with_mode(input, Mode::Strict)
+}
+
+/// Read a `REAL` as an `f64`. In `Mode::Lenient` the binary encodings in bases 8 and 16 and with
+/// a scaling factor are accepted, as are the decimal encodings.
+pub fn real_with_mode(input: &mut Reader, mode: Mode) -> Result<f64> {
+    let value = der::expect_tag_and_get_value_with_mode(input, Tag::REAL, mode)?;
+    decode_real(value.as_slice_less_safe(), mode)
+}
+
+// Decode the contents of a `REAL`.
+pub(crate) fn decode_real(value: &[u8], mode: Mode) -> Result<f64> {
+    let (&first, rest) = match value.split_first() {
+        Some(split) => split,
+        None => return Ok(0.0),
+    };
+
+    match first >> 6 {
+        0b10 | 0b11 => binary(first, rest, mode),
+        0b01 => match (first, rest.is_empty()) {
+            (0x40, true) => Ok(f64::INFINITY),
+            (0x41, true) => Ok(f64::NEG_INFINITY),
+            (0x42, true) => Ok(f64::NAN),
+            (0x43, true) => Ok(-0.0),
+            _ => Err(ErrorKind::WrongValue.into()),
+        },
+        _ if mode == Mode::Lenient => decimal(first, rest),
+        _ => Err(ErrorKind::NonCanonical.into()),
+    }
+}
+
+impl<'a, W: Write> Der<'a, W> {
+    /// Write an `f64` as a `REAL` using the canonical DER encoding.
+    ///
+    /// Finite values are written in base 2 with an odd mantissa and a minimal exponent, zero is
+    /// written with no contents, and infinities, NaN and minus zero use the special values.
+    ///
+    /// ```
+    /// extern crate derp;
+    ///
+    /// use derp::Der;
+    ///
+    /// fn main() {
+    ///     let mut buf = Vec::new();
+    ///     {
+    ///         let mut der = Der::new(&mut buf);
+    ///         der.real(0.75).unwrap();
+    ///     }
+    ///
+    ///     assert_eq!(&buf, &[0x09, 0x03, 0x80, 0xfe, 0x03]);
+    /// }
+    /// ```
+    pub fn real(&mut self, value: f64) -> Result<()> {
+        let (contents, len) = real_contents(value);
+        self.element(Tag::REAL, &contents[..len])
+    }
+}
+
+// The contents of the DER encoding of `value`, in the first octets of the array.
+pub(crate) fn real_contents(value: f64) -> ([u8; 10], usize) {
+    let mut contents = [0; 10];
+    if value == 0.0 && value.is_sign_positive() {
+        return (contents, 0);
+    } else if value == 0.0 {
+        contents[0] = 0x43;
+        return (contents, 1);
+    } else if value.is_nan() {
+        contents[0] = 0x42;
+        return (contents, 1);
+    } else if value.is_infinite() {
+        contents[0] = if value > 0.0 { 0x40 } else { 0x41 };
+        return (contents, 1);
+    }
+
+    let bits = value.to_bits();
+    let biased_exponent = ((bits >> 52) & 0x7ff) as i16;
+    let fraction = bits & ((1 << 52) - 1);
+    let (mut mantissa, mut exponent) = match biased_exponent {
+        0 => (fraction, -1074),
+        _ => (fraction | (1 << 52), biased_exponent - 1075),
+    };
+    // Normalize so the mantissa is odd.
+    let shift = mantissa.trailing_zeros();
+    mantissa >>= shift;
+    exponent += shift as i16;
+
+    let sign = if value < 0.0 { 0x40 } else { 0x00 };
+    let mut len = if exponent == i16::from(exponent as i8) {
+        contents[..2].copy_from_slice(&[0x80 | sign, exponent as u8]);
+        2
+    } else {
+        contents[0] = 0x81 | sign;
+        contents[1..3].copy_from_slice(&exponent.to_be_bytes());
+        3
+    };
+    let mantissa = mantissa.to_be_bytes();
+    let start = mantissa.iter().position(|b| *b != 0).unwrap_or(7);
+    contents[len..len + 8 - start].copy_from_slice(&mantissa[start..]);
+    len += 8 - start;
+    (contents, len)
+}
+
+// Decode the binary encoding whose first contents octet is `first`.
+fn binary(first: u8, rest: &[u8], mode: Mode) -> Result<f64> {
+    let negative = first & 0x40 != 0;
+    let log2_base = match (first >> 4) & 0x03 {
+        0 => 1,
+        1 => 3,
+        2 => 4,
+        _ => return Err(ErrorKind::WrongValue.into()),
+    };
+    let scale = i64::from((first >> 2) & 0x03);
+
+    let (exponent, mantissa) = match first & 0x03 {
+        3 => {
+            let (&len, rest) = rest.split_first().ok_or(Error::from(ErrorKind::UnexpectedEnd))?;
+            if mode == Mode::Strict && len <= 3 {
+                return Err(ErrorKind::NonCanonical.into());
+            }
+            split_at(rest, len as usize)?
+        }
+        n => split_at(rest, n as usize + 1)?,
+    };
+    if mantissa.is_empty() {
+        return Err(ErrorKind::UnexpectedEnd.into());
+    }
+
+    if mode == Mode::Strict {
+        // DER requires base 2, no scaling, and the mantissa to be odd so there is only one encoding
+        // of each value.
+        let odd = mantissa[mantissa.len() - 1] & 1 == 1;
+        if log2_base != 1 || scale != 0 || mantissa[0] == 0 || !odd {
+            return Err(ErrorKind::NonCanonical.into());
+        }
+        der::check_minimal_signed(exponent)?;
+    }
+
+    let mantissa = mantissa.iter().skip_while(|b| **b == 0).cloned().collect::<Vec<_>>();
+    if mantissa.len() > 8 {
+        return Err(ErrorKind::IntegerOverflow.into());
+    }
+    let mantissa = mantissa.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+    // Any exponent this large overflows or underflows, so it is saturated.
+    let exponent = exponent_value(exponent).clamp(-(1 << 32), 1 << 32);
+
+    let value = scale_by_power_of_two(mantissa as f64, exponent * log2_base + scale);
+    Ok(if negative { -value } else { value })
+}
+
+// Decode a two's complement exponent, saturating values that do not fit in an `i64`.
+fn exponent_value(exponent: &[u8]) -> i64 {
+    let negative = exponent[0] & 0x80 != 0;
+    let sign = if negative { 0xff } else { 0x00 };
+    if exponent.iter().skip_while(|b| **b == sign).count() >= 8 {
+        return if negative { i64::MIN } else { i64::MAX };
+    }
+    let sign_extension = if negative { -1 } else { 0 };
+    exponent.iter().fold(sign_extension, |acc, b| (acc << 8) | i64::from(*b))
+}
+
+// Split `value` after `len` octets, erroring if it is too short.
+fn split_at(value: &[u8], len: usize) -> Result<(&[u8], &[u8])> {
+    if len == 0 || value.len() < len {
+        return Err(ErrorKind::UnexpectedEnd.into());
+    }
+    Ok(value.split_at(len))
+}
+
+// Multiply `value` by `2^exponent`, avoiding overflow or underflow in intermediate steps.
+fn scale_by_power_of_two(mut value: f64, mut exponent: i64) -> f64 {
+    if value == 0.0 {
+        return value;
+    }
+    // Past these bounds every non-zero `u64` mantissa overflows or underflows.
+    exponent = exponent.clamp(-1200, 1100);
+    while exponent > 0 {
+        let step = exponent.min(1000);
+        value *= power_of_two(step);
+        exponent -= step;
+    }
+    while exponent < 0 {
+        let step = exponent.max(-1000);
+        value *= power_of_two(step);
+        exponent -= step;
+    }
+    value
+}
+
+// `2^exponent` for an exponent in the range of normal `f64`s, built from its bits since `powi` is
+// not available without `std`.
+fn power_of_two(exponent: i64) -> f64 {
+    f64::from_bits(((1023 + exponent) as u64) << 52)
+}
+
+// Decode the decimal encodings NR1, NR2, and NR3 from ISO 6093.
+fn decimal(first: u8, rest: &[u8]) -> Result<f64> {
+    if !(1..=3).contains(&first) {
+        return Err(ErrorKind::WrongValue.into());
+    }
+    let value = ::std::str::from_utf8(rest).map_err(|_| Error::from(ErrorKind::WrongValue))?;
+    let value = value.trim_start_matches(' ').replace(',', ".");
+    if !value.bytes().all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b)) {
+        return Err(ErrorKind::WrongValue.into());
+    }
+    value.parse().map_err(|_| Error::from(ErrorKind::WrongValue))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use untrusted::Input;
+
+    fn read(value: &[u8], mode: Mode) -> Result<f64> {
+        let mut input = vec![0x09, value.len() as u8];
+        input.extend_from_slice(value);
+        Input::from(&input).read_all(ErrorKind::TrailingData.into(), |input| real_with_mode(input, mode))
+    }
+
+    #[test]
+    fn read_real() {
+        for &(value, expected) in &[
+            (&[][..], 0.0),
+            (&[0x43], -0.0),
+            (&[0x80, 0x00, 0x01], 1.0),
+            (&[0xc0, 0x00, 0x01], -1.0),
+            (&[0x80, 0xfe, 0x03], 0.75),
+            (&[0x80, 0x04, 0x05], 80.0),
+            // 2^-1074, the smallest subnormal
+            (&[0x81, 0xfb, 0xce, 0x01], 5e-324),
+            (&[0x81, 0x03, 0xcb, 0x1f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff], f64::MAX),
+            (&[0x83, 0x04, 0x01, 0x00, 0x00, 0x00, 0x01], f64::INFINITY),
+            (&[0x81, 0xf0, 0x00, 0x01], 0.0),
+            (&[0x40], f64::INFINITY),
+            (&[0x41], f64::NEG_INFINITY),
+        ] {
+            let parsed = read(value, Mode::Strict).unwrap();
+            assert_eq!(parsed, expected);
+            assert_eq!(parsed.is_sign_negative(), expected.is_sign_negative());
+        }
+        assert!(read(&[0x42], Mode::Strict).unwrap().is_nan());
+
+        for &(value, err) in &[
+            // Even mantissa
+            (&[0x80, 0x00, 0x02][..], ErrorKind::NonCanonical),
+            // Leading zero in the mantissa
+            (&[0x80, 0x00, 0x00, 0x01], ErrorKind::NonCanonical),
+            // Zero mantissa
+            (&[0x80, 0x00, 0x00], ErrorKind::NonCanonical),
+            // Base 8 and base 16
+            (&[0x90, 0x00, 0x01], ErrorKind::NonCanonical),
+            (&[0xa0, 0x00, 0x01], ErrorKind::NonCanonical),
+            // Scaling factor
+            (&[0x84, 0x00, 0x01], ErrorKind::NonCanonical),
+            // Exponent that is not minimal
+            (&[0x81, 0x00, 0x01, 0x01], ErrorKind::NonCanonical),
+            (&[0x83, 0x01, 0x01, 0x01], ErrorKind::NonCanonical),
+            // Decimal
+            (&[0x03, b'1', b'.', b'E', b'1'], ErrorKind::NonCanonical),
+            // Reserved base and special values
+            (&[0xb0, 0x00, 0x01], ErrorKind::WrongValue),
+            (&[0x44], ErrorKind::WrongValue),
+            (&[0x40, 0x00], ErrorKind::WrongValue),
+            // Missing exponent or mantissa
+            (&[0x81, 0x00], ErrorKind::UnexpectedEnd),
+            (&[0x80, 0x00], ErrorKind::UnexpectedEnd),
+            (
+                &[0x80, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09],
+                ErrorKind::IntegerOverflow,
+            ),
+        ] {
+            assert_eq!(read(value, Mode::Strict), Err(err.into()));
+        }
+    }
+
+    #[test]
+    fn read_real_lenient() {
+        for &(value, expected) in &[
+            (&[0x80, 0x00, 0x02][..], 2.0),
+            (&[0x80, 0x00, 0x00], 0.0),
+            (&[0x81, 0x00, 0x01, 0x01], 2.0),
+            // 1 * 8^1 * 2^1
+            (&[0x94, 0x01, 0x01], 16.0),
+            // -1 * 16^-1
+            (&[0xe0, 0xff, 0x01], -0.0625),
+            // Exponents beyond the range of an i64
+            (&[0x83, 0x0a, 0x01, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01], f64::INFINITY),
+            (&[0x83, 0x0a, 0xfe, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01], 0.0),
+            (&[0x01, b' ', b'4', b'2'], 42.0),
+            (&[0x02, b'-', b'1', b',', b'5'], -1.5),
+            (&[0x03, b'1', b'5', b'.', b'E', b'-', b'1'], 1.5),
+        ] {
+            assert_eq!(read(value, Mode::Lenient), Ok(expected));
+        }
+
+        for &value in &[&[0x04, b'1'][..], &[0x03, b'x'], &[0x03, b'i', b'n', b'f']] {
+            assert_eq!(read(value, Mode::Lenient), Err(ErrorKind::WrongValue.into()));
+        }
+    }
+
+    #[test]
+    fn write_real() {
+        for &(value, expected) in &[
+            (0.0, &[0x09, 0x00][..]),
+            (-0.0, &[0x09, 0x01, 0x43]),
+            (f64::INFINITY, &[0x09, 0x01, 0x40]),
+            (f64::NEG_INFINITY, &[0x09, 0x01, 0x41]),
+            (f64::NAN, &[0x09, 0x01, 0x42]),
+            (1.0, &[0x09, 0x03, 0x80, 0x00, 0x01]),
+            (-1.0, &[0x09, 0x03, 0xc0, 0x00, 0x01]),
+            (80.0, &[0x09, 0x03, 0x80, 0x04, 0x05]),
+            (5e-324, &[0x09, 0x04, 0x81, 0xfb, 0xce, 0x01]),
+            (f64::MAX, &[0x09, 0x0a, 0x81, 0x03, 0xcb, 0x1f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]),
+        ] {
+            let mut buf = Vec::new();
+            Der::new(&mut buf).real(value).unwrap();
+            assert_eq!(&buf, expected);
+        }
+
+        for &value in &[0.1, -2.5e-310, 1e300, ::std::f64::consts::PI, f64::MIN_POSITIVE] {
+            let mut buf = Vec::new();
+            Der::new(&mut buf).real(value).unwrap();
+            assert_eq!(Input::from(&buf).read_all(ErrorKind::TrailingData.into(), real), Ok(value));
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! The `der_schema!` macro, for declaring a `SEQUENCE` and getting its reader and writer.
+
+/// Declare a struct for a `SEQUENCE` and implement `FromDer`, `ToDer` and `Tagged` for it, as
+/// `#[derive(DerSequence)]` does, without the `derive` feature.
+///
+/// Each field is read and written with the traits for its type, in order. A field can be followed
+/// by a specification in brackets, which is a tagging, a presence, or a tagging and a presence
+/// separated by a comma:
+///
+/// - `explicit n`: the field is wrapped in the context-specific tag `[n]`.
+/// - `implicit n`: the tag of the field is replaced by `[n]`, see `from_der_implicit`.
+/// - `optional`: the field is `OPTIONAL`, and its type is an `Option` of the type that is given.
+/// - `default expr`: the field is left out when it equals `expr`, and is `expr` when it is
+///   missing. DER does not allow the default to be encoded, so reading it fails with
+///   `ErrorKind::NonCanonical` unless the reader is in `Mode::Lenient`.
+///
+/// The struct can have one lifetime, which is used as the lifetime of the input.
+///
+#[cfg_attr(feature = "alloc", doc = "```")]
+#[cfg_attr(not(feature = "alloc"), doc = "```ignore")]
+/// #[macro_use]
+/// extern crate derp;
+///
+/// use derp::{FromDer, Oid, ToDer};
+///
+/// der_schema! {
+///     // Extension ::= SEQUENCE {
+///     //     extnID    OBJECT IDENTIFIER,
+///     //     critical  BOOLEAN DEFAULT FALSE,
+///     //     extnValue OCTET STRING }
+///     #[derive(Debug, PartialEq)]
+///     pub struct Extension<'a> {
+///         pub id: Oid,
+///         pub critical: bool [default false],
+///         pub value: &'a [u8],
+///     }
+/// }
+///
+/// der_schema! {
+///     // Name ::= SEQUENCE {
+///     //     full     [0] IMPLICIT UTF8String,
+///     //     nickname [1] EXPLICIT UTF8String OPTIONAL }
+///     #[derive(Debug, PartialEq)]
+///     struct Name {
+///         full: String [implicit 0],
+///         nickname: String [explicit 1, optional],
+///     }
+/// }
+///
+/// fn main() {
+///     let encoded = [0x30, 0x09, 0x06, 0x03, 0x55, 0x1d, 0x13, 0x04, 0x02, 0x30, 0x00];
+///     let extension = Extension::decode(&encoded).unwrap();
+///     assert!(!extension.critical);
+///     assert_eq!(extension.value, &[0x30, 0x00]);
+///     assert_eq!(extension.encode().unwrap(), encoded);
+///
+///     let name = Name { full: "a".to_owned(), nickname: None };
+///     assert_eq!(name.encode().unwrap(), [0x30, 0x03, 0x80, 0x01, 0x61]);
+///     assert_eq!(Name::decode(&[0x30, 0x03, 0x80, 0x01, 0x61]), Ok(name));
+/// }
+/// ```
+#[macro_export]
+macro_rules! der_schema {
+    // The impls, with the lifetime of the struct if it has one.
+    (@impl $name:ident $(<$lt:lifetime>)? [$($input:lifetime)?] {
+        $($field:ident : $ty:ty $([$($spec:tt)*])?),*
+    }) => {
+        $crate::der_schema!(@from_der $name $(<$lt>)? [$($input)? 'der] {
+            $($field: $ty $([$($spec)*])?),*
+        });
+
+        impl$(<$lt>)? $crate::ToDer for $name$(<$lt>)? {
+            fn write_der<W: $crate::Write>(
+                &self,
+                der: &mut $crate::Der<W>,
+            ) -> $crate::Result<()> {
+                der.sequence(|der| {
+                    $($crate::__der_schema_field!(@write (der, &self.$field); $ty; $($($spec)*)?);)*
+                    Ok(())
+                })
+            }
+
+            fn encoded_len(&self) -> usize {
+                $crate::sequence_len(
+                    0 $(+ $crate::__der_schema_field!(@len (&self.$field); $ty; $($($spec)*)?))*
+                )
+            }
+        }
+
+        impl$(<$lt>)? $crate::Tagged for $name$(<$lt>)? {
+            const TAG: $crate::Tag = $crate::Tag::SEQUENCE;
+        }
+    };
+    // The first lifetime in brackets is that of the input: the struct's, or a new one.
+    (@from_der $name:ident $(<$lt:lifetime>)? [$input:lifetime $($unused:lifetime)?] {
+        $($field:ident : $ty:ty $([$($spec:tt)*])?),*
+    }) => {
+        impl<$input> $crate::FromDer<$input> for $name$(<$lt>)? {
+            fn from_der(reader: &mut $crate::Reader<$input>) -> $crate::Result<Self> {
+                reader.sequence(|reader| {
+                    $(
+                        let context = concat!(stringify!($name), ".", stringify!($field));
+                        let $field = reader.context(context, |reader| {
+                            $crate::__der_schema_field!(@read (reader, $input); $ty; $($($spec)*)?)
+                        })?;
+                    )*
+                    Ok($name { $($field),* })
+                })
+            }
+        }
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident<$lt:lifetime> {
+            $(
+                $(#[$field_attr:meta])*
+                $field_vis:vis $field:ident : $ty:ty $([$($spec:tt)*])?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name<$lt> {
+            $(
+                $(#[$field_attr])*
+                $field_vis $field: $crate::__der_schema_field!(@ty $ty; $($($spec)*)?),
+            )*
+        }
+
+        $crate::der_schema!(@impl $name<$lt> [$lt] { $($field: $ty $([$($spec)*])?),* });
+    };
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_attr:meta])*
+                $field_vis:vis $field:ident : $ty:ty $([$($spec:tt)*])?
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        $vis struct $name {
+            $(
+                $(#[$field_attr])*
+                $field_vis $field: $crate::__der_schema_field!(@ty $ty; $($($spec)*)?),
+            )*
+        }
+
+        $crate::der_schema!(@impl $name [] { $($field: $ty $([$($spec)*])?),* });
+    };
+}
+
+// Split the specification of a field into its tagging and presence, and expand to its type or to
+// an expression reading, writing or measuring it.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __der_schema_field {
+    (@ty $ty:ty; ) => { $ty };
+    (@ty $ty:ty; optional) => { Option<$ty> };
+    (@ty $ty:ty; default $default:expr) => { $ty };
+    (@ty $ty:ty; $tagging:ident $n:literal) => { $ty };
+    (@ty $ty:ty; $tagging:ident $n:literal, optional) => { Option<$ty> };
+    (@ty $ty:ty; $tagging:ident $n:literal, default $default:expr) => { $ty };
+
+    (@$op:ident $args:tt; $ty:ty;) => {
+        $crate::__der_schema_field!(@$op $args; $ty; () (required))
+    };
+    (@$op:ident $args:tt; $ty:ty; optional) => {
+        $crate::__der_schema_field!(@$op $args; $ty; () (optional))
+    };
+    (@$op:ident $args:tt; $ty:ty; default $default:expr) => {
+        $crate::__der_schema_field!(@$op $args; $ty; () (default $default))
+    };
+    (@$op:ident $args:tt; $ty:ty; $tagging:ident $n:literal) => {
+        $crate::__der_schema_field!(@$op $args; $ty; ($tagging $n) (required))
+    };
+    (@$op:ident $args:tt; $ty:ty; $tagging:ident $n:literal, optional) => {
+        $crate::__der_schema_field!(@$op $args; $ty; ($tagging $n) (optional))
+    };
+    (@$op:ident $args:tt; $ty:ty; $tagging:ident $n:literal, default $default:expr) => {
+        $crate::__der_schema_field!(@$op $args; $ty; ($tagging $n) (default $default))
+    };
+    (@read ($reader:ident, $lt:lifetime); $ty:ty; $tagging:tt (required)) => {
+        $crate::__der_schema_tagging!(@read $reader, $lt; $ty; $tagging)
+    };
+    (@read ($reader:ident, $lt:lifetime); $ty:ty; $tagging:tt (optional)) => {
+        $reader.optional($crate::__der_schema_tagging!(@tag $ty; $tagging), |$reader| {
+            $crate::__der_schema_tagging!(@read $reader, $lt; $ty; $tagging)
+        })
+    };
+    (@read ($reader:ident, $lt:lifetime); $ty:ty; $tagging:tt (default $default:expr)) => {
+        $crate::from_der_default(
+            $reader,
+            $crate::__der_schema_tagging!(@tag $ty; $tagging),
+            $default,
+            |$reader| $crate::__der_schema_tagging!(@read $reader, $lt; $ty; $tagging),
+        )
+    };
+
+    (@write ($der:ident, $value:expr); $ty:ty; $tagging:tt (required)) => {
+        $crate::__der_schema_tagging!(@write $der, $value; $tagging)?
+    };
+    (@write ($der:ident, $value:expr); $ty:ty; $tagging:tt (optional)) => {
+        if let Some(ref value) = *$value {
+            $crate::__der_schema_tagging!(@write $der, value; $tagging)?;
+        }
+    };
+    (@write ($der:ident, $value:expr); $ty:ty; $tagging:tt (default $default:expr)) => {
+        if *$value != $default {
+            $crate::__der_schema_tagging!(@write $der, $value; $tagging)?;
+        }
+    };
+
+    (@len ($value:expr); $ty:ty; $tagging:tt (required)) => {
+        $crate::__der_schema_tagging!(@len $value; $tagging)
+    };
+    (@len ($value:expr); $ty:ty; $tagging:tt (optional)) => {
+        match *$value {
+            Some(ref value) => $crate::__der_schema_tagging!(@len value; $tagging),
+            None => 0,
+        }
+    };
+    (@len ($value:expr); $ty:ty; $tagging:tt (default $default:expr)) => {
+        match *$value != $default {
+            true => $crate::__der_schema_tagging!(@len $value; $tagging),
+            false => 0,
+        }
+    };
+}
+
+// Expand to the tag of a field, or an expression reading, writing or measuring it, for how it is
+// tagged.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __der_schema_tagging {
+    (@tag $ty:ty; ()) => {
+        <$ty as $crate::Tagged>::TAG
+    };
+    (@tag $ty:ty; (explicit $n:literal)) => {
+        $crate::Tag::new($crate::Class::ContextSpecific, true, $n)
+    };
+    (@tag $ty:ty; (implicit $n:literal)) => {
+        $crate::Tag::new(
+            $crate::Class::ContextSpecific,
+            <$ty as $crate::Tagged>::TAG.is_constructed(),
+            $n,
+        )
+    };
+
+    (@read $reader:ident, $lt:lifetime; $ty:ty; ()) => {
+        <$ty as $crate::FromDer<$lt>>::from_der($reader)
+    };
+    (@read $reader:ident, $lt:lifetime; $ty:ty; (explicit $n:literal)) => {
+        $reader.explicit($n, <$ty as $crate::FromDer<$lt>>::from_der)
+    };
+    (@read $reader:ident, $lt:lifetime; $ty:ty; (implicit $n:literal)) => {
+        $crate::from_der_implicit::<$ty>($reader, $n)
+    };
+
+    (@write $der:ident, $value:expr; ()) => {
+        $crate::ToDer::write_der($value, $der)
+    };
+    (@write $der:ident, $value:expr; (explicit $n:literal)) => {
+        $der.explicit($n, |der| $crate::ToDer::write_der($value, der))
+    };
+    (@write $der:ident, $value:expr; (implicit $n:literal)) => {
+        $crate::write_der_implicit($der, $n, $value)
+    };
+
+    (@len $value:expr; ()) => {
+        $crate::ToDer::encoded_len($value)
+    };
+    (@len $value:expr; (explicit $n:literal)) => {
+        $crate::element_len(
+            $crate::Tag::new($crate::Class::ContextSpecific, true, $n),
+            $crate::ToDer::encoded_len($value),
+        )
+    };
+    (@len $value:expr; (implicit $n:literal)) => {
+        $crate::implicit_len($n, $value)
+    };
+}
+
+#[cfg(all(test, feature = "alloc"))]
+mod test {
+    use codec::test::round_trip;
+    use {ErrorKind, FromDer, Mode, ParserConfig, Tag, Tagged};
+
+    mod public {
+        der_schema! {
+            /// Visible outside the module it is declared in.
+            #[derive(Debug, Clone, PartialEq)]
+            pub struct Pair {
+                /// Attributes are kept on fields.
+                pub first: u8,
+                #[allow(dead_code)]
+                pub(crate) second: bool [explicit 0, default false],
+            }
+        }
+    }
+
+    der_schema! {
+        // Fields ::= SEQUENCE {
+        //     plain             INTEGER,
+        //     optional          BOOLEAN OPTIONAL,
+        //     default           INTEGER DEFAULT 5,
+        //     explicit          [0] EXPLICIT INTEGER,
+        //     implicit          [1] IMPLICIT BOOLEAN,
+        //     explicit_optional [2] EXPLICIT INTEGER OPTIONAL,
+        //     implicit_default  [3] IMPLICIT INTEGER DEFAULT 7 }
+        #[derive(Debug, PartialEq)]
+        struct Fields {
+            plain: u8,
+            optional: bool [optional],
+            default: u8 [default 5],
+            explicit: u8 [explicit 0],
+            implicit: bool [implicit 1],
+            explicit_optional: u8 [explicit 2, optional],
+            implicit_default: u8 [implicit 3, default 7],
+        }
+    }
+
+    der_schema! {
+        // Borrowed ::= SEQUENCE { name UTF8String, data [0] EXPLICIT OCTET STRING OPTIONAL }
+        #[derive(Debug, PartialEq)]
+        struct Borrowed<'a> {
+            name: &'a str,
+            data: &'a [u8] [explicit 0, optional]
+        }
+    }
+
+    #[test]
+    fn schema_visibility() {
+        let pair = public::Pair { first: 1, second: true };
+        round_trip(pair.clone(), &[0x30, 0x08, 0x02, 0x01, 0x01, 0xa0, 0x03, 0x01, 0x01, 0xff]);
+        round_trip(public::Pair { second: false, ..pair }, &[0x30, 0x03, 0x02, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn schema_fields() {
+        let fields = Fields {
+            plain: 1,
+            optional: None,
+            default: 5,
+            explicit: 2,
+            implicit: true,
+            explicit_optional: None,
+            implicit_default: 7,
+        };
+        round_trip(fields, &[
+            0x30, 0x0b,
+                0x02, 0x01, 0x01,
+                0xa0, 0x03, 0x02, 0x01, 0x02,
+                0x81, 0x01, 0xff,
+        ]);
+
+        let fields = Fields {
+            plain: 1,
+            optional: Some(false),
+            default: 6,
+            explicit: 2,
+            implicit: true,
+            explicit_optional: Some(3),
+            implicit_default: 8,
+        };
+        round_trip(fields, &[
+            0x30, 0x19,
+                0x02, 0x01, 0x01,
+                0x01, 0x01, 0x00,
+                0x02, 0x01, 0x06,
+                0xa0, 0x03, 0x02, 0x01, 0x02,
+                0x81, 0x01, 0xff,
+                0xa2, 0x03, 0x02, 0x01, 0x03,
+                0x83, 0x01, 0x08,
+        ]);
+        assert_eq!(<Fields as Tagged>::TAG, Tag::SEQUENCE);
+    }
+
+    #[test]
+    fn schema_lifetimes() {
+        let encoded = [0x30, 0x08, 0x0c, 0x01, 0x61, 0xa0, 0x03, 0x04, 0x01, 0x62];
+        round_trip(Borrowed { name: "a", data: Some(b"b") }, &encoded);
+        let borrowed = Borrowed::decode(&encoded).unwrap();
+        assert_eq!(borrowed.name.as_ptr(), encoded[4..].as_ptr());
+
+        // A struct without a lifetime can be read from input that does not outlive it.
+        let encoded = vec![0x30, 0x03, 0x02, 0x01, 0x01];
+        assert_eq!(public::Pair::decode(&encoded), Ok(public::Pair { first: 1, second: false }));
+    }
+
+    #[test]
+    fn schema_errors() {
+        // The default of `implicit_default` is encoded.
+        let encoded = &[
+            0x30, 0x0e,
+                0x02, 0x01, 0x01,
+                0xa0, 0x03, 0x02, 0x01, 0x02,
+                0x81, 0x01, 0xff,
+                0x83, 0x01, 0x07,
+        ];
+        let err = Fields::decode(encoded).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NonCanonical);
+        assert_eq!(err.context().collect::<Vec<_>>(), vec!["Fields.implicit_default"]);
+        let config = ParserConfig::DER.with_mode(Mode::Lenient);
+        let fields = ::parse_with_config(encoded, config, Fields::from_der).unwrap();
+        assert_eq!(fields.implicit_default, 7);
+
+        let err = Borrowed::decode(&[0x30, 0x02, 0x04, 0x00]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WrongTag { expected: Tag::UTF8_STRING, found: 0x04 });
+        assert_eq!(err.context().collect::<Vec<_>>(), vec!["Borrowed.name"]);
+    }
+}
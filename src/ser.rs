@@ -0,0 +1,506 @@
+//! Writing values that implement `serde::Serialize` as DER.
+
+use alloc::vec::Vec;
+use serde::ser::{self, Serialize};
+use std::fmt::Display;
+
+use der::Tag;
+use writer::Der;
+use {Error, Result, Write};
+
+/// Write `value` as DER and return the encoding, see `Serializer` for how it is mapped.
+///
+/// ```
+/// extern crate derp;
+/// extern crate serde;
+///
+/// use serde::ser::{Serialize, SerializeStruct, Serializer};
+///
+/// struct Role {
+///     name: String,
+///     threshold: u32,
+/// }
+///
+/// impl Serialize for Role {
+///     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+///         let mut role = serializer.serialize_struct("Role", 2)?;
+///         role.serialize_field("name", &self.name)?;
+///         role.serialize_field("threshold", &self.threshold)?;
+///         role.end()
+///     }
+/// }
+///
+/// fn main() {
+///     let role = Role { name: "root".to_owned(), threshold: 1 };
+///     let encoded = derp::to_vec(&role).unwrap();
+///     assert_eq!(encoded, [0x30, 0x09, 0x0c, 0x04, 0x72, 0x6f, 0x6f, 0x74, 0x02, 0x01, 0x01]);
+/// }
+/// ```
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    encode(value)
+}
+
+/// Write `value` as DER to `writer`, see `Serializer` for how it is mapped.
+pub fn to_writer<W: Write, T: Serialize + ?Sized>(writer: &mut W, value: &T) -> Result<()> {
+    value.serialize(&mut Serializer::new(writer))
+}
+
+/// A `serde::Serializer` that writes DER with `Der`.
+///
+/// Values are mapped onto DER like this, and `Deserializer` reads them back:
+///
+/// - `bool` is a `BOOLEAN`, the integer types are an `INTEGER`, and `f32` and `f64` are a `REAL`.
+/// - `char` and strings are a `UTF8String`, and bytes are an `OCTET STRING`.
+/// - `()`, unit structs and `None` are a `NULL`, and `Some` and newtype structs are the value
+///   they hold. An `Option<()>` with a value therefore looks like `None`.
+/// - Sequences, tuples, tuple structs and structs are a `SEQUENCE` of their elements in order.
+///   The names of the fields are not written, so that every field must be written for a struct
+///   to be read back.
+/// - Maps are a `SEQUENCE` of a `SEQUENCE { key, value }` for each entry, in the order they are
+///   written.
+/// - Enums are a `CHOICE` of their variants, each of which is a context-specific constructed
+///   element `[index]` around the variant's value, the index counting from zero: a `NULL` for a
+///   unit variant, the value for a newtype variant, and a `SEQUENCE` of the fields for tuple and
+///   struct variants.
+pub struct Serializer<'a, W: Write + 'a> {
+    der: Der<'a, W>,
+}
+
+impl<'a, W: Write> Serializer<'a, W> {
+    /// Create a `Serializer` that writes to `writer`.
+    pub fn new(writer: &'a mut W) -> Self {
+        Serializer { der: Der::new(writer) }
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(message: T) -> Error {
+        Error::custom(message)
+    }
+}
+
+// Write `value` to a new buffer.
+fn encode<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    value.serialize(&mut Serializer::<Vec<u8>> { der: Der::in_buffer(&mut buf) })?;
+    Ok(buf)
+}
+
+impl<'s, 'a, W: Write> ser::Serializer for &'s mut Serializer<'a, W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'s, 'a, W>;
+    type SerializeTuple = Compound<'s, 'a, W>;
+    type SerializeTupleStruct = Compound<'s, 'a, W>;
+    type SerializeTupleVariant = Compound<'s, 'a, W>;
+    type SerializeMap = Compound<'s, 'a, W>;
+    type SerializeStruct = Compound<'s, 'a, W>;
+    type SerializeStructVariant = Compound<'s, 'a, W>;
+
+    fn serialize_bool(self, value: bool) -> Result<()> {
+        self.der.boolean(value)
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<()> {
+        self.der.i64(value.into())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<()> {
+        self.der.i64(value.into())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<()> {
+        self.der.i64(value.into())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<()> {
+        self.der.i64(value)
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<()> {
+        self.der.i128(value)
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<()> {
+        self.der.u8(value)
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<()> {
+        self.der.u16(value)
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<()> {
+        self.der.u32(value)
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<()> {
+        self.der.u64(value)
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<()> {
+        self.der.u128(value)
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<()> {
+        self.der.real(value.into())
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<()> {
+        self.der.real(value)
+    }
+
+    fn serialize_char(self, value: char) -> Result<()> {
+        self.der.utf8_string(value.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<()> {
+        self.der.utf8_string(value)
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<()> {
+        self.der.octet_string(value)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.der.null()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.der.null()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        self.der.null()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        self.der.explicit(variant_index, |der| der.null())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let contents = encode(value)?;
+        self.der.explicit(variant_index, |der| der.raw(&contents))
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'s, 'a, W>> {
+        Ok(Compound::new(self, None))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'s, 'a, W>> {
+        Ok(Compound::new(self, None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a, W>> {
+        Ok(Compound::new(self, None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a, W>> {
+        Ok(Compound::new(self, Some(variant_index)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'s, 'a, W>> {
+        Ok(Compound::new(self, None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'s, 'a, W>> {
+        Ok(Compound::new(self, None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a, W>> {
+        Ok(Compound::new(self, Some(variant_index)))
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+/// The `SEQUENCE` being written by a `Serializer`, for each of serde's compound types.
+///
+/// The elements are written to a buffer, since their length has to be known before the tag and
+/// length of the `SEQUENCE` can be written.
+pub struct Compound<'s, 'a: 's, W: Write + 'a> {
+    ser: &'s mut Serializer<'a, W>,
+    // The index of the variant the `SEQUENCE` is the value of, if it is one.
+    variant_index: Option<u32>,
+    contents: Vec<u8>,
+    // The key of a map entry whose value has not been written yet.
+    key: Option<Vec<u8>>,
+}
+
+impl<'s, 'a, W: Write> Compound<'s, 'a, W> {
+    fn new(ser: &'s mut Serializer<'a, W>, variant_index: Option<u32>) -> Self {
+        Compound { ser, variant_index, contents: Vec::new(), key: None }
+    }
+
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut Serializer::new(&mut self.contents))
+    }
+
+    fn end(self) -> Result<()> {
+        let contents = self.contents;
+        match self.variant_index {
+            Some(index) => {
+                self.ser.der.explicit(index, |der| der.element(Tag::SEQUENCE, &contents))
+            }
+            None => self.ser.der.element(Tag::SEQUENCE, &contents),
+        }
+    }
+}
+
+impl<'s, 'a, W: Write> ser::SerializeSeq for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a, W: Write> ser::SerializeTuple for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a, W: Write> ser::SerializeTupleStruct for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a, W: Write> ser::SerializeTupleVariant for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a, W: Write> ser::SerializeMap for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(encode(key)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let mut entry = self.key.take().expect("serialize_value is called after serialize_key");
+        value.serialize(&mut Serializer::new(&mut entry))?;
+        Der::new(&mut self.contents).element(Tag::SEQUENCE, &entry)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a, W: Write> ser::SerializeStruct for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+impl<'s, 'a, W: Write> ser::SerializeStructVariant for Compound<'s, 'a, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+    use {ErrorKind, SliceWriter};
+
+    #[derive(Serialize)]
+    struct Unit;
+
+    #[derive(Serialize)]
+    struct Wrapper(u8);
+
+    #[derive(Serialize)]
+    struct Key<'a> {
+        id: &'a str,
+        #[serde(with = "bytes")]
+        value: &'a [u8],
+        expires: Option<i32>,
+        scheme: Scheme,
+    }
+
+    #[derive(Serialize)]
+    enum Scheme {
+        Ed25519,
+        Rsa(u16),
+        Pair(bool, bool),
+        Named { bits: u16 },
+    }
+
+    mod bytes {
+        use serde::Serializer;
+
+        pub fn serialize<S: Serializer>(value: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(value)
+        }
+    }
+
+    #[test]
+    fn serialize_values() {
+        assert_eq!(to_vec(&true).unwrap(), [0x01, 0x01, 0xff]);
+        assert_eq!(to_vec(&-1i8).unwrap(), [0x02, 0x01, 0xff]);
+        assert_eq!(to_vec(&128u8).unwrap(), [0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(to_vec(&1u128).unwrap(), [0x02, 0x01, 0x01]);
+        assert_eq!(to_vec(&0.0f32).unwrap(), [0x09, 0x00]);
+        assert_eq!(to_vec(&'é').unwrap(), [0x0c, 0x02, 0xc3, 0xa9]);
+        assert_eq!(to_vec("a").unwrap(), [0x0c, 0x01, 0x61]);
+        assert_eq!(to_vec(&()).unwrap(), [0x05, 0x00]);
+        assert_eq!(to_vec(&Unit).unwrap(), [0x05, 0x00]);
+        assert_eq!(to_vec(&None::<u8>).unwrap(), [0x05, 0x00]);
+        assert_eq!(to_vec(&Some(1u8)).unwrap(), [0x02, 0x01, 0x01]);
+        assert_eq!(to_vec(&Wrapper(1)).unwrap(), [0x02, 0x01, 0x01]);
+        assert_eq!(to_vec(&[1u8, 2]).unwrap(), [0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02]);
+        assert_eq!(to_vec(&(true, ())).unwrap(), [0x30, 0x05, 0x01, 0x01, 0xff, 0x05, 0x00]);
+        assert_eq!(to_vec(&Vec::<u8>::new()).unwrap(), [0x30, 0x00]);
+
+        let mut map = BTreeMap::new();
+        map.insert("b", 2u8);
+        map.insert("a", 1u8);
+        assert_eq!(to_vec(&map).unwrap(), [
+            0x30, 0x10,
+                0x30, 0x06, 0x0c, 0x01, 0x61, 0x02, 0x01, 0x01,
+                0x30, 0x06, 0x0c, 0x01, 0x62, 0x02, 0x01, 0x02,
+        ]);
+    }
+
+    #[test]
+    fn serialize_structs() {
+        let key = Key { id: "k", value: b"\x00", expires: None, scheme: Scheme::Ed25519 };
+        assert_eq!(to_vec(&key).unwrap(), [
+            0x30, 0x0c,
+                0x0c, 0x01, 0x6b,
+                0x04, 0x01, 0x00,
+                0x05, 0x00,
+                0xa0, 0x02, 0x05, 0x00,
+        ]);
+
+        assert_eq!(to_vec(&Scheme::Rsa(7)).unwrap(), [0xa1, 0x03, 0x02, 0x01, 0x07]);
+        assert_eq!(to_vec(&Scheme::Pair(true, false)).unwrap(), [
+            0xa2, 0x08, 0x30, 0x06, 0x01, 0x01, 0xff, 0x01, 0x01, 0x00,
+        ]);
+        assert_eq!(to_vec(&Scheme::Named { bits: 1 }).unwrap(), [
+            0xa3, 0x05, 0x30, 0x03, 0x02, 0x01, 0x01,
+        ]);
+
+        let mut buf = Vec::new();
+        to_writer(&mut buf, &[Scheme::Ed25519]).unwrap();
+        assert_eq!(buf, [0x30, 0x04, 0xa0, 0x02, 0x05, 0x00]);
+    }
+
+    #[test]
+    fn serialize_errors() {
+        struct Failing;
+
+        impl Serialize for Failing {
+            fn serialize<S>(&self, _: S) -> ::std::result::Result<S::Ok, S::Error>
+            where
+                S: ser::Serializer,
+            {
+                Err(ser::Error::custom("cannot be written"))
+            }
+        }
+
+        let err = to_vec(&[Failing]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Custom);
+        assert_eq!(err.to_string(), "cannot be written");
+
+        let mut buf = [0; 1];
+        let err = to_writer(&mut SliceWriter::new(&mut buf), &[1u8]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+    }
+}
@@ -0,0 +1,366 @@
+//! Reading DER from a `std::io::Read` without holding all of it in memory.
+
+use std::io::{self, BufRead, BufReader, Read};
+use untrusted::Input;
+
+use config::ParserConfig;
+use der::{self, Tag};
+use {Error, ErrorKind, Result};
+
+/// The identifier and length octets of an element read by a `StreamReader`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    tag: Tag,
+    length: usize,
+    offset: u64,
+}
+
+impl Header {
+    /// The tag of the element.
+    pub fn tag(&self) -> Tag {
+        self.tag
+    }
+
+    /// The length of the contents of the element, in octets.
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    /// How far into the stream the element starts, in octets.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+/// A reader that pulls elements from any `Read`, buffering internally, for input too large to
+/// read into memory at once.
+///
+/// Elements are read one header at a time. A constructed element can be entered to read the
+/// elements it contains, or the contents of any element can be read into memory, for example to
+/// decode them with `parse`. Contents that are not read are skipped. Only definite lengths can be
+/// streamed, so the indefinite form is rejected with `ErrorKind::LongLengthNotSupported` even when
+/// the config is `ParserConfig::BER`.
+///
+/// Errors have a path but no position, which can be found from `offset` instead.
+///
+/// ```
+/// extern crate derp;
+///
+/// use derp::{StreamReader, Tag};
+///
+/// fn main() {
+///     // SEQUENCE OF { INTEGER 1, INTEGER 2, INTEGER 3 }, as if from a file
+///     let file: &[u8] = &[0x30, 0x09, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x02, 0x01, 0x03];
+///     let mut stream = StreamReader::new(file);
+///
+///     let header = stream.next_header().unwrap().unwrap();
+///     assert_eq!(header.tag(), Tag::SEQUENCE);
+///     stream.enter().unwrap();
+///
+///     let mut sum = 0;
+///     while let Some(element) = stream.next_element().unwrap() {
+///         sum += derp::parse(&element, |r| r.u8()).unwrap();
+///     }
+///     assert_eq!(sum, 6);
+///     assert_eq!(stream.next_header(), Ok(None));
+/// }
+/// ```
+#[derive(Debug)]
+pub struct StreamReader<R> {
+    input: BufReader<R>,
+    config: ParserConfig,
+    // How many octets have been read.
+    offset: u64,
+    // The tags of the elements that have been entered and the offsets at which they end,
+    // innermost last.
+    open: Vec<(Tag, u64)>,
+    // The last header read, until its contents are read, skipped or entered.
+    current: Option<Header>,
+    // The identifier and length octets of `current`.
+    header: Vec<u8>,
+}
+
+impl<R: Read> StreamReader<R> {
+    /// Read DER from `input`.
+    pub fn new(input: R) -> Self {
+        StreamReader::with_config(input, ParserConfig::DER)
+    }
+
+    /// Read from `input` using `config`. `Mode::Lenient` accepts lengths that are not in their
+    /// minimal form, and the limits on nesting and length apply to `enter` and `next_header`.
+    pub fn with_config(input: R, config: ParserConfig) -> Self {
+        StreamReader {
+            input: BufReader::new(input),
+            config,
+            offset: 0,
+            open: Vec::new(),
+            current: None,
+            header: Vec::new(),
+        }
+    }
+
+    /// The configuration this reader applies.
+    pub fn config(&self) -> ParserConfig {
+        self.config
+    }
+
+    /// How far into the stream this reader is, in octets.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// How many elements have been entered and not yet left.
+    pub fn depth(&self) -> usize {
+        self.open.len()
+    }
+
+    /// Return the underlying reader. Any input that has been buffered but not read is lost.
+    pub fn into_inner(self) -> R {
+        self.input.into_inner()
+    }
+
+    /// Read the identifier and length octets of the next element, skipping anything left of the
+    /// previous one.
+    ///
+    /// This returns `None` at the end of the element that was last entered, which leaves it, and
+    /// at the end of the stream when no element has been entered.
+    pub fn next_header(&mut self) -> Result<Option<Header>> {
+        let result = self.read_header();
+        self.locate(result)
+    }
+
+    /// Read the next element and return all of its encoding, as `next_header` does, for decoding
+    /// with `parse` or the other functions of this crate.
+    pub fn next_element(&mut self) -> Result<Option<Vec<u8>>> {
+        if self.next_header()?.is_none() {
+            return Ok(None);
+        }
+        let mut encoding = self.header.clone();
+        let result = self.read_rest(&mut encoding);
+        self.locate(result).map(|_| Some(encoding))
+    }
+
+    /// Read the contents of the element whose header was just read.
+    pub fn read_contents(&mut self) -> Result<Vec<u8>> {
+        let mut contents = Vec::new();
+        let result = self.read_rest(&mut contents);
+        self.locate(result).map(|_| contents)
+    }
+
+    /// Skip the contents of the element whose header was just read.
+    pub fn skip_contents(&mut self) -> Result<()> {
+        let result = self.skip_rest();
+        self.locate(result)
+    }
+
+    /// Enter the constructed element whose header was just read, so that `next_header` reads the
+    /// elements it contains.
+    pub fn enter(&mut self) -> Result<()> {
+        let result = match self.current {
+            Some(header) if header.tag.is_constructed() => self.push(header),
+            Some(_) => Err(ErrorKind::WrongValue.into()),
+            None => Err(ErrorKind::UnconsumedContents.into()),
+        };
+        self.locate(result)
+    }
+
+    // Add the path of the elements that have been entered to any error.
+    fn locate<T>(&self, result: Result<T>) -> Result<T> {
+        result.map_err(|err| self.open.iter().rev().fold(err, |err, &(tag, _)| err.within(tag)))
+    }
+
+    fn push(&mut self, header: Header) -> Result<()> {
+        if self.open.len() >= self.config.max_depth() {
+            return Err(ErrorKind::NestingTooDeep.into());
+        }
+        self.current = None;
+        self.open.push((header.tag, self.offset + header.length as u64));
+        Ok(())
+    }
+
+    fn read_header(&mut self) -> Result<Option<Header>> {
+        self.skip_rest()?;
+        let end = match self.open.last() {
+            Some(&(_, end)) if end == self.offset => {
+                self.open.pop();
+                return Ok(None);
+            }
+            Some(&(_, end)) => end,
+            None if self.input.fill_buf()?.is_empty() => return Ok(None),
+            None => u64::MAX,
+        };
+
+        let offset = self.offset;
+        self.header.clear();
+        let first = self.read_octet(end)?;
+        if first & 0x1f == 0x1f {
+            // The tag number follows in base 128, with the high bit set on all but the last octet.
+            while self.read_octet(end)? & 0x80 != 0 && self.header.len() < 6 {}
+        }
+        let length = self.read_octet(end)?;
+        if length & 0x80 != 0 {
+            for _ in 0..length & 0x7f {
+                self.read_octet(end)?;
+            }
+        }
+
+        let (mode, max_len) = (self.config.mode(), self.config.max_len());
+        let (tag, length) = Input::from(&self.header)
+            .read_all(Error::from(ErrorKind::TrailingData), |input| {
+                der::read_header(input, mode, max_len)
+            })?;
+        if length as u64 > end - self.offset {
+            return Err(ErrorKind::UnexpectedEnd.into());
+        }
+        let header = Header { tag, length, offset };
+        self.current = Some(header);
+        Ok(Some(header))
+    }
+
+    // Read one octet of a header, which cannot go past `end`.
+    fn read_octet(&mut self, end: u64) -> Result<u8> {
+        if self.offset == end {
+            return Err(ErrorKind::UnexpectedEnd.into());
+        }
+        let mut octet = [0];
+        self.input.read_exact(&mut octet).map_err(read_error)?;
+        self.offset += 1;
+        self.header.push(octet[0]);
+        Ok(octet[0])
+    }
+
+    // The unread contents of the current element.
+    fn rest(&mut self) -> io::Take<&mut BufReader<R>> {
+        let length = self.current.take().map_or(0, |header| header.length as u64);
+        (&mut self.input).take(length)
+    }
+
+    fn read_rest(&mut self, buf: &mut Vec<u8>) -> Result<()> {
+        let length = self.current.map_or(0, |header| header.length as u64);
+        // Grow `buf` as the contents arrive rather than trusting the length up front.
+        let read = self.rest().read_to_end(buf)? as u64;
+        self.offset += read;
+        match read == length {
+            true => Ok(()),
+            false => Err(ErrorKind::UnexpectedEnd.into()),
+        }
+    }
+
+    fn skip_rest(&mut self) -> Result<()> {
+        let length = self.current.map_or(0, |header| header.length as u64);
+        let skipped = io::copy(&mut self.rest(), &mut io::sink())?;
+        self.offset += skipped;
+        match skipped == length {
+            true => Ok(()),
+            false => Err(ErrorKind::UnexpectedEnd.into()),
+        }
+    }
+}
+
+// The stream ending early is the end of the input, not a failure to read it.
+fn read_error(err: io::Error) -> Error {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof => ErrorKind::UnexpectedEnd.into(),
+        _ => err.into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use der::Mode;
+
+    // Returns one octet per read, so that every header and contents crosses reads.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match (self.0.split_first(), buf.first_mut()) {
+                (Some((&octet, rest)), Some(first)) => {
+                    *first = octet;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn stream_elements() {
+        // SEQUENCE { SEQUENCE { NULL }, OCTET STRING "abc", [0] { } }, BOOLEAN TRUE
+        let encoded = &[
+            0x30, 0x0b, 0x30, 0x02, 0x05, 0x00, 0x04, 0x03, 0x61, 0x62, 0x63, 0xa0, 0x00, 0x01,
+            0x01, 0xff,
+        ];
+        let mut stream = StreamReader::new(Trickle(encoded));
+        let header = stream.next_header().unwrap().unwrap();
+        assert_eq!((header.tag(), header.length(), header.offset()), (Tag::SEQUENCE, 11, 0));
+        stream.enter().unwrap();
+        assert_eq!(stream.depth(), 1);
+
+        // Skipped without being read.
+        assert_eq!(stream.next_header().unwrap().map(|header| header.tag()), Some(Tag::SEQUENCE));
+        let header = stream.next_header().unwrap().unwrap();
+        assert_eq!((header.tag(), header.offset()), (Tag::OCTET_STRING, 6));
+        assert_eq!(stream.read_contents(), Ok(b"abc".to_vec()));
+        assert_eq!(stream.offset(), 11);
+        assert_eq!(stream.next_element(), Ok(Some(vec![0xa0, 0x00])));
+        assert_eq!(stream.next_header(), Ok(None));
+        assert_eq!(stream.depth(), 0);
+
+        assert_eq!(stream.next_element(), Ok(Some(vec![0x01, 0x01, 0xff])));
+        assert_eq!(stream.next_header(), Ok(None));
+        assert_eq!(stream.next_header(), Ok(None));
+        assert_eq!(stream.offset(), encoded.len() as u64);
+
+        // High tag numbers and long lengths.
+        let mut encoded = vec![0x9f, 0x81, 0x00, 0x81, 0x80];
+        encoded.extend(vec![0; 0x80]);
+        let mut stream = StreamReader::new(Trickle(&encoded));
+        let header = stream.next_header().unwrap().unwrap();
+        assert_eq!((header.tag().number(), header.length()), (0x80, 0x80));
+        assert_eq!(stream.next_element(), Ok(None));
+    }
+
+    #[test]
+    fn stream_errors() {
+        let read = |encoded: &[u8], config| -> Result<u64> {
+            let mut stream = StreamReader::with_config(encoded, config);
+            stream.next_header()?;
+            stream.enter()?;
+            while stream.next_element()?.is_some() {}
+            Ok(stream.offset())
+        };
+        let config = ParserConfig::DER;
+        assert_eq!(read(&[0x30, 0x02, 0x05, 0x00], config), Ok(4));
+        assert_eq!(read(&[0x30, 0x03, 0x05, 0x00], config), Err(ErrorKind::UnexpectedEnd.into()));
+        assert_eq!(read(&[0x30, 0x01, 0x05, 0x00], config), Err(ErrorKind::UnexpectedEnd.into()));
+        assert_eq!(read(&[0x30, 0x02, 0x05, 0x01], config), Err(ErrorKind::UnexpectedEnd.into()));
+        assert_eq!(read(&[0x30], config), Err(ErrorKind::UnexpectedEnd.into()));
+        assert_eq!(read(&[0x05, 0x00], config), Err(ErrorKind::WrongValue.into()));
+        let err = read(&[0x30, 0x80, 0x00, 0x00], ParserConfig::BER).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LongLengthNotSupported);
+
+        let encoded = &[0x30, 0x04, 0x04, 0x81, 0x01, 0x61];
+        let err = read(encoded, config).unwrap_err();
+        assert_eq!((err.kind(), err.path()), (ErrorKind::NonCanonical, &[Tag::SEQUENCE][..]));
+        assert_eq!(read(encoded, config.with_mode(Mode::Lenient)), Ok(6));
+        let err = read(encoded, config.with_max_len(1)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::LengthExceedsLimit);
+        let err = read(&[0x30, 0x00], config.with_max_depth(0)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::NestingTooDeep);
+
+        // A header cannot be entered once its contents have been read.
+        let mut stream = StreamReader::new(&[0x30, 0x00][..]);
+        stream.next_header().unwrap();
+        stream.skip_contents().unwrap();
+        assert_eq!(stream.enter(), Err(ErrorKind::UnconsumedContents.into()));
+
+        // Huge lengths are not trusted for allocating.
+        let encoded = &[0x04, 0x84, 0x7f, 0xff, 0xff, 0xff, 0x00];
+        let mut stream = StreamReader::new(&encoded[..]);
+        stream.next_header().unwrap();
+        assert_eq!(stream.read_contents(), Err(ErrorKind::UnexpectedEnd.into()));
+    }
+}
@@ -0,0 +1,247 @@
+//! Checking that input is DER without knowing its schema.
+
+use alloc::vec::Vec;
+use std::cmp::Ordering;
+use untrusted::{Input, Reader};
+
+use datetime::{generalized_time, utc_time};
+use der::{self, Class, Tag};
+use oid::oid;
+use real::real;
+use {Error, ErrorKind, Result};
+
+/// Check that `input` is a single element encoded in DER, without needing to know its schema.
+///
+/// Every element is checked for a minimal length encoding, every constructed element is walked,
+/// and the contents of the universal types this crate can read are checked as their readers would,
+/// for example booleans must be `0x00` or `0xff`, integers must be minimal, and string types must
+/// be primitive and contain only the characters they allow. The elements of a `SET` must be sorted
+/// by either their tags or their encodings, since whether it is a `SET` or a `SET OF` depends on
+/// the schema. Tags outside the universal class are only checked for being properly nested.
+///
+/// The walk does not recurse, so deeply nested input cannot overflow the stack.
+///
+/// ```
+/// extern crate derp;
+///
+/// use derp::ErrorKind;
+///
+/// fn main() {
+///     // SEQUENCE { BOOLEAN TRUE, [0] { INTEGER 1 } }
+///     let encoded = [0x30, 0x08, 0x01, 0x01, 0xff, 0xa0, 0x03, 0x02, 0x01, 0x01];
+///     assert!(derp::validate_der(&encoded).is_ok());
+///
+///     // BER allows any non-zero BOOLEAN to be true, DER does not.
+///     let err = derp::validate_der(&[0x30, 0x03, 0x01, 0x01, 0x01]).unwrap_err();
+///     assert_eq!(err.kind(), ErrorKind::BadBooleanValue);
+/// }
+/// ```
+pub fn validate_der(input: &[u8]) -> Result<()> {
+    let mut open = Vec::new();
+    walk(Input::from(input), &mut open).map_err(|err| {
+        open.iter().rev().fold(err, |err, constructed| err.within(constructed.tag))
+    })
+}
+
+// A constructed element whose contents are being walked.
+struct Constructed<'a> {
+    tag: Tag,
+    contents: Reader<'a>,
+    // The tag and encoding of the previous element in the contents.
+    previous: Option<(Tag, &'a [u8])>,
+    sorted_by_tag: bool,
+    sorted_by_encoding: bool,
+}
+
+fn walk<'a>(input: Input<'a>, open: &mut Vec<Constructed<'a>>) -> Result<()> {
+    let mut reader = Reader::new(input);
+    let (tag, value, encoding) = read_element(&mut reader)?;
+    if !reader.at_end() {
+        return Err(ErrorKind::TrailingData.into());
+    }
+    visit(tag, value, encoding, open)?;
+
+    while let Some(mut current) = open.pop() {
+        if current.contents.at_end() {
+            if current.tag == Tag::SET && !current.sorted_by_tag && !current.sorted_by_encoding {
+                return Err(ErrorKind::NonCanonical.into());
+            }
+            continue;
+        }
+
+        let (tag, value, encoding) = read_element(&mut current.contents)?;
+        if let Some((previous_tag, previous_encoding)) = current.previous {
+            current.sorted_by_tag &= der::tag_order(previous_tag, tag) == Ordering::Less;
+            current.sorted_by_encoding &=
+                der::set_of_order(previous_encoding, encoding) != Ordering::Greater;
+        }
+        current.previous = Some((tag, encoding));
+        open.push(current);
+        visit(tag, value, encoding, open)?;
+    }
+    Ok(())
+}
+
+// Read the next element, returning its tag, contents and whole encoding.
+fn read_element<'a>(input: &mut Reader<'a>) -> Result<(Tag, Input<'a>, &'a [u8])> {
+    let start = input.mark();
+    let (tag, value) = der::read_any(input)?;
+    let encoding = input.get_input_between_marks(start, input.mark())?;
+    Ok((tag, value, encoding.as_slice_less_safe()))
+}
+
+fn visit<'a>(
+    tag: Tag,
+    value: Input<'a>,
+    encoding: &'a [u8],
+    open: &mut Vec<Constructed<'a>>,
+) -> Result<()> {
+    if !tag.is_constructed() {
+        return check_primitive(tag, encoding).map_err(|err| err.at(Some(Input::from(encoding))));
+    }
+
+    if tag.class() == Class::Universal {
+        match tag.number() {
+            // EXTERNAL, EMBEDDED PDV, SEQUENCE, SET and CHARACTER STRING
+            0x08 | 0x0b | 0x10 | 0x11 | 0x1d => (),
+            // BER allows strings to be split into constructed segments, DER does not.
+            number if der::is_string_type(number) => {
+                return Err(Error::from(ErrorKind::NonCanonical).at(Some(value)));
+            }
+            0x00..=0x1e => return Err(Error::from(ErrorKind::WrongValue).at(Some(value))),
+            _ => (),
+        }
+    }
+    open.push(Constructed {
+        tag,
+        contents: Reader::new(value),
+        previous: None,
+        sorted_by_tag: true,
+        sorted_by_encoding: true,
+    });
+    Ok(())
+}
+
+// SEQUENCE and SET are always constructed.
+const PRIMITIVE_SEQUENCE: Tag = Tag::new(Class::Universal, false, 0x10);
+const PRIMITIVE_SET: Tag = Tag::new(Class::Universal, false, 0x11);
+
+// Check the contents of a primitive element, given its whole encoding.
+fn check_primitive(tag: Tag, encoding: &[u8]) -> Result<()> {
+    let check = |read: fn(&mut Reader) -> Result<()>| {
+        Input::from(encoding).read_all(ErrorKind::TrailingData.into(), read)
+    };
+    match tag {
+        Tag::EOC | PRIMITIVE_SEQUENCE | PRIMITIVE_SET => Err(ErrorKind::WrongValue.into()),
+        Tag::BOOLEAN => check(|input| der::boolean(input).map(|_| ())),
+        Tag::INTEGER | Tag::ENUMERATED => check(|input| {
+            let value = der::read_any(input)?.1.as_slice_less_safe();
+            der::check_minimal_signed(value)
+        }),
+        Tag::BIT_STRING => check(|input| der::bit_string(input).map(|_| ())),
+        Tag::NULL => check(|input| match der::read_any(input)?.1.is_empty() {
+            true => Ok(()),
+            false => Err(ErrorKind::WrongValue.into()),
+        }),
+        Tag::OID => check(|input| oid(input).map(|_| ())),
+        Tag::REAL => check(|input| real(input).map(|_| ())),
+        Tag::UTF8_STRING => check(|input| der::utf8_string(input).map(|_| ())),
+        Tag::NUMERIC_STRING => check(|input| der::numeric_string(input).map(|_| ())),
+        Tag::PRINTABLE_STRING => check(|input| der::printable_string(input).map(|_| ())),
+        Tag::IA5_STRING => check(|input| der::ia5_string(input).map(|_| ())),
+        Tag::VISIBLE_STRING => check(|input| der::visible_string(input).map(|_| ())),
+        Tag::UNIVERSAL_STRING => check(|input| der::universal_string(input).map(|_| ())),
+        Tag::BMP_STRING => check(|input| der::bmp_string(input).map(|_| ())),
+        Tag::UTC_TIME => check(|input| utc_time(input).map(|_| ())),
+        Tag::GENERALIZED_TIME => check(|input| generalized_time(input).map(|_| ())),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use writer;
+
+    #[test]
+    fn validate_good() {
+        for encoded in &[
+            &[0x05, 0x00][..],
+            &[0x30, 0x00],
+            &[0x30, 0x06, 0x02, 0x01, 0x7f, 0x02, 0x01, 0x80],
+            // SET sorted by tag
+            &[0x31, 0x06, 0x01, 0x01, 0xff, 0x02, 0x01, 0x01],
+            // SET OF sorted by encoding
+            &[0x31, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02],
+            // SET ordered by tag but not by encoding, because of the constructed bit
+            &[0x31, 0x04, 0xa0, 0x00, 0x81, 0x00],
+            // Contents of other classes are not interpreted
+            &[0x81, 0x03, 0x00, 0x00, 0x00],
+            &[0x6a, 0x04, 0x30, 0x02, 0x05, 0x00],
+            b"\x0c\x02hi",
+            b"\x17\x0d170905120000Z",
+        ] {
+            assert_eq!(validate_der(encoded), Ok(()), "{:02x?}", encoded);
+        }
+    }
+
+    #[test]
+    fn validate_bad() {
+        for &(encoded, err) in &[
+            (&[][..], ErrorKind::EmptyInput),
+            (&[0x05, 0x00, 0x05, 0x00], ErrorKind::TrailingData),
+            (&[0x30, 0x80, 0x00, 0x00], ErrorKind::LongLengthNotSupported),
+            (&[0x30, 0x81, 0x02, 0x05, 0x00], ErrorKind::NonCanonical),
+            (&[0x30, 0x03, 0x05, 0x00], ErrorKind::UnexpectedEnd),
+            (&[0x30, 0x03, 0x01, 0x01, 0x01], ErrorKind::BadBooleanValue),
+            (&[0x30, 0x04, 0x02, 0x02, 0x00, 0x01], ErrorKind::NonCanonical),
+            (&[0x30, 0x03, 0x05, 0x01, 0x00], ErrorKind::WrongValue),
+            (&[0x30, 0x02, 0x00, 0x00], ErrorKind::WrongValue),
+            (&[0x03, 0x02, 0x01, 0x01], ErrorKind::NonZeroUnusedBits),
+            // Constructed strings
+            (&[0x24, 0x03, 0x04, 0x01, 0x00], ErrorKind::NonCanonical),
+            (&[0x2c, 0x00], ErrorKind::NonCanonical),
+            // Constructed INTEGER and primitive SEQUENCE
+            (&[0x22, 0x00], ErrorKind::WrongValue),
+            (&[0x10, 0x00], ErrorKind::WrongValue),
+            // SET sorted by neither tag nor encoding
+            (&[0x31, 0x06, 0x02, 0x01, 0x02, 0x02, 0x01, 0x01], ErrorKind::NonCanonical),
+            (&[0x31, 0x06, 0x01, 0x01, 0xff, 0x01, 0x01, 0x00], ErrorKind::NonCanonical),
+            (b"\x13\x01@", ErrorKind::BadStringValue),
+        ] {
+            assert_eq!(validate_der(encoded), Err(err.into()), "{:02x?}", encoded);
+        }
+    }
+
+    #[test]
+    fn validate_location() {
+        let encoded = &[0x30, 0x07, 0xa0, 0x05, 0x30, 0x03, 0x01, 0x01, 0x01];
+        let err = validate_der(encoded).unwrap_err();
+        assert_eq!(err.offset(encoded), Some(6));
+        let path = [Tag::SEQUENCE, Tag::CONTEXT_SPECIFIC_CONSTRUCTED_0, Tag::SEQUENCE];
+        assert_eq!(err.path(), &path);
+
+        // Deep nesting does not overflow the stack, and is walked down to the innermost element.
+        let nest = |inner: &[u8]| {
+            let mut lens = vec![inner.len()];
+            for _ in 1..10_000 {
+                let len = lens[lens.len() - 1];
+                lens.push(1 + writer::length_octets(len).1 + len);
+            }
+            let mut encoded = Vec::new();
+            for len in lens.into_iter().rev() {
+                let (octets, n) = writer::length_octets(len);
+                encoded.push(0x30);
+                encoded.extend_from_slice(&octets[..n]);
+            }
+            encoded.extend_from_slice(inner);
+            encoded
+        };
+        assert_eq!(validate_der(&nest(&[0x05, 0x00])), Ok(()));
+        let encoded = nest(&[0x01, 0x01, 0x01]);
+        let err = validate_der(&encoded).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadBooleanValue);
+        assert_eq!(err.offset(&encoded), Some(encoded.len() - 3));
+        assert_eq!(err.path().len(), 10_000);
+    }
+}
@@ -0,0 +1,550 @@
+//! Trees of DER elements that can be read and written without a schema.
+
+use alloc::borrow::ToOwned;
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::str;
+use untrusted::Input;
+
+use codec::{FromDer, ToDer};
+use config::ParserConfig;
+use der::{self, Class, Mode, Tag};
+use events::{self, DerVisitor, Element};
+use oid::Oid;
+use reader::Reader;
+use writer::Der;
+use {Error, ErrorKind, Result, Write};
+
+/// An owned DER element and everything it contains, for manipulating DER without a schema.
+///
+/// The universal types that are most often needed have their own variants, with their contents
+/// checked when they are parsed. Anything else is kept as `Primitive` or `Constructed` with its
+/// tag, so that every element can be parsed and written back unchanged.
+///
+/// ```
+/// extern crate derp;
+///
+/// use derp::{Der, Value};
+///
+/// fn main() {
+///     // SEQUENCE { INTEGER 1, [0] { BOOLEAN TRUE } }
+///     let encoded = [0x30, 0x08, 0x02, 0x01, 0x01, 0xa0, 0x03, 0x01, 0x01, 0xff];
+///     let mut value = Value::parse(&encoded).unwrap();
+///     assert_eq!(value, Value::Sequence(vec![
+///         Value::Integer(vec![0x01]),
+///         Value::ContextSpecific(0, Box::new(Value::Boolean(true))),
+///     ]));
+///
+///     if let Value::Sequence(ref mut values) = value {
+///         values.push(Value::Null);
+///     }
+///     let mut buf = Vec::new();
+///     value.write(&mut Der::new(&mut buf)).unwrap();
+///     assert_eq!(&buf[..2], &[0x30, 0x0a]);
+///     assert_eq!(&buf[10..], &[0x05, 0x00]);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Boolean(bool),
+    /// The big-endian two's complement contents of an `INTEGER`.
+    Integer(Vec<u8>),
+    /// The number of unused bits and the contents of a `BIT STRING`.
+    BitString(u8, Vec<u8>),
+    OctetString(Vec<u8>),
+    Null,
+    Oid(Oid),
+    Utf8String(String),
+    PrintableString(String),
+    Ia5String(String),
+    Sequence(Vec<Value>),
+    /// The elements of a `SET`, which are written in the order they are given.
+    Set(Vec<Value>),
+    /// A constructed context-specific element `[n]` holding a single element, as used for
+    /// `EXPLICIT` tagging. Other context-specific elements are `Primitive` or `Constructed`.
+    ContextSpecific(u32, Box<Value>),
+    /// Any other primitive element and its contents.
+    Primitive(Tag, Vec<u8>),
+    /// Any other constructed element and its elements. The tag must be constructed.
+    Constructed(Tag, Vec<Value>),
+}
+
+impl Value {
+    /// Parse the single DER element that is all of `input`.
+    ///
+    /// The parse does not recurse, and deeply nested input fails with `ErrorKind::NestingTooDeep`
+    /// as it does for `ParserConfig::DER`.
+    pub fn parse(input: &[u8]) -> Result<Value> {
+        Value::parse_with_config(input, ParserConfig::DER)
+    }
+
+    /// Parse the single element that is all of `input` using `config`. In `Mode::Lenient` the
+    /// segments of a constructed BER string are kept as a `Constructed` value.
+    pub fn parse_with_config(input: &[u8], config: ParserConfig) -> Result<Value> {
+        ValueRef::parse_with_config(input, config).map(|value| value.to_value())
+    }
+
+    /// The tag this value is written with.
+    pub fn tag(&self) -> Tag {
+        match *self {
+            Value::Boolean(_) => Tag::BOOLEAN,
+            Value::Integer(_) => Tag::INTEGER,
+            Value::BitString(..) => Tag::BIT_STRING,
+            Value::OctetString(_) => Tag::OCTET_STRING,
+            Value::Null => Tag::NULL,
+            Value::Oid(_) => Tag::OID,
+            Value::Utf8String(_) => Tag::UTF8_STRING,
+            Value::PrintableString(_) => Tag::PRINTABLE_STRING,
+            Value::Ia5String(_) => Tag::IA5_STRING,
+            Value::Sequence(_) => Tag::SEQUENCE,
+            Value::Set(_) => Tag::SET,
+            Value::ContextSpecific(number, _) => Tag::new(Class::ContextSpecific, true, number),
+            Value::Primitive(tag, _) | Value::Constructed(tag, _) => tag,
+        }
+    }
+
+    /// Write this value to `der`.
+    ///
+    /// The contents of integers and bit strings are written as they are given, while strings are
+    /// checked as the writers for their types check them. A `Constructed` value with a primitive
+    /// tag fails with `ErrorKind::WrongValue`.
+    pub fn write<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        match *self {
+            Value::Boolean(value) => der.boolean(value),
+            Value::Integer(ref value) => der.integer(value),
+            Value::BitString(unused_bits, ref bits) => der.bit_string(unused_bits, bits),
+            Value::OctetString(ref value) => der.octet_string(value),
+            Value::Null => der.null(),
+            Value::Oid(ref oid) => der.oid(oid),
+            Value::Utf8String(ref string) => der.utf8_string(string),
+            Value::PrintableString(ref string) => der.printable_string(string),
+            Value::Ia5String(ref string) => der.ia5_string(string),
+            Value::Sequence(ref values) => der.sequence(|der| write_all(values, der)),
+            Value::Set(ref values) => der.set(|der| write_all(values, der)),
+            Value::ContextSpecific(number, ref value) => {
+                der.explicit(number, |der| value.write(der))
+            }
+            Value::Primitive(tag, ref contents) => der.element(tag, contents),
+            Value::Constructed(tag, _) if !tag.is_constructed() => {
+                Err(ErrorKind::WrongValue.into())
+            }
+            Value::Constructed(tag, ref values) => der.nested(tag, |der| write_all(values, der)),
+        }
+    }
+}
+
+fn write_all<W: Write>(values: &[Value], der: &mut Der<W>) -> Result<()> {
+    values.iter().try_for_each(|value| value.write(der))
+}
+
+/// A DER element and everything it contains, borrowing their contents from the input, as `Value`
+/// holds them.
+///
+/// Only the elements of constructed values are allocated, which makes this cheaper than `Value`
+/// for inspecting input that is not changed.
+///
+/// ```
+/// extern crate derp;
+///
+/// use derp::ValueRef;
+///
+/// fn main() {
+///     // SEQUENCE { INTEGER 0x0102, UTF8String "hi" }
+///     let encoded = [0x30, 0x08, 0x02, 0x02, 0x01, 0x02, 0x0c, 0x02, 0x68, 0x69];
+///     let value = ValueRef::parse(&encoded).unwrap();
+///     if let ValueRef::Sequence(ref values) = value {
+///         assert_eq!(values[0], ValueRef::Integer(&encoded[4..6]));
+///         assert_eq!(values[1], ValueRef::Utf8String("hi"));
+///     }
+///     assert_eq!(value.to_value().tag(), derp::Tag::SEQUENCE);
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValueRef<'a> {
+    Boolean(bool),
+    /// The big-endian two's complement contents of an `INTEGER`.
+    Integer(&'a [u8]),
+    /// The number of unused bits and the contents of a `BIT STRING`.
+    BitString(u8, &'a [u8]),
+    OctetString(&'a [u8]),
+    Null,
+    Oid(Oid),
+    Utf8String(&'a str),
+    PrintableString(&'a str),
+    Ia5String(&'a str),
+    Sequence(Vec<ValueRef<'a>>),
+    /// The elements of a `SET`, which are written in the order they are given.
+    Set(Vec<ValueRef<'a>>),
+    /// A constructed context-specific element `[n]` holding a single element, as used for
+    /// `EXPLICIT` tagging. Other context-specific elements are `Primitive` or `Constructed`.
+    ContextSpecific(u32, Box<ValueRef<'a>>),
+    /// Any other primitive element and its contents.
+    Primitive(Tag, &'a [u8]),
+    /// Any other constructed element and its elements. The tag must be constructed.
+    Constructed(Tag, Vec<ValueRef<'a>>),
+}
+
+impl<'a> ValueRef<'a> {
+    /// Parse the single DER element that is all of `input`, as `Value::parse` does.
+    pub fn parse(input: &'a [u8]) -> Result<ValueRef<'a>> {
+        ValueRef::parse_with_config(input, ParserConfig::DER)
+    }
+
+    /// Parse the single element that is all of `input` using `config`, as
+    /// `Value::parse_with_config` does.
+    pub fn parse_with_config(input: &'a [u8], config: ParserConfig) -> Result<ValueRef<'a>> {
+        let mut builder = Builder {
+            config,
+            open: vec![Vec::new()],
+        };
+        events::walk_with_config(input, config, &mut builder)?;
+        let value = builder.open.pop().and_then(|mut values| values.pop());
+        value.ok_or(Error::from(ErrorKind::EmptyInput))
+    }
+
+    /// The tag this value is written with.
+    pub fn tag(&self) -> Tag {
+        match *self {
+            ValueRef::Boolean(_) => Tag::BOOLEAN,
+            ValueRef::Integer(_) => Tag::INTEGER,
+            ValueRef::BitString(..) => Tag::BIT_STRING,
+            ValueRef::OctetString(_) => Tag::OCTET_STRING,
+            ValueRef::Null => Tag::NULL,
+            ValueRef::Oid(_) => Tag::OID,
+            ValueRef::Utf8String(_) => Tag::UTF8_STRING,
+            ValueRef::PrintableString(_) => Tag::PRINTABLE_STRING,
+            ValueRef::Ia5String(_) => Tag::IA5_STRING,
+            ValueRef::Sequence(_) => Tag::SEQUENCE,
+            ValueRef::Set(_) => Tag::SET,
+            ValueRef::ContextSpecific(number, _) => Tag::new(Class::ContextSpecific, true, number),
+            ValueRef::Primitive(tag, _) | ValueRef::Constructed(tag, _) => tag,
+        }
+    }
+
+    /// Copy this value and everything it contains into a `Value`.
+    pub fn to_value(&self) -> Value {
+        let all = |values: &[ValueRef]| values.iter().map(ValueRef::to_value).collect();
+        match *self {
+            ValueRef::Boolean(value) => Value::Boolean(value),
+            ValueRef::Integer(value) => Value::Integer(value.to_vec()),
+            ValueRef::BitString(unused_bits, bits) => Value::BitString(unused_bits, bits.to_vec()),
+            ValueRef::OctetString(value) => Value::OctetString(value.to_vec()),
+            ValueRef::Null => Value::Null,
+            ValueRef::Oid(ref oid) => Value::Oid(oid.clone()),
+            ValueRef::Utf8String(string) => Value::Utf8String(string.to_owned()),
+            ValueRef::PrintableString(string) => Value::PrintableString(string.to_owned()),
+            ValueRef::Ia5String(string) => Value::Ia5String(string.to_owned()),
+            ValueRef::Sequence(ref values) => Value::Sequence(all(values)),
+            ValueRef::Set(ref values) => Value::Set(all(values)),
+            ValueRef::ContextSpecific(number, ref value) => {
+                Value::ContextSpecific(number, Box::new(value.to_value()))
+            }
+            ValueRef::Primitive(tag, contents) => Value::Primitive(tag, contents.to_vec()),
+            ValueRef::Constructed(tag, ref values) => Value::Constructed(tag, all(values)),
+        }
+    }
+
+    /// Write this value to `der`, as `Value::write` does.
+    pub fn write<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        let all = |values: &[ValueRef], der: &mut Der<W>| {
+            values.iter().try_for_each(|value| value.write(der))
+        };
+        match *self {
+            ValueRef::Boolean(value) => der.boolean(value),
+            ValueRef::Integer(value) => der.integer(value),
+            ValueRef::BitString(unused_bits, bits) => der.bit_string(unused_bits, bits),
+            ValueRef::OctetString(value) => der.octet_string(value),
+            ValueRef::Null => der.null(),
+            ValueRef::Oid(ref oid) => der.oid(oid),
+            ValueRef::Utf8String(string) => der.utf8_string(string),
+            ValueRef::PrintableString(string) => der.printable_string(string),
+            ValueRef::Ia5String(string) => der.ia5_string(string),
+            ValueRef::Sequence(ref values) => der.sequence(|der| all(values, der)),
+            ValueRef::Set(ref values) => der.set(|der| all(values, der)),
+            ValueRef::ContextSpecific(number, ref value) => {
+                der.explicit(number, |der| value.write(der))
+            }
+            ValueRef::Primitive(tag, contents) => der.element(tag, contents),
+            ValueRef::Constructed(tag, _) if !tag.is_constructed() => {
+                Err(ErrorKind::WrongValue.into())
+            }
+            ValueRef::Constructed(tag, ref values) => der.nested(tag, |der| all(values, der)),
+        }
+    }
+}
+
+/// Any element, read with the config of the reader.
+impl<'a> FromDer<'a> for Value {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        let config = reader.config();
+        Value::parse_with_config(reader.raw()?, config)
+    }
+}
+
+impl ToDer for Value {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        self.write(der)
+    }
+
+    fn encoded_len(&self) -> usize {
+        let len = match *self {
+            Value::Boolean(_) => 1,
+            Value::Integer(ref value) | Value::OctetString(ref value) => value.len(),
+            Value::BitString(_, ref bits) => 1 + bits.len(),
+            Value::Null => 0,
+            Value::Oid(ref oid) => oid.as_bytes().len(),
+            Value::Utf8String(ref string)
+            | Value::PrintableString(ref string)
+            | Value::Ia5String(ref string) => string.len(),
+            Value::Sequence(ref values) | Value::Set(ref values) => values_len(values),
+            Value::ContextSpecific(_, ref value) => value.encoded_len(),
+            Value::Primitive(_, ref contents) => contents.len(),
+            Value::Constructed(_, ref values) => values_len(values),
+        };
+        der::element_len(self.tag(), len)
+    }
+}
+
+/// Any element, read with the config of the reader.
+impl<'a> FromDer<'a> for ValueRef<'a> {
+    fn from_der(reader: &mut Reader<'a>) -> Result<Self> {
+        let config = reader.config();
+        ValueRef::parse_with_config(reader.raw()?, config)
+    }
+}
+
+impl<'a> ToDer for ValueRef<'a> {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> Result<()> {
+        self.write(der)
+    }
+
+    fn encoded_len(&self) -> usize {
+        let len = match *self {
+            ValueRef::Boolean(_) => 1,
+            ValueRef::Integer(value) | ValueRef::OctetString(value) => value.len(),
+            ValueRef::BitString(_, bits) => 1 + bits.len(),
+            ValueRef::Null => 0,
+            ValueRef::Oid(ref oid) => oid.as_bytes().len(),
+            ValueRef::Utf8String(string)
+            | ValueRef::PrintableString(string)
+            | ValueRef::Ia5String(string) => string.len(),
+            ValueRef::Sequence(ref values) | ValueRef::Set(ref values) => values_len(values),
+            ValueRef::ContextSpecific(_, ref value) => value.encoded_len(),
+            ValueRef::Primitive(_, contents) => contents.len(),
+            ValueRef::Constructed(_, ref values) => values_len(values),
+        };
+        der::element_len(self.tag(), len)
+    }
+}
+
+fn values_len<T: ToDer>(values: &[T]) -> usize {
+    values.iter().map(ToDer::encoded_len).sum()
+}
+
+// Builds values from the elements found by `walk`.
+struct Builder<'a> {
+    config: ParserConfig,
+    // The values found in the input, then in each open element, innermost last.
+    open: Vec<Vec<ValueRef<'a>>>,
+}
+
+impl<'a> Builder<'a> {
+    fn add(&mut self, value: ValueRef<'a>) {
+        if let Some(values) = self.open.last_mut() {
+            values.push(value);
+        }
+    }
+}
+
+impl<'a> DerVisitor<'a> for Builder<'a> {
+    fn enter(&mut self, _: &Element<'a>) -> Result<()> {
+        self.open.push(Vec::new());
+        Ok(())
+    }
+
+    fn leave(&mut self, element: &Element<'a>) -> Result<()> {
+        let mut values = self.open.pop().unwrap_or_default();
+        let value = match element.tag() {
+            Tag::SEQUENCE => ValueRef::Sequence(values),
+            Tag::SET => ValueRef::Set(values),
+            tag if tag.class() == Class::ContextSpecific && values.len() == 1 => {
+                ValueRef::ContextSpecific(tag.number(), Box::new(values.remove(0)))
+            }
+            tag => ValueRef::Constructed(tag, values),
+        };
+        self.add(value);
+        Ok(())
+    }
+
+    fn primitive(&mut self, element: &Element<'a>) -> Result<()> {
+        let value = primitive(element.tag(), element.contents(), self.config)?;
+        self.add(value);
+        Ok(())
+    }
+}
+
+// Read the value of a primitive element from its contents, checking those of the types that have
+// their own variant. The length octets have already been checked for `config`.
+fn primitive<'a>(tag: Tag, contents: &'a [u8], config: ParserConfig) -> Result<ValueRef<'a>> {
+    let restricted = |allowed: fn(u8) -> bool| -> fn(u8) -> bool {
+        match config.check_strings() {
+            true => allowed,
+            false => |_| true,
+        }
+    };
+    Ok(match tag {
+        Tag::BOOLEAN => match (contents, config.mode()) {
+            ([0x00], _) => ValueRef::Boolean(false),
+            ([0xff], _) | ([_], Mode::Lenient) => ValueRef::Boolean(true),
+            _ => return Err(ErrorKind::BadBooleanValue.into()),
+        },
+        Tag::INTEGER => {
+            der::check_minimal_signed(contents)?;
+            ValueRef::Integer(contents)
+        }
+        Tag::BIT_STRING => {
+            let input = Input::from(contents);
+            let (unused_bits, bits) =
+                input.read_all(Error::from(ErrorKind::TrailingData), der::bit_string_contents)?;
+            ValueRef::BitString(unused_bits, bits.as_slice_less_safe())
+        }
+        Tag::OCTET_STRING => ValueRef::OctetString(contents),
+        Tag::NULL if contents.is_empty() => ValueRef::Null,
+        Tag::NULL => return Err(ErrorKind::WrongValue.into()),
+        Tag::OID => ValueRef::Oid(Oid::from_bytes(contents)?),
+        Tag::UTF8_STRING => ValueRef::Utf8String(string(contents, |_| true)?),
+        Tag::PRINTABLE_STRING => {
+            ValueRef::PrintableString(string(contents, restricted(der::is_printable_string_char))?)
+        }
+        Tag::IA5_STRING => {
+            ValueRef::Ia5String(string(contents, restricted(der::is_ia5_string_char))?)
+        }
+        tag => ValueRef::Primitive(tag, contents),
+    })
+}
+
+// Check that `contents` are UTF-8 made only of octets that are `allowed`.
+fn string(contents: &[u8], allowed: fn(u8) -> bool) -> Result<&str> {
+    match contents.iter().all(|&b| allowed(b)) {
+        true => str::from_utf8(contents).map_err(|_| Error::from(ErrorKind::BadStringValue)),
+        false => Err(ErrorKind::BadStringValue.into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn write(value: &Value) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        value.write(&mut Der::new(&mut buf))?;
+        Ok(buf)
+    }
+
+    #[test]
+    fn value_round_trip() {
+        for encoded in &[
+            &[0x05, 0x00][..],
+            &[0x30, 0x00],
+            &[0x31, 0x06, 0x01, 0x01, 0xff, 0x02, 0x01, 0x80],
+            &[0x03, 0x02, 0x04, 0xf0],
+            &[0x04, 0x02, 0x00, 0x01],
+            &[0x06, 0x03, 0x2b, 0x65, 0x70],
+            b"\x0c\x02hi",
+            b"\x13\x02hi",
+            b"\x16\x02hi",
+            b"\x17\x0d170905120000Z",
+            &[0xa0, 0x02, 0x05, 0x00],
+            &[0xa1, 0x00],
+            &[0xa2, 0x04, 0x05, 0x00, 0x05, 0x00],
+            &[0x82, 0x01, 0x61],
+            &[0x6a, 0x04, 0x30, 0x02, 0x05, 0x00],
+        ] {
+            let value = Value::parse(encoded).unwrap();
+            assert_eq!(write(&value).as_ref().map(|buf| &buf[..]), Ok(*encoded), "{:?}", value);
+            assert_eq!(value.encoded_len(), encoded.len(), "{:?}", value);
+        }
+
+        // Decoded inside other elements, with long lengths.
+        let mut encoded = vec![0x30, 0x81, 0x85, 0x04, 0x81, 0x80];
+        encoded.extend(vec![0; 0x80]);
+        encoded.extend(&[0x05, 0x00]);
+        let (first, second) = ::parse(&encoded, |r| {
+            r.sequence(|r| Ok((Value::from_der(r)?, ValueRef::from_der(r)?)))
+        })
+        .unwrap();
+        assert_eq!(first, Value::OctetString(vec![0; 0x80]));
+        assert_eq!((first.encoded_len(), second), (0x83, ValueRef::Null));
+
+        let encoded = &[0x30, 0x09, 0x0a, 0x01, 0x01, 0xa3, 0x04, 0x81, 0x02, 0x12, 0x34];
+        assert_eq!(Value::parse(encoded), Ok(Value::Sequence(vec![
+            Value::Primitive(Tag::ENUMERATED, vec![0x01]),
+            Value::ContextSpecific(3, Box::new(Value::Primitive(
+                Tag::new(Class::ContextSpecific, false, 1),
+                vec![0x12, 0x34],
+            ))),
+        ])));
+        assert_eq!(Value::ContextSpecific(3, Box::new(Value::Null)).tag().number(), 3);
+    }
+
+    #[test]
+    fn value_errors() {
+        for &(encoded, err) in &[
+            (&[][..], ErrorKind::EmptyInput),
+            (&[0x30, 0x03, 0x01, 0x01, 0x01], ErrorKind::BadBooleanValue),
+            (&[0x30, 0x04, 0x02, 0x02, 0x00, 0x01], ErrorKind::NonCanonical),
+            (&[0x05, 0x01, 0x00], ErrorKind::WrongValue),
+            (b"\x13\x01@", ErrorKind::BadStringValue),
+            (&[0x05, 0x00, 0x05, 0x00], ErrorKind::TrailingData),
+        ] {
+            assert_eq!(Value::parse(encoded), Err(err.into()), "{:02x?}", encoded);
+        }
+        let err = Value::parse(&[0x30, 0x05, 0xa0, 0x03, 0x01, 0x01, 0x01]).unwrap_err();
+        assert_eq!(err.path(), &[Tag::SEQUENCE, Tag::CONTEXT_SPECIFIC_CONSTRUCTED_0]);
+
+        // BER lengths and BOOLEANs are accepted in `Mode::Lenient`.
+        let encoded = &[0x30, 0x81, 0x09, 0x01, 0x01, 0x01, 0x05, 0x00, 0x0c, 0x81, 0x01, 0x61];
+        let value = Value::parse_with_config(encoded, ParserConfig::BER);
+        let values = vec![Value::Boolean(true), Value::Null, Value::Utf8String("a".to_owned())];
+        assert_eq!(value, Ok(Value::Sequence(values)));
+
+        // Restricted strings need only be UTF-8 when their characters are not checked.
+        let config = ParserConfig::DER.with_check_strings(false);
+        let value = Value::parse_with_config(b"\x13\x01@", config);
+        assert_eq!(value, Ok(Value::PrintableString("@".to_owned())));
+
+        let value = Value::Constructed(Tag::NULL, Vec::new());
+        assert_eq!(write(&value), Err(ErrorKind::WrongValue.into()));
+        let value = Value::PrintableString("@".to_owned());
+        assert_eq!(write(&value), Err(ErrorKind::BadStringValue.into()));
+    }
+
+    #[test]
+    fn value_ref() {
+        // SEQUENCE { OCTET STRING "ab", [2] { [0] 0x01 }, BIT STRING 0x80 }
+        let encoded = &[
+            0x30, 0x0d, 0x04, 0x02, 0x61, 0x62, 0xa2, 0x03, 0x80, 0x01, 0x01, 0x03, 0x02, 0x07, 0x80,
+        ];
+        let value = ValueRef::parse(encoded).unwrap();
+        let implicit = ValueRef::Primitive(Tag::new(Class::ContextSpecific, false, 0), &[0x01]);
+        assert_eq!(value, ValueRef::Sequence(vec![
+            ValueRef::OctetString(b"ab"),
+            ValueRef::ContextSpecific(2, Box::new(implicit)),
+            ValueRef::BitString(7, &[0x80]),
+        ]));
+        if let ValueRef::Sequence(ref values) = value {
+            if let ValueRef::OctetString(contents) = values[0] {
+                assert_eq!(contents.as_ptr(), encoded[4..].as_ptr());
+            }
+        }
+
+        let mut buf = Vec::new();
+        value.write(&mut Der::new(&mut buf)).unwrap();
+        assert_eq!(&buf[..], &encoded[..]);
+        assert_eq!(value.encoded_len(), encoded.len());
+        assert_eq!(write(&value.to_value()), Ok(buf));
+        assert_eq!(Value::parse(encoded), Ok(value.to_value()));
+        assert_eq!(ValueRef::parse(&[0x01, 0x01, 0x01]), Err(ErrorKind::BadBooleanValue.into()));
+    }
+}
@@ -1,60 +1,298 @@
-use std::io::Write;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use std::cmp::Ordering;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "alloc")]
+use std::iter;
+use std::mem;
 
-use Result;
-use der::{self, Tag};
+use untrusted::{Input, Reader};
+
+#[cfg(feature = "alloc")]
+use {BitFlags, Oid};
+use {DerDateTime, ErrorKind, Result, ToDerDateTime, UtcTimePivot};
+use der::{self, Class, Tag};
+
+/// Where `Der` writes to.
+///
+/// With the `std` feature this is implemented for every `std::io::Write`, and without it for
+/// `SliceWriter` and, with the `alloc` feature, `Vec<u8>`.
+pub trait Write {
+    /// Write all of `input`, or fail without saying how much of it was written.
+    fn write_all(&mut self, input: &[u8]) -> Result<()>;
+}
+
+#[cfg(feature = "std")]
+impl<W: io::Write + ?Sized> Write for W {
+    fn write_all(&mut self, input: &[u8]) -> Result<()> {
+        Ok(io::Write::write_all(self, input)?)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write + ?Sized> Write for &mut W {
+    fn write_all(&mut self, input: &[u8]) -> Result<()> {
+        (**self).write_all(input)
+    }
+}
+
+#[cfg(all(feature = "alloc", not(feature = "std")))]
+impl Write for Vec<u8> {
+    fn write_all(&mut self, input: &[u8]) -> Result<()> {
+        self.extend_from_slice(input);
+        Ok(())
+    }
+}
 
 /// Helper for writing DER that automattically encoes tags and content lengths.
+///
+/// Nested elements are built in memory until their length is known, except when writing into a
+/// `SliceWriter` with `Der::slice` where they are built in place. The buffer they are built in is
+/// kept for the next nested element, and `Der::with_scratch` lets it outlive the `Der` so that
+/// writing many messages allocates only until it has grown large enough. Without the `alloc`
+/// feature nested elements can only be written with `Der::slice`, and writing one elsewhere fails
+/// with `ErrorKind::BufferTooSmall`.
 pub struct Der<'a, W: Write + 'a> {
-    writer: &'a mut W,
+    sink: Sink<'a, W>,
+}
+
+// Where a `Der` writes to. Nested elements are built in a single buffer shared by every level of
+// nesting, with each length patched in once its contents are known. `Count` only adds up the
+// number of octets that would have been written. `Slice` writes in place into the buffer of a
+// `SliceWriter`, moving the contents of a nested element along when its length needs the long form.
+enum Sink<'a, W: 'a> {
+    Writer {
+        writer: &'a mut W,
+        #[cfg(feature = "alloc")]
+        scratch: Scratch<'a>,
+    },
+    #[cfg(feature = "alloc")]
+    Buffer(&'a mut Vec<u8>),
+    Count(&'a mut usize),
+    Slice { buf: &'a mut [u8], pos: &'a mut usize },
+}
+
+// The buffer a `Writer` sink builds nested elements in, emptied but not freed after each one.
+#[cfg(feature = "alloc")]
+enum Scratch<'a> {
+    Owned(Vec<u8>),
+    Borrowed(&'a mut Vec<u8>),
+}
+
+#[cfg(feature = "alloc")]
+impl<'a> Scratch<'a> {
+    fn buf(&mut self) -> &mut Vec<u8> {
+        match *self {
+            Scratch::Owned(ref mut buf) => buf,
+            Scratch::Borrowed(ref mut buf) => buf,
+        }
+    }
+}
+
+/// A writer into a caller provided buffer, which fails with `ErrorKind::BufferTooSmall` rather
+/// than growing. Writing to it with `Der::slice` encodes nested elements in place, so nothing is
+/// allocated.
+///
+/// ```
+/// extern crate derp;
+///
+/// use derp::{Der, ErrorKind, SliceWriter};
+///
+/// fn main() {
+///     let mut buf = [0; 8];
+///     let mut writer = SliceWriter::new(&mut buf);
+///     Der::slice(&mut writer).sequence(|der| der.u8(1)).unwrap();
+///     assert_eq!(writer.written(), &[0x30, 0x03, 0x02, 0x01, 0x01]);
+///
+///     let err = Der::slice(&mut writer).sequence(|der| der.octet_string(&[0; 2])).unwrap_err();
+///     assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+///     assert_eq!(writer.len(), 5);
+/// }
+/// ```
+pub struct SliceWriter<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    /// Create a new `SliceWriter` that writes from the start of `buf`.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        SliceWriter { buf, pos: 0 }
+    }
+
+    /// The number of octets written so far.
+    pub fn len(&self) -> usize {
+        self.pos
+    }
+
+    /// Whether nothing has been written yet.
+    pub fn is_empty(&self) -> bool {
+        self.pos == 0
+    }
+
+    /// The octets written so far.
+    pub fn written(&self) -> &[u8] {
+        &self.buf[..self.pos]
+    }
+
+    /// Consume the writer, returning the octets that were written.
+    pub fn into_written(self) -> &'a [u8] {
+        &self.buf[..self.pos]
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<'a> Write for SliceWriter<'a> {
+    fn write_all(&mut self, input: &[u8]) -> Result<()> {
+        write_to_slice(self.buf, &mut self.pos, input)
+    }
+}
+
+// Writes that do not fit are refused whole rather than written in part, with an `io::Error`
+// wrapping `ErrorKind::BufferTooSmall` that converts back into it, so that `Der` fails the same
+// way whether or not it writes through `io::Write`.
+#[cfg(feature = "std")]
+impl<'a> io::Write for SliceWriter<'a> {
+    fn write(&mut self, input: &[u8]) -> io::Result<usize> {
+        write_to_slice(self.buf, &mut self.pos, input)?;
+        Ok(input.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Compute the exact number of octets `func` writes without writing or buffering any of them, for
+/// allocating output up front or filling a fixed size record.
+///
+/// ```
+/// extern crate derp;
+///
+/// use derp::Der;
+///
+/// fn main() {
+///     let write = |der: &mut Der<Vec<u8>>| der.sequence(|der| der.octet_string(&[0; 200]));
+///     let len = derp::encoded_len(write).unwrap();
+///     assert_eq!(len, 206);
+///
+///     let mut buf = Vec::with_capacity(len);
+///     write(&mut Der::new(&mut buf)).unwrap();
+///     assert_eq!(buf.len(), len);
+/// }
+/// ```
+#[cfg(feature = "alloc")]
+pub fn encoded_len<F: FnOnce(&mut Der<Vec<u8>>) -> Result<()>>(func: F) -> Result<usize> {
+    let mut len = 0;
+    func(&mut Der::counting(&mut len))?;
+    Ok(len)
 }
 
 impl<'a, W: Write> Der<'a, W> {
     /// Create a new `Der` structure that writes values to the given writer.
+    ///
+    /// Nested elements are built in a buffer before they are written, as their length comes first.
+    /// Without the `alloc` feature there is no buffer, so `nested`, `set_of` and everything written
+    /// with them fail with `ErrorKind::BufferTooSmall`; use `Der::slice` to write them in place.
     pub fn new(writer: &'a mut W) -> Self {
-        Der { writer: writer }
+        Der {
+            sink: Sink::Writer {
+                writer,
+                #[cfg(feature = "alloc")]
+                scratch: Scratch::Owned(Vec::new()),
+            },
+        }
     }
 
-    fn write_len(&mut self, len: usize) -> Result<()> {
-        if len >= 128 {
-            let n = der::length_of_length(len);
-            self.writer.write_all(&[0x80 | n])?;
+    /// Create a new `Der` structure that writes values to `writer` and builds nested elements in
+    /// `scratch`, whose contents are discarded. Reusing `scratch` for each message saves
+    /// allocating a new buffer for every one.
+    ///
+    /// ```
+    /// extern crate derp;
+    ///
+    /// use derp::Der;
+    ///
+    /// fn main() {
+    ///     let mut scratch = Vec::new();
+    ///     let mut messages = Vec::new();
+    ///     for i in 0..3 {
+    ///         let mut buf = Vec::new();
+    ///         Der::with_scratch(&mut buf, &mut scratch).sequence(|der| der.u8(i)).unwrap();
+    ///         messages.push(buf);
+    ///     }
+    ///
+    ///     assert_eq!(messages[2], &[0x30, 0x03, 0x02, 0x01, 0x02]);
+    ///     assert!(scratch.is_empty() && scratch.capacity() > 0);
+    /// }
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn with_scratch(writer: &'a mut W, scratch: &'a mut Vec<u8>) -> Self {
+        scratch.clear();
+        Der { sink: Sink::Writer { writer, scratch: Scratch::Borrowed(scratch) } }
+    }
 
-            for i in (1..n + 1).rev() {
-                self.writer.write_all(&[(len >> ((i - 1) * 8)) as u8])?;
+    fn write_all(&mut self, input: &[u8]) -> Result<()> {
+        match self.sink {
+            Sink::Writer { ref mut writer, .. } => writer.write_all(input),
+            #[cfg(feature = "alloc")]
+            Sink::Buffer(ref mut buf) => {
+                buf.extend_from_slice(input);
+                Ok(())
             }
-        } else {
-            self.writer.write_all(&[len as u8])?;
+            Sink::Count(ref mut count) => {
+                **count += input.len();
+                Ok(())
+            }
+            Sink::Slice { ref mut buf, ref mut pos } => write_to_slice(buf, pos, input),
         }
+    }
 
-        Ok(())
+    fn write_len(&mut self, len: usize) -> Result<()> {
+        let (octets, n) = length_octets(len);
+        self.write_all(&octets[..n])
+    }
+
+    fn write_tag(&mut self, tag: Tag) -> Result<()> {
+        let (octets, len) = tag.identifier_octets();
+        self.write_all(&octets[..len])
     }
 
     /// Write a `NULL` tag.
     pub fn null(&mut self) -> Result<()> {
-        Ok(self.writer.write_all(&[Tag::Null as u8, 0])?)
+        self.write_tag(Tag::NULL)?;
+        self.write_all(&[0])
+    }
+
+    /// Write a `BOOLEAN` using the DER encoding of `0xFF` for `true` and `0x00` for `false`.
+    pub fn boolean(&mut self, value: bool) -> Result<()> {
+        let byte = if value { 0xff } else { 0x00 };
+        self.write_tag(Tag::BOOLEAN)?;
+        self.write_all(&[1, byte])
     }
 
     /// Write an arbitrary element.
     pub fn element(&mut self, tag: Tag, input: &[u8]) -> Result<()> {
-        self.writer.write_all(&[tag as u8])?;
+        self.write_tag(tag)?;
         self.write_len(input.len())?;
-        self.writer.write_all(input)?;
+        self.write_all(input)?;
         Ok(())
     }
 
     /// Write the given input as an integer.
     pub fn integer(&mut self, input: &[u8]) -> Result<()> {
-        self.writer.write_all(&[Tag::Integer as u8])?;
+        self.write_tag(Tag::INTEGER)?;
         self.write_len(input.len())?;
-        self.writer.write_all(input)?;
+        self.write_all(input)?;
         Ok(())
     }
 
     /// Write the given input as a positive integer.
     pub fn positive_integer(&mut self, input: &[u8]) -> Result<()> {
-        self.writer.write_all(&[Tag::Integer as u8])?;
+        self.write_tag(Tag::INTEGER)?;
 
-        let push_zero = if input.len() > 0 {
+        let push_zero = if !input.is_empty() {
             input[0] & 0x80 == 0x80
         } else {
             false
@@ -63,53 +301,412 @@ impl<'a, W: Write> Der<'a, W> {
         self.write_len(input.len() + push_zero as usize)?;
         
         if push_zero {
-            self.writer.write_all(&[0x00])?;
+            self.write_all(&[0x00])?;
         }
 
-        self.writer.write_all(input)?;
+        self.write_all(input)?;
         Ok(())
     }
 
-    /// Write a nested structure by passing in a handling function that writes to an intermediate
-    /// `Vec` before writing the whole sequence to `self`.
-    pub fn nested<F: FnOnce(&mut Der<Vec<u8>>) -> Result<()>>(
+    /// Write a `u8` as an `INTEGER`.
+    pub fn u8(&mut self, value: u8) -> Result<()> {
+        self.unsigned(u128::from(value))
+    }
+
+    /// Write a `u16` as an `INTEGER`.
+    pub fn u16(&mut self, value: u16) -> Result<()> {
+        self.unsigned(u128::from(value))
+    }
+
+    /// Write a `u32` as an `INTEGER`.
+    pub fn u32(&mut self, value: u32) -> Result<()> {
+        self.unsigned(u128::from(value))
+    }
+
+    /// Write a `u64` as an `INTEGER`, using the minimal encoding with a leading zero if the high
+    /// bit is set.
+    ///
+    #[cfg_attr(feature = "alloc", doc = "```")]
+    #[cfg_attr(not(feature = "alloc"), doc = "```ignore")]
+    /// extern crate derp;
+    ///
+    /// use derp::Der;
+    ///
+    /// fn main() {
+    ///     let mut buf = Vec::new();
+    ///     {
+    ///         let mut der = Der::new(&mut buf);
+    ///         der.u64(0x80).unwrap();
+    ///     }
+    ///
+    ///     assert_eq!(&buf, &[0x02, 0x02, 0x00, 0x80]);
+    /// }
+    /// ```
+    pub fn u64(&mut self, value: u64) -> Result<()> {
+        self.unsigned(u128::from(value))
+    }
+
+    /// Write a `u128` as an `INTEGER`.
+    pub fn u128(&mut self, value: u128) -> Result<()> {
+        self.unsigned(value)
+    }
+
+    fn unsigned(&mut self, value: u128) -> Result<()> {
+        let bytes = value.to_be_bytes();
+        let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len() - 1);
+        self.positive_integer(&bytes[start..])
+    }
+
+    /// Write an `i64` as an `INTEGER` using its minimal two's complement encoding.
+    ///
+    #[cfg_attr(feature = "alloc", doc = "```")]
+    #[cfg_attr(not(feature = "alloc"), doc = "```ignore")]
+    /// extern crate derp;
+    ///
+    /// use derp::Der;
+    ///
+    /// fn main() {
+    ///     let mut buf = Vec::new();
+    ///     {
+    ///         let mut der = Der::new(&mut buf);
+    ///         der.i64(-129).unwrap();
+    ///     }
+    ///
+    ///     assert_eq!(&buf, &[0x02, 0x02, 0xff, 0x7f]);
+    /// }
+    /// ```
+    pub fn i64(&mut self, value: i64) -> Result<()> {
+        self.i128(i128::from(value))
+    }
+
+    /// Write an `i128` as an `INTEGER` using its minimal two's complement encoding.
+    pub fn i128(&mut self, value: i128) -> Result<()> {
+        let (bytes, start) = minimal_twos_complement(value);
+        self.element(Tag::INTEGER, &bytes[start..])
+    }
+
+    /// Write an `ENUMERATED` using the minimal two's complement encoding of `value`.
+    pub fn enumerated(&mut self, value: i64) -> Result<()> {
+        let (bytes, start) = minimal_twos_complement(i128::from(value));
+        self.element(Tag::ENUMERATED, &bytes[start..])
+    }
+
+    /// Write a nested structure by passing in a handling function that writes its contents, and
+    /// return what it returns. The outermost nested element is built in a single buffer that every
+    /// element nested within it shares, so deep structures need no buffer per level, and that
+    /// buffer is reused by the next one.
+    ///
+    #[cfg_attr(feature = "alloc", doc = "```")]
+    #[cfg_attr(not(feature = "alloc"), doc = "```ignore")]
+    /// extern crate derp;
+    ///
+    /// use derp::{Der, Tag};
+    ///
+    /// fn main() {
+    ///     let mut buf = Vec::new();
+    ///     let count = Der::new(&mut buf)
+    ///         .nested(Tag::SEQUENCE, |der| {
+    ///             let values = [1, 2, 3];
+    ///             for value in values.iter() {
+    ///                 der.u8(*value)?;
+    ///             }
+    ///             Ok(values.len())
+    ///         })
+    ///         .unwrap();
+    ///
+    ///     assert_eq!(count, 3);
+    ///     assert_eq!(&buf, &[0x30, 0x09, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x02, 0x01, 0x03]);
+    /// }
+    /// ```
+    pub fn nested<R, F: FnOnce(&mut Der<W>) -> Result<R>>(
         &mut self,
         tag: Tag,
         func: F,
-    ) -> Result<()> {
-        let mut buf = Vec::new();
-
-        {
-            let mut inner = Der::new(&mut buf);
-            func(&mut inner)?;
+    ) -> Result<R> {
+        match self.sink {
+            #[cfg(feature = "alloc")]
+            Sink::Writer { ref mut writer, ref mut scratch } => {
+                let buf = scratch.buf();
+                let result = nest_in_buffer(buf, tag, func)
+                    .and_then(|value| writer.write_all(buf).map(|()| value));
+                buf.clear();
+                result
+            }
+            #[cfg(not(feature = "alloc"))]
+            Sink::Writer { .. } => Err(ErrorKind::BufferTooSmall.into()),
+            #[cfg(feature = "alloc")]
+            Sink::Buffer(ref mut buf) => nest_in_buffer(buf, tag, func),
+            Sink::Count(ref mut count) => {
+                let mut len = 0;
+                let value = func(&mut Der::counting(&mut len))?;
+                **count += der::element_len(tag, len);
+                Ok(value)
+            }
+            Sink::Slice { ref mut buf, ref mut pos } => {
+                nest_in_slice(buf, pos, tag, |buf, pos| func(&mut Der::in_slice(buf, pos)))
+            }
         }
+    }
 
-        self.writer.write_all(&[tag as u8])?;
-        self.write_len(buf.len())?;
-        Ok(self.writer.write_all(&buf)?)
+    /// Write an `EXPLICIT` context-specific tag `[tag_number]` wrapping whatever `func` writes.
+    ///
+    #[cfg_attr(feature = "alloc", doc = "```")]
+    #[cfg_attr(not(feature = "alloc"), doc = "```ignore")]
+    /// extern crate derp;
+    ///
+    /// use derp::Der;
+    ///
+    /// fn main() {
+    ///     let mut buf = Vec::new();
+    ///     {
+    ///         let mut der = Der::new(&mut buf);
+    ///         der.explicit(0, |der| der.integer(&[0x02])).unwrap();
+    ///     }
+    ///
+    ///     assert_eq!(&buf, &[0xa0, 0x03, 0x02, 0x01, 0x02]);
+    /// }
+    /// ```
+    pub fn explicit<R, F: FnOnce(&mut Der<W>) -> Result<R>>(
+        &mut self,
+        tag_number: u32,
+        func: F,
+    ) -> Result<R> {
+        self.tagged_constructed(Class::ContextSpecific, tag_number, func)
     }
 
-    /// Write a `SEQUENCE` by passing in a handling function that writes to an intermediate `Vec`
-    /// before writing the whole sequence to `self`.
-    pub fn sequence<F: FnOnce(&mut Der<Vec<u8>>) -> Result<()>>(
+    /// Write `input` as the contents of a primitive `IMPLICIT` context-specific tag
+    /// `[tag_number]`, which replaces the value's universal tag.
+    ///
+    #[cfg_attr(feature = "alloc", doc = "```")]
+    #[cfg_attr(not(feature = "alloc"), doc = "```ignore")]
+    /// extern crate derp;
+    ///
+    /// use derp::Der;
+    ///
+    /// fn main() {
+    ///     let mut buf = Vec::new();
+    ///     {
+    ///         let mut der = Der::new(&mut buf);
+    ///         // GeneralName dNSName [2] IMPLICIT IA5String
+    ///         der.implicit(2, b"derp").unwrap();
+    ///     }
+    ///
+    ///     assert_eq!(&buf, &[0x82, 0x04, 0x64, 0x65, 0x72, 0x70]);
+    /// }
+    /// ```
+    pub fn implicit(&mut self, tag_number: u32, input: &[u8]) -> Result<()> {
+        self.tagged(Class::ContextSpecific, tag_number, input)
+    }
+
+    /// Write a constructed `IMPLICIT` context-specific tag `[tag_number]` whose contents are
+    /// whatever `func` writes. This is used in place of the universal tag of a constructed type
+    /// such as a `SEQUENCE`.
+    pub fn implicit_constructed<R, F: FnOnce(&mut Der<W>) -> Result<R>>(
         &mut self,
+        tag_number: u32,
         func: F,
-    ) -> Result<()> {
-        self.nested(Tag::Sequence, func)
+    ) -> Result<R> {
+        // A constructed IMPLICIT tag has the same identifier as an EXPLICIT one, the difference is
+        // only in whether the contents include the original tag.
+        self.explicit(tag_number, func)
+    }
+
+    /// Write `input` as the contents of a primitive tag of the given class and number.
+    pub fn tagged(&mut self, class: Class, tag_number: u32, input: &[u8]) -> Result<()> {
+        self.write_tag(Tag::new(class, false, tag_number))?;
+        self.write_len(input.len())?;
+        self.write_all(input)?;
+        Ok(())
+    }
+
+    /// Write a constructed tag of the given class and number whose contents are whatever `func`
+    /// writes.
+    ///
+    #[cfg_attr(feature = "alloc", doc = "```")]
+    #[cfg_attr(not(feature = "alloc"), doc = "```ignore")]
+    /// extern crate derp;
+    ///
+    /// use derp::{Class, Der};
+    ///
+    /// fn main() {
+    ///     let mut buf = Vec::new();
+    ///     {
+    ///         let mut der = Der::new(&mut buf);
+    ///         der.tagged_constructed(Class::Application, 10, |der| der.sequence(|der| der.null()))
+    ///             .unwrap();
+    ///     }
+    ///
+    ///     assert_eq!(&buf, &[0x6a, 0x04, 0x30, 0x02, 0x05, 0x00]);
+    /// }
+    /// ```
+    pub fn tagged_constructed<R, F: FnOnce(&mut Der<W>) -> Result<R>>(
+        &mut self,
+        class: Class,
+        tag_number: u32,
+        func: F,
+    ) -> Result<R> {
+        self.nested(Tag::new(class, true, tag_number), func)
+    }
+
+    /// Write a `SEQUENCE` by passing in a handling function that writes its contents, see
+    /// `nested`.
+    pub fn sequence<R, F: FnOnce(&mut Der<W>) -> Result<R>>(
+        &mut self,
+        func: F,
+    ) -> Result<R> {
+        self.nested(Tag::SEQUENCE, func)
+    }
+
+    /// Write a `SET` by passing in a handling function that writes its contents, see `nested`. The
+    /// elements are written in the order `func` writes them.
+    pub fn set<R, F: FnOnce(&mut Der<W>) -> Result<R>>(
+        &mut self,
+        func: F,
+    ) -> Result<R> {
+        self.nested(Tag::SET, func)
+    }
+
+    /// Write a `SET OF` by calling `func` once per item to encode each element. The encoded elements
+    /// are sorted as DER requires before the set is written to `self`. When writing into a
+    /// `SliceWriter` each call to `func` must write a single element, which is sorted into place
+    /// without allocating, or this errors with `ErrorKind::WrongValue`.
+    ///
+    #[cfg_attr(feature = "alloc", doc = "```")]
+    #[cfg_attr(not(feature = "alloc"), doc = "```ignore")]
+    /// extern crate derp;
+    ///
+    /// use derp::Der;
+    ///
+    /// fn main() {
+    ///     let mut buf = Vec::new();
+    ///     {
+    ///         let mut der = Der::new(&mut buf);
+    ///         der.set_of(&[3u8, 1, 2], |der, x| der.integer(&[*x])).unwrap();
+    ///     }
+    ///
+    ///     assert_eq!(&buf, &[0x31, 0x09, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x02, 0x01, 0x03]);
+    /// }
+    /// ```
+    pub fn set_of<I, F>(&mut self, items: I, mut func: F) -> Result<()>
+    where
+        I: IntoIterator,
+        F: FnMut(&mut Der<W>, I::Item) -> Result<()>,
+    {
+        match self.sink {
+            // Sorting does not change the length, so there is nothing to buffer when only counting.
+            Sink::Count(ref mut count) => {
+                let mut len = 0;
+                for item in items {
+                    func(&mut Der::counting(&mut len), item)?;
+                }
+                **count += der::element_len(Tag::SET, len);
+                Ok(())
+            }
+            Sink::Slice { ref mut buf, ref mut pos } => {
+                nest_in_slice(buf, pos, Tag::SET, |buf, pos| {
+                    let contents = *pos;
+                    for item in items {
+                        let start = *pos;
+                        func(&mut Der::in_slice(buf, pos), item)?;
+                        sort_last_element(&mut buf[contents..*pos], start - contents)?;
+                    }
+                    Ok(())
+                })
+            }
+            #[cfg(feature = "alloc")]
+            _ => self.buffered_set_of(items, func),
+            #[cfg(not(feature = "alloc"))]
+            _ => Err(ErrorKind::BufferTooSmall.into()),
+        }
+    }
+
+    #[cfg(feature = "alloc")]
+    fn buffered_set_of<I, F>(&mut self, items: I, mut func: F) -> Result<()>
+    where
+        I: IntoIterator,
+        F: FnMut(&mut Der<W>, I::Item) -> Result<()>,
+    {
+        // The elements are encoded one after another into a single buffer and sorted by range.
+        let mut buf = Vec::new();
+        let mut elements = Vec::new();
+        for item in items {
+            let start = buf.len();
+            func(&mut Der::in_buffer(&mut buf), item)?;
+            elements.push(start..buf.len());
+        }
+        elements.sort_by(|a, b| der::set_of_order(&buf[a.clone()], &buf[b.clone()]));
+
+        self.write_tag(Tag::SET)?;
+        self.write_len(buf.len())?;
+        for element in elements {
+            self.write_all(&buf[element])?;
+        }
+        Ok(())
     }
 
-    /// Write an `OBJECT IDENTIFIER`.
-    pub fn oid(&mut self, input: &[u8]) -> Result<()> {
-        self.writer.write_all(&[Tag::Oid as u8])?;
+    /// Write an `OBJECT IDENTIFIER` given the contents of its encoding, such as an `Oid`.
+    pub fn oid<O: AsRef<[u8]> + ?Sized>(&mut self, oid: &O) -> Result<()> {
+        let input = oid.as_ref();
+        self.write_tag(Tag::OID)?;
         self.write_len(input.len())?;
-        self.writer.write_all(&input)?;
+        self.write_all(input)?;
         Ok(())
     }
 
+    /// Write an `OBJECT IDENTIFIER` given in dotted decimal notation, such as `1.3.6.1.5.5.7.3.1`.
+    /// Errors with `ErrorKind::WrongValue` if `oid` is malformed.
+    ///
+    /// ```
+    /// extern crate derp;
+    ///
+    /// use derp::Der;
+    ///
+    /// fn main() {
+    ///     let mut buf = Vec::new();
+    ///     {
+    ///         let mut der = Der::new(&mut buf);
+    ///         der.oid_str("1.3.6.1.5.5.7.3.1").unwrap();
+    ///     }
+    ///
+    ///     assert_eq!(&buf, &[0x06, 0x08, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x03, 0x01]);
+    /// }
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn oid_str(&mut self, oid: &str) -> Result<()> {
+        self.oid(&oid.parse::<Oid>()?)
+    }
+
+    /// Write an `OBJECT IDENTIFIER` given its arcs. Errors with `ErrorKind::WrongValue` if the first two
+    /// arcs are out of range, see `Oid::from_arcs`.
+    ///
+    /// ```
+    /// extern crate derp;
+    /// extern crate untrusted;
+    ///
+    /// use derp::Der;
+    /// use untrusted::Input;
+    ///
+    /// fn main() {
+    ///     let mut buf = Vec::new();
+    ///     {
+    ///         let mut der = Der::new(&mut buf);
+    ///         der.oid_arcs(&[1, 3, 101, 112]).unwrap();
+    ///     }
+    ///
+    ///     assert_eq!(&buf, &[0x06, 0x03, 0x2b, 0x65, 0x70]);
+    ///     let arcs = Input::from(&buf).read_all(derp::ErrorKind::TrailingData.into(), derp::oid_arcs).unwrap();
+    ///     assert_eq!(arcs.as_slice(), &[1, 3, 101, 112]);
+    /// }
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn oid_arcs(&mut self, arcs: &[u128]) -> Result<()> {
+        self.oid(&Oid::from_arcs(arcs)?)
+    }
+
     /// Write raw bytes to `self`. This does not calculate length or apply. This should only be used
     /// when you know you are dealing with bytes that are already DER encoded.
     pub fn raw(&mut self, input: &[u8]) -> Result<()> {
-        Ok(self.writer.write_all(input)?)
+        self.write_all(input)
     }
 
     /// Write a `BIT STRING`.
@@ -118,38 +715,346 @@ impl<'a, W: Write> Der<'a, W> {
         unused_bits: u8,
         bit_string: &[u8],
     ) -> Result<()> {
-        self.writer.write_all(&[Tag::BitString as u8])?;
+        self.write_tag(Tag::BIT_STRING)?;
         self.write_len(bit_string.len() + 1)?;
-        self.writer.write_all(&[unused_bits])?;
-        self.writer.write_all(&bit_string)?;
+        self.write_all(&[unused_bits])?;
+        self.write_all(bit_string)?;
         Ok(())
     }
 
+    /// Write a `BIT STRING` holding a named bit list, such as `KeyUsage`. Trailing zero bits are
+    /// dropped and the number of unused bits is computed from the bits that remain.
+    ///
+    /// ```
+    /// extern crate derp;
+    ///
+    /// use derp::{BitFlags, Der};
+    ///
+    /// fn main() {
+    ///     // KeyUsage { digitalSignature, keyCertSign }
+    ///     let mut flags = BitFlags::new();
+    ///     flags.set(0, true);
+    ///     flags.set(5, true);
+    ///
+    ///     let mut buf = Vec::new();
+    ///     {
+    ///         let mut der = Der::new(&mut buf);
+    ///         der.bit_string_flags(&flags).unwrap();
+    ///     }
+    ///
+    ///     assert_eq!(&buf, &[0x03, 0x02, 0x02, 0x84]);
+    /// }
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn bit_string_flags(&mut self, flags: &BitFlags) -> Result<()> {
+        let len = flags.len().div_ceil(8);
+        self.write_tag(Tag::BIT_STRING)?;
+        self.write_len(len + 1)?;
+        self.write_all(&[(len * 8 - flags.len()) as u8])?;
+
+        // The bits are packed and written a byte at a time rather than collected first.
+        let mut byte = 0;
+        for (i, bit) in flags.iter().enumerate() {
+            if bit {
+                byte |= 0x80 >> (i % 8);
+            }
+            if i % 8 == 7 {
+                self.write_all(&[byte])?;
+                byte = 0;
+            }
+        }
+        match flags.len() % 8 {
+            0 => Ok(()),
+            _ => self.write_all(&[byte]),
+        }
+    }
+
     /// Write an `OCTET STRING`.
     pub fn octet_string(
         &mut self,
         octet_string: &[u8],
     ) -> Result<()> {
-        self.writer.write_all(&[Tag::OctetString as u8])?;
+        self.write_tag(Tag::OCTET_STRING)?;
         self.write_len(octet_string.len())?;
-        self.writer.write_all(&octet_string)?;
+        self.write_all(octet_string)?;
         Ok(())
     }
+
+    /// Write a `UTF8String`.
+    pub fn utf8_string(&mut self, string: &str) -> Result<()> {
+        self.element(Tag::UTF8_STRING, string.as_bytes())
+    }
+
+    /// Write a `UTCTime` of the form `YYMMDDHHMMSSZ`. Errors with `ErrorKind::BadTimeValue` if the
+    /// year is outside of the range `[1950, 2049]` which is all that `UTCTime` can represent under
+    /// RFC 5280.
+    pub fn utc_time<T: ToDerDateTime + ?Sized>(&mut self, time: &T) -> Result<()> {
+        self.utc_time_with_pivot(time, UtcTimePivot::RFC_5280)
+    }
+
+    /// Write a `UTCTime` of the form `YYMMDDHHMMSSZ`. Errors with `ErrorKind::BadTimeValue` if the
+    /// year is outside of the range covered by `pivot`.
+    pub fn utc_time_with_pivot<T: ToDerDateTime + ?Sized>(
+        &mut self,
+        time: &T,
+        pivot: UtcTimePivot,
+    ) -> Result<()> {
+        let time = time.to_der_date_time()?;
+        if time.year() < pivot.first_year() || time.year() > pivot.last_year() {
+            return Err(ErrorKind::BadTimeValue.into());
+        }
+
+        let encoded = time_digits(&time);
+        self.element(Tag::UTC_TIME, &encoded[2..])
+    }
+
+    /// Write a `GeneralizedTime` of the form `YYYYMMDDHHMMSSZ`. Fractional seconds are not written
+    /// since RFC 5280 forbids them.
+    pub fn generalized_time<T: ToDerDateTime + ?Sized>(&mut self, time: &T) -> Result<()> {
+        let time = time.to_der_date_time()?;
+        self.element(Tag::GENERALIZED_TIME, &time_digits(&time))
+    }
+
+    /// Write a `UniversalString`, encoding `string` as UTF-32BE.
+    pub fn universal_string(&mut self, string: &str) -> Result<()> {
+        self.write_tag(Tag::UNIVERSAL_STRING)?;
+        self.write_len(string.chars().count() * 4)?;
+        for c in string.chars() {
+            let c = c as u32;
+            self.write_all(&[(c >> 24) as u8, (c >> 16) as u8, (c >> 8) as u8, c as u8])?;
+        }
+        Ok(())
+    }
+
+    /// Write a `PrintableString`. Errors with `ErrorKind::BadStringValue` if `string` contains characters
+    /// outside of the `PrintableString` alphabet.
+    pub fn printable_string(&mut self, string: &str) -> Result<()> {
+        if !string.bytes().all(der::is_printable_string_char) {
+            return Err(ErrorKind::BadStringValue.into());
+        }
+        self.element(Tag::PRINTABLE_STRING, string.as_bytes())
+    }
+
+    /// Write a `NumericString`. Errors with `ErrorKind::BadStringValue` if `string` contains anything
+    /// other than digits and spaces.
+    pub fn numeric_string(&mut self, string: &str) -> Result<()> {
+        if !string.bytes().all(der::is_numeric_string_char) {
+            return Err(ErrorKind::BadStringValue.into());
+        }
+        self.element(Tag::NUMERIC_STRING, string.as_bytes())
+    }
+
+    /// Write a `VisibleString`. Errors with `ErrorKind::BadStringValue` if `string` contains anything
+    /// other than printable ASCII characters and spaces.
+    pub fn visible_string(&mut self, string: &str) -> Result<()> {
+        if !string.bytes().all(der::is_visible_string_char) {
+            return Err(ErrorKind::BadStringValue.into());
+        }
+        self.element(Tag::VISIBLE_STRING, string.as_bytes())
+    }
+
+    /// Write an `IA5String`. Errors with `ErrorKind::BadStringValue` if `string` is not ASCII.
+    pub fn ia5_string(&mut self, string: &str) -> Result<()> {
+        if !string.bytes().all(der::is_ia5_string_char) {
+            return Err(ErrorKind::BadStringValue.into());
+        }
+        self.element(Tag::IA5_STRING, string.as_bytes())
+    }
+}
+
+impl<'a, W: Write> Der<'a, W> {
+    // Write straight into `buf`, nesting in it rather than in a scratch buffer that is copied.
+    #[cfg(feature = "alloc")]
+    pub(crate) fn in_buffer(buf: &'a mut Vec<u8>) -> Self {
+        Der { sink: Sink::Buffer(buf) }
+    }
+
+    fn counting(count: &'a mut usize) -> Self {
+        Der { sink: Sink::Count(count) }
+    }
+
+    fn in_slice(buf: &'a mut [u8], pos: &'a mut usize) -> Self {
+        Der { sink: Sink::Slice { buf, pos } }
+    }
+}
+
+impl<'a, 'b> Der<'a, SliceWriter<'b>> {
+    /// Create a new `Der` structure that writes values in place into the buffer of `writer`,
+    /// failing with `ErrorKind::BufferTooSmall` if they do not fit. A nested element that fails to
+    /// be written, such as a `SEQUENCE`, is removed from the buffer again.
+    pub fn slice(writer: &'a mut SliceWriter<'b>) -> Self {
+        Der { sink: Sink::Slice { buf: &mut *writer.buf, pos: &mut writer.pos } }
+    }
 }
 
-#[cfg(test)]
+fn write_to_slice(buf: &mut [u8], pos: &mut usize, input: &[u8]) -> Result<()> {
+    if input.len() > buf.len() - *pos {
+        return Err(ErrorKind::BufferTooSmall.into());
+    }
+    buf[*pos..*pos + input.len()].copy_from_slice(input);
+    *pos += input.len();
+    Ok(())
+}
+
+// Write the element `tag` with the contents `func` writes at `pos` in `buf`, as `nest_in_buffer`
+// does. When the length needs the long form the contents are moved along to make room for it. On
+// error `pos` is moved back to where the element started.
+fn nest_in_slice<F, R>(buf: &mut [u8], pos: &mut usize, tag: Tag, func: F) -> Result<R>
+where
+    F: FnOnce(&mut [u8], &mut usize) -> Result<R>,
+{
+    let start = *pos;
+    let result = write_nested_to_slice(buf, pos, tag, func);
+    if result.is_err() {
+        *pos = start;
+    }
+    result
+}
+
+fn write_nested_to_slice<F, R>(buf: &mut [u8], pos: &mut usize, tag: Tag, func: F) -> Result<R>
+where
+    F: FnOnce(&mut [u8], &mut usize) -> Result<R>,
+{
+    let (octets, len) = tag.identifier_octets();
+    write_to_slice(buf, pos, &octets[..len])?;
+    write_to_slice(buf, pos, &[0])?;
+    let contents = *pos;
+    let value = func(buf, pos)?;
+
+    let (octets, n) = length_octets(*pos - contents);
+    if n - 1 > buf.len() - *pos {
+        return Err(ErrorKind::BufferTooSmall.into());
+    }
+    buf.copy_within(contents..*pos, contents + n - 1);
+    *pos += n - 1;
+    buf[contents - 1..contents - 1 + n].copy_from_slice(&octets[..n]);
+    Ok(value)
+}
+
+// Move the element at `last` in `elements` back to its place among the sorted elements before it.
+// Equal elements keep the order they were written in.
+fn sort_last_element(elements: &mut [u8], last: usize) -> Result<()> {
+    if elements.len() == last {
+        return Ok(());
+    }
+    if encoded_element_len(&elements[last..])? != elements.len() - last {
+        return Err(ErrorKind::WrongValue.into());
+    }
+
+    let mut at = 0;
+    while at < last {
+        let len = encoded_element_len(&elements[at..last])?;
+        if der::set_of_order(&elements[last..], &elements[at..at + len]) == Ordering::Less {
+            break;
+        }
+        at += len;
+    }
+    let len = elements.len() - last;
+    elements[at..].rotate_right(len);
+    Ok(())
+}
+
+// The length of the encoding of the element at the start of `encoded`.
+fn encoded_element_len(encoded: &[u8]) -> Result<usize> {
+    let mut reader = Reader::new(Input::from(encoded));
+    let start = reader.mark();
+    der::skip_element(&mut reader)?;
+    Ok(reader.get_input_between_marks(start, reader.mark())?.len())
+}
+
+// Append the element `tag` with the contents `func` writes to `buf`. A one octet length is
+// reserved ahead of the contents and widened in place if they turn out to need the long form. On
+// error `buf` is truncated back to where the element started.
+#[cfg(feature = "alloc")]
+fn nest_in_buffer<W, F, R>(buf: &mut Vec<u8>, tag: Tag, func: F) -> Result<R>
+where
+    W: Write,
+    F: FnOnce(&mut Der<W>) -> Result<R>,
+{
+    let start = buf.len();
+    let (octets, len) = tag.identifier_octets();
+    buf.extend_from_slice(&octets[..len]);
+    buf.push(0);
+    let contents = buf.len();
+
+    let value = match func(&mut Der::in_buffer(buf)) {
+        Ok(value) => value,
+        Err(err) => {
+            buf.truncate(start);
+            return Err(err);
+        }
+    };
+
+    let (octets, n) = length_octets(buf.len() - contents);
+    if n > 1 {
+        buf.splice(contents..contents, iter::repeat(0).take(n - 1));
+    }
+    buf[contents - 1..contents - 1 + n].copy_from_slice(&octets[..n]);
+    Ok(value)
+}
+
+// The digits of `time` as `YYYYMMDDHHMMSSZ`, of which `UTCTime` uses all but the first two.
+fn time_digits(time: &DerDateTime) -> [u8; 15] {
+    let mut encoded = *b"YYYYMMDDHHMMSSZ";
+    let fields = [time.month(), time.day(), time.hour(), time.minute(), time.second()];
+    let mut year = time.year();
+    for digit in encoded[..4].iter_mut().rev() {
+        *digit = b'0' + (year % 10) as u8;
+        year /= 10;
+    }
+    for (digits, field) in encoded[4..14].chunks_mut(2).zip(fields.iter()) {
+        digits[0] = b'0' + field / 10;
+        digits[1] = b'0' + field % 10;
+    }
+    encoded
+}
+
+// Encode `len` as DER length octets, returning the buffer and the number of octets used.
+pub(crate) fn length_octets(len: usize) -> ([u8; 1 + mem::size_of::<usize>()], usize) {
+    let mut octets = [0; 1 + mem::size_of::<usize>()];
+    if len < 128 {
+        octets[0] = len as u8;
+        return (octets, 1);
+    }
+
+    let n = der::length_of_length(len) as usize;
+    octets[0] = 0x80 | n as u8;
+    for i in 0..n {
+        octets[1 + i] = (len >> ((n - 1 - i) * 8)) as u8;
+    }
+    (octets, 1 + n)
+}
+
+// Encode `value` as big-endian two's complement, returning the buffer and the index at which the
+// minimal encoding starts.
+pub(crate) fn minimal_twos_complement(value: i128) -> ([u8; 16], usize) {
+    let bytes = value.to_be_bytes();
+
+    // A leading byte is redundant if it and the high bit of the following byte are all sign bits.
+    let mut start = 0;
+    while start < bytes.len() - 1 {
+        match (bytes[start], bytes[start + 1] & 0x80) {
+            (0x00, 0x00) | (0xff, 0x80) => start += 1,
+            _ => break,
+        }
+    }
+
+    (bytes, start)
+}
+
+#[cfg(all(test, feature = "alloc"))]
 mod test {
     use super::*;
-    use Error;
+    use {DerDateTime, Error};
     use untrusted::Input;
     
-    static RSA_2048_PKCS1: &'static [u8] = include_bytes!("../tests/rsa-2048.pkcs1.der");
+    static RSA_2048_PKCS1: &[u8] = include_bytes!("../tests/rsa-2048.pkcs1.der");
 
     #[test]
     fn write_pkcs1() {
         let input = Input::from(RSA_2048_PKCS1);
-        let (n, e) = input.read_all(Error::Read, |input| {
-            der::nested(input, Tag::Sequence, |input| {
+        let (n, e) = input.read_all(ErrorKind::TrailingData.into(), |input| {
+            der::nested(input, Tag::SEQUENCE, |input| {
                 let n = der::positive_integer(input)?;
                 let e = der::positive_integer(input)?;
                 Ok((n.as_slice_less_safe(), e.as_slice_less_safe()))
@@ -168,6 +1073,56 @@ mod test {
         assert_eq!(buf.as_slice(), RSA_2048_PKCS1);
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn write_io_error() {
+        use std::error::Error as StdError;
+
+        struct Failing;
+        impl io::Write for Failing {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let err = Der::new(&mut Failing).null().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::Io);
+        let source = err.source().and_then(|source| source.downcast_ref::<io::Error>());
+        assert_eq!(source.map(io::Error::kind), Some(io::ErrorKind::BrokenPipe));
+
+        let shared = io::Error::from(err.clone());
+        assert_eq!(shared.kind(), io::ErrorKind::BrokenPipe);
+        drop(shared);
+        // Once it is no longer shared the original error is returned.
+        assert_eq!(io::Error::from(err).to_string(), "closed");
+
+        let err = Input::from(&[0x01, 0x01, 0x07])
+            .read_all(ErrorKind::TrailingData.into(), der::boolean)
+            .unwrap_err();
+        let io_err = io::Error::from(err);
+        assert_eq!(io_err.kind(), io::ErrorKind::InvalidData);
+        let inner = io_err.get_ref().and_then(|inner| inner.downcast_ref::<Error>());
+        assert_eq!(inner.map(Error::kind), Some(ErrorKind::BadBooleanValue));
+    }
+
+    #[test]
+    fn write_boolean() {
+        for &(value, encoded) in &[(true, [0x01, 0x01, 0xff]), (false, [0x01, 0x01, 0x00])] {
+            let mut buf = Vec::new();
+            {
+                let mut der = Der::new(&mut buf);
+                der.boolean(value).unwrap();
+            }
+
+            assert_eq!(&buf, &encoded);
+            let parsed = Input::from(&buf).read_all(ErrorKind::TrailingData.into(), der::boolean).unwrap();
+            assert_eq!(parsed, value);
+        }
+    }
+
     #[test]
     fn write_octet_string() {
         let mut buf = Vec::new();
@@ -186,4 +1141,502 @@ mod test {
 
         assert_eq!(&buf, &[0x04, 0x03, 0x0a, 0x0b, 0x0c]);
     }
+
+    #[test]
+    fn write_utf8_string() {
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.utf8_string("dérp").unwrap();
+        }
+
+        assert_eq!(&buf, &[0x0c, 0x05, 0x64, 0xc3, 0xa9, 0x72, 0x70]);
+        let parsed = Input::from(&buf).read_all(ErrorKind::TrailingData.into(), der::utf8_string).unwrap();
+        assert_eq!(parsed, "dérp");
+    }
+
+    #[test]
+    fn write_printable_string() {
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.printable_string("Some-CA (1)").unwrap();
+            assert_eq!(der.printable_string("a@b"), Err(ErrorKind::BadStringValue.into()));
+            assert_eq!(der.printable_string("dérp"), Err(ErrorKind::BadStringValue.into()));
+        }
+
+        assert_eq!(&buf, b"\x13\x0bSome-CA (1)");
+    }
+
+    #[test]
+    fn write_ia5_string() {
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.ia5_string("example.com").unwrap();
+            assert_eq!(der.ia5_string("exämple.com"), Err(ErrorKind::BadStringValue.into()));
+        }
+
+        assert_eq!(&buf, b"\x16\x0bexample.com");
+        let parsed = Input::from(&buf).read_all(ErrorKind::TrailingData.into(), der::ia5_string).unwrap();
+        assert_eq!(parsed, "example.com");
+    }
+
+    #[test]
+    fn write_universal_string() {
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.universal_string("d\u{1f600}").unwrap();
+        }
+
+        assert_eq!(&buf, &[0x1c, 0x08, 0x00, 0x00, 0x00, 0x64, 0x00, 0x01, 0xf6, 0x00]);
+        let parsed = Input::from(&buf).read_all(ErrorKind::TrailingData.into(), der::universal_string).unwrap();
+        assert_eq!(parsed, "d\u{1f600}");
+    }
+
+    #[test]
+    fn write_numeric_and_visible_string() {
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.numeric_string("49 1").unwrap();
+            der.visible_string("a-b").unwrap();
+            assert_eq!(der.numeric_string("4a"), Err(ErrorKind::BadStringValue.into()));
+            assert_eq!(der.visible_string("a\tb"), Err(ErrorKind::BadStringValue.into()));
+        }
+
+        assert_eq!(&buf, b"\x12\x0449 1\x1a\x03a-b");
+    }
+
+    #[test]
+    fn write_times() {
+        let time = DerDateTime::new(2017, 9, 5, 8, 7, 6).unwrap();
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.utc_time(&time).unwrap();
+            der.generalized_time(&time).unwrap();
+        }
+
+        assert_eq!(&buf, b"\x17\x0d170905080706Z\x18\x0f20170905080706Z");
+        let (utc, generalized) = Input::from(&buf)
+            .read_all(Error::from(ErrorKind::TrailingData), |input| {
+                Ok((::utc_time(input)?, ::generalized_time(input)?))
+            })
+            .unwrap();
+        assert_eq!(utc, time);
+        assert_eq!(generalized, time);
+    }
+
+    #[test]
+    fn write_utc_time_out_of_range() {
+        let mut buf = Vec::new();
+        let mut der = Der::new(&mut buf);
+        for &year in &[1949, 2050] {
+            let time = DerDateTime::new(year, 1, 1, 0, 0, 0).unwrap();
+            assert_eq!(der.utc_time(&time), Err(ErrorKind::BadTimeValue.into()));
+            assert!(der.generalized_time(&time).is_ok());
+        }
+    }
+
+    #[test]
+    fn write_utc_time_with_pivot() {
+        let pivot = UtcTimePivot::new(2000).unwrap();
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            let time = DerDateTime::new(2075, 1, 1, 0, 0, 0).unwrap();
+            der.utc_time_with_pivot(&time, pivot).unwrap();
+            let time = DerDateTime::new(1999, 1, 1, 0, 0, 0).unwrap();
+            assert_eq!(der.utc_time_with_pivot(&time, pivot), Err(ErrorKind::BadTimeValue.into()));
+        }
+
+        assert_eq!(&buf, b"\x17\x0d750101000000Z");
+        let parsed = Input::from(&buf)
+            .read_all(ErrorKind::TrailingData.into(), |input| ::utc_time_with_pivot(input, pivot))
+            .unwrap();
+        assert_eq!(parsed.year(), 2075);
+    }
+
+    #[test]
+    fn write_enumerated() {
+        for &value in &[0, 1, 127, 128, 255, 256, -1, -128, -129, i64::MAX, i64::MIN] {
+            let mut buf = Vec::new();
+            {
+                let mut der = Der::new(&mut buf);
+                der.enumerated(value).unwrap();
+            }
+
+            let parsed = Input::from(&buf).read_all(ErrorKind::TrailingData.into(), der::enumerated_i64).unwrap();
+            assert_eq!(parsed, value);
+        }
+
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.enumerated(128).unwrap();
+            der.enumerated(-129).unwrap();
+        }
+        assert_eq!(&buf, &[0x0a, 0x02, 0x00, 0x80, 0x0a, 0x02, 0xff, 0x7f]);
+    }
+
+    #[test]
+    fn write_set() {
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.set(|der| {
+                der.boolean(true)?;
+                der.null()
+            }).unwrap();
+        }
+
+        assert_eq!(&buf, &[0x31, 0x05, 0x01, 0x01, 0xff, 0x05, 0x00]);
+    }
+
+    #[test]
+    fn write_set_of() {
+        let strings = ["b", "a", "aa", "a"];
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.set_of(strings.iter(), |der, s| der.utf8_string(s)).unwrap();
+        }
+
+        assert_eq!(&buf, b"\x31\x0d\x0c\x01a\x0c\x01a\x0c\x01b\x0c\x02aa");
+        let parsed = Input::from(&buf)
+            .read_all(ErrorKind::TrailingData.into(), |input| der::set_of(input, der::utf8_string))
+            .unwrap();
+        assert_eq!(parsed, vec!["a", "a", "b", "aa"]);
+    }
+
+    #[test]
+    fn write_explicit() {
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.explicit(3, |der| der.sequence(|der| der.null())).unwrap();
+        }
+
+        assert_eq!(&buf, &[0xa3, 0x04, 0x30, 0x02, 0x05, 0x00]);
+        Input::from(&buf)
+            .read_all(ErrorKind::TrailingData.into(), |input| {
+                der::read_explicit(input, 3, |input| der::nested(input, Tag::SEQUENCE, der::read_null))
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn write_implicit() {
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.implicit(1, b"a@b").unwrap();
+            der.implicit_constructed(4, |der| der.null()).unwrap();
+        }
+
+        assert_eq!(&buf, b"\x81\x03a@b\xa4\x02\x05\x00");
+        Input::from(&buf)
+            .read_all(Error::from(ErrorKind::TrailingData), |input| {
+                assert_eq!(der::read_implicit(input, 1)?.as_slice_less_safe(), b"a@b");
+                assert_eq!(der::read_implicit(input, 4)?.as_slice_less_safe(), &[0x05, 0x00]);
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn write_tagged() {
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.tagged(Class::Application, 2, &[0x01]).unwrap();
+            der.tagged(Class::Private, 30, &[]).unwrap();
+            der.tagged_constructed(Class::Private, 0, |der| der.null()).unwrap();
+        }
+
+        assert_eq!(&buf, &[0x42, 0x01, 0x01, 0xde, 0x00, 0xe0, 0x02, 0x05, 0x00]);
+        Input::from(&buf)
+            .read_all(ErrorKind::TrailingData.into(), |input| {
+                assert_eq!(der::read_tagged(input, Class::Application, 2)?, Input::from(&[0x01]));
+                assert!(der::read_tagged(input, Class::Private, 30)?.is_empty());
+                der::read_tagged_explicit(input, Class::Private, 0, der::read_null)
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn write_nested_long_lengths() {
+        let mut buf = Vec::new();
+        Der::new(&mut buf)
+            .sequence(|der| {
+                der.sequence(|der| der.octet_string(&[0x55; 200]))?;
+                der.explicit(0, |der| der.sequence(|der| der.octet_string(&[0xaa; 300])))
+            })
+            .unwrap();
+
+        let mut expected = vec![0x30, 0x82, 0x02, 0x06, 0x30, 0x81, 0xcb, 0x04, 0x81, 0xc8];
+        expected.extend_from_slice(&[0x55; 200]);
+        expected.extend_from_slice(&[0xa0, 0x82, 0x01, 0x34, 0x30, 0x82, 0x01, 0x30]);
+        expected.extend_from_slice(&[0x04, 0x82, 0x01, 0x2c]);
+        expected.extend_from_slice(&[0xaa; 300]);
+        assert_eq!(buf, expected);
+    }
+
+    #[test]
+    fn write_encoded_len() {
+        let write = |der: &mut Der<Vec<u8>>| {
+            der.sequence(|der| {
+                der.u64(0x80)?;
+                der.explicit(3, |der| der.octet_string(&[0; 300]))?;
+                der.set_of(&[3u8, 1, 2], |der, x| der.integer(&[*x]))?;
+                der.bit_string_flags(&[true, false, true].iter().cloned().collect())
+            })?;
+            der.utf8_string("derp")
+        };
+
+        let mut buf = Vec::new();
+        write(&mut Der::new(&mut buf)).unwrap();
+        assert_eq!(encoded_len(write).unwrap(), buf.len());
+        assert_eq!(encoded_len(|der| der.null()).unwrap(), 2);
+
+        let err = encoded_len(|der| der.sequence(|der| der.printable_string("@"))).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BadStringValue);
+    }
+
+    #[test]
+    fn write_slice() {
+        fn write<W: Write>(der: &mut Der<W>) -> Result<()> {
+            der.sequence(|der| {
+                der.explicit(0, |der| der.octet_string(&[0x55; 300]))?;
+                der.set_of(&[0x0102u16, 3, 0x80, 0], |der, x| der.u16(*x))?;
+                der.set_of(&["bb", "a", "b"], |der, x| der.utf8_string(x))
+            })?;
+            der.null()
+        }
+        let mut expected = Vec::new();
+        write(&mut Der::new(&mut expected)).unwrap();
+
+        let mut buf = [0; 400];
+        let mut writer = SliceWriter::new(&mut buf);
+        write(&mut Der::slice(&mut writer)).unwrap();
+        assert_eq!(writer.into_written(), &expected[..]);
+
+        // Nested elements that do not fit are dropped, but not the tag of the final `NULL`.
+        for &(len, written) in &[(expected.len() - 1, expected.len() - 1), (130, 0), (2, 0)] {
+            let mut buf = vec![0; len];
+            let mut writer = SliceWriter::new(&mut buf);
+            let err = write(&mut Der::slice(&mut writer)).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+            assert_eq!(writer.written(), &expected[..written]);
+        }
+        let mut buf = [0; 130];
+        let mut writer = SliceWriter::new(&mut buf);
+        let err = Der::slice(&mut writer).sequence(|der| der.raw(&[0; 128])).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+        Der::slice(&mut writer).sequence(|der| der.raw(&[0; 126])).unwrap();
+        assert_eq!(writer.len(), 128);
+
+        let mut buf = [0; 16];
+        let mut writer = SliceWriter::new(&mut buf);
+        let err = Der::slice(&mut writer)
+            .set_of(&[1u8, 2], |der, x| {
+                der.u8(*x)?;
+                der.null()
+            })
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WrongValue);
+        assert!(writer.is_empty());
+
+        // Through `Der::new` writes that do not fit fail the same way, leaving the tag of the final
+        // `NULL` but no partial writes.
+        let mut buf = [0; 3];
+        let mut writer = SliceWriter::new(&mut buf);
+        Der::new(&mut writer).null().unwrap();
+        let err = Der::new(&mut writer).null().unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+        #[cfg(feature = "std")]
+        assert!(io::Write::write(&mut writer, &[1, 2]).is_err());
+        assert_eq!(writer.written(), &[0x05, 0x00, 0x05]);
+
+        // Including a nested element, which is built before it is written.
+        let mut buf = [0; 4];
+        let mut writer = SliceWriter::new(&mut buf);
+        let err = Der::new(&mut writer).sequence(|der| der.octet_string(&[0; 4])).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+        assert!(writer.is_empty());
+    }
+
+    #[test]
+    fn write_nested_error() {
+        let mut buf = Vec::new();
+        Der::new(&mut buf)
+            .sequence(|der| {
+                der.null()?;
+                let err = der
+                    .sequence(|der| {
+                        der.octet_string(&[0; 200])?;
+                        Err::<(), _>(ErrorKind::WrongValue.into())
+                    })
+                    .unwrap_err();
+                assert_eq!(err.kind(), ErrorKind::WrongValue);
+                der.u8(1)
+            })
+            .unwrap();
+
+        // The failed sequence leaves nothing behind.
+        assert_eq!(&buf, &[0x30, 0x05, 0x05, 0x00, 0x02, 0x01, 0x01]);
+
+        let mut buf = Vec::new();
+        let err = Der::new(&mut buf)
+            .sequence(|der| der.sequence(|_| Err::<(), _>(ErrorKind::WrongValue.into())))
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::WrongValue);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn write_nested_value() {
+        fn write<W: Write>(der: &mut Der<W>) -> Result<(usize, bool)> {
+            der.sequence(|der| {
+                let len = der.explicit(0, |der| der.octet_string(&[0; 200]).map(|()| 200))?;
+                let flag = der.set(|der| der.boolean(true).map(|()| true))?;
+                Ok((len, flag))
+            })
+        }
+
+        let mut buf = Vec::new();
+        assert_eq!(write(&mut Der::new(&mut buf)), Ok((200, true)));
+        assert_eq!(encoded_len(|der| write(der).map(|_| ())), Ok(buf.len()));
+
+        let mut out = [0; 256];
+        let mut writer = SliceWriter::new(&mut out);
+        assert_eq!(write(&mut Der::slice(&mut writer)), Ok((200, true)));
+        assert_eq!(writer.written(), &buf[..]);
+    }
+
+    #[test]
+    fn write_scratch() {
+        let mut scratch = vec![0xff; 4];
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::with_scratch(&mut buf, &mut scratch);
+            der.sequence(|der| der.octet_string(&[0; 200])).unwrap();
+            let err = der.sequence(|_| Err::<(), _>(ErrorKind::WrongValue.into())).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::WrongValue);
+            der.sequence(|der| der.u8(1)).unwrap();
+        }
+        assert_eq!(buf.len(), 0xce + 5);
+        assert_eq!(&buf[..6], &[0x30, 0x81, 0xcb, 0x04, 0x81, 0xc8]);
+        assert_eq!(&buf[0xce..], &[0x30, 0x03, 0x02, 0x01, 0x01]);
+
+        // The buffer is left empty, and reused without growing for messages that fit.
+        assert!(scratch.is_empty());
+        let capacity = scratch.capacity();
+        assert!(capacity >= 0xce);
+        let mut buf = Vec::new();
+        Der::with_scratch(&mut buf, &mut scratch).sequence(|der| der.null()).unwrap();
+        assert_eq!(&buf, &[0x30, 0x02, 0x05, 0x00]);
+        assert_eq!(scratch.capacity(), capacity);
+    }
+
+    #[test]
+    fn write_high_tag_number() {
+        for &(number, identifier) in &[
+            (31, &[0x9f, 0x1f][..]),
+            (127, &[0x9f, 0x7f]),
+            (128, &[0x9f, 0x81, 0x00]),
+            (0x3fff, &[0x9f, 0xff, 0x7f]),
+            (0x4000, &[0x9f, 0x81, 0x80, 0x00]),
+            (u32::MAX, &[0x9f, 0x8f, 0xff, 0xff, 0xff, 0x7f]),
+        ] {
+            let mut buf = Vec::new();
+            Der::new(&mut buf).implicit(number, &[]).unwrap();
+            assert_eq!(&buf[..buf.len() - 1], identifier);
+            assert_eq!(buf[buf.len() - 1], 0x00);
+
+            let value = Input::from(&buf)
+                .read_all(ErrorKind::TrailingData.into(), |input| der::read_implicit(input, number))
+                .unwrap();
+            assert!(value.is_empty());
+        }
+
+        let mut buf = Vec::new();
+        Der::new(&mut buf).tagged_constructed(Class::Application, 1000, |der| der.null()).unwrap();
+        assert_eq!(&buf, &[0x7f, 0x87, 0x68, 0x02, 0x05, 0x00]);
+    }
+
+    #[test]
+    fn write_unsigned() {
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.u8(0).unwrap();
+            der.u8(0x7f).unwrap();
+            der.u16(0x80).unwrap();
+            der.u32(0x100).unwrap();
+        }
+        assert_eq!(
+            &buf,
+            &[0x02, 0x01, 0x00, 0x02, 0x01, 0x7f, 0x02, 0x02, 0x00, 0x80, 0x02, 0x02, 0x01, 0x00]
+        );
+
+        let mut buf = Vec::new();
+        {
+            let mut der = Der::new(&mut buf);
+            der.u64(u64::MAX).unwrap();
+            der.u128(u128::MAX).unwrap();
+        }
+        Input::from(&buf)
+            .read_all(Error::from(ErrorKind::TrailingData), |input| {
+                assert_eq!(der::read_u64(input)?, u64::MAX);
+                assert_eq!(der::read_u128(input)?, u128::MAX);
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn write_signed() {
+        for &(value, encoding) in &[
+            (0, &[0x02, 0x01, 0x00][..]),
+            (127, &[0x02, 0x01, 0x7f]),
+            (128, &[0x02, 0x02, 0x00, 0x80]),
+            (-1, &[0x02, 0x01, 0xff]),
+            (-128, &[0x02, 0x01, 0x80]),
+            (-129, &[0x02, 0x02, 0xff, 0x7f]),
+            (i64::MIN, &[0x02, 0x08, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+        ] {
+            let mut buf = Vec::new();
+            Der::new(&mut buf).i64(value).unwrap();
+            assert_eq!(&buf, encoding);
+        }
+
+        for &value in &[i128::MIN, i128::from(i64::MIN) - 1, i128::MAX] {
+            let mut buf = Vec::new();
+            Der::new(&mut buf).i128(value).unwrap();
+            let parsed = Input::from(&buf).read_all(ErrorKind::TrailingData.into(), der::read_i128).unwrap();
+            assert_eq!(parsed, value);
+        }
+    }
+
+    #[test]
+    fn write_bit_string_flags() {
+        for &(bits, encoding) in &[
+            (&[][..], &[0x03, 0x01, 0x00][..]),
+            (&[false, false], &[0x03, 0x01, 0x00]),
+            (&[true], &[0x03, 0x02, 0x07, 0x80]),
+            (&[false, true, true, true, true, true, true, true, true], &[0x03, 0x03, 0x07, 0x7f, 0x80]),
+            (&[true, false, false, false, false, false, false, true, false], &[0x03, 0x02, 0x00, 0x81]),
+        ] {
+            let flags = bits.iter().cloned().collect::<BitFlags>();
+            let mut buf = Vec::new();
+            Der::new(&mut buf).bit_string_flags(&flags).unwrap();
+            assert_eq!(&buf, encoding);
+
+            let parsed = Input::from(&buf).read_all(ErrorKind::TrailingData.into(), der::bit_string_flags).unwrap();
+            assert_eq!(parsed, flags);
+        }
+    }
 }
@@ -0,0 +1,210 @@
+// Reading with the borrowing readers and writing into a `SliceWriter` must not allocate, so that
+// the crate can be used without the `alloc` feature where there is no heap. The global allocator
+// here counts the allocations made on each thread, and the checks below expect none.
+
+#[macro_use]
+extern crate derp;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use derp::{Class, Der, ErrorKind, FromDer, Reader, SliceWriter, Tag, ToDer, Write};
+
+struct Counting;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+// Run `f`, failing if it allocates on this thread.
+fn assert_no_allocations<F: FnOnce() -> R, R>(f: F) -> R {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    assert_eq!(ALLOCATIONS.with(Cell::get), before, "allocated");
+    result
+}
+
+// SEQUENCE { serial INTEGER, name UTF8String, [0] EXPLICIT BOOLEAN OPTIONAL, data OCTET STRING,
+//            SET OF INTEGER }
+const FLAG: Tag = Tag::new(Class::ContextSpecific, true, 0);
+
+#[derive(Debug, PartialEq)]
+struct Record<'a> {
+    serial: u64,
+    name: &'a str,
+    flag: Option<bool>,
+    data: &'a [u8],
+    set: [u8; 3],
+}
+
+impl<'a> FromDer<'a> for Record<'a> {
+    fn from_der(reader: &mut Reader<'a>) -> derp::Result<Self> {
+        reader.sequence(|r| {
+            let serial = r.context("serial", |r| r.u64())?;
+            let name = r.utf8_string()?;
+            let flag = r.optional(FLAG, |r| r.explicit(0, |r| r.boolean()))?;
+            let data = r.expect(Tag::OCTET_STRING)?;
+            let mut set = [0; 3];
+            r.set(|r| {
+                for value in set.iter_mut() {
+                    *value = r.u8()?;
+                }
+                Ok(())
+            })?;
+            Ok(Record { serial, name, flag, data, set })
+        })
+    }
+}
+
+impl<'a> ToDer for Record<'a> {
+    fn write_der<W: Write>(&self, der: &mut Der<W>) -> derp::Result<()> {
+        der.sequence(|der| {
+            der.u64(self.serial)?;
+            der.utf8_string(self.name)?;
+            if let Some(flag) = self.flag {
+                der.explicit(0, |der| der.boolean(flag))?;
+            }
+            der.octet_string(self.data)?;
+            der.set_of(self.set.iter(), |der, value| der.u8(*value))
+        })
+    }
+
+    fn encoded_len(&self) -> usize {
+        let flag = match self.flag {
+            Some(flag) => derp::element_len(FLAG, flag.encoded_len()),
+            None => 0,
+        };
+        let set = self.set.iter().map(ToDer::encoded_len).sum();
+        derp::sequence_len(
+            self.serial.encoded_len()
+                + self.name.encoded_len()
+                + flag
+                + self.data.encoded_len()
+                + derp::element_len(Tag::SET, set),
+        )
+    }
+}
+
+// The name is long enough that it and the `SEQUENCE` need long form lengths, so writing them in
+// place has to move what was written after widening their lengths.
+const HEADER: &[u8] = &[0x30, 0x81, 0x9b, 0x02, 0x01, 0x2a, 0x0c, 0x81, 0x80];
+
+fn record(name: &str) -> Record<'_> {
+    Record { serial: 42, name, flag: Some(true), data: &[1, 2, 3], set: [3, 1, 2] }
+}
+
+#[test]
+fn alloc_free_round_trip() {
+    let name = "a".repeat(0x80);
+    let value = record(&name);
+    let mut buf = [0; 256];
+
+    let encoded = assert_no_allocations(|| value.encode_to_slice(&mut buf).unwrap());
+    assert_eq!(encoded.len(), value.encoded_len());
+    assert_eq!(&encoded[..HEADER.len()], HEADER);
+    assert!(encoded[HEADER.len()..][..0x80].iter().all(|&b| b == b'a'));
+    assert_eq!(
+        &encoded[HEADER.len() + 0x80..],
+        &[
+            0xa0, 0x03, 0x01, 0x01, 0xff, 0x04, 0x03, 0x01, 0x02, 0x03, 0x31, 0x09, 0x02, 0x01,
+            0x01, 0x02, 0x01, 0x02, 0x02, 0x01, 0x03,
+        ][..],
+    );
+
+    let decoded = assert_no_allocations(|| Record::decode(encoded).unwrap());
+    assert_eq!(decoded, Record { set: [1, 2, 3], ..value });
+}
+
+#[test]
+fn alloc_free_errors() {
+    let value = record("short");
+    let mut buf = [0; 8];
+    let err = assert_no_allocations(|| value.encode_to_slice(&mut buf).unwrap_err());
+    assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+
+    // With `alloc` an error records the elements it was found in, which does allocate.
+    let mut buf = [0; 64];
+    let encoded = value.encode_to_slice(&mut buf).unwrap();
+    let truncated = &encoded[..encoded.len() - 1];
+    let decode = || Record::decode(truncated).unwrap_err();
+    let err = match cfg!(feature = "alloc") {
+        true => decode(),
+        false => assert_no_allocations(decode),
+    };
+    assert_eq!(err.kind(), ErrorKind::UnexpectedEnd);
+}
+
+// Without `alloc` a `Der` over a writer has nowhere to build nested elements, only `Der::slice`
+// writes them in place.
+#[cfg(not(feature = "alloc"))]
+#[test]
+fn alloc_free_writer() {
+    let mut buf = [0; 16];
+    let mut writer = SliceWriter::new(&mut buf);
+    assert_no_allocations(|| {
+        let mut der = Der::new(&mut writer);
+        der.u8(1).unwrap();
+        let err = der.sequence(|der| der.null()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+        let err = der.set_of([1u8].iter(), |der, value| der.u8(*value)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BufferTooSmall);
+    });
+    assert_eq!(writer.written(), &[0x02, 0x01, 0x01]);
+}
+
+// `ToDer::encode` nests in the buffer it returns, so that is all it allocates.
+#[cfg(feature = "alloc")]
+#[test]
+fn encode_allocates_once() {
+    let value = vec![vec![1u8, 2], vec![3]];
+    let before = ALLOCATIONS.with(Cell::get);
+    let encoded = value.encode().unwrap();
+    assert_eq!(ALLOCATIONS.with(Cell::get) - before, 1);
+    assert_eq!(
+        encoded,
+        [0x30, 0x0d, 0x30, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02, 0x30, 0x03, 0x02, 0x01, 0x03],
+    );
+}
+
+// AlgorithmIdentifier { algorithm OBJECT IDENTIFIER } with the parameters absent, as for Ed25519.
+struct Algorithm<'a>(&'a [u8]);
+
+impl<'a> FromDer<'a> for Algorithm<'a> {
+    fn from_der(reader: &mut Reader<'a>) -> derp::Result<Self> {
+        reader.sequence(|r| r.expect(Tag::OID)).map(Algorithm)
+    }
+}
+
+const ED25519: &[u8] = oid!("1.3.101.112");
+
+// `oid!` and the `const` encoder it expands to need no `alloc`, so OIDs can be written and compared
+// against without a heap.
+#[test]
+fn alloc_free_oid() {
+    assert_eq!(ED25519, &[0x2b, 0x65, 0x70]);
+    let (encoded, len) = assert_no_allocations(|| derp::encode_oid_str("1.3.101.112"));
+    assert_eq!(&encoded[..len], ED25519);
+
+    let mut buf = [0; 8];
+    let mut writer = SliceWriter::new(&mut buf);
+    assert_no_allocations(|| Der::new(&mut writer).oid(ED25519).unwrap());
+    assert_eq!(writer.written(), &[0x06, 0x03, 0x2b, 0x65, 0x70]);
+
+    let encoded = &[0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70];
+    let algorithm = assert_no_allocations(|| Algorithm::decode(encoded).unwrap());
+    assert_eq!(algorithm.0, ED25519);
+}